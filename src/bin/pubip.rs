@@ -0,0 +1,599 @@
+//! `pubip` looks up the public IP address of this machine, or of a target, from the command line.
+//!
+//! Built with the `cli` feature: `cargo install public-ip-address --features cli`
+
+use public_ip_address::{
+    cache::{CacheEntryView, ResponseCache},
+    error::Error,
+    lookup::{LookupProvider, LookupTarget, Parameters},
+    perform_cached_lookup_with, perform_lookup_target_with, perform_lookup_with,
+    response::LookupResponse,
+    watch::Watcher,
+    Config,
+};
+use std::{
+    env,
+    net::IpAddr,
+    process::{Command, ExitCode},
+    time::Duration,
+};
+
+const USAGE: &str = "\
+Usage: pubip [OPTIONS] [TARGET]
+       pubip --watch [OPTIONS]
+       pubip cache list [OPTIONS]
+
+Looks up the public IP address of this machine, or of TARGET (an IP address or a hostname).
+With --watch the address is polled and printed every time it changes.
+`cache list` prints the cached responses with their age and remaining time to live.
+
+Options:
+  -p, --provider <NAME>  Provider to use, can be repeated to build a fallback chain
+  -k, --key <KEY>        API key for the preceding provider
+  -f, --format <FORMAT>  Output format: table (default), json or csv
+  -c, --config <PATH>    Configuration file, defaults to $PUBLIC_IP_CONFIG
+      --no-cache         Don't read or write the cache
+      --flush            Ignore the cached response and refresh it
+  -w, --watch            Keep polling the public address and report changes
+      --interval <SECS>  Seconds between polls while watching, defaults to 300
+      --debounce <N>     Polls a new address must be seen in before it is reported
+      --confirm <NAME>   Provider confirming a new address before it is reported
+  -e, --exec <COMMAND>   Shell command run on every change, with the addresses in
+                         $PUBIP_ADDRESS and $PUBIP_PREVIOUS
+  -h, --help             Print this help
+  -V, --version          Print the version";
+
+/// Output format of the responses
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Format {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// Parsed command line arguments
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    target: Option<LookupTarget>,
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    format: Format,
+    config: Option<String>,
+    no_cache: bool,
+    flush: bool,
+    watch: bool,
+    interval: Option<u64>,
+    debounce: Option<u32>,
+    confirm: Option<LookupProvider>,
+    exec: Option<String>,
+    cache_list: bool,
+    help: bool,
+    version: bool,
+}
+
+/// Parses the command line arguments, without the program name
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", name))
+        };
+        match arg.as_str() {
+            "-p" | "--provider" => {
+                let name = value(&arg)?;
+                let provider = name
+                    .parse()
+                    .map_err(|_| format!("Unknown provider: {}", name))?;
+                parsed.providers.push((provider, None));
+            }
+            "-k" | "--key" => {
+                let key = value(&arg)?;
+                let (_, parameters) = parsed
+                    .providers
+                    .last_mut()
+                    .ok_or("--key must follow a --provider")?;
+                *parameters = Some(Parameters::new(key));
+            }
+            "-f" | "--format" => {
+                parsed.format = match value(&arg)?.to_lowercase().as_str() {
+                    "table" => Format::Table,
+                    "json" => Format::Json,
+                    "csv" => Format::Csv,
+                    format => return Err(format!("Unknown format: {}", format)),
+                }
+            }
+            "-c" | "--config" => parsed.config = Some(value(&arg)?),
+            "--no-cache" => parsed.no_cache = true,
+            "--flush" => parsed.flush = true,
+            "-w" | "--watch" => parsed.watch = true,
+            "--interval" => parsed.interval = Some(number(&arg, value(&arg)?)?),
+            "--debounce" => parsed.debounce = Some(number(&arg, value(&arg)?)?),
+            "--confirm" => {
+                let name = value(&arg)?;
+                parsed.confirm = Some(
+                    name.parse()
+                        .map_err(|_| format!("Unknown provider: {}", name))?,
+                );
+            }
+            "-e" | "--exec" => parsed.exec = Some(value(&arg)?),
+            "-h" | "--help" => parsed.help = true,
+            "-V" | "--version" => parsed.version = true,
+            option if option.starts_with('-') => return Err(format!("Unknown option: {}", option)),
+            "cache" if parsed.target.is_none() && !parsed.cache_list => {
+                match args.next().as_deref() {
+                    Some("list") => parsed.cache_list = true,
+                    Some(command) => return Err(format!("Unknown cache command: {}", command)),
+                    None => return Err("Missing cache command".to_string()),
+                }
+            }
+            target if parsed.target.is_none() => {
+                parsed.target = Some(
+                    target
+                        .parse()
+                        .map_err(|_| format!("Invalid target: {}", target))?,
+                );
+            }
+            target => return Err(format!("Unexpected argument: {}", target)),
+        }
+    }
+    if parsed.watch && parsed.target.is_some() {
+        return Err("--watch can't be combined with a target".to_string());
+    }
+    if parsed.cache_list && (parsed.watch || parsed.target.is_some()) {
+        return Err("cache list can't be combined with --watch or a target".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Parses the numeric value of an option
+fn number<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number for {}: {}", name, value))
+}
+
+/// Fields printed in the table and CSV formats
+fn fields(response: &LookupResponse) -> Vec<(&'static str, Option<String>)> {
+    vec![
+        ("ip", Some(response.ip.to_string())),
+        ("continent", response.continent.clone()),
+        ("country", response.country.clone()),
+        ("country_code", response.country_code.clone()),
+        ("region", response.region.clone()),
+        ("city", response.city.clone()),
+        ("postal_code", response.postal_code.clone()),
+        ("latitude", response.latitude.map(|v| v.to_string())),
+        ("longitude", response.longitude.map(|v| v.to_string())),
+        ("time_zone", response.time_zone.clone()),
+        ("asn", response.asn.clone()),
+        ("asn_org", response.asn_org.clone()),
+        ("isp", response.isp.clone()),
+        ("network", response.network.clone()),
+        ("hostname", response.hostname.clone()),
+        ("is_proxy", response.is_proxy.map(|v| v.to_string())),
+        ("provider", Some(response.provider.to_string())),
+    ]
+}
+
+/// Columns printed for every cache entry
+fn entry_fields(entry: &CacheEntryView) -> Vec<(&'static str, String)> {
+    let seconds = |duration: Option<Duration>| {
+        duration
+            .map(|duration| duration.as_secs().to_string())
+            .unwrap_or("never".to_string())
+    };
+    vec![
+        (
+            "target",
+            entry
+                .target
+                .map(|target| target.to_string())
+                .unwrap_or("current".to_string()),
+        ),
+        ("ip", entry.ip.to_string()),
+        ("provider", entry.provider.to_string()),
+        ("age", entry.age.as_secs().to_string()),
+        ("ttl", seconds(entry.ttl)),
+        ("remaining", seconds(entry.remaining_ttl)),
+        ("expired", entry.expired.to_string()),
+        ("summary", entry.summary.clone()),
+    ]
+}
+
+/// Renders the cache entries in the given format, one entry per row
+fn render_entries(entries: &[CacheEntryView], format: Format) -> serde_json::Result<String> {
+    let rows: Vec<Vec<(&str, String)>> = entries.iter().map(entry_fields).collect();
+    let header: Vec<&str> = rows
+        .first()
+        .map(|row| row.iter().map(|(name, _)| *name).collect())
+        .unwrap_or_default();
+    let output = match format {
+        Format::Json => {
+            let rows: Vec<serde_json::Map<String, serde_json::Value>> = rows
+                .into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|(name, value)| (name.to_string(), value.into()))
+                        .collect()
+                })
+                .collect();
+            serde_json::to_string_pretty(&rows)?
+        }
+        Format::Csv => std::iter::once(header.join(","))
+            .chain(rows.iter().map(|row| {
+                row.iter()
+                    .map(|(_, value)| csv_escape(value))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Format::Table => {
+            let widths: Vec<usize> = header
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    rows.iter()
+                        .map(|row| row[i].1.len())
+                        .chain([name.len()])
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            let line = |values: Vec<&str>| {
+                values
+                    .iter()
+                    .zip(&widths)
+                    .map(|(value, width)| format!("{:width$}", value, width = width))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            };
+            std::iter::once(line(header.clone()))
+                .chain(
+                    rows.iter()
+                        .map(|row| line(row.iter().map(|(_, value)| value.as_str()).collect())),
+                )
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    };
+    Ok(output)
+}
+
+/// Quotes a CSV value if needed
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Renders the responses in the given format
+fn render(responses: &[LookupResponse], format: Format) -> serde_json::Result<String> {
+    let output = match format {
+        Format::Json => match responses {
+            [response] => serde_json::to_string_pretty(response),
+            responses => serde_json::to_string_pretty(responses),
+        }?,
+        Format::Csv => {
+            let mut lines = Vec::with_capacity(responses.len() + 1);
+            if let Some(response) = responses.first() {
+                let header: Vec<&str> = fields(response).iter().map(|(name, _)| *name).collect();
+                lines.push(header.join(","));
+            }
+            for response in responses {
+                let values: Vec<String> = fields(response)
+                    .into_iter()
+                    .map(|(_, value)| csv_escape(&value.unwrap_or_default()))
+                    .collect();
+                lines.push(values.join(","));
+            }
+            lines.join("\n")
+        }
+        Format::Table => {
+            let tables: Vec<String> = responses
+                .iter()
+                .map(|response| {
+                    let fields: Vec<(&str, String)> = fields(response)
+                        .into_iter()
+                        .filter_map(|(name, value)| value.map(|value| (name, value)))
+                        .collect();
+                    let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+                    fields
+                        .iter()
+                        .map(|(name, value)| format!("{:width$}  {}", name, value, width = width))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .collect();
+            tables.join("\n\n")
+        }
+    };
+    Ok(output)
+}
+
+/// Loads the configuration and returns the provider chain
+fn setup(args: &mut Args) -> Result<Vec<(LookupProvider, Option<Parameters>)>, Error> {
+    let config = match &args.config {
+        Some(path) => Some(Config::from_file(path)?),
+        None => Config::from_env()?,
+    };
+    let config = config.unwrap_or_default();
+    let providers = if args.providers.is_empty() {
+        config.providers.clone()
+    } else {
+        std::mem::take(&mut args.providers)
+    };
+    public_ip_address::try_init(config)?;
+    Ok(providers)
+}
+
+/// Lists the entries of the cache
+fn list_cache(mut args: Args) -> Result<Vec<CacheEntryView>, Error> {
+    setup(&mut args)?;
+    let cache = ResponseCache::load(None).unwrap_or_default();
+    Ok(cache.entries().collect())
+}
+
+/// Performs the lookups requested by the arguments
+#[maybe_async::maybe_async]
+async fn run(mut args: Args) -> Result<Vec<LookupResponse>, Error> {
+    let providers = setup(&mut args)?;
+    let ttl = public_ip_address::config::config().cache_ttl;
+
+    let target = match args.target {
+        Some(LookupTarget::Ip(ip)) => Some(ip),
+        Some(target) => return perform_lookup_target_with(providers, target).await,
+        None => None,
+    };
+    let response = if args.no_cache {
+        perform_lookup_with(providers, target).await?
+    } else {
+        perform_cached_lookup_with(providers, target, ttl, args.flush).await?
+    };
+    Ok(vec![response])
+}
+
+/// Polls the public address forever, printing it and running the hook on every change
+#[maybe_async::maybe_async]
+async fn watch(mut args: Args) -> Result<(), Error> {
+    let providers = setup(&mut args)?;
+    let format = args.format;
+    let exec = args.exec.take();
+    let mut watcher = Watcher::new(providers)
+        .with_debounce(args.debounce.unwrap_or(1))
+        .on_change(move |previous, response| {
+            match render(std::slice::from_ref(response), format) {
+                Ok(output) => println!("{}", output),
+                Err(e) => eprintln!("pubip: {}", report(&e)),
+            }
+            if let Some(command) = &exec {
+                run_hook(command, previous, response.ip);
+            }
+        });
+    if let Some(interval) = args.interval {
+        watcher = watcher.with_interval(Duration::from_secs(interval));
+    }
+    if let Some(provider) = args.confirm {
+        watcher = watcher.with_confirmation(provider, None);
+    }
+    loop {
+        if let Err(e) = watcher.poll().await {
+            eprintln!("pubip: {}", report(&e));
+        }
+        sleep(watcher.next_delay()).await;
+    }
+}
+
+/// Runs the shell command with the new and the previous address in the environment
+fn run_hook(command: &str, previous: Option<IpAddr>, address: IpAddr) {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let status = Command::new(shell)
+        .args([flag, command])
+        .env("PUBIP_ADDRESS", address.to_string())
+        .env(
+            "PUBIP_PREVIOUS",
+            previous.map(|ip| ip.to_string()).unwrap_or_default(),
+        )
+        .status();
+    match status {
+        Ok(status) if !status.success() => eprintln!("pubip: hook exited with {}", status),
+        Ok(_) => {}
+        Err(e) => eprintln!("pubip: failed to run hook: {}", e),
+    }
+}
+
+#[cfg(not(feature = "blocking"))]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(feature = "blocking")]
+fn sleep(duration: Duration) {
+    std::thread::sleep(duration)
+}
+
+#[cfg_attr(not(feature = "blocking"), tokio::main(flavor = "current_thread"))]
+#[maybe_async::maybe_async]
+async fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("pubip: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    if args.version {
+        println!("pubip {}", env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+
+    if args.watch {
+        if let Err(e) = watch(args).await {
+            eprintln!("pubip: {}", report(&e));
+        }
+        return ExitCode::FAILURE;
+    }
+
+    let format = args.format;
+    let output = if args.cache_list {
+        match list_cache(args) {
+            Ok(entries) => render_entries(&entries, format).map_err(|e| report(&e)),
+            Err(e) => Err(report(&e)),
+        }
+    } else {
+        match run(args).await {
+            Ok(responses) => render(&responses, format).map_err(|e| report(&e)),
+            Err(e) => Err(report(&e)),
+        }
+    };
+    match output {
+        Ok(output) => {
+            println!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("pubip: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Formats the error with its chain of sources
+fn report(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message.push_str(&format!(": {}", e));
+        source = e.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(args(&[]).unwrap(), Args::default());
+
+        let parsed = args(&[
+            "8.8.8.8",
+            "-p",
+            "ipinfo",
+            "--key",
+            "abc",
+            "--provider",
+            "ipwhois",
+            "-f",
+            "json",
+            "--flush",
+        ])
+        .unwrap();
+        assert_eq!(
+            parsed.target,
+            Some(LookupTarget::Ip("8.8.8.8".parse().unwrap()))
+        );
+        assert_eq!(
+            parsed.providers,
+            vec![
+                (
+                    LookupProvider::IpInfo,
+                    Some(Parameters::new("abc".to_string()))
+                ),
+                (LookupProvider::IpWhoIs, None)
+            ]
+        );
+        assert_eq!(parsed.format, Format::Json);
+        assert!(parsed.flush);
+
+        assert!(args(&["--key", "abc"]).is_err(), "Key without provider");
+        assert!(args(&["-f", "xml"]).is_err());
+        assert!(args(&["-p"]).is_err());
+        assert!(args(&["--verbose"]).is_err());
+        assert!(args(&["1.1.1.1", "8.8.8.8"]).is_err());
+
+        let parsed = args(&[
+            "-w",
+            "--interval",
+            "60",
+            "--confirm",
+            "ipinfo",
+            "-e",
+            "echo",
+        ])
+        .unwrap();
+        assert!(parsed.watch);
+        assert_eq!(parsed.interval, Some(60));
+        assert_eq!(parsed.confirm, Some(LookupProvider::IpInfo));
+        assert_eq!(parsed.exec.as_deref(), Some("echo"));
+        assert!(args(&["--watch", "1.1.1.1"]).is_err());
+        assert!(args(&["--interval", "soon"]).is_err());
+
+        let parsed = args(&["cache", "list", "-f", "csv"]).unwrap();
+        assert!(parsed.cache_list);
+        assert_eq!(parsed.format, Format::Csv);
+        assert!(args(&["cache"]).is_err());
+        assert!(args(&["cache", "purge"]).is_err());
+        assert!(args(&["cache", "list", "1.1.1.1"]).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut response = LookupResponse::new(
+            "1.1.1.1".parse().unwrap(),
+            LookupProvider::Mock("1.1.1.1".to_string()),
+        );
+        response.asn_org = Some("Cloudflare, Inc.".to_string());
+        let csv = render(&[response.clone()], Format::Csv).unwrap();
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().starts_with("ip,continent,"));
+        assert!(lines.next().unwrap().contains(",\"Cloudflare, Inc.\","));
+
+        let table = render(&[response.clone()], Format::Table).unwrap();
+        assert!(table.starts_with("ip        1.1.1.1"));
+
+        let json = render(&[response], Format::Json).unwrap();
+        assert!(json.starts_with('{'));
+    }
+
+    #[test]
+    fn test_render_entries() {
+        let mut cache = ResponseCache::default();
+        let response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpWhoIs);
+        cache.update_current(&response, None);
+        let entries: Vec<CacheEntryView> = cache.entries().collect();
+
+        let table = render_entries(&entries, Format::Table).unwrap();
+        let mut lines = table.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("target   ip       provider"));
+        assert!(lines.next().unwrap().starts_with("current  1.1.1.1  "));
+
+        let csv = render_entries(&entries, Format::Csv).unwrap();
+        assert!(csv.ends_with(",never,never,false,"));
+
+        let json = render_entries(&entries, Format::Json).unwrap();
+        assert!(json.contains("\"remaining\": \"never\""));
+    }
+}
@@ -0,0 +1,249 @@
+//! 🧱 Synchronous interface for async builds
+//!
+//! The module mirrors the lookup functions of the crate root with a synchronous signature, so
+//! that synchronous and async code can share a single build of the crate. The requests are driven
+//! on the small internal runtime of [`LookupServiceBlocking`](crate::lookup::LookupServiceBlocking),
+//! calls made from inside an async runtime are moved to a helper thread instead of panicking.
+//!
+//! With the `blocking` feature the crate root is already synchronous and this module only
+//! re-exports it, so code written against `public_ip_address::blocking` compiles in both modes.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use public_ip_address::{blocking, lookup::LookupProvider};
+//!
+//! let providers = vec![(LookupProvider::IpWhoIs, None)];
+//! let response = blocking::perform_lookup_with(providers, None)?;
+//! println!("{}", response.ip);
+//! # Ok::<(), public_ip_address::error::Error>(())
+//! ```
+
+pub use crate::lookup::LookupServiceBlocking as LookupService;
+
+use crate::{
+    cache::CacheLocation,
+    error::Result,
+    lookup::{
+        sync::block_on, LookupOptions, LookupProvider, LookupTarget, Parameters, ProviderConfig,
+        ProviderHealth,
+    },
+    LookupResponse,
+};
+use std::{net::IpAddr, path::Path, time::Duration};
+
+/// Performs a lookup with the configured providers and caches the result, see [`crate::perform_lookup`]
+pub fn perform_lookup(target: Option<IpAddr>) -> Result<LookupResponse> {
+    block_on(crate::perform_lookup(target))?
+}
+
+/// Performs a cached lookup with the providers of a configuration file, see [`crate::perform_lookup_from_config`]
+pub fn perform_lookup_from_config(path: impl AsRef<Path>) -> Result<LookupResponse> {
+    let path = path.as_ref().to_path_buf();
+    block_on(crate::perform_lookup_from_config(path))?
+}
+
+/// Performs a lookup using a list of providers, see [`crate::perform_lookup_with`]
+pub fn perform_lookup_with(
+    providers: Vec<impl Into<ProviderConfig>>,
+    target: Option<IpAddr>,
+) -> Result<LookupResponse> {
+    let providers: Vec<ProviderConfig> = providers.into_iter().map(Into::into).collect();
+    block_on(crate::perform_lookup_with(providers, target))?
+}
+
+/// Performs a lookup within a time budget, see [`crate::perform_lookup_with_options`]
+pub fn perform_lookup_with_options(
+    providers: Vec<impl Into<ProviderConfig>>,
+    target: Option<IpAddr>,
+    options: LookupOptions,
+) -> Result<LookupResponse> {
+    let providers: Vec<ProviderConfig> = providers.into_iter().map(Into::into).collect();
+    block_on(crate::perform_lookup_with_options(
+        providers, target, options,
+    ))?
+}
+
+/// Looks up the origin network of an address, see [`crate::lookup_asn`]
+pub fn lookup_asn(target: Option<IpAddr>) -> Result<LookupResponse> {
+    block_on(crate::lookup_asn(target))?
+}
+
+/// Performs a lookup for every address of the target, see [`crate::perform_lookup_target_with`]
+pub fn perform_lookup_target_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: LookupTarget,
+) -> Result<Vec<LookupResponse>> {
+    block_on(crate::perform_lookup_target_with(providers, target))?
+}
+
+/// Queries all providers concurrently and returns the first response, see [`crate::perform_race_lookup_with`]
+pub fn perform_race_lookup_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: Option<IpAddr>,
+) -> Result<LookupResponse> {
+    block_on(crate::perform_race_lookup_with(providers, target))?
+}
+
+/// Performs a lookup with the providers drawn by weight and remaining quota, see [`crate::perform_rotating_lookup_with`]
+pub fn perform_rotating_lookup_with(
+    providers: Vec<impl Into<ProviderConfig>>,
+    target: Option<IpAddr>,
+) -> Result<LookupResponse> {
+    let providers: Vec<ProviderConfig> = providers.into_iter().map(Into::into).collect();
+    block_on(crate::perform_rotating_lookup_with(providers, target))?
+}
+
+/// Performs a rotating lookup with the request counts kept at the given location, see [`crate::perform_rotating_lookup_at`]
+pub fn perform_rotating_lookup_at(
+    providers: Vec<impl Into<ProviderConfig>>,
+    target: Option<IpAddr>,
+    location: &CacheLocation,
+) -> Result<LookupResponse> {
+    let providers: Vec<ProviderConfig> = providers.into_iter().map(Into::into).collect();
+    block_on(crate::perform_rotating_lookup_at(
+        providers, target, location,
+    ))?
+}
+
+/// Performs a lookup and caches the result, see [`crate::perform_cached_lookup_with`]
+pub fn perform_cached_lookup_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: Option<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+) -> Result<LookupResponse> {
+    block_on(crate::perform_cached_lookup_with(
+        providers, target, ttl, flush,
+    ))?
+}
+
+/// Performs a lookup and caches the result at the given location, see [`crate::perform_cached_lookup_at`]
+pub fn perform_cached_lookup_at(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: Option<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+    location: &CacheLocation,
+) -> Result<LookupResponse> {
+    block_on(crate::perform_cached_lookup_at(
+        providers, target, ttl, flush, location,
+    ))?
+}
+
+/// Performs cached lookups for a list of targets, see [`crate::perform_cached_bulk_lookup_with`]
+pub fn perform_cached_bulk_lookup_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    targets: Vec<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+) -> Vec<Result<LookupResponse>> {
+    let count = targets.len();
+    block_on(crate::perform_cached_bulk_lookup_with(
+        providers, targets, ttl, flush,
+    ))
+    .unwrap_or_else(|e| failed(count, e))
+}
+
+/// Spreads cached lookups for a list of targets across the providers, see [`crate::perform_fan_out_bulk_lookup_with`]
+pub fn perform_fan_out_bulk_lookup_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    targets: Vec<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+) -> Vec<Result<LookupResponse>> {
+    let count = targets.len();
+    block_on(crate::perform_fan_out_bulk_lookup_with(
+        providers, targets, ttl, flush,
+    ))
+    .unwrap_or_else(|e| failed(count, e))
+}
+
+/// Performs cached lookups for a list of targets with the cache stored at the given location, see [`crate::perform_cached_bulk_lookup_at`]
+pub fn perform_cached_bulk_lookup_at(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    targets: Vec<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+    location: &CacheLocation,
+) -> Vec<Result<LookupResponse>> {
+    let count = targets.len();
+    block_on(crate::perform_cached_bulk_lookup_at(
+        providers, targets, ttl, flush, location,
+    ))
+    .unwrap_or_else(|e| failed(count, e))
+}
+
+/// Spreads cached lookups for a list of targets across the providers with the cache stored at the given location, see [`crate::perform_fan_out_bulk_lookup_at`]
+pub fn perform_fan_out_bulk_lookup_at(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    targets: Vec<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+    location: &CacheLocation,
+) -> Vec<Result<LookupResponse>> {
+    let count = targets.len();
+    block_on(crate::perform_fan_out_bulk_lookup_at(
+        providers, targets, ttl, flush, location,
+    ))
+    .unwrap_or_else(|e| failed(count, e))
+}
+
+/// Checks the health of a list of providers concurrently, see [`crate::check_providers`]
+pub fn check_providers(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+) -> Vec<ProviderHealth> {
+    let checked: Vec<LookupProvider> = providers.iter().map(|(p, _)| p.clone()).collect();
+    block_on(crate::check_providers(providers)).unwrap_or_else(|e| {
+        checked
+            .into_iter()
+            .map(|provider| ProviderHealth {
+                provider,
+                reachable: false,
+                parsed: false,
+                latency: Duration::ZERO,
+                error: Some(e.to_string()),
+            })
+            .collect()
+    })
+}
+
+/// Reports the runtime failure for every target of a bulk lookup
+fn failed(count: usize, error: crate::lookup::error::LookupError) -> Vec<Result<LookupResponse>> {
+    let message = error.to_string();
+    (0..count)
+        .map(|_| Err(crate::lookup::error::LookupError::GenericError(message.clone()).into()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::error::LookupError;
+
+    #[test]
+    fn test_blocking_lookup() {
+        let providers = vec![(LookupProvider::Mock("1.1.1.1".to_string()), None)];
+        let response = perform_lookup_with(providers.clone(), None).unwrap();
+        assert_eq!(response.ip, "1.1.1.1".parse::<IpAddr>().unwrap());
+
+        let responses = perform_lookup_target_with(providers, "8.8.8.8".parse().unwrap()).unwrap();
+        assert_eq!(responses.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_inside_runtime() {
+        let providers = vec![(LookupProvider::Mock("1.1.1.1".to_string()), None)];
+        let blocking = perform_lookup_with(providers.clone(), Some("10.0.0.1".parse().unwrap()));
+        assert!(
+            matches!(
+                blocking,
+                Err(crate::error::Error::LookupError(
+                    LookupError::NonPublicAddress(_)
+                ))
+            ),
+            "Lookup inside a runtime should not panic"
+        );
+        let response = crate::perform_lookup_with(providers, None).await.unwrap();
+        assert_eq!(response.ip, "1.1.1.1".parse::<IpAddr>().unwrap());
+    }
+}
@@ -0,0 +1,258 @@
+//! # 🏗️ Lookup builder
+//!
+//! `Lookup::builder()` configures a lookup step by step instead of through the positional
+//! arguments of the `perform_*` functions. Options left out fall back to the defaults: the
+//! configured provider chain, the current public address, no caching and providers tried in order.
+//!
+//! ## Example
+//! ```rust
+//! use public_ip_address::{lookup::LookupProvider, Lookup, LookupStrategy};
+//!
+//! # use std::error::Error;
+//! # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+//! # #[maybe_async::maybe_async]
+//! # async fn main() -> Result<(), Box<dyn Error>> {
+//! let lookup = Lookup::builder()
+//!     .with_provider(LookupProvider::IpWhoIs, None)
+//!     .with_provider(LookupProvider::IpInfo, None)
+//!     .with_target("8.8.8.8".parse()?)
+//!     .with_cache_ttl(Some(60))
+//!     .with_strategy(LookupStrategy::Race);
+//!
+//! match lookup.perform().await {
+//!     Ok(response) => {
+//!         // Handle successful response
+//!     }
+//!     Err(e) => {
+//!         // Handle error
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    cache::CacheLocation,
+    cached_lookup, config,
+    error::Result,
+    lookup::{LookupOptions, LookupProvider, Parameters, ProviderConfig},
+    lookup_chain, perform_race_lookup_with, perform_rotating_lookup_at, LookupResponse,
+};
+use std::{net::IpAddr, time::Duration};
+
+/// How the providers of a lookup are queried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum LookupStrategy {
+    /// Providers are tried in order until one answers, see `perform_lookup_with`
+    #[default]
+    Sequential,
+    /// All providers are queried concurrently and the first answer wins, see `perform_race_lookup_with`
+    Race,
+    /// Providers are tried in a weighted random order spreading the requests over their daily
+    /// quotas, see `perform_rotating_lookup_with`
+    Rotate,
+}
+
+/// Entry point of the lookup builder
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Lookup;
+
+impl Lookup {
+    /// Returns a builder with the default options
+    pub fn builder() -> LookupBuilder {
+        LookupBuilder::default()
+    }
+}
+
+/// Options of a lookup, performed with [`LookupBuilder::perform`]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct LookupBuilder {
+    providers: Vec<ProviderConfig>,
+    target: Option<IpAddr>,
+    cache_ttl: Option<Option<u64>>,
+    flush: bool,
+    cache_location: CacheLocation,
+    strategy: LookupStrategy,
+    options: LookupOptions,
+}
+
+impl LookupBuilder {
+    /// Sets the provider chain, replacing the providers added so far
+    pub fn with_providers(mut self, providers: Vec<impl Into<ProviderConfig>>) -> Self {
+        self.providers = providers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Adds a provider at the end of the chain
+    pub fn with_provider(
+        mut self,
+        provider: LookupProvider,
+        parameters: Option<Parameters>,
+    ) -> Self {
+        self.providers.push((provider, parameters).into());
+        self
+    }
+
+    /// Adds a provider with its own timeout, retries and weight at the end of the chain
+    pub fn with_provider_config(mut self, config: ProviderConfig) -> Self {
+        self.providers.push(config);
+        self
+    }
+
+    /// Looks up the target address instead of the current public address
+    pub fn with_target(mut self, target: IpAddr) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Caches the response for `ttl` seconds, `None` never expires
+    pub fn with_cache_ttl(mut self, ttl: Option<u64>) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Ignores the cached response and makes a new request, the new response is still cached
+    pub fn with_flush(mut self) -> Self {
+        self.flush = true;
+        self
+    }
+
+    /// Serves the expired cached response instead of an error when all providers fail
+    ///
+    /// Only applies together with [`LookupBuilder::with_cache_ttl`].
+    pub fn with_serve_stale(mut self) -> Self {
+        self.options = self.options.with_serve_stale();
+        self
+    }
+
+    /// Refreshes cached responses older than `soft_ttl` in the background, see
+    /// [`LookupOptions::with_stale_while_revalidate`]
+    ///
+    /// Only applies together with [`LookupBuilder::with_cache_ttl`].
+    pub fn with_stale_while_revalidate(mut self, soft_ttl: Duration) -> Self {
+        self.options = self.options.with_stale_while_revalidate(soft_ttl);
+        self
+    }
+
+    /// Stores the cached response at the given location instead of the default cache file
+    ///
+    /// Only applies together with [`LookupBuilder::with_cache_ttl`].
+    pub fn with_cache_location(mut self, location: CacheLocation) -> Self {
+        self.cache_location = location;
+        self
+    }
+
+    /// Sets how the providers are queried
+    pub fn with_strategy(mut self, strategy: LookupStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets the deadline for the whole provider chain
+    ///
+    /// Only applies to the sequential strategy.
+    pub fn with_total_timeout(mut self, timeout: Duration) -> Self {
+        self.options = self.options.with_total_timeout(timeout);
+        self
+    }
+
+    /// Sets the timeout for each provider request
+    ///
+    /// Only applies to the sequential strategy.
+    pub fn with_per_provider_timeout(mut self, timeout: Duration) -> Self {
+        self.options = self.options.with_per_provider_timeout(timeout);
+        self
+    }
+
+    /// Performs the lookup
+    ///
+    /// # Returns
+    ///
+    /// * A `Result` containing either a successful `LookupResponse` or an `Error` if the lookup or caching failed.
+    #[maybe_async::maybe_async]
+    pub async fn perform(self) -> Result<LookupResponse> {
+        let providers = if self.providers.is_empty() {
+            let providers = config::config().providers.clone();
+            providers.into_iter().map(Into::into).collect()
+        } else {
+            self.providers
+        };
+        match (self.cache_ttl, self.strategy) {
+            (Some(ttl), strategy) => {
+                cached_lookup(
+                    providers,
+                    self.target,
+                    ttl,
+                    self.flush,
+                    strategy,
+                    self.options,
+                    &self.cache_location,
+                )
+                .await
+            }
+            (None, LookupStrategy::Sequential) => {
+                let location = &self.cache_location;
+                lookup_chain(
+                    providers,
+                    self.target,
+                    self.options,
+                    location,
+                    None,
+                    &mut Vec::new(),
+                )
+                .await
+            }
+            (None, LookupStrategy::Race) => {
+                let providers = providers.into_iter().map(Into::into).collect();
+                perform_race_lookup_with(providers, self.target).await
+            }
+            (None, LookupStrategy::Rotate) => {
+                perform_rotating_lookup_at(providers, self.target, &self.cache_location).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::SelectionReason;
+
+    #[test]
+    fn test_builder() {
+        let builder = Lookup::builder()
+            .with_providers(vec![(LookupProvider::IpInfo, None)])
+            .with_provider(LookupProvider::IpWhoIs, None)
+            .with_cache_ttl(None)
+            .with_flush();
+        assert_eq!(builder.providers.len(), 2);
+        assert_eq!(builder.cache_ttl, Some(None));
+        assert!(builder.flush);
+        assert_eq!(builder.strategy, LookupStrategy::Sequential);
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_perform() {
+        let response = Lookup::builder()
+            .with_provider(LookupProvider::Mock("timeout".to_string()), None)
+            .with_provider(LookupProvider::Mock("1.1.1.1".to_string()), None)
+            .with_per_provider_timeout(Duration::from_secs(1))
+            .perform()
+            .await;
+        let response = response.unwrap();
+        assert_eq!(response.ip, "1.1.1.1".parse::<IpAddr>().unwrap());
+        assert_eq!(response.meta.selection_reason, SelectionReason::Fallback(1));
+
+        let response = Lookup::builder()
+            .with_provider(LookupProvider::Mock("timeout".to_string()), None)
+            .with_provider(LookupProvider::Mock("1.1.1.1".to_string()), None)
+            .with_strategy(LookupStrategy::Race)
+            .perform()
+            .await;
+        let response = response.unwrap();
+        assert_eq!(response.meta.selection_reason, SelectionReason::RaceWinner);
+    }
+}
@@ -0,0 +1,328 @@
+//! # 📊 Bulk result helpers
+//!
+//! This module provides the `BulkResults` trait with the common post-processing steps for the
+//! responses of a bulk lookup: stable sorting by country, ASN or latency, grouping with summary
+//! counts, and pagination. `BulkSummary` condenses the responses into a human readable report.
+//!
+//! ## Example
+//! ```rust
+//! use public_ip_address::{
+//!     bulk::{BulkResults, BulkSummary},
+//!     lookup::LookupProvider,
+//!     response::LookupResponse,
+//! };
+//!
+//! let mut responses = vec![
+//!     LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpWhoIs),
+//!     LookupResponse::new("8.8.8.8".parse().unwrap(), LookupProvider::IpWhoIs),
+//! ];
+//! responses.sort_by_country();
+//! for group in responses.group_by_country() {
+//!     println!("{}: {}", group.key.as_deref().unwrap_or("unknown"), group.count());
+//! }
+//! println!("{}", BulkSummary::from(responses.as_slice()));
+//! ```
+
+use crate::response::LookupResponse;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// Responses sharing the same grouping key
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ResponseGroup<'a> {
+    /// Value the responses are grouped by, `None` for responses missing it
+    pub key: Option<String>,
+    /// Responses in the group, in their original order
+    pub responses: Vec<&'a LookupResponse>,
+}
+
+impl ResponseGroup<'_> {
+    /// Returns the number of responses in the group
+    pub fn count(&self) -> usize {
+        self.responses.len()
+    }
+}
+
+/// Sorting, grouping and pagination of bulk lookup results
+///
+/// All sorts are stable, responses with equal keys keep their order. Responses missing the
+/// sorting key are moved to the end.
+pub trait BulkResults {
+    /// Sorts the responses by country code, then by country name
+    fn sort_by_country(&mut self);
+    /// Sorts the responses by autonomous system number
+    fn sort_by_asn(&mut self);
+    /// Sorts the responses by the duration of their request, fastest first
+    fn sort_by_latency(&mut self);
+    /// Groups the responses by country code, largest group first
+    fn group_by_country(&self) -> Vec<ResponseGroup<'_>>;
+    /// Groups the responses by autonomous system number, largest group first
+    fn group_by_asn(&self) -> Vec<ResponseGroup<'_>>;
+    /// Returns the page with the given zero based index, empty past the last page
+    fn page(&self, index: usize, per_page: usize) -> &[LookupResponse];
+    /// Returns the number of pages of the given size
+    fn page_count(&self, per_page: usize) -> usize;
+}
+
+impl BulkResults for [LookupResponse] {
+    fn sort_by_country(&mut self) {
+        self.sort_by(|a, b| {
+            missing_last(&a.country_code, &b.country_code)
+                .then_with(|| missing_last(&a.country, &b.country))
+        });
+    }
+
+    fn sort_by_asn(&mut self) {
+        self.sort_by(|a, b| missing_last(&asn_number(a), &asn_number(b)));
+    }
+
+    fn sort_by_latency(&mut self) {
+        self.sort_by(|a, b| missing_last(&a.meta.duration, &b.meta.duration));
+    }
+
+    fn group_by_country(&self) -> Vec<ResponseGroup<'_>> {
+        group_by(self, |response| {
+            response.country_code.clone().or(response.country.clone())
+        })
+    }
+
+    fn group_by_asn(&self) -> Vec<ResponseGroup<'_>> {
+        group_by(self, |response| {
+            asn_number(response)
+                .map(|asn| format!("AS{}", asn))
+                .or(response.asn.clone())
+        })
+    }
+
+    fn page(&self, index: usize, per_page: usize) -> &[LookupResponse] {
+        let per_page = per_page.max(1);
+        let start = index.saturating_mul(per_page).min(self.len());
+        let end = start.saturating_add(per_page).min(self.len());
+        &self[start..end]
+    }
+
+    fn page_count(&self, per_page: usize) -> usize {
+        self.len().div_ceil(per_page.max(1))
+    }
+}
+
+/// Summary statistics of bulk lookup results
+#[derive(Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct BulkSummary {
+    /// Number of responses
+    pub total: usize,
+    /// Number of responses per country code, largest first, without the unknown countries
+    pub countries: Vec<(String, usize)>,
+    /// Number of responses per autonomous system, largest first, without the unknown ones
+    pub asns: Vec<(String, usize)>,
+    /// Number of addresses reported as proxy or VPN
+    pub proxies: usize,
+    /// Number of distinct `/24` IPv4 and `/48` IPv6 prefixes
+    pub prefixes: usize,
+}
+
+impl BulkSummary {
+    /// Returns the share of addresses reported as proxy or VPN, between `0.0` and `1.0`
+    pub fn proxy_share(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.proxies as f64 / self.total as f64
+        }
+    }
+}
+
+impl From<&[LookupResponse]> for BulkSummary {
+    fn from(responses: &[LookupResponse]) -> Self {
+        let counts = |groups: Vec<ResponseGroup>| {
+            groups
+                .into_iter()
+                .filter_map(|group| Some((group.key.clone()?, group.count())))
+                .collect()
+        };
+        let prefixes: BTreeSet<IpAddr> = responses.iter().map(|r| prefix(r.ip)).collect();
+        BulkSummary {
+            total: responses.len(),
+            countries: counts(responses.group_by_country()),
+            asns: counts(responses.group_by_asn()),
+            proxies: responses
+                .iter()
+                .filter(|response| response.is_proxy == Some(true))
+                .count(),
+            prefixes: prefixes.len(),
+        }
+    }
+}
+
+impl fmt::Display for BulkSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |counts: &[(String, usize)]| {
+            counts
+                .iter()
+                .map(|(key, count)| format!("{} {}", key, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(f, "Addresses: {}", self.total)?;
+        writeln!(f, "Prefixes: {}", self.prefixes)?;
+        writeln!(
+            f,
+            "Proxy/VPN: {} ({:.1}%)",
+            self.proxies,
+            self.proxy_share() * 100.0
+        )?;
+        writeln!(f, "Countries: {}", list(&self.countries))?;
+        write!(f, "ASNs: {}", list(&self.asns))
+    }
+}
+
+/// Masks the address to its `/24` IPv4 or `/48` IPv6 prefix
+fn prefix(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => Ipv4Addr::from(u32::from(ip) & 0xffff_ff00).into(),
+        IpAddr::V6(ip) => Ipv6Addr::from(u128::from(ip) & !((1u128 << 80) - 1)).into(),
+    }
+}
+
+/// Orders present values ascending, followed by the missing ones
+fn missing_last<T: Ord>(a: &Option<T>, b: &Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Parses the numeric part of the ASN, providers report it as `13335` or `AS13335`
+fn asn_number(response: &LookupResponse) -> Option<u32> {
+    let asn = response.asn.as_deref()?.trim();
+    let asn = asn
+        .strip_prefix("AS")
+        .or_else(|| asn.strip_prefix("as"))
+        .unwrap_or(asn);
+    asn.parse().ok()
+}
+
+/// Groups the responses by key, largest group first, ties ordered by key with missing keys last
+fn group_by(
+    responses: &[LookupResponse],
+    key: impl Fn(&LookupResponse) -> Option<String>,
+) -> Vec<ResponseGroup<'_>> {
+    let mut groups: BTreeMap<Option<String>, Vec<&LookupResponse>> = BTreeMap::new();
+    for response in responses {
+        groups.entry(key(response)).or_default().push(response);
+    }
+    let mut groups: Vec<ResponseGroup> = groups
+        .into_iter()
+        .map(|(key, responses)| ResponseGroup { key, responses })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.count()
+            .cmp(&a.count())
+            .then_with(|| missing_last(&a.key, &b.key))
+    });
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::LookupProvider;
+    use std::time::Duration;
+
+    fn response(ip: &str, country: Option<&str>, asn: Option<&str>) -> LookupResponse {
+        let mut response = LookupResponse::new(ip.parse().unwrap(), LookupProvider::IpWhoIs);
+        response.country_code = country.map(str::to_string);
+        response.asn = asn.map(str::to_string);
+        response
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut responses = [
+            response("1.1.1.1", Some("US"), Some("AS13335")),
+            response("2.2.2.2", None, None),
+            response("3.3.3.3", Some("DE"), Some("3320")),
+            response("4.4.4.4", Some("US"), Some("AS15169")),
+        ];
+        responses.sort_by_country();
+        let ips: Vec<String> = responses.iter().map(|r| r.ip.to_string()).collect();
+        assert_eq!(ips, ["3.3.3.3", "1.1.1.1", "4.4.4.4", "2.2.2.2"]);
+
+        responses.sort_by_asn();
+        let ips: Vec<String> = responses.iter().map(|r| r.ip.to_string()).collect();
+        assert_eq!(ips, ["3.3.3.3", "1.1.1.1", "4.4.4.4", "2.2.2.2"]);
+
+        responses[3].meta.duration = Some(Duration::from_millis(10));
+        responses[1].meta.duration = Some(Duration::from_millis(20));
+        responses.sort_by_latency();
+        let ips: Vec<String> = responses.iter().map(|r| r.ip.to_string()).collect();
+        assert_eq!(ips, ["2.2.2.2", "1.1.1.1", "3.3.3.3", "4.4.4.4"]);
+    }
+
+    #[test]
+    fn test_group() {
+        let responses = [
+            response("1.1.1.1", Some("US"), Some("AS13335")),
+            response("2.2.2.2", None, Some("13335")),
+            response("3.3.3.3", Some("DE"), None),
+            response("4.4.4.4", Some("US"), Some("AS15169")),
+        ];
+        let groups = responses.group_by_country();
+        let counts: Vec<(Option<&str>, usize)> = groups
+            .iter()
+            .map(|g| (g.key.as_deref(), g.count()))
+            .collect();
+        assert_eq!(counts, [(Some("US"), 2), (Some("DE"), 1), (None, 1)]);
+
+        let groups = responses.group_by_asn();
+        assert_eq!(groups[0].key.as_deref(), Some("AS13335"));
+        assert_eq!(groups[0].count(), 2, "ASN formats should be merged");
+    }
+
+    #[test]
+    fn test_page() {
+        let responses: Vec<LookupResponse> = (1..=5)
+            .map(|i| response(&format!("1.1.1.{}", i), None, None))
+            .collect();
+        assert_eq!(responses.page_count(2), 3);
+        assert_eq!(responses.page(0, 2).len(), 2);
+        assert_eq!(responses.page(2, 2)[0].ip.to_string(), "1.1.1.5");
+        assert!(responses.page(3, 2).is_empty());
+        assert!(responses.page(usize::MAX, usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut responses = [
+            response("1.1.1.1", Some("US"), Some("AS13335")),
+            response("1.1.1.2", Some("US"), Some("13335")),
+            response("8.8.8.8", Some("US"), None),
+            response("2a00:1450::1", Some("DE"), Some("AS15169")),
+        ];
+        responses[2].is_proxy = Some(true);
+        let summary = BulkSummary::from(responses.as_slice());
+        assert_eq!(summary.total, 4);
+        assert_eq!(
+            summary.countries,
+            [("US".to_string(), 3), ("DE".to_string(), 1)]
+        );
+        assert_eq!(
+            summary.asns,
+            [("AS13335".to_string(), 2), ("AS15169".to_string(), 1)]
+        );
+        assert_eq!(summary.prefixes, 3, "1.1.1.0/24 should be counted once");
+        assert_eq!(summary.proxy_share(), 0.25);
+        assert!(summary.to_string().contains("Proxy/VPN: 1 (25.0%)"));
+
+        let summary = BulkSummary::from(&[][..]);
+        assert_eq!(summary.proxy_share(), 0.0);
+    }
+}
@@ -9,6 +9,10 @@
 //!
 //! If the `encryption` feature is enabled, the cache is encrypted using AEAD.
 //!
+//! If the `integrity` feature is enabled, the cache file is tagged with an HMAC-SHA256 keyed with
+//! the machine id or the secret set through [`Config::with_cache_secret`](crate::Config::with_cache_secret).
+//! A tampered file is treated as a cache miss instead of being deserialized.
+//!
 //! ## Example
 //! ```rust
 //! use std::error::Error;
@@ -30,16 +34,21 @@
 //! }
 //! ```
 
-use crate::{error::CacheError, LookupResponse};
+use crate::{
+    error::CacheError,
+    lookup::{DailyQuota, LookupProvider, Parameters},
+    time::{self, Instant},
+    LookupResponse,
+};
 use directories::BaseDirs;
-use log::{debug, trace};
+use log::{debug, trace, warn};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
-    fs,
-    fs::File,
-    io::prelude::*,
+    env, fs,
     net::IpAddr,
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
     time::{Duration, SystemTime},
 };
 
@@ -49,6 +58,11 @@ use cocoon::Cocoon;
 /// Result type wrapper for the cache
 pub type Result<T> = std::result::Result<T, CacheError>;
 
+/// Version of the cache file schema written by this crate
+///
+/// Cache files written by older versions are migrated when they are loaded or imported.
+pub const CACHE_VERSION: u32 = 1;
+
 /// Represents an entry of the cached response
 ///
 /// It contains the `LookupResponse`, the time when the response was cached, and the time-to-live (TTL) of the cache.
@@ -59,6 +73,16 @@ pub struct ResponseRecord {
     pub response: LookupResponse,
     response_time: SystemTime,
     ttl: Option<u64>,
+    /// Provider chosen to refresh the response once it expires
+    #[serde(default)]
+    pub refresh_hint: Option<LookupProvider>,
+    /// Entity tag of the provider reply, used to revalidate the response once it expires
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// The response is past its soft TTL and is refreshed on the next lookup, set when it
+    /// couldn't be refreshed in the background
+    #[serde(default)]
+    pub refresh_pending: bool,
 }
 
 impl ResponseRecord {
@@ -68,12 +92,15 @@ impl ResponseRecord {
     ///
     /// * `response` - A `LookupResponse` to be cached.
     /// * `ttl` - An optional `u64` value representing after how many seconds the cached value expires.
-    /// None means the cache never expires.
+    ///   None means the cache never expires.
     pub fn new(response: LookupResponse, ttl: Option<u64>) -> ResponseRecord {
         ResponseRecord {
+            etag: response.meta.etag().map(str::to_string),
             response,
-            response_time: SystemTime::now(),
+            response_time: time::now(),
             ttl,
+            refresh_hint: None,
+            refresh_pending: false,
         }
     }
 
@@ -82,7 +109,7 @@ impl ResponseRecord {
     /// If the TTL is not set, the function assumes that the cache never expires and returns false.
     pub fn is_expired(&self) -> bool {
         if let Some(ttl) = self.ttl {
-            let difference = SystemTime::now()
+            let difference = time::now()
                 .duration_since(self.response_time)
                 .unwrap_or_default();
             difference >= Duration::from_secs(ttl)
@@ -92,27 +119,181 @@ impl ResponseRecord {
         }
     }
 
+    /// Determines if the cached response is still fresh but about to expire.
+    ///
+    /// A response is about to expire once less than a fifth of its TTL remains.
+    pub fn is_expiring(&self) -> bool {
+        if let Some(ttl) = self.ttl {
+            let difference = time::now()
+                .duration_since(self.response_time)
+                .unwrap_or_default();
+            let ttl = Duration::from_secs(ttl);
+            difference < ttl && difference >= ttl - ttl / 5
+        } else {
+            // No TTL, cache never expires
+            false
+        }
+    }
+
     /// Returns the IP address of the cached response.
     pub fn ip(&self) -> std::net::IpAddr {
         self.response.ip
     }
+
+    /// Returns how long ago the response was cached.
+    pub fn age(&self) -> Duration {
+        time::now()
+            .duration_since(self.response_time)
+            .unwrap_or_default()
+    }
+
+    /// Returns the time-to-live of the cached response, `None` if it never expires.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl.map(Duration::from_secs)
+    }
+
+    /// Returns how long until the cached response expires.
+    ///
+    /// Expired responses return zero, responses without a TTL return `None`.
+    pub fn remaining_ttl(&self) -> Option<Duration> {
+        self.ttl().map(|ttl| ttl.saturating_sub(self.age()))
+    }
+}
+
+/// Read only view of a cache entry, returned by [`ResponseCache::entries`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct CacheEntryView {
+    /// Looked up target, `None` for the current address entry
+    pub target: Option<IpAddr>,
+    /// IP address of the cached response
+    pub ip: IpAddr,
+    /// Provider that returned the response
+    pub provider: LookupProvider,
+    /// Short summary of the response, like `"Sydney, Australia, Cloudflare, Inc."`
+    pub summary: String,
+    /// How long ago the response was cached
+    pub age: Duration,
+    /// Time-to-live of the entry, `None` if it never expires
+    pub ttl: Option<Duration>,
+    /// Time until the entry expires, `None` if it never expires
+    pub remaining_ttl: Option<Duration>,
+    /// Whether the entry has expired
+    pub expired: bool,
+}
+
+impl CacheEntryView {
+    fn new(target: Option<IpAddr>, record: &ResponseRecord) -> CacheEntryView {
+        let response = &record.response;
+        let summary = [&response.city, &response.country, &response.asn_org]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty())
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        CacheEntryView {
+            target,
+            ip: response.ip,
+            provider: response.provider.clone(),
+            summary,
+            age: record.age(),
+            ttl: record.ttl(),
+            remaining_ttl: record.remaining_ttl(),
+            expired: record.is_expired(),
+        }
+    }
 }
 
 /// Holds the current IP address lookup response
 ///
 /// The cache can be saved to disk, loaded from disk, and deleted from disk. It also provides methods to clear the cache,
 /// update the cache with a new response, check if the cache has expired, and retrieve the IP address or the entire response from the cache.
-#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[non_exhaustive]
 pub struct ResponseCache {
+    /// Version of the cache file schema, see [`CACHE_VERSION`]
+    #[serde(default)]
+    pub version: u32,
     /// The current IP address lookup response
     pub current_address: Option<ResponseRecord>,
     /// A tree of arbitrary IP address responses
+    ///
+    /// With the `compression` feature the tree is stored gzip compressed in a separate file.
+    #[serde(default)]
+    #[cfg_attr(feature = "compression", serde(skip_serializing))]
     pub lookup_address: BTreeMap<IpAddr, ResponseRecord>,
+    /// Daily request counters per provider
+    #[serde(default)]
+    pub usage: BTreeMap<String, ProviderUsage>,
+    /// Recent failures per provider, skipped by the lookup chain until they expire
+    #[serde(default)]
+    pub failures: BTreeMap<String, FailureRecord>,
     /// The cache file name
     file_name: Option<String>,
 }
 
+/// Number of requests made to a provider during the current day
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub struct ProviderUsage {
+    /// Number of requests made since `day_start`
+    pub requests: u64,
+    day_start: SystemTime,
+}
+
+impl ProviderUsage {
+    /// Returns the number of requests made during the last day.
+    pub fn requests_today(&self) -> u64 {
+        let difference = time::now()
+            .duration_since(self.day_start)
+            .unwrap_or_default();
+        if difference >= Duration::from_secs(24 * 60 * 60) {
+            0
+        } else {
+            self.requests
+        }
+    }
+}
+
+/// Represents a negative entry of the cache, a failed request to a provider
+///
+/// It contains the reason of the failure, the time when it happened and for how long the provider
+/// should be skipped.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub struct FailureRecord {
+    /// Description of the error returned by the provider
+    pub reason: String,
+    failure_time: SystemTime,
+    ttl: u64,
+}
+
+impl FailureRecord {
+    /// Creates a new `FailureRecord` expiring after `ttl` seconds.
+    pub fn new(reason: String, ttl: u64) -> FailureRecord {
+        FailureRecord {
+            reason,
+            failure_time: time::now(),
+            ttl,
+        }
+    }
+
+    /// Determines if the provider can be tried again.
+    pub fn is_expired(&self) -> bool {
+        let difference = time::now()
+            .duration_since(self.failure_time)
+            .unwrap_or_default();
+        difference >= Duration::from_secs(self.ttl)
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        ResponseCache::new(None)
+    }
+}
+
 impl ResponseCache {
     /// Creates a new `ResponseCache` instance.
     ///
@@ -143,8 +324,11 @@ impl ResponseCache {
     pub fn new(file_name: Option<String>) -> ResponseCache {
         trace!("Creating new cache structure");
         ResponseCache {
+            version: CACHE_VERSION,
             current_address: None,
             lookup_address: BTreeMap::new(),
+            usage: BTreeMap::new(),
+            failures: BTreeMap::new(),
             file_name,
         }
     }
@@ -202,6 +386,13 @@ impl ResponseCache {
             .insert(ip, ResponseRecord::new(response.to_owned(), ttl));
     }
 
+    /// Updates the lookup cache with a list of responses, keyed by their IP address.
+    pub fn update_targets(&mut self, responses: &[LookupResponse], ttl: Option<u64>) {
+        for response in responses {
+            self.update_target(response.ip, response, ttl);
+        }
+    }
+
     /// Checks if the lookup cache entry for the given IP address has expired.
     pub fn target_is_expired(&self, ip: &IpAddr) -> bool {
         match self.lookup_address.get(ip) {
@@ -217,6 +408,176 @@ impl ResponseCache {
             .map(|lookup| lookup.response.to_owned())
     }
 
+    /// Returns a view of every cached entry, the current address first followed by the
+    /// looked up targets in address order.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use public_ip_address::cache::ResponseCache;
+    /// # let cache = ResponseCache::new(None);
+    /// for entry in cache.entries() {
+    ///     println!("{} {} {:?}", entry.ip, entry.provider, entry.remaining_ttl);
+    /// }
+    /// ```
+    pub fn entries(&self) -> impl Iterator<Item = CacheEntryView> + '_ {
+        self.current_address
+            .iter()
+            .map(|current| CacheEntryView::new(None, current))
+            .chain(
+                self.lookup_address
+                    .iter()
+                    .map(|(target, record)| CacheEntryView::new(Some(*target), record)),
+            )
+    }
+
+    /// Records a request made to the given provider.
+    pub fn record_usage(&mut self, provider: &LookupProvider) {
+        let requests = self.requests_today(provider);
+        let entry = self
+            .usage
+            .entry(provider.to_string())
+            .or_insert(ProviderUsage {
+                requests: 0,
+                day_start: time::now(),
+            });
+        if requests == 0 {
+            entry.day_start = time::now();
+        }
+        entry.requests = requests + 1;
+    }
+
+    /// Returns the number of requests made to the given provider during the last day.
+    pub fn requests_today(&self, provider: &LookupProvider) -> u64 {
+        self.usage
+            .get(&provider.to_string())
+            .map(|usage| usage.requests_today())
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of requests left in the daily quota of the given provider, if the quota is
+    /// known and limited.
+    pub fn remaining_quota(&self, provider: &LookupProvider) -> Option<u64> {
+        provider
+            .daily_quota()
+            .and_then(|quota| quota.requests())
+            .map(|quota| quota.saturating_sub(self.requests_today(provider)))
+    }
+
+    /// Returns the fraction of the daily quota of the given provider left today.
+    ///
+    /// Providers with an unknown or unlimited quota always have their full quota left.
+    pub fn quota_share(&self, provider: &LookupProvider) -> f64 {
+        match provider.daily_quota() {
+            Some(DailyQuota::Requests(quota)) if quota > 0 => {
+                quota.saturating_sub(self.requests_today(provider)) as f64 / quota as f64
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Returns the provider with the most remaining daily quota.
+    ///
+    /// Providers with an unlimited quota have the most quota left, providers with an unknown quota
+    /// are not considered. If several providers have the same remaining quota, the one listed first
+    /// wins.
+    pub fn usage_hint(&self, providers: &[LookupProvider]) -> Option<LookupProvider> {
+        let mut best: Option<(&LookupProvider, u64)> = None;
+        for provider in providers {
+            let remaining = match provider.daily_quota() {
+                Some(DailyQuota::Unlimited) => Some(u64::MAX),
+                _ => self.remaining_quota(provider),
+            };
+            if let Some(remaining) = remaining {
+                if best.is_none_or(|(_, most)| remaining > most) {
+                    best = Some((provider, remaining));
+                }
+            }
+        }
+        best.map(|(provider, _)| provider.to_owned())
+    }
+
+    /// Records a failed request to the given provider, skipping it for the next `ttl` seconds.
+    pub fn record_failure(&mut self, provider: &LookupProvider, reason: String, ttl: u64) {
+        debug!("Caching failure of {} for {}s", provider, ttl);
+        self.failures
+            .insert(provider.to_string(), FailureRecord::new(reason, ttl));
+    }
+
+    /// Removes the failure of the given provider after a successful request.
+    pub fn clear_failure(&mut self, provider: &LookupProvider) {
+        self.failures.remove(&provider.to_string());
+    }
+
+    /// Checks if the given provider failed recently and should be skipped.
+    pub fn is_failing(&self, provider: &LookupProvider) -> bool {
+        self.failures
+            .get(&provider.to_string())
+            .is_some_and(|failure| !failure.is_expired())
+    }
+
+    /// Splits the targets of a bulk lookup across the given providers.
+    ///
+    /// Providers without target lookup support or without remaining daily quota are skipped. The
+    /// targets are handed out round-robin, one bulk request worth of targets at a time, until every
+    /// target is assigned or the quota of all providers is used up. Targets that could not be
+    /// assigned are left out of the schedule.
+    pub fn schedule_bulk(
+        &self,
+        providers: &[(LookupProvider, Option<Parameters>)],
+        targets: &[IpAddr],
+    ) -> Vec<(LookupProvider, Option<Parameters>, Vec<IpAddr>)> {
+        let mut schedule: Vec<_> = providers
+            .iter()
+            .filter(|(provider, _)| provider.supports_target_lookup())
+            .map(|(provider, param)| {
+                let budget = self.remaining_quota(provider);
+                let chunk = provider.bulk_limit().unwrap_or(1).max(1);
+                (
+                    provider.to_owned(),
+                    param.to_owned(),
+                    Vec::new(),
+                    chunk,
+                    budget,
+                )
+            })
+            .filter(|(.., budget)| budget.is_none_or(|budget| budget > 0))
+            .collect();
+
+        let mut pending = targets;
+        while !pending.is_empty() {
+            let mut assigned = false;
+            for (_, _, share, chunk, budget) in schedule.iter_mut() {
+                if pending.is_empty() {
+                    break;
+                }
+                if budget.is_some_and(|budget| budget == 0) {
+                    continue;
+                }
+                let (head, tail) = pending.split_at((*chunk).min(pending.len()));
+                share.extend_from_slice(head);
+                pending = tail;
+                if let Some(budget) = budget {
+                    *budget -= 1;
+                }
+                assigned = true;
+            }
+            if !assigned {
+                debug!(
+                    "Quota exhausted, {} targets left unscheduled",
+                    pending.len()
+                );
+                break;
+            }
+        }
+
+        schedule
+            .into_iter()
+            .filter(|(_, _, share, ..)| !share.is_empty())
+            .map(|(provider, param, share, ..)| (provider, param, share))
+            .collect()
+    }
+
     /// Writes the `ResponseCache` instance to a file on disk.
     ///
     /// This method serializes the `ResponseCache` instance into a JSON string, encrypts the data if the "encryption" feature is enabled,
@@ -230,14 +591,75 @@ impl ResponseCache {
     /// _ = cache.save();
     /// ```
     pub fn save(&self) -> Result<()> {
-        debug!("Saving cache to {}", get_cache_path(&self.file_name));
+        let path = resolve_cache_path(&self.file_name)?;
+        debug!("Saving cache to {}", path);
         let data = serde_json::to_string(self)?.into_bytes();
 
         #[cfg(feature = "encryption")]
         let data = encrypt(data)?;
 
-        let mut file = File::create(get_cache_path(&self.file_name))?;
-        file.write_all(&data)?;
+        #[cfg(feature = "integrity")]
+        let data = sign(data)?;
+
+        storage::write(path, &data)?;
+
+        #[cfg(feature = "compression")]
+        self.save_targets()?;
+        Ok(())
+    }
+
+    /// Writes the target tree to its own gzip compressed file.
+    #[cfg(feature = "compression")]
+    fn save_targets(&self) -> Result<()> {
+        use flate2::{write::GzEncoder, Compression};
+
+        let path = get_targets_path(&self.file_name)?;
+        if self.lookup_address.is_empty() {
+            return match storage::remove(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        trace!("Compressing target cache");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, &self.lookup_address)?;
+        let data = encoder.finish()?;
+
+        #[cfg(feature = "encryption")]
+        let data = encrypt(data)?;
+
+        #[cfg(feature = "integrity")]
+        let data = sign(data)?;
+
+        storage::write(&path, &data)?;
+        Ok(())
+    }
+
+    /// Reads the target tree from its gzip compressed file, if it exists.
+    #[cfg(feature = "compression")]
+    fn load_targets(&mut self) -> Result<()> {
+        use flate2::read::GzDecoder;
+
+        let data = match storage::read(get_targets_path(&self.file_name)?) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        #[cfg(feature = "integrity")]
+        let data = verify(data)?;
+
+        #[cfg(feature = "encryption")]
+        let data = decrypt(data)?;
+
+        trace!("Decompressing target cache");
+        let mut targets: serde_json::Value =
+            serde_json::from_reader(GzDecoder::new(data.as_slice()))?;
+        if let Some(targets) = targets.as_object_mut() {
+            targets.values_mut().for_each(migrate_record);
+        }
+        let targets: BTreeMap<IpAddr, ResponseRecord> = serde_json::from_value(targets)?;
+        self.lookup_address.extend(targets);
         Ok(())
     }
 
@@ -249,7 +671,7 @@ impl ResponseCache {
     /// # Arguments
     ///
     /// * `file_name` - An `Option<String>` representing the name of the file from which the cache will be loaded.
-    /// If `None`, the default file name `lookup.cache` will be used.
+    ///   If `None`, the default file name `lookup.cache` will be used.
     ///
     /// # Examples
     ///
@@ -258,37 +680,400 @@ impl ResponseCache {
     /// let cache = ResponseCache::load(Some("cache.txt".to_string()));
     /// ```
     pub fn load(file_name: Option<String>) -> Result<ResponseCache> {
-        debug!("Loading cache from {}", get_cache_path(&file_name));
-        let mut file = File::open(get_cache_path(&file_name))?;
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+        let path = resolve_cache_path(&file_name)?;
+        debug!("Loading cache from {}", path);
+        let data = storage::read(path)?;
+
+        #[cfg(feature = "integrity")]
+        let data = verify(data)?;
 
         #[cfg(feature = "encryption")]
-        let data = decrypt(data)?;
+        let data = match decrypt(data.clone()) {
+            Ok(data) => data,
+            // a cache written before the encryption was enabled is migrated on the next save
+            Err(e) if serde_json::from_slice::<serde_json::Value>(&data).is_ok() => {
+                debug!("Loading unencrypted cache, {}", e);
+                data
+            }
+            Err(e) => return Err(e),
+        };
+
+        #[allow(unused_mut)]
+        let mut deserialized = match String::from_utf8(data) {
+            Ok(decoded) => ResponseCache::from_json(&decoded)?,
+            Err(e) => {
+                warn!("Cache file is not readable, it may be encrypted by another build");
+                return Err(e.into());
+            }
+        };
 
-        let decoded = String::from_utf8(data).unwrap_or_default();
-        let deserialized: ResponseCache = serde_json::from_str(&decoded)?;
+        #[cfg(feature = "compression")]
+        deserialized.load_targets()?;
         Ok(deserialized)
     }
 
+    /// Deserializes a cache, migrating it from an older schema version.
+    fn from_json(json: &str) -> Result<ResponseCache> {
+        let value = migrate(serde_json::from_str(json)?);
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Writes the cache to a plain JSON file, unencrypted and including the target tree.
+    ///
+    /// The exported file can be read by [`ResponseCache::import_json`] of any build of the crate,
+    /// with or without the `encryption` and `compression` features, to carry the cache over.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use public_ip_address::cache::ResponseCache;
+    /// let path = std::env::temp_dir().join("exported-cache.json");
+    /// ResponseCache::default().export_json(&path).unwrap();
+    /// let cache = ResponseCache::import_json(&path).unwrap();
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn export_json(&self, path: impl AsRef<Path>) -> Result<()> {
+        debug!("Exporting cache to {}", path.as_ref().display());
+        let mut value = serde_json::to_value(self)?;
+        value["version"] = CACHE_VERSION.into();
+        // the compressed target tree is left out of the cache file itself
+        value["lookup_address"] = serde_json::to_value(&self.lookup_address)?;
+        storage::write(path, serde_json::to_vec_pretty(&value)?)?;
+        Ok(())
+    }
+
+    /// Reads a cache exported by [`ResponseCache::export_json`], migrating older schema versions.
+    ///
+    /// Call [`ResponseCache::save`] on the imported cache to replace the cache file.
+    pub fn import_json(path: impl AsRef<Path>) -> Result<ResponseCache> {
+        debug!("Importing cache from {}", path.as_ref().display());
+        let data = storage::read(path)?;
+        let mut cache = ResponseCache::from_json(&String::from_utf8(data)?)?;
+        cache.version = CACHE_VERSION;
+        Ok(cache)
+    }
+
     /// Deletes the `ResponseCache` instance from disk.
     pub fn delete(self) -> Result<()> {
-        trace!("Deleting cache file {}", get_cache_path(&self.file_name));
-        fs::remove_file(get_cache_path(&self.file_name))?;
+        let path = resolve_cache_path(&self.file_name)?;
+        trace!("Deleting cache file {}", path);
+        storage::remove(path)?;
+
+        #[cfg(feature = "compression")]
+        match storage::remove(get_targets_path(&self.file_name)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Batches cache writes of long running bulk lookups.
+///
+/// Updated targets are written to disk once `batch` results are pending or `interval` has passed
+/// since the last write, instead of serializing the whole cache after every result. Pending results
+/// are written by [`WriteBehind::finish`].
+#[derive(Debug)]
+pub struct WriteBehind {
+    cache: ResponseCache,
+    batch: usize,
+    interval: Duration,
+    pending: usize,
+    last_flush: Instant,
+}
+
+impl WriteBehind {
+    /// Wraps a cache, writing it after `batch` results or `interval`, whichever comes first.
+    pub fn new(cache: ResponseCache, batch: usize, interval: Duration) -> Self {
+        WriteBehind {
+            cache,
+            batch: batch.max(1),
+            interval,
+            pending: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Updates the cache with the responses, writing it to disk if a flush is due.
+    pub fn update_targets(&mut self, responses: &[LookupResponse], ttl: Option<u64>) {
+        self.cache.update_targets(responses, ttl);
+        self.pending += responses.len();
+        if self.pending >= self.batch || self.last_flush.elapsed() >= self.interval {
+            if let Err(e) = self.flush() {
+                warn!("Failed to save cache: {}", e);
+            }
+        }
+    }
+
+    /// Returns the number of results not yet written to disk.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Writes the pending results to disk.
+    pub fn flush(&mut self) -> Result<()> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+        trace!("Flushing {} cached results", self.pending);
+        self.cache.save()?;
+        self.pending = 0;
+        self.last_flush = Instant::now();
         Ok(())
     }
+
+    /// Writes the remaining results and the provider usage to disk and returns the cache.
+    pub fn finish(mut self) -> Result<ResponseCache> {
+        self.cache.save()?;
+        self.pending = 0;
+        Ok(self.cache)
+    }
+}
+
+impl Deref for WriteBehind {
+    type Target = ResponseCache;
+
+    fn deref(&self) -> &ResponseCache {
+        &self.cache
+    }
+}
+
+impl DerefMut for WriteBehind {
+    fn deref_mut(&mut self) -> &mut ResponseCache {
+        &mut self.cache
+    }
+}
+
+/// Migrates a deserialized cache file to the current schema version.
+///
+/// Files without a version were written before the schema was versioned, their fields are read
+/// with defaults. Files of a newer version are read as well as possible, unknown fields are ignored.
+fn migrate(mut value: serde_json::Value) -> serde_json::Value {
+    if !value.is_object() {
+        return value;
+    }
+    // the responses carry their own schema version, indexing would insert the missing fields
+    if let Some(current) = value.get_mut("current_address") {
+        migrate_record(current);
+    }
+    if let Some(targets) = value
+        .get_mut("lookup_address")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        targets.values_mut().for_each(migrate_record);
+    }
+    let version = value["version"].as_u64().unwrap_or_default();
+    if version > CACHE_VERSION as u64 {
+        warn!(
+            "Cache file version {} is newer than {}, reading it anyway",
+            version, CACHE_VERSION
+        );
+        return value;
+    }
+    if version < 1 {
+        trace!("Migrating unversioned cache file");
+        if let Some(cache) = value.as_object_mut() {
+            for field in ["lookup_address", "usage", "failures"] {
+                if cache.get(field).is_none_or(serde_json::Value::is_null) {
+                    cache.insert(field.to_string(), serde_json::json!({}));
+                }
+            }
+        }
+    }
+    value["version"] = CACHE_VERSION.into();
+    value
+}
+
+/// Migrates the response of a serialized cache record, see [`crate::response::SCHEMA_VERSION`].
+fn migrate_record(record: &mut serde_json::Value) {
+    if let Some(response) = record.get_mut("response") {
+        *response = crate::response::migrate(response.take());
+    }
+}
+
+/// Environment variable overriding the directory where the cache file is stored.
+///
+/// Useful to isolate the cache state in tests and sandboxed applications.
+pub const CACHE_DIR_ENV: &str = "PUBLIC_IP_CACHE_DIR";
+
+/// Location of a cache file, used to keep the cache of a lookup apart from the default one
+///
+/// The directory takes precedence over the [`CACHE_DIR_ENV`] environment variable and the
+/// configured cache directory, without it the file is stored in the directory chosen by
+/// [`get_cache_path`].
+///
+/// # Example
+///
+/// ```rust
+/// # use public_ip_address::cache::{CacheLocation, ResponseCache};
+/// let location = CacheLocation::new()
+///     .with_dir(std::env::temp_dir())
+///     .with_file_name("my-app.cache");
+/// let cache = ResponseCache::new(location.file_name());
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct CacheLocation {
+    /// Directory of the cache file, created if it doesn't exist
+    pub dir: Option<PathBuf>,
+    /// Name of the cache file, `None` uses `lookup.cache`
+    pub file_name: Option<String>,
+}
+
+impl CacheLocation {
+    /// Creates a location pointing to the default cache file
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores the cache file in the given directory
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Names the cache file
+    pub fn with_file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Returns the file name to create or load a [`ResponseCache`] with
+    ///
+    /// With a directory set, the file name is joined onto it and used as is by [`get_cache_path`].
+    pub fn file_name(&self) -> Option<String> {
+        let Some(dir) = &self.dir else {
+            return self.file_name.clone();
+        };
+        if !dir.exists() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let file_name = self.file_name.as_deref().unwrap_or(CACHE_FILE);
+        Some(dir.join(file_name).to_string_lossy().into_owned())
+    }
+
+    /// Returns the path of the cache file
+    pub fn path(&self) -> Result<String> {
+        resolve_cache_path(&self.file_name())
+    }
+}
+
+/// Default name of the cache file
+const CACHE_FILE: &str = "lookup.cache";
+
+/// Determines the path for the compressed target cache, stored next to the cache file.
+#[cfg(feature = "compression")]
+fn get_targets_path(file_name: &Option<String>) -> Result<String> {
+    Ok(format!("{}.targets.gz", resolve_cache_path(file_name)?))
+}
+
+/// Directory the cache files can be stored in, see [`StoragePolicy`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum StorageLocation {
+    /// System cache directory, like `~/.cache` on Linux, created if it doesn't exist
+    CacheDir,
+    /// System data directory, like `~/.local/share` on Linux, created if it doesn't exist
+    DataDir,
+    /// Home directory of the user
+    HomeDir,
+    /// Current working directory of the process
+    CurrentDir,
+}
+
+impl StorageLocation {
+    /// Returns the directory if it exists or could be created
+    fn dir(&self, base_dirs: Option<&BaseDirs>) -> Option<PathBuf> {
+        let create = |dir: &Path| dir.exists() || fs::create_dir_all(dir).is_ok();
+        match self {
+            StorageLocation::CacheDir => base_dirs
+                .map(|base_dirs| base_dirs.cache_dir())
+                .filter(|dir| create(dir))
+                .map(Path::to_path_buf),
+            StorageLocation::DataDir => base_dirs
+                .map(|base_dirs| base_dirs.data_dir())
+                .filter(|dir| create(dir))
+                .map(Path::to_path_buf),
+            StorageLocation::HomeDir => base_dirs
+                .map(|base_dirs| base_dirs.home_dir())
+                .filter(|dir| dir.exists())
+                .map(Path::to_path_buf),
+            StorageLocation::CurrentDir => Some(PathBuf::new()),
+        }
+    }
+}
+
+/// Directories tried in order for the cache files when no cache directory is set
+///
+/// The default policy falls back from the system cache directory to the data directory, the
+/// home directory and finally the current working directory. [`StoragePolicy::strict`] never
+/// writes to the home or the working directory, storage fails with
+/// [`CacheError::StorageError`] instead.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::{cache::StoragePolicy, Config};
+///
+/// let config = Config::default().with_storage_policy(StoragePolicy::strict());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StoragePolicy {
+    /// Locations tried in order, the first usable one is picked
+    pub locations: Vec<StorageLocation>,
+}
+
+impl Default for StoragePolicy {
+    fn default() -> Self {
+        StoragePolicy::new(vec![
+            StorageLocation::CacheDir,
+            StorageLocation::DataDir,
+            StorageLocation::HomeDir,
+            StorageLocation::CurrentDir,
+        ])
+    }
+}
+
+impl StoragePolicy {
+    /// Creates a policy trying the locations in order
+    pub fn new(locations: Vec<StorageLocation>) -> Self {
+        StoragePolicy { locations }
+    }
+
+    /// Creates a policy using only the system cache and data directories
+    pub fn strict() -> Self {
+        StoragePolicy::new(vec![StorageLocation::CacheDir, StorageLocation::DataDir])
+    }
+
+    /// Returns the first usable directory of the policy
+    pub fn dir(&self) -> Result<PathBuf> {
+        let base_dirs = BaseDirs::new();
+        self.locations
+            .iter()
+            .find_map(|location| location.dir(base_dirs.as_ref()))
+            .ok_or_else(|| {
+                CacheError::StorageError(format!(
+                    "No usable cache directory in {:?}",
+                    self.locations
+                ))
+            })
+    }
 }
 
 /// Determines the path for the cache file.
 ///
-/// This function uses a series of fallbacks to find a suitable directory for the cache file:
-/// 1. It first tries to use the system's cache directory, as determined by the `BaseDirs` struct.
-/// 2. If the cache directory doesn't exist, it tries to create it.
-/// 3. If it can't create the cache directory, it falls back to the system's data directory.
-/// 4. If it can't use the data directory, it falls back to the user's home directory.
-/// 5. If it can't use the home directory, it falls back to the current directory.
+/// If the [`CACHE_DIR_ENV`] environment variable is set, the cache file is stored in that directory,
+/// which is created if it doesn't exist. Otherwise the cache directory of the process-wide
+/// [`Config`](crate::Config) is used, if set.
+///
+/// Otherwise the first usable directory of the configured [`StoragePolicy`] is used, by default
+/// the system cache directory, the data directory, the home directory and as last resort the
+/// current directory.
 ///
 /// The cache file is named "lookup.cache" by default. However, this can be overridden by providing a different name as a parameter.
+/// A name with a directory, like the one returned by [`CacheLocation::file_name`], is used as is.
 ///
 /// # Arguments
 ///
@@ -296,7 +1081,8 @@ impl ResponseCache {
 ///
 /// # Returns
 ///
-/// * `String` - The path to the cache file.
+/// * `String` - The path to the cache file. If the storage policy has no usable directory, the
+///   bare file name is returned, see [`resolve_cache_path`] for the error instead.
 ///
 /// # Examples
 ///
@@ -305,29 +1091,146 @@ impl ResponseCache {
 /// let cache_path = get_cache_path(&Some("my_cache.txt".to_string()));
 /// ```
 pub fn get_cache_path(file_name: &Option<String>) -> String {
+    resolve_cache_path(file_name)
+        .unwrap_or_else(|_| file_name.as_deref().unwrap_or(CACHE_FILE).to_string())
+}
+
+/// Determines the path for the cache file like [`get_cache_path`], failing with
+/// [`CacheError::StorageError`] if the storage policy has no usable directory.
+pub fn resolve_cache_path(file_name: &Option<String>) -> Result<String> {
     let file_name = if let Some(file_name) = file_name {
         file_name
     } else {
-        "lookup.cache"
+        CACHE_FILE
     };
+    if Path::new(file_name)
+        .parent()
+        .is_some_and(|dir| !dir.as_os_str().is_empty())
+    {
+        return Ok(file_name.to_string());
+    }
 
-    if let Some(base_dirs) = BaseDirs::new() {
-        let mut dir = base_dirs.cache_dir();
-        // Create cache directory if it doesn't exist
-        if !dir.exists() && fs::create_dir_all(dir).is_err() {
-            // If we can't create the cache directory, fallback to data directory
-            dir = base_dirs.data_dir();
-            if !dir.exists() && fs::create_dir_all(dir).is_err() {
-                // If we can't create the data directory, fallback to home directory
-                dir = base_dirs.home_dir();
+    let config = crate::config::config();
+    let dir = env::var_os(CACHE_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| config.cache_dir.clone());
+    if let Some(dir) = dir {
+        if dir.exists() || fs::create_dir_all(&dir).is_ok() {
+            if let Some(path) = dir.join(file_name).to_str() {
+                return Ok(path.to_string());
             }
         }
-        if let Some(path) = dir.join(file_name).to_str() {
-            return path.to_string();
-        }
+    }
+
+    let dir = config.storage_policy.dir()?;
+    Ok(dir.join(file_name).to_string_lossy().into_owned())
+}
+
+/// Storage of the cache files
+///
+/// Browsers have no file system, on `wasm32-unknown-unknown` the files are kept in memory for the
+/// lifetime of the page instead.
+pub(crate) mod storage {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub(crate) use std::fs::{read, remove_file as remove, write};
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    use std::{
+        collections::BTreeMap,
+        io::{Error, ErrorKind, Result},
+        path::{Path, PathBuf},
+        sync::{Mutex, MutexGuard},
     };
-    // As last resort, fallback to current directory
-    file_name.to_string()
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    static FILES: Mutex<BTreeMap<PathBuf, Vec<u8>>> = Mutex::new(BTreeMap::new());
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    fn files() -> Result<MutexGuard<'static, BTreeMap<PathBuf, Vec<u8>>>> {
+        FILES
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::Other, "Cache storage poisoned"))
+    }
+
+    /// Reads the contents of the stored file
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub(crate) fn read(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+        files()?
+            .get(path.as_ref())
+            .cloned()
+            .ok_or(Error::from(ErrorKind::NotFound))
+    }
+
+    /// Replaces the contents of the stored file
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub(crate) fn write(path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> Result<()> {
+        files()?.insert(path.as_ref().to_path_buf(), data.as_ref().to_vec());
+        Ok(())
+    }
+
+    /// Removes the stored file
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub(crate) fn remove(path: impl AsRef<Path>) -> Result<()> {
+        files()?
+            .remove(path.as_ref())
+            .map(|_| ())
+            .ok_or(Error::from(ErrorKind::NotFound))
+    }
+}
+
+/// Returns the secret keying the cache, the configured secret or the machine id.
+#[cfg(any(feature = "encryption", feature = "integrity"))]
+fn cache_secret() -> String {
+    match &crate::config::config().cache_secret {
+        Some(secret) => secret.clone(),
+        None => mid::get(env!("CARGO_PKG_NAME")).unwrap_or("lookup".to_string()),
+    }
+}
+
+/// Length of the HMAC-SHA256 integrity tag in front of the cache file
+#[cfg(feature = "integrity")]
+const TAG_LENGTH: usize = 32;
+
+/// Returns the HMAC-SHA256 keyed with the cache secret
+#[cfg(feature = "integrity")]
+fn integrity_mac() -> Result<hmac::Hmac<sha2::Sha256>> {
+    use hmac::Mac;
+    hmac::Hmac::new_from_slice(cache_secret().as_bytes())
+        .map_err(|e| CacheError::IntegrityError(format!("Invalid key: {}", e)))
+}
+
+/// Prepends the integrity tag of the data.
+#[cfg(feature = "integrity")]
+fn sign(data: Vec<u8>) -> Result<Vec<u8>> {
+    use hmac::Mac;
+    let mut mac = integrity_mac()?;
+    mac.update(&data);
+    let mut signed = mac.finalize().into_bytes().to_vec();
+    signed.extend(data);
+    Ok(signed)
+}
+
+/// Checks the integrity tag in front of the data and strips it.
+///
+/// A tampered or unsigned file fails with `CacheError::IntegrityError`, so it is treated as a
+/// cache miss instead of being deserialized.
+#[cfg(feature = "integrity")]
+fn verify(mut data: Vec<u8>) -> Result<Vec<u8>> {
+    use hmac::Mac;
+    if data.len() < TAG_LENGTH {
+        return Err(CacheError::IntegrityError("Missing tag".to_string()));
+    }
+    let content = data.split_off(TAG_LENGTH);
+    let mut mac = integrity_mac()?;
+    mac.update(&content);
+    match mac.verify_slice(&data) {
+        Ok(()) => Ok(content),
+        Err(_) => {
+            warn!("Cache integrity check failed, ignoring the cache file");
+            Err(CacheError::IntegrityError("Tag mismatch".to_string()))
+        }
+    }
 }
 
 /// Decrypts the given data using AEAD.
@@ -345,7 +1248,7 @@ pub fn get_cache_path(file_name: &Option<String>) -> String {
 #[cfg(feature = "encryption")]
 fn decrypt(data: Vec<u8>) -> Result<Vec<u8>> {
     trace!("Decrypting data");
-    let password = mid::get(env!("CARGO_PKG_NAME")).unwrap_or("lookup".to_string());
+    let password = cache_secret();
     let cocoon = if cfg!(debug_assertions) {
         Cocoon::new(password.as_bytes()).with_weak_kdf()
     } else {
@@ -375,7 +1278,7 @@ fn decrypt(data: Vec<u8>) -> Result<Vec<u8>> {
 #[cfg(feature = "encryption")]
 fn encrypt(data: Vec<u8>) -> Result<Vec<u8>> {
     trace!("Encrypting data");
-    let password = mid::get(env!("CARGO_PKG_NAME")).unwrap_or("lookup".to_string());
+    let password = cache_secret();
     let mut cocoon = if cfg!(debug_assertions) {
         Cocoon::new(password.as_bytes()).with_weak_kdf()
     } else {
@@ -396,6 +1299,135 @@ mod tests {
     use crate::lookup::LookupProvider;
     use serial_test::serial;
 
+    #[cfg(feature = "integrity")]
+    #[test]
+    fn test_integrity() {
+        let data = b"{\"current_address\":null}".to_vec();
+        let signed = sign(data.clone()).unwrap();
+        assert_eq!(signed.len(), data.len() + TAG_LENGTH);
+        assert_eq!(verify(signed.clone()).unwrap(), data);
+
+        let mut tampered = signed;
+        let last = tampered.len() - 2;
+        tampered[last] ^= 1;
+        assert!(
+            matches!(verify(tampered), Err(CacheError::IntegrityError(_))),
+            "Tampered data should be rejected"
+        );
+        assert!(verify(data).is_err(), "Unsigned data should be rejected");
+    }
+
+    #[test]
+    fn test_migrate() {
+        let old = r#"{"current_address": null, "file_name": null}"#;
+        let cache = ResponseCache::from_json(old).unwrap();
+        assert_eq!(cache.version, CACHE_VERSION, "Version should be stamped");
+        assert!(cache.usage.is_empty());
+
+        let newer = format!(
+            r#"{{"version": {}, "current_address": null, "lookup_address": {{}}, "future": 1}}"#,
+            CACHE_VERSION + 1
+        );
+        let cache = ResponseCache::from_json(&newer);
+        assert!(cache.is_ok(), "Newer versions should still be read");
+        assert!(ResponseCache::from_json("[]").is_err());
+
+        // responses written before their schema was versioned
+        let response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpWhoIs);
+        let mut cache = ResponseCache::default();
+        cache.update_current(&response, Some(60));
+        let mut value = serde_json::to_value(&cache).unwrap();
+        value["current_address"]["response"]
+            .as_object_mut()
+            .unwrap()
+            .remove("schema_version");
+        let cache = ResponseCache::from_json(&value.to_string()).unwrap();
+        assert_eq!(
+            cache.current_address.unwrap().response.schema_version,
+            crate::response::SCHEMA_VERSION,
+            "Response version should be stamped"
+        );
+    }
+
+    #[test]
+    fn test_export_import() {
+        let path = env::temp_dir().join("public-ip-address-export-test.json");
+        let response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpWhoIs);
+        let mut cache = ResponseCache::default();
+        cache.update_current(&response, Some(60));
+        cache.update_target(response.ip, &response, None);
+        cache.record_usage(&LookupProvider::IpWhoIs);
+        cache.export_json(&path).unwrap();
+
+        let imported = ResponseCache::import_json(&path).unwrap();
+        assert_eq!(imported, cache, "Imported cache should match the export");
+        assert_eq!(imported.target_response(&response.ip), Some(response));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_cache_dir_env() {
+        let dir = env::temp_dir().join("public-ip-address-cache-test");
+        env::set_var(CACHE_DIR_ENV, &dir);
+        let path = get_cache_path(&None);
+        assert!(
+            path.starts_with(dir.to_str().unwrap()),
+            "Cache path not overridden"
+        );
+
+        let response = LookupResponse::new(
+            "1.1.1.1".parse().unwrap(),
+            LookupProvider::Mock("1.1.1.1".to_string()),
+        );
+        let mut cache = ResponseCache::new(None);
+        cache.update_current(&response, None);
+        cache.save().unwrap();
+        assert!(PathBuf::from(&path).exists(), "Cache file not in override");
+        cache.delete().unwrap();
+
+        env::remove_var(CACHE_DIR_ENV);
+        assert!(
+            !get_cache_path(&None).starts_with(dir.to_str().unwrap()),
+            "Cache path still overridden"
+        );
+        _ = fs::remove_dir(dir);
+    }
+
+    #[test]
+    fn test_storage_policy() {
+        let policy = StoragePolicy::new(vec![StorageLocation::CurrentDir]);
+        assert_eq!(policy.dir().unwrap(), PathBuf::new());
+        let policy = StoragePolicy::new(Vec::new());
+        assert!(
+            matches!(policy.dir(), Err(CacheError::StorageError(_))),
+            "Policy without locations should fail"
+        );
+        let policy = StoragePolicy::default();
+        assert_eq!(policy.locations.last(), Some(&StorageLocation::CurrentDir));
+        assert!(policy.dir().is_ok());
+        assert!(!StoragePolicy::strict()
+            .locations
+            .contains(&StorageLocation::HomeDir));
+    }
+
+    #[test]
+    fn test_cache_location() {
+        assert_eq!(CacheLocation::new().file_name(), None);
+        let location = CacheLocation::new().with_file_name("custom.cache");
+        assert_eq!(location.file_name(), Some("custom.cache".to_string()));
+
+        let dir = env::temp_dir().join("public-ip-address-location");
+        let location = CacheLocation::new().with_dir(&dir);
+        assert_eq!(
+            PathBuf::from(location.path().unwrap()),
+            dir.join(CACHE_FILE),
+            "Directory should take precedence"
+        );
+        assert!(dir.exists(), "Directory should be created");
+        _ = fs::remove_dir(dir);
+    }
+
     #[test]
     #[serial]
     fn test_cache_file() {
@@ -416,6 +1448,89 @@ mod tests {
         cache.delete().unwrap();
     }
 
+    #[test]
+    #[serial]
+    #[cfg(feature = "compression")]
+    fn test_compressed_targets() {
+        let file_name = Some("compressed_test.cache".to_string());
+        let response = LookupResponse::new(
+            "1.1.1.1".parse().unwrap(),
+            LookupProvider::Mock("1.1.1.1".to_string()),
+        );
+        let target = LookupResponse::new(
+            "8.8.8.8".parse().unwrap(),
+            LookupProvider::Mock("8.8.8.8".to_string()),
+        );
+        let mut cache = ResponseCache::new(file_name.clone());
+        cache.update_current(&response, None);
+        cache.update_targets(&[target], None);
+        cache.save().unwrap();
+
+        let main = fs::read(get_cache_path(&file_name)).unwrap();
+        assert!(
+            !String::from_utf8_lossy(&main).contains("8.8.8.8"),
+            "Targets should not be stored in the main cache file"
+        );
+        assert!(PathBuf::from(get_targets_path(&file_name).unwrap()).exists());
+
+        let cached = ResponseCache::load(file_name.clone()).unwrap();
+        assert_eq!(cached, cache, "Cache not matching after reload");
+        cached.delete().unwrap();
+        assert!(!PathBuf::from(get_targets_path(&file_name).unwrap()).exists());
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_compressed_round_trip() {
+        let response = LookupResponse::new(
+            "1.1.1.1".parse().unwrap(),
+            LookupProvider::Mock("1.1.1.1".to_string()),
+        );
+        let mut cache = ResponseCache::default();
+        cache.update_current(&response, Some(60));
+        cache.update_target(response.ip, &response, None);
+        let json = serde_json::to_string(&cache).unwrap();
+        assert!(
+            !json.contains("lookup_address"),
+            "Targets should be left to their own file"
+        );
+        let cached = ResponseCache::from_json(&json).unwrap();
+        assert_eq!(cached.current_address, cache.current_address);
+        assert!(cached.lookup_address.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_write_behind() {
+        let file_name = Some("write_behind_test.cache".to_string());
+        let responses: Vec<LookupResponse> = (1..=3)
+            .map(|i| {
+                LookupResponse::new(
+                    IpAddr::from([1, 1, 1, i]),
+                    LookupProvider::Mock(format!("1.1.1.{}", i)),
+                )
+            })
+            .collect();
+        let mut writer = WriteBehind::new(
+            ResponseCache::new(file_name.clone()),
+            2,
+            Duration::from_secs(60),
+        );
+        writer.update_targets(&responses[..1], None);
+        assert_eq!(writer.pending(), 1, "Result should be pending");
+        assert!(
+            ResponseCache::load(file_name.clone()).is_err(),
+            "Cache written too early"
+        );
+        writer.update_targets(&responses[1..], None);
+        assert_eq!(writer.pending(), 0, "Batch should be flushed");
+        let cached = ResponseCache::load(file_name.clone()).unwrap();
+        assert_eq!(cached.lookup_address.len(), 3);
+
+        let cache = writer.finish().unwrap();
+        cache.delete().unwrap();
+    }
+
     #[test]
     fn test_expired() {
         let response = LookupResponse::new(
@@ -491,6 +1606,161 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_expiring() {
+        let response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::Ipify);
+        let mut record = ResponseRecord::new(response.clone(), Some(100));
+        assert!(!record.is_expiring(), "Fresh record should not be expiring");
+        // age the record instead of sleeping through its TTL
+        record.response_time = time::now() - Duration::from_secs(90);
+        assert!(record.is_expiring(), "Record should be about to expire");
+        record.response_time = time::now() - Duration::from_secs(100);
+        assert!(
+            !record.is_expiring(),
+            "Expired record should not be expiring"
+        );
+        assert!(record.is_expired());
+        let record = ResponseRecord::new(response, None);
+        assert!(
+            !record.is_expiring(),
+            "Record with no TTL should never expire"
+        );
+    }
+
+    #[test]
+    fn test_entries() {
+        let mut cache = ResponseCache::default();
+        assert_eq!(cache.entries().count(), 0);
+        let mut response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpWhoIs);
+        response.city = Some("Sydney".to_string());
+        response.country = Some("Australia".to_string());
+        cache.update_current(&response, Some(60));
+        let target = LookupResponse::new("8.8.8.8".parse().unwrap(), LookupProvider::IpInfo);
+        cache.update_target(target.ip, &target, None);
+        cache.update_target("9.9.9.9".parse().unwrap(), &target, Some(0));
+
+        let entries: Vec<CacheEntryView> = cache.entries().collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].target, None, "Current address should be first");
+        assert_eq!(entries[0].provider, LookupProvider::IpWhoIs);
+        assert_eq!(entries[0].summary, "Sydney, Australia");
+        assert!(entries[0].remaining_ttl.unwrap() <= Duration::from_secs(60));
+        assert!(!entries[0].expired);
+        assert_eq!(entries[1].target, Some("8.8.8.8".parse().unwrap()));
+        assert_eq!(entries[1].remaining_ttl, None, "No TTL should never expire");
+        assert!(entries[1].summary.is_empty());
+        assert_eq!(entries[2].remaining_ttl, Some(Duration::ZERO));
+        assert!(entries[2].expired);
+    }
+
+    #[test]
+    fn test_failures() {
+        let mut cache = ResponseCache::default();
+        assert!(!cache.is_failing(&LookupProvider::IpWhoIs));
+        cache.record_failure(
+            &LookupProvider::IpWhoIs,
+            "Too many requests".to_string(),
+            60,
+        );
+        assert!(cache.is_failing(&LookupProvider::IpWhoIs));
+        assert!(!cache.is_failing(&LookupProvider::IpInfo));
+        cache.clear_failure(&LookupProvider::IpWhoIs);
+        assert!(!cache.is_failing(&LookupProvider::IpWhoIs));
+        cache.record_failure(&LookupProvider::IpInfo, "Timeout".to_string(), 0);
+        assert!(
+            !cache.is_failing(&LookupProvider::IpInfo),
+            "Expired failure should not skip the provider"
+        );
+    }
+
+    #[test]
+    fn test_schedule_bulk() {
+        let mut cache = ResponseCache::default();
+        let providers = vec![
+            (LookupProvider::MyIp, None),
+            (LookupProvider::IpWhoIs, None),
+            (LookupProvider::IpInfo, None),
+        ];
+        let targets: Vec<IpAddr> = (1..=5).map(|i| IpAddr::from([8, 8, 8, i])).collect();
+
+        let schedule = cache.schedule_bulk(&providers, &targets);
+        assert_eq!(
+            schedule.len(),
+            2,
+            "Providers without target lookup should be skipped"
+        );
+        assert_eq!(schedule[0].0, LookupProvider::IpWhoIs);
+        assert_eq!(
+            schedule[0].2,
+            targets[..1],
+            "Single target providers get one target per round"
+        );
+        assert_eq!(schedule[1].0, LookupProvider::IpInfo);
+        assert_eq!(
+            schedule[1].2,
+            targets[1..],
+            "Bulk providers get a whole chunk per round"
+        );
+
+        for _ in 0..333 {
+            cache.record_usage(&LookupProvider::IpWhoIs);
+        }
+        let schedule = cache.schedule_bulk(&providers, &targets);
+        assert_eq!(
+            schedule.len(),
+            1,
+            "Providers without quota should be skipped"
+        );
+        assert_eq!(schedule[0].0, LookupProvider::IpInfo);
+        assert_eq!(schedule[0].2, targets);
+
+        let schedule = cache.schedule_bulk(&providers[..2], &targets);
+        assert!(
+            schedule.is_empty(),
+            "No targets should be scheduled without quota"
+        );
+    }
+
+    #[test]
+    fn test_usage_hint() {
+        let mut cache = ResponseCache::default();
+        let providers = [
+            LookupProvider::IpBase,
+            LookupProvider::MyIp,
+            LookupProvider::IpWhoIs,
+            LookupProvider::IpApiCo,
+        ];
+        assert_eq!(
+            cache.usage_hint(&providers),
+            Some(LookupProvider::IpApiCo),
+            "Provider with the largest quota should be selected"
+        );
+        for _ in 0..700 {
+            cache.record_usage(&LookupProvider::IpApiCo);
+        }
+        assert_eq!(cache.requests_today(&LookupProvider::IpApiCo), 700);
+        assert_eq!(cache.remaining_quota(&LookupProvider::IpApiCo), Some(300));
+        assert_eq!(
+            cache.usage_hint(&providers),
+            Some(LookupProvider::IpWhoIs),
+            "Provider with the most remaining quota should be selected"
+        );
+        assert_eq!(
+            cache.usage_hint(&[LookupProvider::MyIp]),
+            None,
+            "Providers with unknown quota should not be selected"
+        );
+        assert_eq!(cache.quota_share(&LookupProvider::IpApiCo), 0.3);
+        assert_eq!(cache.quota_share(&LookupProvider::MyIp), 1.0);
+        assert_eq!(
+            cache.usage_hint(&[LookupProvider::IpApiCo, LookupProvider::Ipify]),
+            Some(LookupProvider::Ipify),
+            "Providers without a limit should have the most quota left"
+        );
+        assert_eq!(cache.remaining_quota(&LookupProvider::Ipify), None);
+        assert_eq!(cache.quota_share(&LookupProvider::Ipify), 1.0);
+    }
+
     #[test]
     #[cfg(feature = "encryption")]
     fn test_encrypt_decrypt() {
@@ -0,0 +1,514 @@
+//! ⚙️ Process-wide configuration.
+//!
+//! Large applications can configure the defaults once at startup instead of passing options at every call-site.
+//! The configuration is consulted by [`perform_lookup`](crate::perform_lookup), the lookup requests and the cache.
+//!
+//! ## Example
+//! ```rust
+//! use public_ip_address::{lookup::LookupProvider, Config};
+//! use std::time::Duration;
+//!
+//! public_ip_address::init(
+//!     Config::default()
+//!         .with_providers(vec![(LookupProvider::IpWhoIs, None), (LookupProvider::FreeIpApi, None)])
+//!         .with_cache_ttl(Some(60))
+//!         .with_timeout(Duration::from_secs(3)),
+//! );
+//! ```
+//!
+//! ## Configuration file
+//! The configuration can also be loaded from a JSON file, or a TOML file with the `toml` feature,
+//! with [`Config::from_file`] or from the path in the `PUBLIC_IP_CONFIG` environment variable with
+//! [`Config::from_env`]. All fields are optional and default to the values of [`Config::default`].
+//! ```toml
+//! providers = ["ipwhois", "ipinfo", "ipdata"]
+//! cache_ttl = 60
+//! cache_dir = "/var/cache/my-app"
+//! # never fall back to the home or the working directory
+//! storage = ["cache-dir", "data-dir"]
+//! timeout = 2.5
+//! # "none", "follow" or { limited = 5 }
+//! redirects = "none"
+//!
+//! [api_keys]
+//! ipdata = "my-api-key"
+//! ```
+
+use crate::{
+    cache::{StorageLocation, StoragePolicy},
+    error::{Error, Result},
+    lookup::{redirect::RedirectPolicy, LookupProvider, Parameters, ProviderPolicy},
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+/// Environment variable holding the path of the configuration file
+pub const CONFIG_PATH_ENV: &str = "PUBLIC_IP_CONFIG";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+static DEFAULT: OnceLock<Config> = OnceLock::new();
+
+/// Process-wide defaults
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Config {
+    /// Provider chain used by `perform_lookup`.
+    pub providers: Vec<(LookupProvider, Option<Parameters>)>,
+    /// Number of seconds before a cached response used by `perform_lookup` expires, `None` never expires.
+    pub cache_ttl: Option<u64>,
+    /// Number of seconds a provider is skipped after a failed request, `None` disables the negative cache.
+    pub negative_cache_ttl: Option<u64>,
+    /// Directory where the cache file is stored, `None` uses the storage policy.
+    pub cache_dir: Option<PathBuf>,
+    /// Directories tried for the cache files when no cache directory is set.
+    pub storage_policy: StoragePolicy,
+    /// Timeout applied to every provider request, `None` waits indefinitely.
+    pub timeout: Option<Duration>,
+    /// Time an idle pooled connection is kept open, `None` keeps it open indefinitely.
+    pub pool_idle_timeout: Option<Duration>,
+    /// Largest number of idle connections kept open per provider host.
+    pub pool_max_idle_per_host: usize,
+    /// Interval of the TCP keep-alive probes on the provider connections, `None` disables them.
+    pub tcp_keepalive: Option<Duration>,
+    /// Redirects followed by the provider requests.
+    pub redirect_policy: RedirectPolicy,
+    /// Follow redirects from HTTPS to plain HTTP instead of failing with `LookupError::InsecureRedirect`.
+    pub allow_https_downgrade: bool,
+    /// Largest provider reply read, in bytes, longer replies are refused.
+    pub max_response_size: usize,
+    /// Shortest time between two requests for the same cache entry, whatever the TTL and flush
+    /// arguments of the cached lookups, `None` disables the guard.
+    pub min_lookup_interval: Option<Duration>,
+    /// Number of bulk lookup results collected before the cache is written to disk.
+    pub cache_flush_batch: usize,
+    /// Longest time bulk lookup results are kept before the cache is written to disk.
+    pub cache_flush_interval: Duration,
+    /// Policy filtering the provider chain of every lookup.
+    pub policy: ProviderPolicy,
+    /// Refuse provider requests over plain HTTP, upgrading the providers that offer HTTPS on paid plans.
+    pub force_https: bool,
+    /// Allow custom providers whose endpoint resolves to a private, loopback or link-local address.
+    pub allow_private_endpoints: bool,
+    /// Read API keys from `PUBLIC_IP_<PROVIDER>_APIKEY` environment variables for providers without parameters.
+    pub env_api_keys: bool,
+    /// Record the requests and failures of every provider in the statistics file, see [`crate::stats`].
+    pub stats: bool,
+    /// Serve the expired cached response when all providers of a cached lookup fail.
+    pub serve_stale: bool,
+    /// Secret keying the cache encryption and integrity tag, `None` uses the machine id.
+    pub cache_secret: Option<String>,
+    /// Record the provider replies to disk, or replay them without making requests.
+    #[cfg(feature = "fixtures")]
+    pub fixtures: Option<crate::lookup::fixtures::Fixtures>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut providers = vec![
+            (LookupProvider::IpInfo, None),
+            (LookupProvider::IpWhoIs, None),
+            (LookupProvider::MyIp, None),
+            (LookupProvider::FreeIpApi, None),
+        ];
+        // the gateway answers without leaving the local network
+        #[cfg(feature = "local-discovery")]
+        providers.insert(0, (LookupProvider::Gateway, None));
+        Config {
+            providers,
+            cache_ttl: Some(5),
+            negative_cache_ttl: Some(60),
+            cache_dir: None,
+            storage_policy: StoragePolicy::default(),
+            timeout: None,
+            min_lookup_interval: None,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+            tcp_keepalive: None,
+            redirect_policy: RedirectPolicy::default(),
+            allow_https_downgrade: false,
+            max_response_size: 4 * 1024 * 1024,
+            cache_flush_batch: 100,
+            cache_flush_interval: Duration::from_secs(5),
+            policy: ProviderPolicy::default(),
+            force_https: false,
+            allow_private_endpoints: false,
+            env_api_keys: true,
+            stats: false,
+            serve_stale: false,
+            cache_secret: None,
+            #[cfg(feature = "fixtures")]
+            fixtures: None,
+        }
+    }
+}
+
+impl Config {
+    /// Sets the provider chain
+    pub fn with_providers(mut self, providers: Vec<(LookupProvider, Option<Parameters>)>) -> Self {
+        self.providers = providers;
+        self
+    }
+
+    /// Sets the cache expiration time in seconds
+    pub fn with_cache_ttl(mut self, ttl: Option<u64>) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Sets for how many seconds a failed provider is skipped, `None` always retries it
+    pub fn with_negative_cache_ttl(mut self, ttl: Option<u64>) -> Self {
+        self.negative_cache_ttl = ttl;
+        self
+    }
+
+    /// Sets the cache directory
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the directories tried for the cache files when no cache directory is set
+    pub fn with_storage_policy(mut self, policy: StoragePolicy) -> Self {
+        self.storage_policy = policy;
+        self
+    }
+
+    /// Sets how long idle connections are kept open and how many are kept per provider host
+    ///
+    /// Bulk lookups against a single provider benefit from a larger pool, short lived processes
+    /// from a shorter timeout. Only applies to clients built after the configuration is set.
+    pub fn with_connection_pool(mut self, idle_timeout: Option<Duration>, max_idle: usize) -> Self {
+        self.pool_idle_timeout = idle_timeout;
+        self.pool_max_idle_per_host = max_idle;
+        self
+    }
+
+    /// Sends TCP keep-alive probes on the provider connections at the given interval
+    pub fn with_tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Sets the redirects followed by the provider requests
+    ///
+    /// Only applies to clients built after the configuration is set, redirects aren't controlled
+    /// by the library on WebAssembly.
+    pub fn with_redirect_policy(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect_policy = policy;
+        self
+    }
+
+    /// Follows redirects from HTTPS to plain HTTP, which are refused by default
+    pub fn with_allow_https_downgrade(mut self) -> Self {
+        self.allow_https_downgrade = true;
+        self
+    }
+
+    /// Sets the largest provider reply read, in bytes
+    ///
+    /// Longer replies fail with `LookupError::ResponseTooLarge` instead of being read into memory.
+    /// Defaults to 4 MiB.
+    pub fn with_max_response_size(mut self, size: usize) -> Self {
+        self.max_response_size = size;
+        self
+    }
+
+    /// Sets the shortest time between two requests for the same cache entry
+    ///
+    /// Cached lookups serve the cached response, even an expired one, until it is older than the
+    /// interval, extended by a random jitter of up to a tenth. This protects against getting
+    /// banned by the providers when `perform_lookup` is called in a loop. Flushed lookups always
+    /// query the providers.
+    pub fn with_min_lookup_interval(mut self, interval: Duration) -> Self {
+        self.min_lookup_interval = Some(interval);
+        self
+    }
+
+    /// Sets the request timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the policy filtering the provider chain
+    pub fn with_policy(mut self, policy: ProviderPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Enforces HTTPS for all provider requests
+    pub fn with_force_https(mut self) -> Self {
+        self.force_https = true;
+        self
+    }
+
+    /// Allows custom providers to point to non public addresses, like a service on the local network
+    pub fn with_allow_private_endpoints(mut self) -> Self {
+        self.allow_private_endpoints = true;
+        self
+    }
+
+    /// Stops reading API keys from the `PUBLIC_IP_<PROVIDER>_APIKEY` environment variables
+    pub fn without_env_api_keys(mut self) -> Self {
+        self.env_api_keys = false;
+        self
+    }
+
+    /// Sets the secret keying the cache encryption and integrity tag instead of the machine id
+    ///
+    /// Caches shared between machines, like on a network drive, need the same secret everywhere.
+    pub fn with_cache_secret(mut self, secret: impl Into<String>) -> Self {
+        self.cache_secret = Some(secret.into());
+        self
+    }
+
+    /// Records the requests and failures of every provider in the statistics file
+    pub fn with_stats(mut self) -> Self {
+        self.stats = true;
+        self
+    }
+
+    /// Serves the expired cached response instead of an error when all providers fail
+    pub fn with_serve_stale(mut self) -> Self {
+        self.serve_stale = true;
+        self
+    }
+
+    /// Records the provider replies to disk, or replays them without making requests
+    #[cfg(feature = "fixtures")]
+    pub fn with_fixtures(mut self, fixtures: crate::lookup::fixtures::Fixtures) -> Self {
+        self.fixtures = Some(fixtures);
+        self
+    }
+
+    /// Sets how often bulk lookups write their results to the cache
+    pub fn with_cache_flush(mut self, batch: usize, interval: Duration) -> Self {
+        self.cache_flush_batch = batch;
+        self.cache_flush_interval = interval;
+        self
+    }
+}
+
+/// Declarative configuration as stored in a configuration file
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    providers: Option<Vec<String>>,
+    api_keys: HashMap<String, String>,
+    cache_ttl: Option<u64>,
+    cache_dir: Option<PathBuf>,
+    storage: Option<Vec<StorageLocation>>,
+    timeout: Option<f64>,
+    min_lookup_interval: Option<f64>,
+    redirects: Option<RedirectPolicy>,
+}
+
+impl ConfigFile {
+    /// Parses the file contents, TOML is picked by the `.toml` extension and JSON otherwise
+    fn parse(path: &Path, contents: &str) -> Result<ConfigFile> {
+        let is_toml = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+        if is_toml {
+            #[cfg(feature = "toml")]
+            return toml::from_str(contents).map_err(|e| Error::ConfigError(e.to_string()));
+            #[cfg(not(feature = "toml"))]
+            return Err(Error::ConfigError(
+                "TOML configuration requires the toml feature".to_string(),
+            ));
+        }
+        serde_json::from_str(contents).map_err(|e| Error::ConfigError(e.to_string()))
+    }
+
+    /// Applies the values set in the file on top of the defaults
+    fn into_config(self) -> Result<Config> {
+        let mut config = Config::default();
+        let keys = self
+            .api_keys
+            .into_iter()
+            .map(|(name, key)| {
+                parse_provider(&name).map(|provider| (provider, Parameters::new(key)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(providers) = self.providers {
+            config.providers = providers
+                .iter()
+                .map(|name| parse_provider(name).map(|provider| (provider, None)))
+                .collect::<Result<_>>()?;
+        }
+        for (provider, parameters) in config.providers.iter_mut() {
+            if let Some((_, key)) = keys.iter().find(|(p, _)| p == provider) {
+                *parameters = Some(key.clone());
+            }
+        }
+        if self.cache_ttl.is_some() {
+            config.cache_ttl = self.cache_ttl;
+        }
+        config.cache_dir = self.cache_dir;
+        if let Some(locations) = self.storage {
+            config.storage_policy = StoragePolicy::new(locations);
+        }
+        if let Some(policy) = self.redirects {
+            config.redirect_policy = policy;
+        }
+        if let Some(timeout) = self.timeout {
+            config.timeout = Some(
+                Duration::try_from_secs_f64(timeout)
+                    .map_err(|e| Error::ConfigError(format!("Invalid timeout: {}", e)))?,
+            );
+        }
+        if let Some(interval) = self.min_lookup_interval {
+            config.min_lookup_interval =
+                Some(Duration::try_from_secs_f64(interval).map_err(|e| {
+                    Error::ConfigError(format!("Invalid minimum lookup interval: {}", e))
+                })?);
+        }
+        Ok(config)
+    }
+}
+
+fn parse_provider(name: &str) -> Result<LookupProvider> {
+    name.parse()
+        .map_err(|_| Error::ConfigError(format!("Unknown provider: {}", name)))
+}
+
+impl Config {
+    /// Loads the configuration from a JSON file, or a TOML file with the `toml` feature
+    ///
+    /// Fields missing from the file keep their default values.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use public_ip_address::Config;
+    ///
+    /// let config = Config::from_file("public-ip.json")?;
+    /// public_ip_address::init(config);
+    /// # Ok::<(), public_ip_address::error::Error>(())
+    /// ```
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Error::ConfigError(format!("{}: {}", path.display(), e)))?;
+        ConfigFile::parse(path, &contents)?.into_config()
+    }
+
+    /// Loads the configuration from the file in the `PUBLIC_IP_CONFIG` environment variable
+    ///
+    /// Returns `None` if the variable is not set.
+    pub fn from_env() -> Result<Option<Config>> {
+        match env::var_os(CONFIG_PATH_ENV).filter(|path| !path.is_empty()) {
+            Some(path) => Config::from_file(path).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Establishes the process-wide configuration
+///
+/// # Panics
+///
+/// Panics if the configuration was already initialized, use [`try_init`] to handle this case.
+pub fn init(config: Config) {
+    try_init(config).expect("public-ip-address configuration already initialized");
+}
+
+/// Establishes the process-wide configuration, failing if it was already initialized
+pub fn try_init(config: Config) -> Result<()> {
+    CONFIG.set(config).map_err(|_| Error::AlreadyInitialized)
+}
+
+/// Returns the process-wide configuration, or the defaults if it was not initialized
+pub fn config() -> &'static Config {
+    CONFIG
+        .get()
+        .unwrap_or_else(|| DEFAULT.get_or_init(Config::default))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_init() {
+        assert_eq!(config(), &Config::default());
+        assert!(try_init(Config::default()).is_ok(), "First init failed");
+        assert!(
+            matches!(try_init(Config::default()), Err(Error::AlreadyInitialized)),
+            "Second init should fail"
+        );
+    }
+
+    #[test]
+    fn test_connection_settings() {
+        let config = Config::default()
+            .with_connection_pool(Some(Duration::from_secs(10)), 4)
+            .with_tcp_keepalive(Duration::from_secs(30));
+        assert_eq!(config.pool_idle_timeout, Some(Duration::from_secs(10)));
+        assert_eq!(config.pool_max_idle_per_host, 4);
+        assert_eq!(config.tcp_keepalive, Some(Duration::from_secs(30)));
+        assert!(crate::lookup::client::builder().build().is_ok());
+    }
+
+    #[test]
+    fn test_config_file() {
+        let path = Path::new("config.json");
+        let file = ConfigFile::parse(
+            path,
+            r#"{"providers": ["ipwhois", "ipdata"], "api_keys": {"ipdata": "key"}, "timeout": 1.5, "min_lookup_interval": 30, "redirects": "none"}"#,
+        )
+        .unwrap();
+        let config = file.into_config().unwrap();
+        assert_eq!(
+            config.providers,
+            vec![
+                (LookupProvider::IpWhoIs, None),
+                (
+                    LookupProvider::IpData,
+                    Some(Parameters::new("key".to_string()))
+                )
+            ]
+        );
+        assert_eq!(config.timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(config.min_lookup_interval, Some(Duration::from_secs(30)));
+        assert_eq!(config.redirect_policy, RedirectPolicy::None);
+        assert_eq!(config.cache_ttl, Config::default().cache_ttl);
+        assert_eq!(config.storage_policy, StoragePolicy::default());
+
+        let file = ConfigFile::parse(path, r#"{"storage": ["cache-dir", "data-dir"]}"#).unwrap();
+        let config = file.into_config().unwrap();
+        assert_eq!(config.storage_policy, StoragePolicy::strict());
+
+        let file = ConfigFile::parse(path, r#"{"providers": ["nope"]}"#).unwrap();
+        assert!(matches!(file.into_config(), Err(Error::ConfigError(_))));
+        assert!(
+            ConfigFile::parse(path, r#"{"ttl": 5}"#).is_err(),
+            "Unknown fields should be refused"
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_config_file_toml() {
+        let file = ConfigFile::parse(
+            Path::new("config.toml"),
+            "providers = [\"ipinfo\"]\ncache_ttl = 60\n\n[api_keys]\nipinfo = \"key\"\n",
+        )
+        .unwrap();
+        let config = file.into_config().unwrap();
+        assert_eq!(
+            config.providers,
+            vec![(
+                LookupProvider::IpInfo,
+                Some(Parameters::new("key".to_string()))
+            )]
+        );
+        assert_eq!(config.cache_ttl, Some(60));
+    }
+}
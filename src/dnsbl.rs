@@ -0,0 +1,220 @@
+//! 🚫 DNS blocklist checks.
+//!
+//! Checks an IP address against DNS blocklists (DNSBL), like Spamhaus ZEN or Barracuda, so that
+//! mail server operators can verify that their public address isn't listed. The address is
+//! queried as a hostname in the zone of every blocklist, `2.0.0.127.zen.spamhaus.org` for
+//! `127.0.0.2`, through the system resolver. The resolver blocks the current thread, async builds
+//! run the queries of [`check`] and [`enrich`] on a blocking thread.
+//!
+//! Some blocklists refuse queries sent through public resolvers, these results are reported with
+//! an unknown listing, like queries the resolver failed to answer.
+//!
+//! Enabled through the `dnsbl` feature flag.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use public_ip_address::dnsbl;
+//! use std::error::Error;
+//! #[cfg_attr(not(feature = "blocking"), tokio::main)]
+//! #[maybe_async::maybe_async]
+//! async fn main() -> Result<(), Box<dyn Error>> {
+//!     let mut response = public_ip_address::perform_lookup(None).await?;
+//!     dnsbl::enrich(&mut response, dnsbl::DEFAULT_BLOCKLISTS).await;
+//!     println!("{:?}", response.security);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::response::{BlocklistResult, LookupResponse};
+use log::warn;
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs},
+};
+
+/// Blocklists checked by default: Spamhaus ZEN and the Barracuda reputation list
+pub const DEFAULT_BLOCKLISTS: &[&str] = &["zen.spamhaus.org", "b.barracudacentral.org"];
+
+/// Resolver messages of a name that doesn't exist or has no address, `EAI_NONAME` and
+/// `EAI_NODATA` of glibc, musl and the BSDs
+const NOT_FOUND_MESSAGES: [&str; 6] = [
+    "name or service not known",
+    "no address associated with hostname",
+    "name does not resolve",
+    "name has no usable address",
+    "nodename nor servname provided",
+    "no address associated with nodename",
+];
+
+/// Returns the hostname queried for the address in the zone of a blocklist
+///
+/// IPv4 addresses are written with their octets reversed, IPv6 addresses with their nibbles
+/// reversed.
+pub fn query_name(ip: &IpAddr, zone: &str) -> String {
+    let labels: Vec<String> = match ip {
+        IpAddr::V4(ip) => ip.octets().iter().rev().map(u8::to_string).collect(),
+        IpAddr::V6(ip) => ip
+            .octets()
+            .iter()
+            .rev()
+            .flat_map(|octet| [octet & 0x0f, octet >> 4])
+            .map(|nibble| format!("{:x}", nibble))
+            .collect(),
+    };
+    format!("{}.{}", labels.join("."), zone.trim_matches('.'))
+}
+
+/// Checks the IP address against a single blocklist
+///
+/// Addresses that don't exist in the zone are not listed. Other resolver failures, like a timeout
+/// or an unreachable name server, leave the listing unknown. The query blocks the current thread.
+pub fn check_zone(ip: &IpAddr, zone: &str) -> BlocklistResult {
+    match (query_name(ip, zone).as_str(), 0).to_socket_addrs() {
+        Ok(addresses) => {
+            let codes = addresses
+                .filter_map(|address| match address {
+                    SocketAddr::V4(address) => Some(*address.ip()),
+                    SocketAddr::V6(_) => None,
+                })
+                .collect();
+            classify(zone, codes)
+        }
+        Err(e) if is_not_found(&e) => classify(zone, Vec::new()),
+        Err(e) => {
+            warn!("Blocklist {} query failed: {}", zone, e);
+            unknown(zone)
+        }
+    }
+}
+
+/// Checks the IP address against a list of blocklists
+///
+/// Async builds run the queries on a blocking thread, instead of the executor of the caller.
+#[maybe_async::maybe_async]
+pub async fn check(ip: &IpAddr, zones: &[&str]) -> Vec<BlocklistResult> {
+    #[cfg(all(not(feature = "blocking"), not(target_arch = "wasm32")))]
+    {
+        let ip = *ip;
+        let owned: Vec<String> = zones.iter().map(|zone| zone.to_string()).collect();
+        let results = crate::lookup::sync::unblock(move || {
+            owned.iter().map(|zone| check_zone(&ip, zone)).collect()
+        })
+        .await;
+        results.unwrap_or_else(|e| {
+            warn!("Blocklist queries failed: {}", e);
+            zones.iter().map(|zone| unknown(zone)).collect()
+        })
+    }
+    #[cfg(any(feature = "blocking", target_arch = "wasm32"))]
+    zones.iter().map(|zone| check_zone(ip, zone)).collect()
+}
+
+/// Checks the address of the response against the blocklists and attaches the results to its `security`
+#[maybe_async::maybe_async]
+pub async fn enrich(response: &mut LookupResponse, zones: &[&str]) {
+    let results = check(&response.ip, zones).await;
+    response
+        .security
+        .get_or_insert_with(Default::default)
+        .blocklists = results;
+}
+
+/// Returns the result of a blocklist that couldn't be queried
+fn unknown(zone: &str) -> BlocklistResult {
+    BlocklistResult {
+        zone: zone.to_string(),
+        listed: None,
+        codes: Vec::new(),
+    }
+}
+
+/// Checks if the resolver failed because the name doesn't exist in the zone
+///
+/// The standard library only reports the message of the `getaddrinfo` error on Unix, Windows
+/// reports the socket error code.
+fn is_not_found(error: &io::Error) -> bool {
+    if cfg!(windows) {
+        // WSAHOST_NOT_FOUND and WSANO_DATA
+        return matches!(error.raw_os_error(), Some(11001 | 11004));
+    }
+    let message = error.to_string().to_ascii_lowercase();
+    NOT_FOUND_MESSAGES
+        .iter()
+        .any(|not_found| message.contains(not_found))
+}
+
+/// Interprets the return codes of a blocklist query
+///
+/// Listings are answered with addresses in `127.0.0.0/8`, refused queries with `127.255.255.0/24`.
+fn classify(zone: &str, codes: Vec<Ipv4Addr>) -> BlocklistResult {
+    let (refused, codes): (Vec<Ipv4Addr>, Vec<Ipv4Addr>) = codes
+        .into_iter()
+        .filter(Ipv4Addr::is_loopback)
+        .partition(|code| code.octets()[1..3] == [255, 255]);
+    let listed = match (codes.is_empty(), refused.is_empty()) {
+        (true, false) => None,
+        (empty, _) => Some(!empty),
+    };
+    BlocklistResult {
+        zone: zone.to_string(),
+        listed,
+        codes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ignore]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_check() {
+        // 127.0.0.2 is listed by every blocklist for testing
+        let results = check(&"127.0.0.2".parse().unwrap(), DEFAULT_BLOCKLISTS).await;
+        assert_eq!(results.len(), 2);
+        assert!(
+            results.iter().all(|r| r.listed != Some(false)),
+            "Test address should be listed {:?}",
+            results
+        );
+    }
+
+    #[test]
+    fn test_query_name() {
+        assert_eq!(
+            query_name(&"127.0.0.2".parse().unwrap(), "zen.spamhaus.org"),
+            "2.0.0.127.zen.spamhaus.org"
+        );
+        assert_eq!(
+            query_name(&"2001:db8::1".parse().unwrap(), "zen.spamhaus.org."),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.zen.spamhaus.org"
+        );
+    }
+
+    #[test]
+    fn test_classify() {
+        let result = classify("zen.spamhaus.org", vec!["127.0.0.2".parse().unwrap()]);
+        assert_eq!(result.listed, Some(true));
+        assert_eq!(result.codes, vec!["127.0.0.2".parse::<Ipv4Addr>().unwrap()]);
+
+        let result = classify("zen.spamhaus.org", Vec::new());
+        assert_eq!(result.listed, Some(false));
+
+        let result = classify("zen.spamhaus.org", vec!["127.255.255.254".parse().unwrap()]);
+        assert_eq!(result.listed, None, "Refused query should be unknown");
+        assert!(result.codes.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_not_found() {
+        let error =
+            io::Error::other("failed to lookup address information: Name or service not known");
+        assert!(is_not_found(&error), "NXDOMAIN should not be listed");
+        let error = io::Error::other(
+            "failed to lookup address information: Temporary failure in name resolution",
+        );
+        assert!(!is_not_found(&error), "Resolver failure should be unknown");
+        assert_eq!(unknown("zen.spamhaus.org").listed, None);
+    }
+}
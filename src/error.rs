@@ -19,6 +19,15 @@ pub enum Error {
     /// System time error, usually when converting from a timestamp
     #[error("Time error")]
     TimeError(#[from] std::time::SystemTimeError),
+    /// Network monitor error when subscribing to network change events
+    #[error("Network monitor error")]
+    NetworkMonitorError(#[from] std::io::Error),
+    /// The process-wide configuration was already initialized
+    #[error("Already initialized")]
+    AlreadyInitialized,
+    /// Configuration file could not be read or is invalid
+    #[error("Configuration error")]
+    ConfigError(String),
 }
 
 /// Error type for the cache module
@@ -37,4 +46,10 @@ pub enum CacheError {
     /// Encryption error when encrypting or decrypting data
     #[error("Encryption error")]
     EncryptionError(String),
+    /// Integrity tag of the cache file is missing or doesn't match its contents
+    #[error("Integrity error")]
+    IntegrityError(String),
+    /// No directory of the storage policy can hold the cache files
+    #[error("Storage error: {0}")]
+    StorageError(String),
 }
@@ -9,6 +9,7 @@
 //!
 //! The library also includes caching functionality to improve performance for repeated lookups and minimaze rate-limiting.
 //! The cache file can be encrypted when enabled through the `encryption` feature flag for additional privacy.
+//! With the `integrity` feature flag the cache file is tagged with an HMAC, and a tampered cache is treated as a miss.
 //!
 //! ## Usage
 //! ```toml
@@ -27,33 +28,127 @@
 //! }
 //! ```
 //!
+//! ## Runtimes
+//! The lookup functions don't use any runtime specific primitive: time budgets are measured with
+//! `Instant`, cancellation and races are built on `futures-util`, and the watcher leaves the
+//! sleeping to the caller. The HTTP requests are made with `reqwest`, which needs a tokio reactor,
+//! or with `ureq` through the synchronous `ureq` feature. Under async-std or smol wrap the lookup
+//! futures with `async_compat::Compat`, which provides a tokio reactor:
+//!
+//! ```rust,ignore
+//! use async_compat::Compat;
+//!
+//! let response = smol::block_on(Compat::new(public_ip_address::perform_lookup(None)))?;
+//! ```
+//!
 //! ## Features
 //! - Unified interface for multiple IP lookup providers
 //! - Caching of lookup results to improve performance
 //! - Customizable cache expiration time
+//! - Optional gzip compression of the cached target lookups, enabled through the `compression` feature flag
+//! - Optional gzip and brotli compressed provider replies, enabled through the `http-compression` feature flag
+//! - Optional certificate pinning of the provider connections, enabled through the `cert-pinning` feature flag
+//! - Sorting, grouping and pagination helpers for bulk lookup results
+//! - Polling watcher reporting public address changes, with jitter, backoff, debounce, confirmation by a second provider and change callbacks
+//! - Optional network change monitor refreshing the cache on network switches, enabled through the `network-monitor` feature flag
+//! - Optional `tracing` spans for every lookup, enabled through the `tracing` feature flag
+//! - Optional OpenTelemetry attributes (`peer.service`, `url.full` without API keys, `error.type`, ...) on the lookup spans, enabled through the `otel` feature flag
+//! - Optional WHOIS/RDAP enrichment with network ownership data, enabled through the `rdap` feature flag
+//! - Optional Tor exit node detection against the exit list of the Tor Project, enabled through the `tor` feature flag
+//! - Optional DNS blocklist checks, like Spamhaus ZEN and Barracuda, enabled through the `dnsbl` feature flag
+//! - Optional GeoJSON export of the responses, enabled through the `geojson` feature flag
+//! - Optional time zone helpers returning the UTC offset and the local time of the responses, enabled through the `chrono` feature flag
+//! - Optional ISO 3166 country table normalizing the country names and codes of the providers, enabled through the `iso3166` feature flag
+//! - Async interface compiling for `wasm32-unknown-unknown`, with the cache kept in memory
+//! - Provider health checks reporting reachability, latency and parsing of every provider
+//! - Persisted per-provider request, failure and last success statistics, enabled through `Config::with_stats`
+//! - Process-wide defaults configured once through `init`, or loaded from a JSON or TOML configuration file
+//! - Providers can be compiled out through their `provider-*` feature flags
+//! - Curated provider presets, like the fast keyless or the IPv6 capable providers
+//! - TLS backend selected through the `default-tls`, `native-tls` and `rustls-tls` feature flags
+//! - Optional `ureq` HTTP backend for synchronous programs, without tokio and reqwest, enabled through the `ureq` feature flag
+//! - Test helpers for downstream crates, enabled through the `test-util` feature flag
+//! - Record and replay of the provider replies for offline tests, enabled through the `fixtures` feature flag
 //!
 //! For more details, please refer to the API documentation.
 
 #![warn(missing_docs)]
 
+#[cfg(all(feature = "blocking", target_arch = "wasm32"))]
+compile_error!("The `blocking` feature is not supported on wasm32, use the async interface");
+#[cfg(all(
+    feature = "cert-pinning",
+    not(any(
+        feature = "default-tls",
+        feature = "native-tls",
+        feature = "rustls-tls"
+    ))
+))]
+compile_error!("The `cert-pinning` feature requires a TLS backend, enable `default-tls`, `native-tls` or `rustls-tls`");
+
 use log::{debug, trace, warn};
-use std::net::IpAddr;
+use std::{net::IpAddr, time::Duration};
+use time::Instant;
+
+use cache::{CacheLocation, ResponseCache, WriteBehind};
+use error::{CacheError, Error, Result};
+use lookup::{
+    bogon::non_public_kind, dedup_providers, error::LookupError, LookupOptions, LookupProvider,
+    LookupService, LookupTarget, Parameters, ProviderConfig, ProviderHealth,
+};
+use response::{LookupResponse, SelectionReason};
 
-use cache::ResponseCache;
-use error::{Error, Result};
-use lookup::{error::LookupError, LookupProvider, LookupService, Parameters};
-use response::LookupResponse;
+pub use builder::{Lookup, LookupBuilder, LookupStrategy};
+pub use config::{init, try_init, Config};
 
+#[cfg(all(not(feature = "blocking"), not(target_arch = "wasm32")))]
+pub mod blocking;
+/// Synchronous interface, the `blocking` feature makes the crate root synchronous
+#[cfg(feature = "blocking")]
+pub mod blocking {
+    pub use crate::{
+        check_providers, lookup::LookupService, lookup_asn, perform_cached_bulk_lookup_at,
+        perform_cached_bulk_lookup_with, perform_cached_lookup_at, perform_cached_lookup_with,
+        perform_fan_out_bulk_lookup_at, perform_fan_out_bulk_lookup_with, perform_lookup,
+        perform_lookup_from_config, perform_lookup_target_with, perform_lookup_with,
+        perform_lookup_with_options, perform_race_lookup_with, perform_rotating_lookup_at,
+        perform_rotating_lookup_with,
+    };
+}
+pub mod builder;
+pub mod bulk;
 pub mod cache;
+pub mod config;
+#[cfg(feature = "iso3166")]
+pub mod country;
+#[cfg(feature = "dnsbl")]
+pub mod dnsbl;
 pub mod error;
 pub mod lookup;
+#[cfg(feature = "network-monitor")]
+pub mod monitor;
+pub mod providers;
 pub mod response;
+mod singleflight;
+pub mod stats;
+#[cfg(feature = "tracing")]
+mod telemetry;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod time;
+#[cfg(feature = "tor")]
+pub mod tor;
+pub mod watch;
+#[cfg(feature = "rdap")]
+pub mod whois;
 
 /// Performs a lookup using a predefined list of `LookupProvider`s and caches the result.
 ///
 /// This function performs a lookup using a predefined list of `LookupProvider`s. The list includes
 /// `IpInfo`, `IpWhoIs`, `MyIp`, and `FreeIpApi`. The result of the lookup is cached locally for 5 seconds.
-/// If a subsequent request is made within 2 seconds, the cached result is returned.
+/// If a subsequent request is made within 5 seconds, the cached result is returned.
+///
+/// The provider list and the cache expiration time can be changed process-wide through [`init`].
 ///
 /// # Arguments
 ///
@@ -83,38 +178,67 @@ pub mod response;
 /// * A `Result` containing either a successful `LookupResponse` or an `Error` if the lookup or caching failed.
 #[maybe_async::maybe_async]
 pub async fn perform_lookup(target: Option<IpAddr>) -> Result<LookupResponse> {
-    perform_cached_lookup_with(
-        vec![
-            (LookupProvider::IpInfo, None),
-            (LookupProvider::IpWhoIs, None),
-            (LookupProvider::MyIp, None),
-            (LookupProvider::FreeIpApi, None),
-        ],
-        target,
-        Some(5),
-        false,
-    )
-    .await
+    let config = config::config();
+    perform_cached_lookup_with(config.providers.clone(), target, config.cache_ttl, false).await
+}
+
+/// Performs a cached lookup of the current public address as declared in a configuration file.
+///
+/// The configuration is loaded with [`Config::from_file`] and established as the process-wide
+/// configuration, so the cache directory and timeout of the file apply to this and later lookups.
+/// If the process-wide configuration was already initialized, only the providers and the cache
+/// expiration time of the file are used.
+///
+/// # Arguments
+///
+/// * `path` - Path of the JSON or TOML configuration file.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let response = public_ip_address::perform_lookup_from_config("public-ip.json").await?;
+/// println!("{}", response.ip);
+/// # Ok(())
+/// # }
+/// ```
+#[maybe_async::maybe_async]
+pub async fn perform_lookup_from_config(
+    path: impl AsRef<std::path::Path>,
+) -> Result<LookupResponse> {
+    let config = Config::from_file(path)?;
+    let (providers, ttl) = (config.providers.clone(), config.cache_ttl);
+    if try_init(config).is_err() {
+        warn!("Configuration already initialized, using only the providers of the file");
+    }
+    perform_cached_lookup_with(providers, None, ttl, false).await
 }
 
 /// Performs a lookup using a list of providers until a successful response is received.
 ///
 /// This function iterates over the provided list of `LookupProvider`s, making a request with each one
 /// until a successful `LookupResponse` is received. If a provider fails to return a successful response,
-/// the error is stored and the next provider is tried.
+/// the error is stored and the next provider is tried. Duplicate entries in the list are skipped.
+///
+/// If none of the provider addresses can be resolved, the machine is most likely offline and
+/// `LookupError::Offline` is returned.
 ///
 /// If all providers fail to return a successful response, a `LookupError` is returned containing a list
 /// of all the errors received.
 ///
 /// # Arguments
 ///
-/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to use for the lookup.
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters`, or of `ProviderConfig`s with
+///   per-provider timeouts, retries and weights, to use for the lookup.
 /// * `target` - Target address for the lookup, `None` will look up the current public address.
 ///
 /// # Example
 ///
 /// ```rust
-/// use public_ip_address::lookup::LookupProvider;
+/// use public_ip_address::lookup::{LookupProvider, ProviderConfig};
 ///
 /// # use std::error::Error;
 /// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
@@ -123,7 +247,8 @@ pub async fn perform_lookup(target: Option<IpAddr>) -> Result<LookupResponse> {
 ///
 /// let providers = vec![
 ///     // List of providers to use for the lookup
-///     // (LookupProvider::IpWhoIs, Some(Parameters::new(apikey)))
+///     ProviderConfig::new(LookupProvider::IpWhoIs).with_retries(1),
+///     ProviderConfig::new(LookupProvider::MyIp),
 /// ];
 ///
 /// match public_ip_address::perform_lookup_with(providers, None).await {
@@ -143,53 +268,303 @@ pub async fn perform_lookup(target: Option<IpAddr>) -> Result<LookupResponse> {
 /// * A `Result` containing either a successful `LookupResponse` or a `LookupError` containing a list of all errors received.
 #[maybe_async::maybe_async]
 pub async fn perform_lookup_with(
-    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    providers: Vec<impl Into<ProviderConfig>>,
     target: Option<IpAddr>,
 ) -> Result<LookupResponse> {
-    let mut errors = Vec::new();
-    if providers.is_empty() {
-        return Err(Error::LookupError(LookupError::GenericError(
-            "No providers given".to_string(),
-        )));
+    perform_lookup_with_options(providers, target, LookupOptions::default()).await
+}
+
+/// Performs a lookup using a list of providers within the time budget given by `LookupOptions`.
+///
+/// Works like `perform_lookup_with`, but every provider request is limited to the per provider
+/// timeout and the remaining total budget, whichever is shorter. Once the total budget is used up
+/// the remaining providers are skipped and `LookupError::Timeout` is returned.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters`, or of `ProviderConfig`s, to use for the lookup.
+/// * `target` - Target address for the lookup, `None` will look up the current public address.
+/// * `options` - `LookupOptions` with the total and per provider timeouts, a `ProviderConfig`
+///   timeout takes precedence over the per provider timeout.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::lookup::{LookupOptions, LookupProvider};
+/// use std::time::Duration;
+///
+/// # use std::error::Error;
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let providers = vec![(LookupProvider::IpWhoIs, None), (LookupProvider::MyIp, None)];
+/// let options = LookupOptions::default()
+///     .with_total_timeout(Duration::from_secs(3))
+///     .with_per_provider_timeout(Duration::from_secs(1));
+///
+/// match public_ip_address::perform_lookup_with_options(providers, None, options).await {
+///     Ok(response) => {
+///         // Handle successful response
+///     }
+///     Err(e) => {
+///         // Handle error
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `Result` containing either a successful `LookupResponse` or a `LookupError`.
+#[maybe_async::maybe_async]
+pub async fn perform_lookup_with_options(
+    providers: Vec<impl Into<ProviderConfig>>,
+    target: Option<IpAddr>,
+    options: LookupOptions,
+) -> Result<LookupResponse> {
+    let providers = providers.into_iter().map(Into::into).collect();
+    let location = CacheLocation::default();
+    lookup_chain(providers, target, options, &location, None, &mut Vec::new()).await
+}
+
+/// Tries the providers in order, collecting the errors of the providers that failed
+///
+/// Rotating lookups weigh the providers by the quota usage recorded in the cache at `location`.
+/// The `revalidate` response and its entity tag are handed to the provider that answered it, so
+/// that it can confirm the response with a conditional request.
+#[maybe_async::maybe_async]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "lookup_chain",
+        skip(providers, location, revalidate, errors),
+        fields(providers = providers.len(), duration_ms = tracing::field::Empty),
+        err(Display)
+    )
+)]
+async fn lookup_chain(
+    providers: Vec<ProviderConfig>,
+    target: Option<IpAddr>,
+    options: LookupOptions,
+    location: &CacheLocation,
+    revalidate: Option<(String, LookupResponse)>,
+    errors: &mut Vec<(LookupProvider, LookupError)>,
+) -> Result<LookupResponse> {
+    #[cfg(feature = "tracing")]
+    let _timer = telemetry::SpanTimer::start();
+    let mut providers = prepare_configs(providers)?;
+    if options.rotate {
+        let cache = ResponseCache::load(location.file_name()).unwrap_or_default();
+        providers = rotate(providers, &cache);
+    } else {
+        // heavier providers are tried first
+        providers.sort_by_key(|entry| std::cmp::Reverse(entry.weight));
     }
+    let started = Instant::now();
+    let mut attempts = Vec::new();
 
-    for (provider, param) in providers {
+    for (index, entry) in providers.into_iter().enumerate() {
+        let provider = entry.provider;
+        let remaining = match options.total_timeout {
+            Some(total) => match total.checked_sub(started.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => Some(remaining),
+                _ => {
+                    warn!("Lookup time budget exhausted, skipping remaining providers");
+                    stats::record(&attempts);
+                    return Err(Error::LookupError(LookupError::Timeout));
+                }
+            },
+            None => None,
+        };
         debug!("Performing lookup with provider {}", &provider);
-        let response = LookupService::new(provider, param).lookup(target).await;
-        if let Ok(response) = response {
+        let mut service = LookupService::new(provider.clone(), entry.parameters);
+        let timeout = match (entry.timeout.or(options.per_provider_timeout), remaining) {
+            (Some(per_provider), Some(remaining)) => Some(per_provider.min(remaining)),
+            (per_provider, remaining) => per_provider.or(remaining),
+        };
+        if let Some(timeout) = timeout {
+            service.set_timeout(timeout);
+        }
+        if let Some((etag, cached)) = revalidate.as_ref().filter(|(_, r)| r.provider == provider) {
+            service.set_cached(etag.clone(), cached.clone());
+        }
+        let mut response = lookup_once(&service, target, options).await;
+        for _ in 0..entry.retries {
+            let retry = response.as_ref().is_err_and(is_retryable)
+                && options
+                    .total_timeout
+                    .is_none_or(|total| started.elapsed() < total);
+            if !retry {
+                break;
+            }
+            debug!("Retrying lookup with provider {}", &provider);
+            attempts.push((provider.clone(), false));
+            response = lookup_once(&service, target, options).await;
+        }
+        attempts.push((provider.clone(), response.is_ok()));
+        if let Ok(mut response) = response {
             trace!("Successful response from provider");
+            response.meta.attempts = index + 1;
+            if index > 0 {
+                response.meta.selection_reason = SelectionReason::Fallback(index);
+            } else if options.rotate {
+                response.meta.selection_reason = SelectionReason::Rotation;
+            }
+            stats::record(&attempts);
             return Ok(response);
         }
         warn!("Provider failed to perform lookup");
-        errors.push(response.unwrap_err());
+        match response.unwrap_err() {
+            e @ LookupError::Offline(_) => {
+                // a single provider failing to resolve doesn't mean the machine is offline
+                warn!("Could not resolve provider address");
+                errors.push((provider, e));
+            }
+            LookupError::NonPublicAddress(kind) => {
+                warn!("Target is a {} address, skipping remaining providers", kind);
+                // the target was refused before the provider was asked
+                attempts.pop();
+                stats::record(&attempts);
+                return Err(Error::LookupError(LookupError::NonPublicAddress(kind)));
+            }
+            e => errors.push((provider, e)),
+        }
     }
+    stats::record(&attempts);
 
     // if we reach here no responses were found
+    if options
+        .total_timeout
+        .is_some_and(|total| started.elapsed() >= total)
+    {
+        warn!("Lookup time budget exhausted");
+        return Err(Error::LookupError(LookupError::Timeout));
+    }
+    // no provider address could be resolved, the machine is most likely offline
+    if let Some((_, LookupError::Offline(e))) = errors.last() {
+        if errors
+            .iter()
+            .all(|(_, e)| matches!(e, LookupError::Offline(_)))
+        {
+            warn!("Could not resolve any provider address");
+            return Err(Error::LookupError(LookupError::Offline(e.clone())));
+        }
+    }
     warn!("No responses from providers");
+    let errors: Vec<&LookupError> = errors.iter().map(|(_, e)| e).collect();
     Err(Error::LookupError(LookupError::GenericError(format!(
         "No responses from providers: {:?}",
         errors
     ))))
 }
 
-/// Performs a lookup with a list of specific service providers and caches the result.
+/// Orders the providers for a rotating lookup
 ///
-/// This function performs a lookup using the provided list of `LookupProvider`s. The result of the lookup
-/// is cached locally.
-/// If subsequent requests are made, the cached result is returned as long as the previous
-/// request was made within `cache_expire_time` seconds.
+/// Every provider is drawn with a probability proportional to its weight and the share of its
+/// daily quota left, without replacement. Providers with no weight or no quota left are tried last.
+fn rotate(providers: Vec<ProviderConfig>, cache: &ResponseCache) -> Vec<ProviderConfig> {
+    let mut drawn: Vec<(f64, ProviderConfig)> = providers
+        .into_iter()
+        .map(|entry| {
+            let weight = f64::from(entry.weight) * cache.quota_share(&entry.provider);
+            // weighted sampling keys, the largest key is drawn first
+            let key = if weight > 0.0 {
+                time::random_unit().powf(1.0 / weight)
+            } else {
+                -1.0
+            };
+            (key, entry)
+        })
+        .collect();
+    drawn.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    drawn.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Makes a single request with the service, fetching only the network data if requested
+#[maybe_async::maybe_async]
+async fn lookup_once(
+    service: &LookupService,
+    target: Option<IpAddr>,
+    options: LookupOptions,
+) -> std::result::Result<LookupResponse, LookupError> {
+    if options.asn_only {
+        service.lookup_asn(target).await
+    } else {
+        service.lookup(target).await
+    }
+}
+
+/// Looks up the origin network of an address without its geolocation.
 ///
-/// If `cache_expire_time` is `None`, then the cache never expires.
+/// Only the ASN, the organization and, when reported, the routed prefix are fetched, from the
+/// providers of the configured chain that support ASN lookups. Without any, ipinfo, ip-api,
+/// iplocate, BGPView and RIPEstat are tried in that order. Responses are not cached.
 ///
-/// If `cache_expire_time` is `0`, then the cache is expired immediately after the request.
+/// # Arguments
+///
+/// * `target` - Target address for the lookup, `None` will look up the current public address.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// match public_ip_address::lookup_asn(Some("1.1.1.1".parse()?)).await {
+///     Ok(response) => {
+///         // Handle the ASN, e.g. response.asn and response.network
+///     }
+///     Err(e) => {
+///         // Handle error
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `Result` containing either a `LookupResponse` with the network data or a `LookupError`.
+#[maybe_async::maybe_async]
+pub async fn lookup_asn(target: Option<IpAddr>) -> Result<LookupResponse> {
+    let mut providers: Vec<ProviderConfig> = config::config()
+        .providers
+        .iter()
+        .filter(|(provider, _)| provider.supports_asn_lookup())
+        .cloned()
+        .map(Into::into)
+        .collect();
+    if providers.is_empty() {
+        providers = [
+            LookupProvider::IpInfo,
+            LookupProvider::IpApiCom,
+            LookupProvider::IpLocateIo,
+            LookupProvider::BgpView,
+            LookupProvider::RipeStat,
+        ]
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    }
+    let options = LookupOptions::default().with_asn_only();
+    perform_lookup_with_options(providers, target, options).await
+}
+
+/// Performs a lookup using a list of providers that can be cancelled by the caller.
+///
+/// Works like `perform_lookup_with`, but stops as soon as the `cancel` future completes. In-flight
+/// provider requests are dropped and `LookupError::Cancelled` is returned. Any future can be used,
+/// for example `tokio_util::sync::CancellationToken::cancelled` or a timer.
+///
+/// Only available in the async interface.
 ///
 /// # Arguments
 ///
 /// * `providers` - A vector of `LookupProvider`s and their `Parameters` to use for the lookup.
 /// * `target` - Target address for the lookup, `None` will look up the current public address.
-/// * `cache_expire_time` - An `Option` containing the number of seconds before the cache expires. If `None`,
-///   the cache never expires. If `0`, the cache expires immediately after the request.
-/// * `flush` - A `bool` indicating whether to force the cache to flush and make a new request.
+/// * `cancel` - Future that cancels the lookup once it completes.
 ///
 /// # Example
 ///
@@ -197,17 +572,158 @@ pub async fn perform_lookup_with(
 /// use public_ip_address::lookup::LookupProvider;
 ///
 /// # use std::error::Error;
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let providers = vec![(LookupProvider::IpWhoIs, None)];
+/// // e.g. token.cancelled()
+/// let cancel = std::future::pending::<()>();
+///
+/// match public_ip_address::perform_lookup_with_cancel(providers, None, cancel).await {
+///     Ok(response) => {
+///         // Handle successful response
+///     }
+///     Err(e) => {
+///         // Handle error or cancellation
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `Result` containing either a successful `LookupResponse` or a `LookupError`.
+#[cfg(not(feature = "blocking"))]
+pub async fn perform_lookup_with_cancel<C>(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: Option<IpAddr>,
+    cancel: C,
+) -> Result<LookupResponse>
+where
+    C: std::future::Future<Output = ()>,
+{
+    use futures_util::future::{select, Either};
+
+    let lookup = std::pin::pin!(perform_lookup_with(providers, target));
+    // the cancellation is polled first, so that a lookup answering right away is still cancelled
+    match select(std::pin::pin!(cancel), lookup).await {
+        Either::Right((response, _)) => response,
+        Either::Left(_) => {
+            warn!("Lookup cancelled");
+            Err(Error::LookupError(LookupError::Cancelled))
+        }
+    }
+}
+
+/// Performs a lookup of every address of a target using a list of providers.
+///
+/// Hostname targets are resolved through the system resolver, async builds run it on a blocking
+/// thread instead of the executor of the caller. Every resolved address is looked up with
+/// `perform_lookup_with`, addresses that are not publicly routable are skipped.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to use for the lookup.
+/// * `target` - `LookupTarget` holding an IP address or a hostname.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::lookup::{LookupProvider, LookupTarget};
+///
+/// # use std::error::Error;
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let providers = vec![(LookupProvider::IpWhoIs, None)];
+/// let target = "example.com".parse::<LookupTarget>()?;
+///
+/// match public_ip_address::perform_lookup_target_with(providers, target).await {
+///     Ok(responses) => {
+///         // Handle a response for every address
+///     }
+///     Err(e) => {
+///         // Handle error
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `Result` containing the `LookupResponse`s of all addresses that could be looked up, or the first error
+///   if none of them could.
+#[maybe_async::maybe_async]
+pub async fn perform_lookup_target_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: LookupTarget,
+) -> Result<Vec<LookupResponse>> {
+    #[cfg(all(not(feature = "blocking"), not(target_arch = "wasm32")))]
+    let addresses = {
+        let target = target.clone();
+        lookup::sync::unblock(move || target.resolve()).await??
+    };
+    #[cfg(any(feature = "blocking", target_arch = "wasm32"))]
+    let addresses = target.resolve()?;
+    let mut responses = Vec::new();
+    let mut first_error = None;
+    for address in addresses {
+        if let Some(kind) = non_public_kind(&address) {
+            warn!("Skipping {} address {} of {}", kind, address, target);
+            first_error.get_or_insert(Error::LookupError(LookupError::NonPublicAddress(kind)));
+            continue;
+        }
+        match perform_lookup_with(providers.clone(), Some(address)).await {
+            Ok(response) => responses.push(response),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    match (responses.is_empty(), first_error) {
+        (true, Some(e)) => Err(e),
+        (true, None) => Err(Error::LookupError(LookupError::GenericError(format!(
+            "No addresses found for {}",
+            target
+        )))),
+        _ => Ok(responses),
+    }
+}
+
+/// Performs a lookup rotating through the providers to stay within their free tier quotas.
+///
+/// Instead of always starting with the first provider, the providers are tried in a weighted random
+/// order: each provider is drawn with a probability proportional to its `ProviderConfig` weight
+/// and the share of its daily quota left today, so the requests of an always-on monitor are spread
+/// over all providers and no free tier is used up. The remaining providers are tried in the drawn
+/// order if the first one fails.
+///
+/// The requests made to every provider are counted in the cache file, so the quotas carry over
+/// between runs. A count that can't be stored is logged without failing the lookup, and a cache
+/// file that can't be read is left untouched.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters`, or of `ProviderConfig`s with
+///   their weights, to use for the lookup.
+/// * `target` - Target address for the lookup, `None` will look up the current public address.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::lookup::{LookupProvider, ProviderConfig};
+///
+/// # use std::error::Error;
 /// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
 /// # #[maybe_async::maybe_async]
 /// # async fn main() -> Result<(), Box<dyn Error>> {
 /// let providers = vec![
-///     // List of providers to use for the lookup
-///     // (LookupProvider::IpWhoIs, Some(Parameters::new(apikey)))
+///     ProviderConfig::new(LookupProvider::IpWhoIs).with_weight(3),
+///     ProviderConfig::new(LookupProvider::IpInfo),
 /// ];
-/// let expire_time = Some(60); // Cache expires after 60 seconds
-/// let flush = false; // Do not force cache flush
 ///
-/// match public_ip_address::perform_cached_lookup_with(providers, None, expire_time, flush).await {
+/// match public_ip_address::perform_rotating_lookup_with(providers, None).await {
 ///     Ok(response) => {
 ///         // Handle successful response
 ///     }
@@ -221,50 +737,1203 @@ pub async fn perform_lookup_with(
 ///
 /// # Returns
 ///
-/// * A `Result` containing either a successful `LookupResponse` or an `Error` if the lookup or caching failed.
+/// * A `Result` containing either a successful `LookupResponse` or a `LookupError`.
 #[maybe_async::maybe_async]
-pub async fn perform_cached_lookup_with(
-    providers: Vec<(LookupProvider, Option<Parameters>)>,
+pub async fn perform_rotating_lookup_with(
+    providers: Vec<impl Into<ProviderConfig>>,
     target: Option<IpAddr>,
-    ttl: Option<u64>,
-    flush: bool,
 ) -> Result<LookupResponse> {
-    let cached_file = ResponseCache::load(None);
-    // load the cache if it exists
-    let mut cache = match cached_file {
-        Ok(cache) => {
-            // check if we are looking for a specific target
-            if let Some(target) = target {
-                if !cache.target_is_expired(&target) && !flush {
-                    if let Some(target) = cache.lookup_address.get(&target) {
-                        trace!("Using cached value");
-                        return Ok(target.response.to_owned());
-                    }
-                }
-            } else if !cache.current_is_expired() && !flush {
-                if let Some(current) = cache.current_address {
-                    trace!("Using cached value");
-                    return Ok(current.response);
-                }
-            }
-            cache
-        }
-        // no cache file, create a new cache
-        Err(_) => ResponseCache::default(),
-    };
+    perform_rotating_lookup_at(providers, target, &CacheLocation::default()).await
+}
 
-    trace!("Performing new lookup");
-    // no cache or it's too old, make a new request.
-    match perform_lookup_with(providers, target).await {
-        Ok(result) => {
-            if let Some(target) = target {
-                cache.update_target(target, &result, ttl);
-            } else {
-                cache.update_current(&result, ttl);
-            }
-            cache.save()?;
-            Ok(result)
+/// Performs the lookup of `perform_rotating_lookup_with` with the request counts kept in the cache
+/// at the given location
+///
+/// See [`CacheLocation`] for how the location is resolved.
+#[maybe_async::maybe_async]
+pub async fn perform_rotating_lookup_at(
+    providers: Vec<impl Into<ProviderConfig>>,
+    target: Option<IpAddr>,
+    location: &CacheLocation,
+) -> Result<LookupResponse> {
+    let providers = providers.into_iter().map(Into::into).collect();
+    let options = LookupOptions::default().with_rotation();
+    let response =
+        lookup_chain(providers, target, options, location, None, &mut Vec::new()).await?;
+    // the request count is best effort, it never fails a successful lookup
+    let file_name = location.file_name();
+    let mut cache = match ResponseCache::load(file_name.clone()) {
+        Ok(cache) => cache,
+        Err(CacheError::IOError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            ResponseCache::new(file_name)
+        }
+        Err(e) => {
+            // saving over an unreadable cache would discard its contents
+            warn!("Failed to load cache, request not counted: {}", e);
+            return Ok(response);
         }
-        Err(e) => Err(e),
+    };
+    cache.record_usage(&response.provider);
+    if let Err(e) = cache.save() {
+        warn!("Failed to save cache: {}", e);
     }
+    Ok(response)
+}
+
+/// Performs a lookup with all providers concurrently and returns the first successful response.
+///
+/// Unlike `perform_lookup_with`, which tries the providers one after another, this function
+/// queries all providers at the same time and returns as soon as any of them answers.
+/// This trades API quota for latency.
+///
+/// If all providers fail to return a successful response, a `LookupError` is returned containing a list
+/// of all the errors received.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to use for the lookup.
+/// * `target` - Target address for the lookup, `None` will look up the current public address.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::lookup::LookupProvider;
+///
+/// # use std::error::Error;
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let providers = vec![
+///     (LookupProvider::IpInfo, None),
+///     (LookupProvider::IpWhoIs, None),
+/// ];
+///
+/// match public_ip_address::perform_race_lookup_with(providers, None).await {
+///     Ok(response) => {
+///         // Handle successful response
+///     }
+///     Err(e) => {
+///         // Handle error
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `Result` containing either the first successful `LookupResponse` or a `LookupError` containing a list of all errors received.
+#[cfg(not(feature = "blocking"))]
+pub async fn perform_race_lookup_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: Option<IpAddr>,
+) -> Result<LookupResponse> {
+    use futures_util::stream::{FuturesUnordered, StreamExt};
+
+    let providers = prepare_chain(providers)?;
+    if let Some(kind) = target.as_ref().and_then(non_public_kind) {
+        return Err(Error::LookupError(LookupError::NonPublicAddress(kind)));
+    }
+
+    let mut lookups = providers
+        .into_iter()
+        .map(|(provider, param)| async move {
+            debug!("Performing lookup with provider {}", &provider);
+            LookupService::new(provider, param).lookup(target).await
+        })
+        .collect::<FuturesUnordered<_>>();
+
+    let mut errors = Vec::new();
+    while let Some(response) = lookups.next().await {
+        match response {
+            Ok(mut response) => {
+                trace!("Provider {} won the race", response.provider);
+                response.meta.selection_reason = SelectionReason::RaceWinner;
+                return Ok(response);
+            }
+            Err(e) => {
+                warn!("Provider failed to perform lookup");
+                errors.push(e);
+            }
+        }
+    }
+
+    // if we reach here no responses were found
+    warn!("No responses from providers");
+    Err(Error::LookupError(LookupError::GenericError(format!(
+        "No responses from providers: {:?}",
+        errors
+    ))))
+}
+
+/// Performs a lookup with all providers concurrently and returns the first successful response.
+///
+/// Every provider is queried from its own thread. Threads of the providers that lost the race
+/// are left to finish in the background.
+///
+/// If all providers fail to return a successful response, a `LookupError` is returned containing a list
+/// of all the errors received.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to use for the lookup.
+/// * `target` - Target address for the lookup, `None` will look up the current public address.
+///
+/// # Returns
+///
+/// * A `Result` containing either the first successful `LookupResponse` or a `LookupError` containing a list of all errors received.
+#[cfg(feature = "blocking")]
+pub fn perform_race_lookup_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: Option<IpAddr>,
+) -> Result<LookupResponse> {
+    let providers = prepare_chain(providers)?;
+    if let Some(kind) = target.as_ref().and_then(non_public_kind) {
+        return Err(Error::LookupError(LookupError::NonPublicAddress(kind)));
+    }
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    for (provider, param) in providers {
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            debug!("Performing lookup with provider {}", &provider);
+            // the receiver is gone once a winner was found
+            _ = sender.send(LookupService::new(provider, param).lookup(target));
+        });
+    }
+    drop(sender);
+
+    let mut errors = Vec::new();
+    for response in receiver {
+        match response {
+            Ok(mut response) => {
+                trace!("Provider {} won the race", response.provider);
+                response.meta.selection_reason = SelectionReason::RaceWinner;
+                return Ok(response);
+            }
+            Err(e) => {
+                warn!("Provider failed to perform lookup");
+                errors.push(e);
+            }
+        }
+    }
+
+    // if we reach here no responses were found
+    warn!("No responses from providers");
+    Err(Error::LookupError(LookupError::GenericError(format!(
+        "No responses from providers: {:?}",
+        errors
+    ))))
+}
+
+/// Performs a lookup with a list of specific service providers and caches the result.
+///
+/// This function performs a lookup using the provided list of `LookupProvider`s. The result of the lookup
+/// is cached locally.
+/// If subsequent requests are made, the cached result is returned as long as the previous
+/// request was made within `cache_expire_time` seconds.
+///
+/// If `cache_expire_time` is `None`, then the cache never expires.
+///
+/// If `cache_expire_time` is `0`, then the cache is expired immediately after the request.
+///
+/// When a cached response is about to expire, the provider with the most remaining daily quota
+/// is picked for the upcoming refresh and moved to the front of the list.
+///
+/// Expired responses of providers supporting ETags, like `IpInfo` and `IpData`, are revalidated
+/// with a conditional request. A `304 Not Modified` reply refreshes the cached response.
+///
+/// Providers failing with a rate limit, a timeout or an unexpected status are remembered in the
+/// cache and skipped for `Config::negative_cache_ttl` seconds, unless every provider is failing.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to use for the lookup.
+/// * `target` - Target address for the lookup, `None` will look up the current public address.
+/// * `cache_expire_time` - An `Option` containing the number of seconds before the cache expires. If `None`,
+///   the cache never expires. If `0`, the cache expires immediately after the request.
+/// * `flush` - A `bool` indicating whether to force the cache to flush and make a new request.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::lookup::LookupProvider;
+///
+/// # use std::error::Error;
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let providers = vec![
+///     // List of providers to use for the lookup
+///     // (LookupProvider::IpWhoIs, Some(Parameters::new(apikey)))
+/// ];
+/// let expire_time = Some(60); // Cache expires after 60 seconds
+/// let flush = false; // Do not force cache flush
+///
+/// match public_ip_address::perform_cached_lookup_with(providers, None, expire_time, flush).await {
+///     Ok(response) => {
+///         // Handle successful response
+///     }
+///     Err(e) => {
+///         // Handle error
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `Result` containing either a successful `LookupResponse` or an `Error` if the lookup or caching failed.
+#[maybe_async::maybe_async]
+pub async fn perform_cached_lookup_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: Option<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+) -> Result<LookupResponse> {
+    let providers = providers.into_iter().map(Into::into).collect();
+    let strategy = LookupStrategy::Sequential;
+    cached_lookup(
+        providers,
+        target,
+        ttl,
+        flush,
+        strategy,
+        LookupOptions::default(),
+        &CacheLocation::default(),
+    )
+    .await
+}
+
+/// Performs the lookup of `perform_cached_lookup_with` with the cache stored at the given location
+///
+/// Lookups with different locations keep separate caches, for example one per application
+/// profile. See [`CacheLocation`] for how the location is resolved.
+///
+/// # Example
+///
+/// ```rust
+/// # use public_ip_address::{cache::CacheLocation, lookup::LookupProvider};
+/// # use std::error::Error;
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let providers = vec![(LookupProvider::Mock("1.1.1.1".to_string()), None)];
+/// let location = CacheLocation::new()
+///     .with_dir(std::env::temp_dir())
+///     .with_file_name("profile.cache");
+/// let response =
+///     public_ip_address::perform_cached_lookup_at(providers, None, Some(60), false, &location)
+///         .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[maybe_async::maybe_async]
+pub async fn perform_cached_lookup_at(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    target: Option<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+    location: &CacheLocation,
+) -> Result<LookupResponse> {
+    let providers = providers.into_iter().map(Into::into).collect();
+    let strategy = LookupStrategy::Sequential;
+    cached_lookup(
+        providers,
+        target,
+        ttl,
+        flush,
+        strategy,
+        LookupOptions::default(),
+        location,
+    )
+    .await
+}
+
+/// Performs the lookup of `perform_cached_lookup_with` with the given strategy and time budget
+///
+/// Concurrent identical lookups share the result of the first one, see [`singleflight`].
+#[maybe_async::maybe_async]
+async fn cached_lookup(
+    providers: Vec<ProviderConfig>,
+    target: Option<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+    strategy: LookupStrategy,
+    options: LookupOptions,
+    location: &CacheLocation,
+) -> Result<LookupResponse> {
+    let key = singleflight::Key {
+        file_name: location.file_name(),
+        target,
+        ttl,
+        flush,
+        strategy,
+        options,
+        providers: providers.clone(),
+    };
+    loop {
+        match singleflight::join(key.clone()) {
+            singleflight::Role::Leader(leader) => {
+                let result =
+                    run_cached_lookup(providers, target, ttl, flush, strategy, options, location)
+                        .await;
+                leader.complete(&result);
+                return result;
+            }
+            singleflight::Role::Follower(flight) => {
+                trace!("Identical lookup in flight, waiting for its result");
+                if let Some(result) = flight.wait().await {
+                    return result;
+                }
+                trace!("Identical lookup cancelled, taking over");
+            }
+        }
+    }
+}
+
+/// Performs a cached lookup, without sharing it with concurrent identical lookups
+#[maybe_async::maybe_async]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "cached_lookup",
+        skip(providers, options, location),
+        fields(
+            cache_hit = tracing::field::Empty,
+            duration_ms = tracing::field::Empty
+        ),
+        err(Display)
+    )
+)]
+async fn run_cached_lookup(
+    providers: Vec<ProviderConfig>,
+    target: Option<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+    strategy: LookupStrategy,
+    options: LookupOptions,
+    location: &CacheLocation,
+) -> Result<LookupResponse> {
+    #[cfg(feature = "tracing")]
+    let _timer = telemetry::SpanTimer::start();
+    // load the cache if it exists, otherwise create a new cache
+    let file_name = location.file_name();
+    let mut cache =
+        ResponseCache::load(file_name.clone()).unwrap_or_else(|_| ResponseCache::new(file_name));
+
+    // check if we are looking for a specific target
+    let record = match target {
+        Some(target) => cache.lookup_address.get(&target),
+        None => cache.current_address.as_ref(),
+    };
+    let hint = record.and_then(|record| record.refresh_hint.to_owned());
+    let revalidate = record
+        .filter(|_| !flush)
+        .and_then(|record| Some((record.etag.clone()?, record.response.clone())));
+    // a cached value younger than the minimum interval is served even if it expired, unless the
+    // lookup is flushed
+    let throttled = !flush
+        && record.is_some_and(|record| {
+            config::config()
+                .min_lookup_interval
+                .is_some_and(|interval| record.age() < jittered(interval))
+        });
+    // cached value past its soft TTL that couldn't be refreshed in the background, kept as the
+    // answer in case the refresh fails
+    let mut pending = None;
+    if let Some(record) = record {
+        if !record.is_expired() && !flush && record.refresh_pending && !throttled {
+            trace!("Refreshing cached value past its soft TTL");
+            let mut response = record.response.to_owned();
+            response.meta.selection_reason = SelectionReason::CacheHit;
+            pending = Some(response);
+        } else if (!record.is_expired() && !flush) || throttled {
+            if record.is_expired() {
+                trace!("Lookup within the minimum interval, using cached value");
+            }
+            trace!("Using cached value");
+            #[cfg(feature = "tracing")]
+            tracing::Span::current().record("cache_hit", true);
+            let mut response = record.response.to_owned();
+            response.meta.selection_reason = SelectionReason::CacheHit;
+            let soft_expired = options
+                .soft_ttl
+                .is_some_and(|soft_ttl| record.age() >= soft_ttl);
+            if record.is_expiring() && hint.is_none() {
+                // pick the provider with the most remaining quota for the upcoming refresh
+                let candidates: Vec<LookupProvider> = providers
+                    .iter()
+                    .map(|entry| entry.provider.to_owned())
+                    .collect();
+                if let Some(provider) = cache.usage_hint(&candidates) {
+                    trace!(
+                        "Cached value about to expire, next refresh with {}",
+                        provider
+                    );
+                    let record = match target {
+                        Some(target) => cache.lookup_address.get_mut(&target),
+                        None => cache.current_address.as_mut(),
+                    };
+                    if let Some(record) = record {
+                        record.refresh_hint = Some(provider);
+                    }
+                    // the hint only saves quota, the cached value is served regardless
+                    if let Err(e) = cache.save() {
+                        warn!("Failed to save the refresh hint: {}", e);
+                    }
+                }
+            }
+            if soft_expired {
+                let options = LookupOptions {
+                    soft_ttl: None,
+                    ..options
+                };
+                if !refresh_in_background(providers, target, ttl, strategy, options, location) {
+                    trace!("Cached value past its soft TTL, refreshing on the next lookup");
+                    let record = match target {
+                        Some(target) => cache.lookup_address.get_mut(&target),
+                        None => cache.current_address.as_mut(),
+                    };
+                    if let Some(record) = record {
+                        record.refresh_pending = true;
+                    }
+                    // the cached value is served regardless, it is only refreshed later
+                    if let Err(e) = cache.save() {
+                        warn!("Failed to save the pending refresh: {}", e);
+                    }
+                }
+            }
+            return Ok(response);
+        }
+    }
+
+    // move the hinted provider to the front of the list, a rotation draws its own order
+    let hint = hint.filter(|_| strategy != LookupStrategy::Rotate);
+    let mut providers = providers;
+    if let Some(hint) = &hint {
+        if let Some(index) = providers.iter().position(|entry| &entry.provider == hint) {
+            trace!("Refreshing with provider {} from usage hint", hint);
+            let preferred = providers.remove(index);
+            providers.insert(0, preferred);
+        }
+    }
+
+    // skip the providers that failed recently, unless there is nothing else left to try
+    let negative_ttl = config::config().negative_cache_ttl;
+    if negative_ttl.is_some()
+        && providers
+            .iter()
+            .any(|entry| !cache.is_failing(&entry.provider))
+    {
+        providers.retain(|entry| {
+            let failing = cache.is_failing(&entry.provider);
+            if failing {
+                trace!(
+                    "Skipping provider {} after a recent failure",
+                    entry.provider
+                );
+            }
+            !failing
+        });
+    }
+
+    trace!("Performing new lookup");
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("cache_hit", false);
+    // no cache or it's too old, make a new request.
+    let mut errors = Vec::new();
+    let result = match strategy {
+        LookupStrategy::Sequential => {
+            lookup_chain(
+                providers,
+                target,
+                options,
+                location,
+                revalidate,
+                &mut errors,
+            )
+            .await
+        }
+        LookupStrategy::Rotate => {
+            let options = options.with_rotation();
+            lookup_chain(
+                providers,
+                target,
+                options,
+                location,
+                revalidate,
+                &mut errors,
+            )
+            .await
+        }
+        LookupStrategy::Race => {
+            let providers = providers.into_iter().map(Into::into).collect();
+            perform_race_lookup_with(providers, target).await
+        }
+    };
+    if let Some(ttl) = negative_ttl {
+        for (provider, error) in errors.iter().filter(|(_, e)| is_negative_cacheable(e)) {
+            cache.record_failure(provider, error.to_string(), ttl);
+        }
+    }
+    match result {
+        Ok(mut result) => {
+            if hint.as_ref() == Some(&result.provider) {
+                result.meta.selection_reason = SelectionReason::UsageHint;
+            }
+            cache.clear_failure(&result.provider);
+            if result.meta.is_not_modified() {
+                // revalidated responses are not counted against the quota
+                trace!("Cached value confirmed by provider");
+            } else {
+                cache.record_usage(&result.provider);
+            }
+            if let Some(target) = target {
+                cache.update_target(target, &result, ttl);
+            } else {
+                cache.update_current(&result, ttl);
+            }
+            cache.save()?;
+            Ok(result)
+        }
+        Err(e) => {
+            if !errors.is_empty() && negative_ttl.is_some() {
+                if let Err(error) = cache.save() {
+                    warn!("Failed to save the provider failures: {}", error);
+                }
+            }
+            let record = match target {
+                Some(target) => cache.lookup_address.get(&target),
+                None => cache.current_address.as_ref(),
+            };
+            if let Some(response) = pending {
+                warn!(
+                    "Failed to refresh cached value, serving it until it expires: {}",
+                    e
+                );
+                return Ok(response);
+            }
+            match record {
+                Some(record) if options.serve_stale || config::config().serve_stale => {
+                    warn!("All providers failed, serving stale cached value: {}", e);
+                    let mut response = record.response.to_owned();
+                    response.meta.selection_reason = SelectionReason::StaleCache;
+                    Ok(response)
+                }
+                _ => Err(e),
+            }
+        }
+    }
+}
+
+/// Extends the minimum interval between lookups by up to a tenth, so that a fleet of clients
+/// started together doesn't refresh in lockstep
+fn jittered(interval: Duration) -> Duration {
+    interval + interval.mul_f64(time::random_unit() / 10.0)
+}
+
+/// Refreshes the cache entry on a background task, see [`LookupOptions::with_stale_while_revalidate`]
+///
+/// Returns `false` if no task could be spawned, the entry is then refreshed on the next lookup.
+#[cfg(all(not(feature = "blocking"), not(target_arch = "wasm32")))]
+fn refresh_in_background(
+    providers: Vec<ProviderConfig>,
+    target: Option<IpAddr>,
+    ttl: Option<u64>,
+    strategy: LookupStrategy,
+    options: LookupOptions,
+    location: &CacheLocation,
+) -> bool {
+    trace!("Cached value past its soft TTL, refreshing in the background");
+    let location = location.clone();
+    let refresh = async move {
+        let result = cached_lookup(providers, target, ttl, true, strategy, options, &location);
+        if let Err(e) = result.await {
+            warn!("Background refresh of the cached value failed: {}", e);
+        }
+    };
+    lookup::sync::spawn(refresh).is_ok()
+}
+
+/// Blocking builds and the browser have no executor to spawn the refresh on
+#[cfg(any(feature = "blocking", target_arch = "wasm32"))]
+fn refresh_in_background(
+    _providers: Vec<ProviderConfig>,
+    _target: Option<IpAddr>,
+    _ttl: Option<u64>,
+    _strategy: LookupStrategy,
+    _options: LookupOptions,
+    _location: &CacheLocation,
+) -> bool {
+    false
+}
+
+/// Performs lookups for a list of targets, using the cache and the bulk endpoints of the providers.
+///
+/// Every target is first checked in the target cache, only the cache misses are queried. The providers
+/// are tried in order for the remaining targets, providers with a bulk endpoint answer for many
+/// targets with a single request. The successful responses are stored in the cache.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to use for the lookup.
+/// * `targets` - Target addresses for the lookup.
+/// * `cache_expire_time` - An `Option` containing the number of seconds before the cache expires. If `None`,
+///   the cache never expires. If `0`, the cache expires immediately after the request.
+/// * `flush` - A `bool` indicating whether to ignore the cached responses and make new requests.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::lookup::LookupProvider;
+///
+/// # use std::error::Error;
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let providers = vec![(LookupProvider::IpWhoIs, None)];
+/// let targets = vec!["1.1.1.1".parse()?, "8.8.8.8".parse()?];
+///
+/// for result in public_ip_address::perform_cached_bulk_lookup_with(providers, targets, Some(60), false).await {
+///     match result {
+///         Ok(response) => {
+///             // Handle successful response
+///         }
+///         Err(e) => {
+///             // Handle error
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `Vec` with a `Result` for every target, in the order of the targets.
+#[maybe_async::maybe_async]
+pub async fn perform_cached_bulk_lookup_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    targets: Vec<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+) -> Vec<Result<LookupResponse>> {
+    let location = CacheLocation::default();
+    perform_cached_bulk_lookup_at(providers, targets, ttl, flush, &location).await
+}
+
+/// Performs the lookups of `perform_cached_bulk_lookup_with` with the cache stored at the given
+/// location
+///
+/// See [`CacheLocation`] for how the location is resolved.
+#[maybe_async::maybe_async]
+pub async fn perform_cached_bulk_lookup_at(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    targets: Vec<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+    location: &CacheLocation,
+) -> Vec<Result<LookupResponse>> {
+    let mut cache = bulk_cache_writer(location);
+    let (mut found, mut misses) = split_cached_targets(&cache, &targets, flush);
+
+    let mut errors = Vec::new();
+    bulk_lookup_in_order(
+        &mut cache,
+        config::config().policy.apply(dedup_providers(providers)),
+        &mut misses,
+        &mut found,
+        &mut errors,
+        ttl,
+    )
+    .await;
+    if let Err(e) = cache.finish() {
+        warn!("Failed to save cache: {}", e);
+    }
+
+    collect_bulk_results(&targets, &found, &errors)
+}
+
+/// Performs lookups for a list of targets, splitting them across several providers in parallel.
+///
+/// Works like `perform_cached_bulk_lookup_with`, but instead of sending all cache misses to the first
+/// provider, the targets are scheduled across all providers that support target lookups, respecting
+/// their bulk limits and remaining daily quota (see `ResponseCache::schedule_bulk`). Every provider
+/// works through its share concurrently with the others. Targets of failed or unscheduled shares
+/// are retried with the providers in order, like `perform_cached_bulk_lookup_with` does.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to use for the lookup.
+/// * `targets` - Target addresses for the lookup.
+/// * `cache_expire_time` - An `Option` containing the number of seconds before the cache expires. If `None`,
+///   the cache never expires. If `0`, the cache expires immediately after the request.
+/// * `flush` - A `bool` indicating whether to ignore the cached responses and make new requests.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::lookup::LookupProvider;
+///
+/// # use std::error::Error;
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let providers = vec![(LookupProvider::IpWhoIs, None), (LookupProvider::FreeIpApi, None)];
+/// let targets = vec!["1.1.1.1".parse()?, "8.8.8.8".parse()?];
+///
+/// for result in public_ip_address::perform_fan_out_bulk_lookup_with(providers, targets, Some(60), false).await {
+///     match result {
+///         Ok(response) => {
+///             // Handle successful response
+///         }
+///         Err(e) => {
+///             // Handle error
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `Vec` with a `Result` for every target, in the order of the targets.
+#[maybe_async::maybe_async]
+pub async fn perform_fan_out_bulk_lookup_with(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    targets: Vec<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+) -> Vec<Result<LookupResponse>> {
+    let location = CacheLocation::default();
+    perform_fan_out_bulk_lookup_at(providers, targets, ttl, flush, &location).await
+}
+
+/// Performs the lookups of `perform_fan_out_bulk_lookup_with` with the cache stored at the given
+/// location
+///
+/// See [`CacheLocation`] for how the location is resolved.
+#[maybe_async::maybe_async]
+pub async fn perform_fan_out_bulk_lookup_at(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    targets: Vec<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+    location: &CacheLocation,
+) -> Vec<Result<LookupResponse>> {
+    let mut cache = bulk_cache_writer(location);
+    let (mut found, mut misses) = split_cached_targets(&cache, &targets, flush);
+    let providers = config::config().policy.apply(dedup_providers(providers));
+
+    let mut errors = Vec::new();
+    let schedule = cache.schedule_bulk(&providers, &misses);
+    for (provider, result) in run_bulk_schedule(schedule).await {
+        match result {
+            Ok(responses) => {
+                cache.record_usage(&provider);
+                let fresh: Vec<LookupResponse> = responses
+                    .into_iter()
+                    .filter(|response| misses.contains(&response.ip))
+                    .collect();
+                misses.retain(|ip| !fresh.iter().any(|response| &response.ip == ip));
+                cache.update_targets(&fresh, ttl);
+                found.extend(fresh);
+            }
+            Err(e) => {
+                warn!(
+                    "Provider {} failed to perform its share of the bulk lookup",
+                    provider
+                );
+                errors.push(e);
+            }
+        }
+    }
+
+    bulk_lookup_in_order(
+        &mut cache,
+        providers,
+        &mut misses,
+        &mut found,
+        &mut errors,
+        ttl,
+    )
+    .await;
+    if let Err(e) = cache.finish() {
+        warn!("Failed to save cache: {}", e);
+    }
+
+    collect_bulk_results(&targets, &found, &errors)
+}
+
+/// Performs a cached bulk lookup that can be cancelled by the caller.
+///
+/// Works like `perform_cached_bulk_lookup_with`, but stops as soon as the `cancel` future completes.
+/// In-flight provider requests are dropped and every target resolves to `LookupError::Cancelled`.
+/// Results the cache already wrote in batches, see `Config::with_cache_flush`, stay in the cache,
+/// the ones still pending are discarded.
+///
+/// Only available in the async interface.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to use for the lookup.
+/// * `targets` - Target addresses for the lookup.
+/// * `cache_expire_time` - An `Option` containing the number of seconds before the cache expires. If `None`,
+///   the cache never expires. If `0`, the cache expires immediately after the request.
+/// * `flush` - A `bool` indicating whether to ignore the cached responses and make new requests.
+/// * `cancel` - Future that cancels the lookup once it completes.
+///
+/// # Returns
+///
+/// * A `Vec` with a `Result` for every target, in the order of the targets.
+#[cfg(not(feature = "blocking"))]
+pub async fn perform_cached_bulk_lookup_with_cancel<C>(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    targets: Vec<IpAddr>,
+    ttl: Option<u64>,
+    flush: bool,
+    cancel: C,
+) -> Vec<Result<LookupResponse>>
+where
+    C: std::future::Future<Output = ()>,
+{
+    use futures_util::future::{select, Either};
+
+    let count = targets.len();
+    let lookup = std::pin::pin!(perform_cached_bulk_lookup_with(
+        providers, targets, ttl, flush
+    ));
+    match select(lookup, std::pin::pin!(cancel)).await {
+        Either::Left((responses, _)) => responses,
+        Either::Right(_) => {
+            warn!("Bulk lookup cancelled");
+            (0..count)
+                .map(|_| Err(Error::LookupError(LookupError::Cancelled)))
+                .collect()
+        }
+    }
+}
+
+/// Runs the shares of a bulk schedule concurrently
+#[cfg(not(feature = "blocking"))]
+async fn run_bulk_schedule(
+    schedule: Vec<(LookupProvider, Option<Parameters>, Vec<IpAddr>)>,
+) -> Vec<(LookupProvider, lookup::error::Result<Vec<LookupResponse>>)> {
+    let lookups = schedule
+        .into_iter()
+        .map(|(provider, param, share)| async move {
+            debug!(
+                "Performing bulk lookup of {} targets with provider {}",
+                share.len(),
+                &provider
+            );
+            let result = LookupService::new(provider.clone(), param)
+                .bulk_lookup(&share)
+                .await;
+            (provider, result)
+        });
+    futures_util::future::join_all(lookups).await
+}
+
+/// Runs the shares of a bulk schedule concurrently, every share on its own thread
+#[cfg(feature = "blocking")]
+fn run_bulk_schedule(
+    schedule: Vec<(LookupProvider, Option<Parameters>, Vec<IpAddr>)>,
+) -> Vec<(LookupProvider, lookup::error::Result<Vec<LookupResponse>>)> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = schedule
+            .into_iter()
+            .map(|(provider, param, share)| {
+                let handle = scope.spawn({
+                    let provider = provider.clone();
+                    move || {
+                        debug!(
+                            "Performing bulk lookup of {} targets with provider {}",
+                            share.len(),
+                            &provider
+                        );
+                        LookupService::new(provider, param).bulk_lookup(&share)
+                    }
+                });
+                (provider, handle)
+            })
+            .collect();
+        // the targets of a panicked share are left to the remaining providers
+        handles
+            .into_iter()
+            .map(|(provider, handle)| {
+                let result = handle.join().unwrap_or_else(|_| {
+                    Err(LookupError::GenericError(format!(
+                        "Bulk lookup with provider {} panicked",
+                        provider
+                    )))
+                });
+                (provider, result)
+            })
+            .collect()
+    })
+}
+
+/// Checks the health of a list of providers.
+///
+/// Every provider looks up the current public address once, all providers are checked
+/// concurrently. Use it at startup to pick a working set of providers.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to check.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::lookup::LookupProvider;
+///
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() {
+/// let providers = vec![(LookupProvider::IpWhoIs, None), (LookupProvider::MyIp, None)];
+/// let working: Vec<LookupProvider> = public_ip_address::check_providers(providers)
+///     .await
+///     .into_iter()
+///     .filter(|health| health.is_healthy())
+///     .map(|health| health.provider)
+///     .collect();
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `ProviderHealth` for every provider, in the order of the providers.
+#[cfg(not(feature = "blocking"))]
+pub async fn check_providers(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+) -> Vec<ProviderHealth> {
+    let checks = providers.into_iter().map(|(provider, param)| async move {
+        debug!("Checking health of provider {}", &provider);
+        LookupService::new(provider, param).health_check().await
+    });
+    futures_util::future::join_all(checks).await
+}
+
+/// Checks the health of a list of providers.
+///
+/// Every provider looks up the current public address once, all providers are checked
+/// concurrently. Use it at startup to pick a working set of providers.
+///
+/// # Arguments
+///
+/// * `providers` - A vector of `LookupProvider`s and their `Parameters` to check.
+///
+/// # Example
+///
+/// ```rust
+/// use public_ip_address::lookup::LookupProvider;
+///
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() {
+/// let providers = vec![(LookupProvider::IpWhoIs, None), (LookupProvider::MyIp, None)];
+/// let working: Vec<LookupProvider> = public_ip_address::check_providers(providers)
+///     .await
+///     .into_iter()
+///     .filter(|health| health.is_healthy())
+///     .map(|health| health.provider)
+///     .collect();
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * A `ProviderHealth` for every provider, in the order of the providers.
+#[cfg(feature = "blocking")]
+pub fn check_providers(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+) -> Vec<ProviderHealth> {
+    let started = Instant::now();
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = providers
+            .into_iter()
+            .map(|(provider, param)| {
+                let handle = scope.spawn({
+                    let provider = provider.clone();
+                    move || {
+                        debug!("Checking health of provider {}", &provider);
+                        LookupService::new(provider, param).health_check()
+                    }
+                });
+                (provider, handle)
+            })
+            .collect();
+        // a panicked check still reports its provider, as unhealthy
+        handles
+            .into_iter()
+            .map(|(provider, handle)| {
+                handle.join().unwrap_or_else(|_| ProviderHealth {
+                    error: Some(format!("Health check of provider {} panicked", provider)),
+                    provider,
+                    reachable: false,
+                    parsed: false,
+                    latency: started.elapsed(),
+                })
+            })
+            .collect()
+    })
+}
+
+/// Removes duplicate providers and the providers rejected by the configured provider policy
+fn prepare_chain(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+) -> Result<Vec<(LookupProvider, Option<Parameters>)>> {
+    if providers.is_empty() {
+        return Err(Error::LookupError(LookupError::GenericError(
+            "No providers given".to_string(),
+        )));
+    }
+    let providers = config::config().policy.apply(dedup_providers(providers));
+    if providers.is_empty() {
+        return Err(Error::LookupError(LookupError::GenericError(
+            "No providers allowed by the provider policy".to_string(),
+        )));
+    }
+    Ok(providers)
+}
+
+/// Checks if a failed request may succeed when repeated
+fn is_retryable(error: &LookupError) -> bool {
+    matches!(error, LookupError::Timeout | LookupError::RequestStatus(_))
+        || error.client_error().is_some()
+}
+
+/// Filters a chain of provider configurations like `prepare_chain`
+fn prepare_configs(providers: Vec<ProviderConfig>) -> Result<Vec<ProviderConfig>> {
+    let pairs = providers
+        .iter()
+        .map(|entry| (entry.provider.clone(), entry.parameters.clone()))
+        .collect();
+    let mut providers = providers;
+    let prepared = prepare_chain(pairs)?
+        .into_iter()
+        .filter_map(|(provider, parameters)| {
+            let index = providers
+                .iter()
+                .position(|entry| entry.provider == provider && entry.parameters == parameters)?;
+            Some(providers.remove(index))
+        })
+        .collect();
+    Ok(prepared)
+}
+
+/// Checks if a provider error is worth remembering, the provider is rate limited or down
+fn is_negative_cacheable(error: &LookupError) -> bool {
+    matches!(
+        error,
+        LookupError::TooManyRequests(_) | LookupError::RequestStatus(_) | LookupError::Timeout
+    ) || error.client_error().is_some()
+}
+
+/// Loads the cache at the location for a bulk lookup, batching the writes as configured
+fn bulk_cache_writer(location: &CacheLocation) -> WriteBehind {
+    let config = config::config();
+    let file_name = location.file_name();
+    WriteBehind::new(
+        ResponseCache::load(file_name.clone()).unwrap_or_else(|_| ResponseCache::new(file_name)),
+        config.cache_flush_batch,
+        config.cache_flush_interval,
+    )
+}
+
+/// Splits bulk targets into cached responses and addresses that still need a lookup
+fn split_cached_targets(
+    cache: &ResponseCache,
+    targets: &[IpAddr],
+    flush: bool,
+) -> (Vec<LookupResponse>, Vec<IpAddr>) {
+    let mut found: Vec<LookupResponse> = Vec::new();
+    let mut misses: Vec<IpAddr> = Vec::new();
+    for target in targets {
+        if non_public_kind(target).is_some() || misses.contains(target) {
+            continue;
+        }
+        match cache.lookup_address.get(target) {
+            Some(record) if !record.is_expired() && !flush => {
+                trace!("Using cached value for {}", target);
+                let mut response = record.response.to_owned();
+                response.meta.selection_reason = SelectionReason::CacheHit;
+                found.push(response);
+            }
+            _ => misses.push(*target),
+        }
+    }
+    (found, misses)
+}
+
+/// Tries the providers in order until all missing targets are found
+#[maybe_async::maybe_async]
+async fn bulk_lookup_in_order(
+    cache: &mut WriteBehind,
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    misses: &mut Vec<IpAddr>,
+    found: &mut Vec<LookupResponse>,
+    errors: &mut Vec<LookupError>,
+    ttl: Option<u64>,
+) {
+    for (index, (provider, param)) in providers.into_iter().enumerate() {
+        if misses.is_empty() {
+            break;
+        }
+        debug!(
+            "Performing bulk lookup of {} targets with provider {}",
+            misses.len(),
+            &provider
+        );
+        let service = LookupService::new(provider.clone(), param);
+        match service.bulk_lookup(misses).await {
+            Ok(responses) => {
+                cache.record_usage(&provider);
+                let mut fresh = Vec::with_capacity(responses.len());
+                for mut response in responses {
+                    if !misses.contains(&response.ip) {
+                        continue;
+                    }
+                    if index > 0 {
+                        response.meta.selection_reason = SelectionReason::Fallback(index);
+                    }
+                    misses.retain(|ip| ip != &response.ip);
+                    fresh.push(response);
+                }
+                cache.update_targets(&fresh, ttl);
+                found.extend(fresh);
+            }
+            Err(e) => {
+                warn!("Provider failed to perform bulk lookup");
+                errors.push(e);
+            }
+        }
+    }
+}
+
+/// Maps every bulk target to its response or error
+fn collect_bulk_results(
+    targets: &[IpAddr],
+    found: &[LookupResponse],
+    errors: &[LookupError],
+) -> Vec<Result<LookupResponse>> {
+    targets
+        .iter()
+        .map(|target| {
+            if let Some(kind) = non_public_kind(target) {
+                return Err(Error::LookupError(LookupError::NonPublicAddress(kind)));
+            }
+            match found.iter().find(|response| &response.ip == target) {
+                Some(response) => Ok(response.to_owned()),
+                None => Err(Error::LookupError(LookupError::GenericError(format!(
+                    "No responses from providers for {}: {:?}",
+                    target, errors
+                )))),
+            }
+        })
+        .collect()
 }
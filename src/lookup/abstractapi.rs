@@ -1,23 +1,27 @@
 //! <https://abstractapi.com> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{check_error_body, error_field, parse_response_ip, ProviderResponse, Result};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
+    response::ConnectionType,
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://docs.abstractapi.com/ip-geolocation>
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AbstractApiResponse {
     ip_address: String,
     city: Option<String>,
+    city_geoname_id: Option<u64>,
     region: Option<String>,
     region_iso_code: Option<String>,
+    region_geoname_id: Option<u64>,
     postal_code: Option<String>,
     country: Option<String>,
     country_code: Option<String>,
+    country_geoname_id: Option<u64>,
     country_is_eu: Option<bool>,
     continent: Option<String>,
     continent_code: Option<String>,
@@ -41,17 +45,16 @@ struct Timezone {
 #[derive(Serialize, Deserialize, Debug)]
 struct Connection {
     autonomous_system_number: Option<i64>,
+    autonomous_system_organization: Option<String>,
     connection_type: Option<String>,
     organization_name: Option<String>,
     isp_name: Option<String>,
 }
 
 impl ProviderResponse<AbstractApiResponse> for AbstractApiResponse {
-    fn into_response(self) -> LookupResponse {
+    fn into_response(self) -> Result<LookupResponse> {
         let mut response = LookupResponse::new(
-            self.ip_address
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
+            parse_response_ip(&self.ip_address)?,
             LookupProvider::AbstractApi,
         );
         response.country = self.country;
@@ -60,20 +63,32 @@ impl ProviderResponse<AbstractApiResponse> for AbstractApiResponse {
         response.region = self.region;
         response.postal_code = self.postal_code;
         response.city = self.city;
+        response.country_geoname_id = self.country_geoname_id;
+        response.region_geoname_id = self.region_geoname_id;
+        response.city_geoname_id = self.city_geoname_id;
         response.latitude = self.latitude;
         response.longitude = self.longitude;
         if let Some(timezone) = self.timezone {
             response.time_zone = timezone.name;
         }
         if let Some(connection) = self.connection {
-            response.asn_org = connection.organization_name;
-            response.asn = connection.isp_name;
+            response.asn_org = connection
+                .autonomous_system_organization
+                .or(connection.organization_name);
+            if let Some(number) = connection.autonomous_system_number {
+                response.asn = Some(format!("AS{number}"));
+            }
+            response.isp = connection.isp_name;
+            response.connection_type = connection
+                .connection_type
+                .filter(|t| !t.is_empty())
+                .map(|t| ConnectionType::from(t.as_str()));
         }
         if let Some(security) = self.security {
             response.is_proxy = security.is_vpn;
         }
 
-        response
+        Ok(response)
     }
 }
 
@@ -94,14 +109,27 @@ impl Provider for AbstractApi {
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::AbstractApi, &json, |reply| {
+            let error = reply.get("error")?;
+            let message = error_field(error, "message").unwrap_or(error.to_string());
+            Some((error_field(error, "code"), message))
+        })?;
         let response = AbstractApiResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::AbstractApi
     }
 
+    fn requires_key(&self) -> bool {
+        true
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(1_000))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
@@ -110,6 +138,7 @@ impl Provider for AbstractApi {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lookup::error::LookupError;
     const TEST_INPUT: &str = r#"
 {
     "ip_address": "1.1.1.1",
@@ -180,11 +209,42 @@ mod tests {
     fn test_parse() {
         let response = AbstractApiResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip_address, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
             "IP address not matching"
         );
+        assert_eq!(
+            lookup.connection_type,
+            Some(ConnectionType::Cellular),
+            "Connection type not matching"
+        );
+        assert_eq!(lookup.asn, Some("AS20057".to_string()), "ASN not matching");
+        assert_eq!(
+            lookup.city_geoname_id,
+            Some(5392171),
+            "City id not matching"
+        );
+        assert_eq!(lookup.country_geoname_id, Some(6252001));
+        assert_eq!(
+            lookup.isp,
+            Some("AT&T Mobility LLC".to_string()),
+            "ISP not matching"
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let input = r#"{"error": {"message": "Invalid API key provided.", "code": "unauthorized", "details": null}}"#;
+        let response = AbstractApi.parse_reply(input.to_string());
+        assert!(
+            matches!(
+                response,
+                Err(LookupError::ProviderError { code: Some(code), message, .. })
+                    if code == "unauthorized" && message == "Invalid API key provided."
+            ),
+            "Error body should be detected"
+        );
     }
 }
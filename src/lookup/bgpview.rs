@@ -0,0 +1,239 @@
+//! <https://bgpview.io> lookup provider
+//!
+//! BGPView returns the routing data of an address: the announced prefixes, their origin AS and the
+//! allocation by the regional internet registry, reported in the `whois` field of the response.
+//! It only looks up a given target and has no geolocation, see [`crate::lookup_asn`].
+
+use super::{check_error_body, error_field, parse_response_ip, ProviderResponse, Result};
+use crate::{
+    lookup::{LookupProvider, Provider},
+    response::WhoisInfo,
+    LookupResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// <https://bgpview.docs.apiary.io/#reference/0/ip/view-ip-address-details>
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BgpViewResponse {
+    status: String,
+    status_message: Option<String>,
+    data: BgpViewData,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct BgpViewData {
+    ip: String,
+    ptr_record: Option<String>,
+    #[serde(default)]
+    prefixes: Vec<Prefix>,
+    rir_allocation: Option<RirAllocation>,
+}
+
+/// Prefix announcing the address
+#[derive(Serialize, Deserialize, Debug)]
+struct Prefix {
+    prefix: String,
+    cidr: Option<u8>,
+    asn: Option<Asn>,
+    name: Option<String>,
+    description: Option<String>,
+    country_code: Option<String>,
+}
+
+/// Allocation of the address block by the regional internet registry
+#[derive(Serialize, Deserialize, Debug)]
+struct RirAllocation {
+    rir_name: Option<String>,
+    country_code: Option<String>,
+    prefix: Option<String>,
+    date_allocated: Option<String>,
+    allocation_status: Option<String>,
+}
+
+/// Origin AS of a prefix
+#[derive(Serialize, Deserialize, Debug)]
+struct Asn {
+    asn: u32,
+    name: Option<String>,
+    description: Option<String>,
+    country_code: Option<String>,
+}
+
+impl ProviderResponse<BgpViewResponse> for BgpViewResponse {
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.data.ip)?, LookupProvider::BgpView);
+        response.hostname = self.data.ptr_record.filter(|p| !p.is_empty());
+        // the most specific prefix is the one the traffic is routed along
+        if let Some(prefix) = self
+            .data
+            .prefixes
+            .into_iter()
+            .max_by_key(|prefix| prefix.cidr.unwrap_or_default())
+        {
+            response.network = Some(prefix.prefix);
+            if let Some(asn) = prefix.asn {
+                response.asn = Some(format!("AS{}", asn.asn));
+                response.asn_org = asn.description.or(asn.name);
+                response.country_code = asn.country_code;
+            }
+        }
+        if let Some(allocation) = self.data.rir_allocation {
+            response.whois = Some(WhoisInfo {
+                country: allocation.country_code,
+                registry: allocation.rir_name,
+                allocation: allocation.prefix,
+                allocation_status: allocation.allocation_status,
+                allocation_date: allocation.date_allocated,
+                ..Default::default()
+            });
+        }
+        Ok(response)
+    }
+}
+
+/// BgpView lookup provider
+pub struct BgpView;
+
+impl Provider for BgpView {
+    fn get_endpoint(&self, _key: &Option<String>, target: &Option<IpAddr>) -> String {
+        let target = target.map(|t| t.to_string()).unwrap_or_default();
+        format!("https://api.bgpview.io/ip/{}", target)
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::BgpView, &json, |reply| {
+            (reply["status"] == "error").then(|| {
+                let message = error_field(reply, "status_message");
+                (None, message.unwrap_or("Request failed".to_string()))
+            })
+        })?;
+        let response = BgpViewResponse::parse(json)?;
+        response.into_response()
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::BgpView
+    }
+
+    fn supports_target_lookup(&self) -> bool {
+        true
+    }
+
+    fn requires_target(&self) -> bool {
+        true
+    }
+
+    fn supports_asn_lookup(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::error::LookupError;
+    const TEST_INPUT: &str = r#"
+{
+  "status": "ok",
+  "status_message": "Query was successful",
+  "data": {
+    "ip": "1.1.1.1",
+    "ptr_record": "one.one.one.one",
+    "prefixes": [
+      {
+        "prefix": "1.1.1.0/24",
+        "ip": "1.1.1.0",
+        "cidr": 24,
+        "asn": {
+          "asn": 13335,
+          "name": "CLOUDFLARENET",
+          "description": "Cloudflare, Inc.",
+          "country_code": "US"
+        },
+        "name": "APNIC-LABS",
+        "description": "APNIC and Cloudflare DNS Resolver project",
+        "country_code": "AU"
+      },
+      {
+        "prefix": "1.1.0.0/16",
+        "ip": "1.1.0.0",
+        "cidr": 16,
+        "asn": {
+          "asn": 13335,
+          "name": "CLOUDFLARENET",
+          "description": "Cloudflare, Inc.",
+          "country_code": "US"
+        },
+        "name": null,
+        "description": null,
+        "country_code": null
+      }
+    ],
+    "rir_allocation": {
+      "rir_name": "APNIC",
+      "country_code": "AU",
+      "ip": "1.0.0.0",
+      "cidr": 8,
+      "prefix": "1.0.0.0/8",
+      "date_allocated": "2010-01-01 00:00:00",
+      "allocation_status": "allocated"
+    }
+  }
+}
+"#;
+
+    #[ignore]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_request() {
+        let service = Box::new(BgpView);
+        let result = service
+            .get_client(None, "1.1.1.1".parse().ok())
+            .send()
+            .await;
+        let result = super::super::handle_response(result).await.unwrap();
+        assert!(!result.is_empty(), "Result is empty");
+        println!("BgpView: {:#?}", result);
+        let response = BgpViewResponse::parse(result);
+        assert!(response.is_ok(), "Failed parsing response {:#?}", response);
+    }
+
+    #[test]
+    fn test_parse() {
+        let response = BgpViewResponse::parse(TEST_INPUT.to_string()).unwrap();
+        assert_eq!(response.data.ip, "1.1.1.1", "IP address not matching");
+        let lookup = response.into_response().unwrap();
+        assert_eq!(
+            lookup.ip,
+            "1.1.1.1".parse::<IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+        assert_eq!(lookup.asn, Some("AS13335".to_string()), "ASN not matching");
+        assert_eq!(
+            lookup.asn_org,
+            Some("Cloudflare, Inc.".to_string()),
+            "ASN organization not matching"
+        );
+        assert_eq!(
+            lookup.network,
+            Some("1.1.1.0/24".to_string()),
+            "Most specific prefix should be picked"
+        );
+        let whois = lookup.whois.unwrap();
+        assert_eq!(whois.registry, Some("APNIC".to_string()));
+        assert_eq!(whois.allocation, Some("1.0.0.0/8".to_string()));
+        assert_eq!(whois.allocation_status, Some("allocated".to_string()));
+    }
+
+    #[test]
+    fn test_error() {
+        let reply = r#"{"status": "error", "status_message": "Malformed input"}"#;
+        let response = BgpView.parse_reply(reply.to_string());
+        assert!(
+            matches!(response, Err(LookupError::ProviderError { ref message, .. }) if message == "Malformed input"),
+            "Error status should fail, got {:?}",
+            response
+        );
+    }
+}
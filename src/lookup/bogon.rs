@@ -0,0 +1,156 @@
+//! Detection of addresses that can't be looked up on the public internet
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// Kind of a non public (bogon) address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AddressKind {
+    /// Unspecified address, `0.0.0.0` or `::`
+    Unspecified,
+    /// Loopback address, `127.0.0.0/8` or `::1`
+    Loopback,
+    /// Private network, `10.0.0.0/8`, `172.16.0.0/12`, `192.168.0.0/16` or IPv6 unique local `fc00::/7`
+    Private,
+    /// Carrier-grade NAT shared address space, `100.64.0.0/10`
+    SharedAddressSpace,
+    /// Link-local address, `169.254.0.0/16` or `fe80::/10`
+    LinkLocal,
+    /// Documentation address, `192.0.2.0/24`, `198.51.100.0/24`, `203.0.113.0/24` or `2001:db8::/32`
+    Documentation,
+    /// Benchmarking address, `198.18.0.0/15`
+    Benchmarking,
+    /// Multicast address, `224.0.0.0/4` or `ff00::/8`
+    Multicast,
+    /// Broadcast address, `255.255.255.255`
+    Broadcast,
+    /// Other reserved address space
+    Reserved,
+}
+
+impl fmt::Display for AddressKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self {
+            AddressKind::Unspecified => "unspecified",
+            AddressKind::Loopback => "loopback",
+            AddressKind::Private => "private",
+            AddressKind::SharedAddressSpace => "shared address space",
+            AddressKind::LinkLocal => "link-local",
+            AddressKind::Documentation => "documentation",
+            AddressKind::Benchmarking => "benchmarking",
+            AddressKind::Multicast => "multicast",
+            AddressKind::Broadcast => "broadcast",
+            AddressKind::Reserved => "reserved",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// Returns the kind of the address if it is not publicly routable, `None` for public addresses
+///
+/// # Example
+///
+/// ```rust
+/// # use public_ip_address::lookup::bogon::{non_public_kind, AddressKind};
+/// assert_eq!(non_public_kind(&"192.168.1.1".parse().unwrap()), Some(AddressKind::Private));
+/// assert_eq!(non_public_kind(&"1.1.1.1".parse().unwrap()), None);
+/// ```
+pub fn non_public_kind(ip: &IpAddr) -> Option<AddressKind> {
+    match ip {
+        IpAddr::V4(ip) => non_public_kind_v4(ip),
+        IpAddr::V6(ip) => non_public_kind_v6(ip),
+    }
+}
+
+fn non_public_kind_v4(ip: &Ipv4Addr) -> Option<AddressKind> {
+    let [a, b, c, _] = ip.octets();
+    if ip.is_unspecified() || a == 0 {
+        Some(AddressKind::Unspecified)
+    } else if ip.is_loopback() {
+        Some(AddressKind::Loopback)
+    } else if ip.is_private() {
+        Some(AddressKind::Private)
+    } else if a == 100 && (b & 0xc0) == 64 {
+        Some(AddressKind::SharedAddressSpace)
+    } else if ip.is_link_local() {
+        Some(AddressKind::LinkLocal)
+    } else if ip.is_documentation() {
+        Some(AddressKind::Documentation)
+    } else if a == 198 && (b & 0xfe) == 18 {
+        Some(AddressKind::Benchmarking)
+    } else if ip.is_multicast() {
+        Some(AddressKind::Multicast)
+    } else if ip.is_broadcast() {
+        Some(AddressKind::Broadcast)
+    } else if a >= 240 || (a == 192 && b == 0 && c == 0) {
+        Some(AddressKind::Reserved)
+    } else {
+        None
+    }
+}
+
+fn non_public_kind_v6(ip: &Ipv6Addr) -> Option<AddressKind> {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return non_public_kind_v4(&ip);
+    }
+    let segments = ip.segments();
+    if ip.is_unspecified() {
+        Some(AddressKind::Unspecified)
+    } else if ip.is_loopback() {
+        Some(AddressKind::Loopback)
+    } else if (segments[0] & 0xfe00) == 0xfc00 {
+        Some(AddressKind::Private)
+    } else if (segments[0] & 0xffc0) == 0xfe80 {
+        Some(AddressKind::LinkLocal)
+    } else if segments[0] == 0x2001 && segments[1] == 0x0db8 {
+        Some(AddressKind::Documentation)
+    } else if ip.is_multicast() {
+        Some(AddressKind::Multicast)
+    } else if segments[0] == 0x0100 && segments[1..4] == [0, 0, 0] {
+        // discard-only prefix 100::/64
+        Some(AddressKind::Reserved)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(ip: &str) -> Option<AddressKind> {
+        non_public_kind(&ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_non_public_kind() {
+        assert_eq!(kind("0.0.0.0"), Some(AddressKind::Unspecified));
+        assert_eq!(kind("127.0.0.1"), Some(AddressKind::Loopback));
+        assert_eq!(kind("10.1.2.3"), Some(AddressKind::Private));
+        assert_eq!(kind("172.16.0.1"), Some(AddressKind::Private));
+        assert_eq!(kind("100.64.0.1"), Some(AddressKind::SharedAddressSpace));
+        assert_eq!(kind("169.254.1.1"), Some(AddressKind::LinkLocal));
+        assert_eq!(kind("203.0.113.5"), Some(AddressKind::Documentation));
+        assert_eq!(kind("198.19.0.1"), Some(AddressKind::Benchmarking));
+        assert_eq!(kind("224.0.0.1"), Some(AddressKind::Multicast));
+        assert_eq!(kind("255.255.255.255"), Some(AddressKind::Broadcast));
+        assert_eq!(kind("240.0.0.1"), Some(AddressKind::Reserved));
+        assert_eq!(kind("::"), Some(AddressKind::Unspecified));
+        assert_eq!(kind("::1"), Some(AddressKind::Loopback));
+        assert_eq!(kind("fd00::1"), Some(AddressKind::Private));
+        assert_eq!(kind("fe80::1"), Some(AddressKind::LinkLocal));
+        assert_eq!(kind("2001:db8::1"), Some(AddressKind::Documentation));
+        assert_eq!(kind("::ffff:192.168.0.1"), Some(AddressKind::Private));
+    }
+
+    #[test]
+    fn test_public() {
+        assert_eq!(kind("1.1.1.1"), None);
+        assert_eq!(kind("100.128.0.1"), None);
+        assert_eq!(kind("172.32.0.1"), None);
+        assert_eq!(kind("2606:4700:4700::1111"), None);
+    }
+}
@@ -0,0 +1,137 @@
+//! Per-provider options of a provider chain
+//!
+//! Every entry of the chain passed to `perform_lookup_with` can carry its own timeout, retries,
+//! weight and API key. Plain `(LookupProvider, Option<Parameters>)` tuples and providers convert
+//! into entries with the default options, so existing chains keep working.
+//!
+//! ```rust
+//! use public_ip_address::lookup::{chain::ProviderConfig, LookupProvider};
+//! use std::time::Duration;
+//!
+//! let providers = vec![
+//!     ProviderConfig::new(LookupProvider::IpInfo)
+//!         .with_api_key("token")
+//!         .with_timeout(Duration::from_secs(2))
+//!         .with_retries(1),
+//!     ProviderConfig::from(LookupProvider::IpWhoIs),
+//! ];
+//! ```
+
+use super::{LookupProvider, Parameters};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Provider of a chain and its options
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ProviderConfig {
+    /// Provider to query
+    pub provider: LookupProvider,
+    /// Parameters of the requests, `None` reads the API key from the environment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Parameters>,
+    /// Timeout of the requests to this provider, overriding the timeout of the lookup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Duration>,
+    /// Number of times a request failing with a timeout or a network error is repeated
+    #[serde(default)]
+    pub retries: usize,
+    /// Relative weight of the provider, providers with a higher weight are tried first
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+impl ProviderConfig {
+    /// Creates an entry with the default options
+    pub fn new(provider: LookupProvider) -> Self {
+        ProviderConfig {
+            provider,
+            parameters: None,
+            timeout: None,
+            retries: 0,
+            weight: default_weight(),
+        }
+    }
+
+    /// Sets the parameters of the requests
+    pub fn with_parameters(mut self, parameters: Parameters) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+
+    /// Sets the API key, keeping the other parameters
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        let key = key.into();
+        match &mut self.parameters {
+            Some(parameters) => parameters.api_key = key,
+            None => self.parameters = Some(Parameters::new(key)),
+        }
+        self
+    }
+
+    /// Sets the timeout of the requests to this provider
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how many times a failed request is repeated
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the relative weight of the provider
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+impl From<LookupProvider> for ProviderConfig {
+    fn from(provider: LookupProvider) -> Self {
+        ProviderConfig::new(provider)
+    }
+}
+
+impl From<(LookupProvider, Option<Parameters>)> for ProviderConfig {
+    fn from((provider, parameters): (LookupProvider, Option<Parameters>)) -> Self {
+        ProviderConfig {
+            parameters,
+            ..ProviderConfig::new(provider)
+        }
+    }
+}
+
+impl From<ProviderConfig> for (LookupProvider, Option<Parameters>) {
+    fn from(config: ProviderConfig) -> Self {
+        (config.provider, config.parameters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_config() {
+        let config = ProviderConfig::new(LookupProvider::IpData)
+            .with_parameters(Parameters::new("old".to_string()).with_language("de"))
+            .with_api_key("new")
+            .with_retries(2);
+        let parameters = config.parameters.clone().unwrap();
+        assert_eq!(parameters.api_key, "new");
+        assert_eq!(parameters.language, Some("de".to_string()));
+        assert_eq!(config.retries, 2);
+        assert_eq!(config.weight, 1);
+
+        let config: ProviderConfig = (LookupProvider::IpWhoIs, None).into();
+        assert_eq!(config, ProviderConfig::new(LookupProvider::IpWhoIs));
+        let parsed: ProviderConfig = serde_json::from_str(r#"{"provider": "ipwhois"}"#).unwrap();
+        assert_eq!(parsed, config);
+    }
+}
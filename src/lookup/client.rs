@@ -1,5 +0,0 @@
-#[cfg(not(feature = "blocking"))]
-pub use ::reqwest::*;
-
-#[cfg(feature = "blocking")]
-pub use reqwest::blocking::*;
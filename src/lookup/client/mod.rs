@@ -0,0 +1,547 @@
+//! HTTP client of the lookups
+//!
+//! Requests are described with the types of this module and sent through a [`Backend`], which is
+//! reqwest by default. The `ureq` feature swaps in ureq, a small synchronous client that doesn't
+//! need tokio, it implies `blocking` and wins when both backends are enabled.
+//!
+//! The backends differ in what they support, unsupported settings fail instead of being ignored:
+//! ureq can't bind the connections to a local address or a network interface and doesn't expose
+//! the certificate of the server for pinning.
+
+#[cfg(not(any(feature = "reqwest", feature = "ureq")))]
+compile_error!("An HTTP backend is required, enable the `reqwest` or the `ureq` feature");
+
+#[cfg(not(feature = "ureq"))]
+mod reqwest;
+#[cfg(feature = "ureq")]
+mod ureq;
+
+pub use http::{header, Method, StatusCode};
+pub use url::Url;
+
+#[cfg(not(feature = "ureq"))]
+pub(crate) use self::reqwest::is_connect;
+#[cfg(all(feature = "otel", not(feature = "ureq")))]
+pub(crate) use self::reqwest::is_timeout;
+#[cfg(feature = "ureq")]
+pub(crate) use self::ureq::is_connect;
+#[cfg(all(feature = "otel", feature = "ureq"))]
+pub(crate) use self::ureq::is_timeout;
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::Parameters;
+use super::{
+    error::{LookupError, Result},
+    redirect::RedirectPolicy,
+    AddressFamily,
+};
+use header::{HeaderMap, HeaderName, HeaderValue};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{collections::HashMap, sync::Mutex};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::OnceLock,
+    time::Duration,
+};
+
+/// Backend sending the requests
+#[cfg(not(feature = "ureq"))]
+type Selected = self::reqwest::Reqwest;
+/// Backend sending the requests
+#[cfg(feature = "ureq")]
+type Selected = self::ureq::Ureq;
+
+/// Error of the backend, for requests that failed before a reply was received
+#[cfg(not(feature = "ureq"))]
+pub type Error = ::reqwest::Error;
+/// Error of the backend, for requests that failed before a reply was received
+#[cfg(feature = "ureq")]
+pub type Error = ::ureq::Transport;
+
+/// HTTP client implementation the requests are sent through
+#[maybe_async::maybe_async(AFIT)]
+pub(crate) trait Backend: Clone + fmt::Debug + Sized {
+    /// Unread body of a response
+    type Body;
+
+    /// Builds the client with the given settings, failing on settings it can't apply
+    fn build(builder: ClientBuilder) -> Result<Self>;
+
+    /// Sends the request, following redirects as configured
+    ///
+    /// Replies with an error status are returned as responses, only requests that failed without
+    /// a reply are errors.
+    async fn execute(&self, request: Request) -> Result<Response>;
+
+    /// Reads the body, failing once it grows larger than the limit
+    async fn read(body: Self::Body, limit: usize) -> Result<Vec<u8>>;
+}
+
+/// Settings of a [`Client`]
+///
+/// The defaults match those of the process-wide configuration.
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: usize,
+    tcp_keepalive: Option<Duration>,
+    redirect: RedirectPolicy,
+    allow_https_downgrade: bool,
+    local_address: Option<IpAddr>,
+    interface: Option<String>,
+    family: Option<AddressFamily>,
+    resolve: Vec<(String, Vec<SocketAddr>)>,
+    root_certificates: Vec<String>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder {
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+            tcp_keepalive: None,
+            redirect: RedirectPolicy::default(),
+            allow_https_downgrade: false,
+            local_address: None,
+            interface: None,
+            family: None,
+            resolve: Vec::new(),
+            root_certificates: Vec::new(),
+        }
+    }
+}
+
+impl ClientBuilder {
+    /// Returns a builder with the default settings
+    pub fn new() -> Self {
+        ClientBuilder::default()
+    }
+
+    /// Sets how long idle connections are kept open, `None` keeps them until the server closes them
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Sets the number of idle connections kept open per host
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = max;
+        self
+    }
+
+    /// Sets the interval of the TCP keep-alive probes, `None` disables them
+    pub fn tcp_keepalive(mut self, interval: Option<Duration>) -> Self {
+        self.tcp_keepalive = interval;
+        self
+    }
+
+    /// Sets the redirects followed by the requests
+    pub fn redirect(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect = policy;
+        self
+    }
+
+    /// Allows following redirects from HTTPS to plain HTTP
+    pub fn https_downgrade(mut self, allow: bool) -> Self {
+        self.allow_https_downgrade = allow;
+        self
+    }
+
+    /// Binds the outgoing connections to a local address
+    pub fn local_address(mut self, address: IpAddr) -> Self {
+        self.local_address = Some(address);
+        self
+    }
+
+    /// Binds the outgoing connections to a network interface
+    pub fn interface(mut self, interface: &str) -> Self {
+        self.interface = Some(interface.to_string());
+        self
+    }
+
+    /// Restricts the outgoing connections to an address family
+    pub fn family(mut self, family: AddressFamily) -> Self {
+        self.family = Some(family);
+        self
+    }
+
+    /// Resolves the host to the given address instead of asking the system resolver
+    pub fn resolve(self, host: &str, address: SocketAddr) -> Self {
+        self.resolve_to_addrs(host, &[address])
+    }
+
+    /// Resolves the host to the given addresses, tried in order, instead of asking the system resolver
+    pub fn resolve_to_addrs(mut self, host: &str, addresses: &[SocketAddr]) -> Self {
+        self.resolve.push((host.to_lowercase(), addresses.to_vec()));
+        self
+    }
+
+    /// Trusts the PEM encoded root certificate, the system roots are no longer trusted once one is added
+    pub fn add_root_certificate(mut self, pem: impl Into<String>) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Builds the client, failing on settings the backend can't apply
+    pub fn build(self) -> Result<Client> {
+        Ok(Client {
+            backend: Selected::build(self)?,
+        })
+    }
+}
+
+/// HTTP client, clones share the connection pool
+#[derive(Debug, Clone)]
+pub struct Client {
+    backend: Selected,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new()
+    }
+}
+
+impl Client {
+    /// Returns a client with the default settings
+    ///
+    /// # Panics
+    /// Panics if the backend fails to initialize, like [`ClientBuilder::build`] with the defaults.
+    pub fn new() -> Self {
+        ClientBuilder::new()
+            .build()
+            .expect("Failed building the HTTP client")
+    }
+
+    /// Returns a builder to configure a client
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// Starts a GET request to the URL
+    pub fn get(&self, url: impl AsRef<str>) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    /// Starts a POST request to the URL
+    pub fn post(&self, url: impl AsRef<str>) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    /// Starts a request with the given method to the URL
+    pub fn request(&self, method: Method, url: impl AsRef<str>) -> RequestBuilder {
+        let url = url.as_ref();
+        let request = Url::parse(url)
+            .map(|url| Request::new(method, url))
+            .map_err(|e| format!("Invalid URL {}: {}", url, e));
+        RequestBuilder {
+            client: self.clone(),
+            request,
+        }
+    }
+
+    /// Sends the request, see [`Backend::execute`]
+    #[maybe_async::maybe_async]
+    pub async fn execute(&self, request: Request) -> Result<Response> {
+        self.backend.execute(request).await
+    }
+}
+
+/// HTTP request
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    body: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+}
+
+impl Request {
+    /// Creates a request without headers or body
+    pub fn new(method: Method, url: Url) -> Self {
+        Request {
+            method,
+            url,
+            headers: HeaderMap::new(),
+            body: None,
+            timeout: None,
+        }
+    }
+
+    /// Returns the method of the request
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the URL of the request
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Returns the headers of the request
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the body of the request
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+
+    /// Returns the timeout of the request, it overrides the timeout of the client
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+}
+
+/// Builder of a [`Request`]
+///
+/// Invalid input is kept until the request is built or sent, which then fails with it.
+#[derive(Debug, Clone)]
+pub struct RequestBuilder {
+    client: Client,
+    request: std::result::Result<Request, String>,
+}
+
+impl RequestBuilder {
+    /// Applies the change to the request, unless the builder already failed
+    fn and_then(
+        mut self,
+        change: impl FnOnce(&mut Request) -> std::result::Result<(), String>,
+    ) -> Self {
+        if let Ok(request) = &mut self.request {
+            if let Err(e) = change(request) {
+                self.request = Err(e);
+            }
+        }
+        self
+    }
+
+    /// Adds a header to the request
+    pub fn header<K, V>(self, name: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: fmt::Display,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: fmt::Display,
+    {
+        self.and_then(|request| {
+            let name = HeaderName::try_from(name).map_err(|e| format!("Invalid header: {}", e))?;
+            let value = HeaderValue::try_from(value)
+                .map_err(|e| format!("Invalid value of header {}: {}", name, e))?;
+            request.headers.append(name, value);
+            Ok(())
+        })
+    }
+
+    /// Adds an `Authorization` header with the bearer token, marked as sensitive
+    pub fn bearer_auth(self, token: impl fmt::Display) -> Self {
+        self.and_then(|request| {
+            let mut value = HeaderValue::try_from(format!("Bearer {}", token))
+                .map_err(|_| "Invalid bearer token".to_string())?;
+            value.set_sensitive(true);
+            request.headers.append(header::AUTHORIZATION, value);
+            Ok(())
+        })
+    }
+
+    /// Appends the pairs to the query of the URL
+    pub fn query<K: AsRef<str>, V: AsRef<str>>(self, pairs: &[(K, V)]) -> Self {
+        self.and_then(|request| {
+            if !pairs.is_empty() {
+                request.url.query_pairs_mut().extend_pairs(pairs);
+            }
+            Ok(())
+        })
+    }
+
+    /// Sets the body of the request
+    pub fn body(self, body: impl Into<Vec<u8>>) -> Self {
+        self.and_then(|request| {
+            request.body = Some(body.into());
+            Ok(())
+        })
+    }
+
+    /// Sets the timeout of the request, from sending it until the body is read
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.and_then(|request| {
+            request.timeout = Some(timeout);
+            Ok(())
+        })
+    }
+
+    /// Builds the request
+    pub fn build(self) -> Result<Request> {
+        self.request.map_err(LookupError::GenericError)
+    }
+
+    /// Sends the request
+    #[maybe_async::maybe_async]
+    pub async fn send(self) -> Result<Response> {
+        let request = self.request.map_err(LookupError::GenericError)?;
+        self.client.execute(request).await
+    }
+}
+
+/// HTTP response with an unread body
+pub struct Response {
+    status: StatusCode,
+    headers: HeaderMap,
+    url: Url,
+    body: <Selected as Backend>::Body,
+    peer_certificate: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("url", &self.url.as_str())
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Response {
+    /// Returns the status of the reply
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the headers of the reply
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the final URL of the response, after the redirects
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Returns the announced length of the body
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers
+            .get(header::CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Returns the DER encoded certificate of the server, if the backend exposes it
+    pub fn peer_certificate(&self) -> Option<&[u8]> {
+        self.peer_certificate.as_deref()
+    }
+
+    /// Reads the body, failing once it grows larger than the limit
+    #[maybe_async::maybe_async]
+    pub async fn read_limited(self, limit: usize) -> Result<Vec<u8>> {
+        Selected::read(self.body, limit).await
+    }
+}
+
+/// Sends a GET request through the [`shared`] client
+#[maybe_async::maybe_async]
+pub async fn get(url: impl AsRef<str>) -> Result<Response> {
+    shared().get(url).send().await
+}
+
+/// Returns the HTTP client shared by all lookups
+///
+/// Clones of a client share its connection pool, so repeated lookups reuse the connections and
+/// TLS sessions of the previous ones instead of opening new ones. The client is built on first
+/// use with the connection settings of the process-wide configuration.
+pub fn shared() -> Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            builder().build().unwrap_or_else(|e| {
+                log::warn!("Failed building the HTTP client: {}", e);
+                Client::new()
+            })
+        })
+        .clone()
+}
+
+/// Returns a client builder with the connection pool, keep-alive and redirect settings of the
+/// process-wide configuration
+///
+/// Responses are decompressed transparently with the `http-compression` feature, which
+/// advertises gzip and brotli in the `Accept-Encoding` header.
+pub fn builder() -> ClientBuilder {
+    let config = crate::config::config();
+    Client::builder()
+        .pool_idle_timeout(config.pool_idle_timeout)
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .tcp_keepalive(config.tcp_keepalive)
+        .redirect(config.redirect_policy)
+        .https_downgrade(config.allow_https_downgrade)
+}
+
+/// Binds the outgoing connections of the client to the local address, network interface and
+/// address family of the parameters, and trusts only their root certificates, if any
+///
+/// Bindings the backend can't apply fail when the client is built, instead of sending the request
+/// through the default route. With reqwest binding to an interface is only supported on Linux,
+/// Android and Fuchsia. An invalid certificate, or root certificates in a build without TLS,
+/// fail instead of falling back to the system roots.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn bind(builder: ClientBuilder, parameters: &Parameters) -> ClientBuilder {
+    let builder = match parameters.local_address {
+        Some(address) => builder.local_address(address),
+        None => builder,
+    };
+    let builder = match parameters.interface.as_deref() {
+        Some(interface) => builder.interface(interface),
+        None => builder,
+    };
+    let builder = match parameters.family {
+        Some(family) => builder.family(family),
+        None => builder,
+    };
+    parameters
+        .root_certificates
+        .iter()
+        .fold(builder, |builder, pem| builder.add_root_certificate(pem))
+}
+
+/// Returns the client bound as requested by the parameters, see [`bind`]
+///
+/// Bound clients are kept for the lifetime of the process, one per binding, so lookups through
+/// the same interface share their connection pool like the [`shared`] client. A binding that
+/// can't be applied fails, the request is never sent through the default route instead.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn bound(parameters: &Parameters) -> Result<Client> {
+    type Binding = (
+        Option<IpAddr>,
+        Option<String>,
+        Option<AddressFamily>,
+        Vec<String>,
+    );
+    static CLIENTS: OnceLock<Mutex<HashMap<Binding, Client>>> = OnceLock::new();
+    if !parameters.is_bound() {
+        return Ok(shared());
+    }
+    let binding = (
+        parameters.local_address,
+        parameters.interface.clone(),
+        parameters.family,
+        parameters.root_certificates.clone(),
+    );
+    let mut clients = match CLIENTS.get_or_init(Default::default).lock() {
+        Ok(clients) => clients,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if let Some(client) = clients.get(&binding) {
+        return Ok(client.clone());
+    }
+    let client = bind(builder(), parameters).build().map_err(|e| {
+        log::warn!("Failed building bound client: {}", e);
+        e
+    })?;
+    clients.insert(binding, client.clone());
+    Ok(client)
+}
@@ -0,0 +1,309 @@
+//! reqwest backend, the default
+//!
+//! Async builds use the async client, `blocking` builds the blocking one. On `wasm32` requests go
+//! through the fetch API of the browser, which manages connections, redirects and certificates
+//! itself, so only the settings of the request are applied.
+
+use super::{Backend, ClientBuilder, Request, Response};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::lookup::redirect::{is_downgrade, RedirectPolicy};
+use crate::lookup::{
+    error::{LookupError, Result},
+    redirect::DowngradeRefused,
+};
+use std::sync::Arc;
+
+#[cfg(not(feature = "blocking"))]
+use ::reqwest as backend;
+#[cfg(feature = "blocking")]
+use ::reqwest::blocking as backend;
+
+/// Client of the reqwest backend
+#[derive(Debug, Clone)]
+pub(crate) struct Reqwest(backend::Client);
+
+#[maybe_async::maybe_async(AFIT)]
+impl Backend for Reqwest {
+    type Body = backend::Response;
+
+    fn build(builder: ClientBuilder) -> Result<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let client = configure(backend::Client::builder(), builder)?;
+        #[cfg(target_arch = "wasm32")]
+        let client = {
+            let _ = builder;
+            backend::Client::builder()
+        };
+        Ok(Reqwest(client.build()?))
+    }
+
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let mut builder = self
+            .0
+            .request(request.method, request.url)
+            .headers(request.headers);
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let response = builder.send().await.map_err(error)?;
+        Ok(Response {
+            status: response.status(),
+            headers: response.headers().clone(),
+            url: response.url().clone(),
+            peer_certificate: peer_certificate(&response),
+            body: response,
+        })
+    }
+
+    async fn read(body: Self::Body, limit: usize) -> Result<Vec<u8>> {
+        read_limited(body, limit).await
+    }
+}
+
+/// Applies the settings to the reqwest builder
+///
+/// Binding to an interface is only supported on Linux, Android and Fuchsia, elsewhere it fails
+/// instead of sending the request through the default route.
+#[cfg(not(target_arch = "wasm32"))]
+fn configure(
+    client: backend::ClientBuilder,
+    settings: ClientBuilder,
+) -> Result<backend::ClientBuilder> {
+    let client = client
+        .pool_idle_timeout(settings.pool_idle_timeout)
+        .pool_max_idle_per_host(settings.pool_max_idle_per_host)
+        .tcp_keepalive(settings.tcp_keepalive)
+        .redirect(policy(settings.redirect, settings.allow_https_downgrade));
+    // an explicit local address already decides the family
+    let local_address = settings
+        .local_address
+        .or(settings.family.map(|family| family.local_address()));
+    let client = match local_address {
+        Some(address) => client.local_address(address),
+        None => client,
+    };
+    let client = match settings.interface.as_deref() {
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        Some(interface) => client.interface(interface),
+        #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+        Some(interface) => {
+            return Err(LookupError::GenericError(format!(
+                "Binding to interface {} is not supported on this platform",
+                interface
+            )))
+        }
+        None => client,
+    };
+    let client = settings
+        .resolve
+        .iter()
+        .fold(client, |client, (host, addresses)| {
+            client.resolve_to_addrs(host, addresses)
+        });
+    #[cfg(feature = "http-compression")]
+    let client = client.gzip(true).brotli(true);
+    // rustls was asked for explicitly, it wins over the platform TLS stack
+    #[cfg(feature = "rustls-tls")]
+    let client = client.use_rustls_tls();
+    // the peer certificate is checked against the pins of the parameters
+    #[cfg(all(
+        feature = "cert-pinning",
+        any(
+            feature = "default-tls",
+            feature = "native-tls",
+            feature = "rustls-tls"
+        )
+    ))]
+    let client = client.tls_info(true);
+    with_root_certificates(client, &settings.root_certificates)
+}
+
+/// Trusts only the given root certificates, if any
+#[cfg(not(target_arch = "wasm32"))]
+fn with_root_certificates(
+    client: backend::ClientBuilder,
+    certificates: &[String],
+) -> Result<backend::ClientBuilder> {
+    if certificates.is_empty() {
+        return Ok(client);
+    }
+    #[cfg(not(any(
+        feature = "default-tls",
+        feature = "native-tls",
+        feature = "rustls-tls"
+    )))]
+    return Err(LookupError::GenericError(
+        "Root certificates require the default-tls, native-tls or rustls-tls feature".to_string(),
+    ));
+    #[cfg(any(
+        feature = "default-tls",
+        feature = "native-tls",
+        feature = "rustls-tls"
+    ))]
+    certificates
+        .iter()
+        .try_fold(client.tls_built_in_root_certs(false), |client, pem| {
+            // rustls skips PEM input without certificates instead of failing
+            let certificate = Some(pem)
+                .filter(|pem| pem.contains("-----BEGIN CERTIFICATE-----"))
+                .ok_or_else(|| "no PEM certificate found".to_string())
+                .and_then(|pem| {
+                    ::reqwest::Certificate::from_pem(pem.as_bytes()).map_err(|e| e.to_string())
+                })
+                .map_err(|e| {
+                    log::warn!("Invalid root certificate: {}", e);
+                    LookupError::GenericError(format!("Invalid root certificate: {}", e))
+                })?;
+            Ok(client.add_root_certificate(certificate))
+        })
+}
+
+/// Returns the reqwest redirect policy, refusing HTTPS downgrades unless they are allowed
+#[cfg(not(target_arch = "wasm32"))]
+fn policy(policy: RedirectPolicy, allow_downgrade: bool) -> ::reqwest::redirect::Policy {
+    if policy == RedirectPolicy::None {
+        return ::reqwest::redirect::Policy::none();
+    }
+    ::reqwest::redirect::Policy::custom(move |attempt| {
+        if !allow_downgrade && is_downgrade(attempt.previous(), attempt.url()) {
+            let url = attempt.url().to_string();
+            return attempt.error(DowngradeRefused(url));
+        }
+        match policy {
+            RedirectPolicy::Limited(max) if attempt.previous().len() > max => {
+                attempt.error(format!("Too many redirects, the limit is {}", max))
+            }
+            _ => attempt.follow(),
+        }
+    })
+}
+
+/// Returns the certificate of the server the response was received from
+#[cfg(all(
+    feature = "cert-pinning",
+    any(
+        feature = "default-tls",
+        feature = "native-tls",
+        feature = "rustls-tls"
+    ),
+    not(target_arch = "wasm32")
+))]
+fn peer_certificate(response: &backend::Response) -> Option<Vec<u8>> {
+    response
+        .extensions()
+        .get::<::reqwest::tls::TlsInfo>()
+        .and_then(|info| info.peer_certificate())
+        .map(<[u8]>::to_vec)
+}
+
+/// Returns the certificate of the server, which isn't recorded without pinning
+#[cfg(not(all(
+    feature = "cert-pinning",
+    any(
+        feature = "default-tls",
+        feature = "native-tls",
+        feature = "rustls-tls"
+    ),
+    not(target_arch = "wasm32")
+)))]
+fn peer_certificate(_response: &backend::Response) -> Option<Vec<u8>> {
+    None
+}
+
+/// Reads the response body, failing as soon as it exceeds the limit
+#[cfg(all(not(feature = "blocking"), not(target_arch = "wasm32")))]
+async fn read_limited(mut response: backend::Response, limit: usize) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(LookupError::ResponseTooLarge(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Reads the response body, failing as soon as it exceeds the limit
+#[cfg(all(not(feature = "blocking"), target_arch = "wasm32"))]
+async fn read_limited(response: backend::Response, limit: usize) -> Result<Vec<u8>> {
+    // the browser buffers the whole body anyway
+    let body = response.bytes().await?;
+    if body.len() > limit {
+        return Err(LookupError::ResponseTooLarge(limit));
+    }
+    Ok(body.to_vec())
+}
+
+/// Reads the response body, failing as soon as it exceeds the limit
+#[cfg(feature = "blocking")]
+fn read_limited(response: backend::Response, limit: usize) -> Result<Vec<u8>> {
+    use std::io::Read;
+    let mut body = Vec::new();
+    response
+        .take(limit as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(
+            |e| match e.into_inner().map(|e| e.downcast::<::reqwest::Error>()) {
+                Some(Ok(e)) => LookupError::from(*e),
+                Some(Err(e)) => LookupError::GenericError(e.to_string()),
+                None => LookupError::GenericError("Failed reading the response".to_string()),
+            },
+        )?;
+    if body.len() > limit {
+        return Err(LookupError::ResponseTooLarge(limit));
+    }
+    Ok(body)
+}
+
+/// Classifies a request that failed without a reply
+fn error(error: ::reqwest::Error) -> LookupError {
+    if is_dns_error(&error) {
+        return LookupError::Offline(Arc::new(error));
+    }
+    match refused_downgrade(&error) {
+        Some(url) => LookupError::InsecureRedirect(url),
+        None => LookupError::from(error),
+    }
+}
+
+/// Checks if the request failed because the provider host name could not be resolved
+fn is_dns_error(error: &::reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        if e.to_string().contains("dns error") {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// Returns the refused address if the request failed on a refused HTTPS downgrade
+fn refused_downgrade(error: &::reqwest::Error) -> Option<String> {
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        if let Some(refused) = e.downcast_ref::<DowngradeRefused>() {
+            return Some(refused.0.clone());
+        }
+        source = e.source();
+    }
+    None
+}
+
+/// Checks if the request timed out
+#[cfg(feature = "otel")]
+pub(crate) fn is_timeout(error: &super::Error) -> bool {
+    error.is_timeout()
+}
+
+/// Checks if the connection to the server failed
+pub(crate) fn is_connect(error: &super::Error) -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    return error.is_connect();
+    #[cfg(target_arch = "wasm32")]
+    return false;
+}
@@ -0,0 +1,357 @@
+//! ureq backend, enabled through the `ureq` feature
+//!
+//! ureq is a small synchronous client without tokio. It follows no redirects itself, they are
+//! followed here so that the redirect policy and the refused HTTPS downgrades behave like with
+//! reqwest. The address family is applied by ordering the resolved addresses, ureq tries them in
+//! turn. Connections can't be bound to a local address or an interface, such clients fail to build
+//! instead of sending their requests through the default route. The idle timeout and keep-alive
+//! settings of the connection pool are left to ureq.
+
+use super::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Backend, ClientBuilder, Method, Request, Response, StatusCode,
+};
+use crate::lookup::{
+    error::{LookupError, Result},
+    redirect::{is_downgrade, RedirectPolicy},
+    AddressFamily,
+};
+use log::warn;
+use rustls_pki_types::{pem::PemObject, CertificateDer};
+use std::{
+    fmt,
+    io::{self, Read},
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+};
+
+/// Client of the ureq backend
+#[derive(Debug, Clone)]
+pub(crate) struct Ureq {
+    agent: ::ureq::Agent,
+    redirect: RedirectPolicy,
+    allow_https_downgrade: bool,
+}
+
+impl Backend for Ureq {
+    type Body = Box<dyn Read + Send + Sync>;
+
+    fn build(builder: ClientBuilder) -> Result<Self> {
+        if builder.local_address.is_some() || builder.interface.is_some() {
+            return Err(LookupError::GenericError(
+                "Binding to a local address or an interface is not supported by the ureq backend"
+                    .to_string(),
+            ));
+        }
+        let overrides = builder.resolve;
+        let family = builder.family;
+        let agent = ::ureq::AgentBuilder::new()
+            .redirects(0)
+            .max_idle_connections_per_host(builder.pool_max_idle_per_host)
+            .resolver(move |netloc: &str| resolve(&overrides, family, netloc));
+        let agent = match builder.root_certificates.as_slice() {
+            [] => agent,
+            certificates => agent.tls_config(Arc::new(tls_config(certificates)?)),
+        };
+        Ok(Ureq {
+            agent: agent.build(),
+            redirect: builder.redirect,
+            allow_https_downgrade: builder.allow_https_downgrade,
+        })
+    }
+
+    fn execute(&self, request: Request) -> Result<Response> {
+        let Request {
+            mut method,
+            mut url,
+            mut headers,
+            mut body,
+            timeout,
+        } = request;
+        let mut previous = Vec::new();
+        loop {
+            let mut call = self.agent.request_url(method.as_str(), &url);
+            for (name, value) in &headers {
+                let value = value.to_str().map_err(|_| {
+                    LookupError::GenericError(format!("Invalid value of header {}", name))
+                })?;
+                call = call.set(name.as_str(), value);
+            }
+            if let Some(timeout) = timeout {
+                call = call.timeout(timeout);
+            }
+            let reply = match &body {
+                Some(body) => call.send_bytes(body),
+                None => call.call(),
+            };
+            let reply = match reply {
+                Ok(reply) | Err(::ureq::Error::Status(_, reply)) => reply,
+                Err(::ureq::Error::Transport(e)) => return Err(error(e)),
+            };
+            let status = StatusCode::from_u16(reply.status())
+                .map_err(|e| LookupError::GenericError(format!("Invalid status: {}", e)))?;
+            let next = reply
+                .header(header::LOCATION.as_str())
+                .filter(|_| status.is_redirection() && self.redirect != RedirectPolicy::None)
+                .and_then(|location| url.join(location).ok());
+            let Some(next) = next else {
+                return Ok(response(status, url, reply));
+            };
+            previous.push(url);
+            if !self.allow_https_downgrade && is_downgrade(&previous, &next) {
+                warn!("Refused redirect from HTTPS to {}", next);
+                return Err(LookupError::InsecureRedirect(next.to_string()));
+            }
+            if let RedirectPolicy::Limited(max) = self.redirect {
+                if previous.len() > max {
+                    return Err(LookupError::GenericError(format!(
+                        "Too many redirects, the limit is {}",
+                        max
+                    )));
+                }
+            }
+            // the body is dropped where browsers switch to GET
+            let switch_to_get = status == StatusCode::SEE_OTHER
+                || (method == Method::POST
+                    && matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND));
+            if switch_to_get {
+                method = Method::GET;
+                body = None;
+                headers.remove(header::CONTENT_TYPE);
+                headers.remove(header::CONTENT_LENGTH);
+            }
+            // credentials are only sent to the host they were meant for
+            if previous.last().and_then(|url| url.host_str()) != next.host_str() {
+                headers.remove(header::AUTHORIZATION);
+                headers.remove(header::COOKIE);
+            }
+            url = next;
+        }
+    }
+
+    fn read(body: Self::Body, limit: usize) -> Result<Vec<u8>> {
+        let mut read = Vec::new();
+        body.take(limit as u64 + 1)
+            .read_to_end(&mut read)
+            .map_err(|e| {
+                LookupError::GenericError(format!("Failed reading the response: {}", e))
+            })?;
+        if read.len() > limit {
+            return Err(LookupError::ResponseTooLarge(limit));
+        }
+        Ok(read)
+    }
+}
+
+/// Converts the ureq reply, leaving its body unread
+fn response(status: StatusCode, url: url::Url, reply: ::ureq::Response) -> Response {
+    let mut headers = HeaderMap::new();
+    for name in reply.headers_names() {
+        let Ok(header) = HeaderName::from_bytes(name.as_bytes()) else {
+            continue;
+        };
+        for value in reply.all(&name) {
+            if let Ok(value) = HeaderValue::from_str(value) {
+                headers.append(header.clone(), value);
+            }
+        }
+    }
+    Response {
+        status,
+        headers,
+        url,
+        peer_certificate: None,
+        body: reply.into_reader(),
+    }
+}
+
+/// Resolves the `host:port` of a connection, applying the overrides and the address family
+fn resolve(
+    overrides: &[(String, Vec<SocketAddr>)],
+    family: Option<AddressFamily>,
+    netloc: &str,
+) -> io::Result<Vec<SocketAddr>> {
+    let host = netloc.rsplit_once(':').map_or(netloc, |(host, _)| host);
+    let addresses = match overrides.iter().find(|(name, _)| name == host) {
+        Some((_, addresses)) => addresses.clone(),
+        None => netloc.to_socket_addrs()?.collect(),
+    };
+    let Some(family) = family else {
+        return Ok(addresses);
+    };
+    let ordered = family.order(addresses);
+    if ordered.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            NoAddressOfFamily(family),
+        ));
+    }
+    Ok(ordered)
+}
+
+/// Host has no address of the required family, a connection failure rather than a failed lookup
+#[derive(Debug)]
+struct NoAddressOfFamily(AddressFamily);
+
+impl fmt::Display for NoAddressOfFamily {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No address of the host matches {}", self.0)
+    }
+}
+
+impl std::error::Error for NoAddressOfFamily {}
+
+/// Returns the TLS settings trusting only the given PEM encoded root certificates
+fn tls_config(certificates: &[String]) -> Result<rustls::ClientConfig> {
+    let invalid = |e: String| {
+        warn!("Invalid root certificate: {}", e);
+        LookupError::GenericError(format!("Invalid root certificate: {}", e))
+    };
+    let mut roots = rustls::RootCertStore::empty();
+    for pem in certificates {
+        let certificate =
+            CertificateDer::from_pem_slice(pem.as_bytes()).map_err(|e| invalid(e.to_string()))?;
+        roots.add(certificate).map_err(|e| invalid(e.to_string()))?;
+    }
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    Ok(rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| LookupError::GenericError(format!("Failed configuring TLS: {}", e)))?
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Classifies a request that failed without a reply
+fn error(error: ::ureq::Transport) -> LookupError {
+    let error = Arc::new(error);
+    if error.kind() == ::ureq::ErrorKind::Dns && !is_family_mismatch(&error) {
+        return LookupError::Offline(error);
+    }
+    LookupError::UreqError(error)
+}
+
+/// Walks the sources of the error for the first one of type `T`
+fn find_source<T: std::error::Error + 'static>(error: &super::Error) -> Option<&T> {
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        if let Some(found) = e.downcast_ref::<T>() {
+            return Some(found);
+        }
+        source = e.source();
+    }
+    None
+}
+
+/// Checks if the resolved addresses were all dropped by the required address family
+fn is_family_mismatch(error: &super::Error) -> bool {
+    find_source::<io::Error>(error)
+        .and_then(|e| e.get_ref())
+        .is_some_and(|e| e.is::<NoAddressOfFamily>())
+}
+
+/// Checks if the request timed out
+#[cfg(feature = "otel")]
+pub(crate) fn is_timeout(error: &super::Error) -> bool {
+    find_source::<io::Error>(error).is_some_and(|e| {
+        matches!(
+            e.kind(),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        )
+    })
+}
+
+/// Checks if the connection to the server failed
+pub(crate) fn is_connect(error: &super::Error) -> bool {
+    error.kind() == ::ureq::ErrorKind::ConnectionFailed || is_family_mismatch(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::client::{self, Client};
+    use std::{io::Write, net::TcpListener, thread};
+
+    /// Serves the replies in turn, returning the received requests
+    fn serve(replies: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let server = thread::spawn(move || {
+            replies
+                .into_iter()
+                .map(|reply| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut buffer = [0; 1024];
+                    let read = stream.read(&mut buffer).unwrap_or_default();
+                    let _ = stream.write_all(reply.as_bytes());
+                    String::from_utf8_lossy(&buffer[..read]).into_owned()
+                })
+                .collect()
+        });
+        (endpoint, server)
+    }
+
+    #[test]
+    fn test_unsupported_binding() {
+        let client = Client::builder()
+            .local_address("127.0.0.1".parse().unwrap())
+            .build();
+        assert!(
+            client.is_err(),
+            "Binding should fail instead of being ignored"
+        );
+        let client = Client::builder().interface("eth0").build();
+        assert!(
+            client.is_err(),
+            "Binding should fail instead of being ignored"
+        );
+    }
+
+    #[test]
+    fn test_redirect() {
+        let (endpoint, server) = serve(vec![
+            "HTTP/1.0 303 See Other\r\nLocation: /next\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\nok",
+        ]);
+        let response = Client::new()
+            .post(format!("http://{}/", endpoint))
+            .body("{}")
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.url().path(), "/next");
+        assert_eq!(response.read_limited(16).unwrap(), b"ok");
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST / "));
+        assert!(
+            requests[1].starts_with("GET /next "),
+            "See Other should switch to GET, got {}",
+            requests[1]
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let (endpoint, server) = serve(vec!["HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\nok"]);
+        let address: SocketAddr = endpoint.parse().unwrap();
+        let url = format!("http://provider.invalid:{}/", address.port());
+        let client = Client::builder()
+            .resolve("provider.invalid", address)
+            .build()
+            .unwrap();
+        let response = client.get(&url).send().unwrap();
+        assert_eq!(response.read_limited(16).unwrap(), b"ok");
+        server.join().unwrap();
+
+        let client = Client::builder()
+            .resolve("provider.invalid", address)
+            .family(AddressFamily::RequireV6)
+            .build()
+            .unwrap();
+        let result = client.get(&url).send();
+        assert!(
+            matches!(result, Err(LookupError::UreqError(ref e)) if client::is_connect(e)),
+            "Missing address of the family should fail to connect, got {:?}",
+            result
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Custom lookup provider
+//!
+//! Looks up the public address through a user supplied URL answering with the address as plain
+//! text, or as a JSON object with an `ip` field.
+//!
+//! Before a request is made the host of the URL is resolved and refused if any of its addresses is
+//! not publicly routable, unless private endpoints are allowed through
+//! [`Config::with_allow_private_endpoints`](crate::Config::with_allow_private_endpoints). The checked
+//! address is pinned for the request, so a second DNS answer can't redirect it (DNS rebinding).
+//! Redirects aren't followed either, their target was never checked. A redirect reply fails the
+//! lookup with its status.
+//! The host is resolved through the system resolver before every lookup, which blocks the calling
+//! thread also in async builds, since the pinned address is kept by the provider.
+//! On `wasm32` the browser resolves the host, only endpoints given as IP addresses are checked.
+
+use super::{
+    bogon,
+    client::{self, Client, ClientBuilder, Url},
+    redirect::RedirectPolicy,
+    LookupError, Result,
+};
+use crate::{
+    lookup::{client::RequestBuilder, LookupProvider, Parameters, Provider},
+    LookupResponse,
+};
+use log::warn;
+use std::{
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    sync::Mutex,
+};
+
+/// Custom lookup provider
+pub struct Custom {
+    url: String,
+    client: Mutex<Option<Client>>,
+    pin: Mutex<Option<(String, Vec<SocketAddr>)>>,
+}
+
+impl Custom {
+    /// Creates a new custom provider for the given URL
+    pub fn new(url: &str) -> Self {
+        Custom {
+            url: url.to_string(),
+            client: Mutex::new(None),
+            pin: Mutex::new(None),
+        }
+    }
+
+    /// Resolves the host of the URL and checks that all its addresses are publicly routable
+    ///
+    /// Returns the host and the address to pin the request to, or `None` if the host is an IP address.
+    /// The system resolver is used, which blocks the current thread.
+    pub fn check_endpoint(&self, allow_private: bool) -> Result<Option<(String, SocketAddr)>> {
+        Ok(self
+            .resolve_endpoint(allow_private)?
+            .and_then(|(host, addresses)| Some((host, *addresses.first()?))))
+    }
+
+    /// Resolves the host of the URL to all its checked addresses, see [`Custom::check_endpoint`]
+    fn resolve_endpoint(&self, allow_private: bool) -> Result<Option<(String, Vec<SocketAddr>)>> {
+        let url = Url::parse(&self.url).map_err(|e| {
+            LookupError::GenericError(format!("Invalid custom provider URL: {}", e))
+        })?;
+        let host = url.host_str().ok_or(LookupError::GenericError(
+            "Custom provider URL has no host".to_string(),
+        ))?;
+        let port = url.port_or_known_default().unwrap_or(443);
+
+        if let Ok(address) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            check_address(host, &address, allow_private)?;
+            return Ok(None);
+        }
+        // browsers resolve host names themselves and offer no way to pin the address
+        if cfg!(target_arch = "wasm32") {
+            return Ok(None);
+        }
+        let addresses: Vec<SocketAddr> = (host, port)
+            .to_socket_addrs()
+            .map_err(LookupError::ResolveError)?
+            .collect();
+        for address in &addresses {
+            check_address(host, &address.ip(), allow_private)?;
+        }
+        Ok((!addresses.is_empty()).then(|| (host.to_string(), addresses)))
+    }
+}
+
+/// Returns a client builder that doesn't follow redirects, see the [module](self) documentation
+fn builder() -> ClientBuilder {
+    client::builder().redirect(RedirectPolicy::None)
+}
+
+/// Refuses non public addresses unless they are allowed
+fn check_address(host: &str, address: &IpAddr, allow_private: bool) -> Result<()> {
+    match bogon::non_public_kind(address) {
+        Some(kind) if !allow_private => {
+            warn!("Custom provider {} resolves to a {} address", host, kind);
+            Err(LookupError::ForbiddenEndpoint(format!(
+                "{} resolves to {} ({} address)",
+                host, address, kind
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Parses a plain text or JSON reply containing the IP address
+pub fn parse_reply(body: &str, url: &str) -> Result<LookupResponse> {
+    let provider = LookupProvider::Custom(url.to_string());
+    if let Ok(ip) = body.trim().parse::<IpAddr>() {
+        return Ok(LookupResponse::new(ip, provider));
+    }
+    let value: serde_json::Value = serde_json::from_str(body)?;
+    value
+        .get("ip")
+        .and_then(|ip| ip.as_str())
+        .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+        .map(|ip| LookupResponse::new(ip, provider))
+        .ok_or_else(|| LookupError::InvalidResponseIp(body.trim().to_string()))
+}
+
+impl Provider for Custom {
+    fn get_endpoint(&self, _key: &Option<String>, _target: &Option<IpAddr>) -> String {
+        self.url.clone()
+    }
+
+    fn get_client(&self, key: Option<String>, target: Option<IpAddr>) -> RequestBuilder {
+        let client = self
+            .client
+            .lock()
+            .ok()
+            .and_then(|client| client.clone())
+            .or_else(|| builder().build().ok())
+            .unwrap_or_default();
+        let request = client.get(self.get_endpoint(&key, &target));
+        self.add_auth(request, &key)
+    }
+
+    fn get_client_with_parameters(
+        &self,
+        key: Option<String>,
+        target: Option<IpAddr>,
+        parameters: &Parameters,
+    ) -> Result<RequestBuilder> {
+        // keep the client pinned to the checked address
+        #[cfg(not(target_arch = "wasm32"))]
+        if parameters.is_bound() {
+            let builder = match self.pin.lock().ok().and_then(|pin| pin.clone()) {
+                Some((host, addresses)) => {
+                    let addresses = match parameters.family {
+                        Some(family) => family.order(addresses),
+                        None => addresses,
+                    };
+                    builder().resolve_to_addrs(&host, &addresses)
+                }
+                None => builder(),
+            };
+            let client = client::bind(builder, parameters).build()?;
+            let request = client.get(self.get_endpoint(&key, &target));
+            return Ok(parameters.apply(self.add_auth(request, &key)));
+        }
+        #[cfg(target_arch = "wasm32")]
+        parameters.client()?;
+        Ok(parameters.apply(self.get_client(key, target)))
+    }
+
+    fn prepare(&self) -> Result<()> {
+        let allow_private = crate::config::config().allow_private_endpoints;
+        let pin = self.resolve_endpoint(allow_private)?;
+        let builder = match &pin {
+            #[cfg(not(target_arch = "wasm32"))]
+            Some((host, addresses)) => builder().resolve(host, addresses[0]),
+            _ => builder(),
+        };
+        let client = builder.build()?;
+        if let Ok(mut pinned) = self.client.lock() {
+            *pinned = Some(client);
+        }
+        if let Ok(mut pinned) = self.pin.lock() {
+            *pinned = pin;
+        }
+        Ok(())
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        parse_reply(&json, &self.url)
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::Custom(self.url.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_endpoint() {
+        let provider = Custom::new("http://127.0.0.1:8080/ip");
+        assert!(matches!(
+            provider.check_endpoint(false),
+            Err(LookupError::ForbiddenEndpoint(_))
+        ));
+        assert!(matches!(provider.check_endpoint(true), Ok(None)));
+
+        let provider = Custom::new("http://[fe80::1]/ip");
+        assert!(matches!(
+            provider.check_endpoint(false),
+            Err(LookupError::ForbiddenEndpoint(_))
+        ));
+
+        let provider = Custom::new("http://localhost:8080/ip");
+        assert!(
+            matches!(
+                provider.check_endpoint(false),
+                Err(LookupError::ForbiddenEndpoint(_))
+            ),
+            "Host names resolving to loopback should be refused"
+        );
+
+        let provider = Custom::new("not a url");
+        assert!(provider.check_endpoint(true).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let url = "https://example.com/ip";
+        let response = parse_reply("1.1.1.1\n", url).unwrap();
+        assert_eq!(response.ip, "1.1.1.1".parse::<IpAddr>().unwrap());
+        assert_eq!(response.provider, LookupProvider::Custom(url.to_string()));
+        let response = parse_reply(r#"{"ip": "2606:4700::1111"}"#, url).unwrap();
+        assert_eq!(response.ip, "2606:4700::1111".parse::<IpAddr>().unwrap());
+        assert!(parse_reply(r#"{"address": "1.1.1.1"}"#, url).is_err());
+        assert!(parse_reply("<html>", url).is_err());
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_redirect_not_followed() {
+        use std::{io::Write, net::TcpListener};
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/ip", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 1024];
+            let _ = std::io::Read::read(&mut stream, &mut buffer);
+            let _ = stream.write_all(
+                b"HTTP/1.0 302 Found\r\nLocation: http://127.0.0.2/ip\r\nContent-Length: 0\r\n\r\n",
+            );
+        });
+        let provider = Custom::new(&endpoint);
+        let response = provider.get_client(None, None).send().await.unwrap();
+        assert_eq!(
+            response.status(),
+            client::StatusCode::FOUND,
+            "Redirect to loopback should not be followed"
+        );
+        assert_eq!(response.url().as_str(), endpoint);
+        let result = crate::lookup::handle_response(Ok(response)).await;
+        assert!(matches!(result, Err(LookupError::RequestStatus(_))));
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+//! Diagnostics of provider replies that could not be deserialized
+//!
+//! A bare serde error doesn't tell which provider changed its API or which field broke. The error
+//! is turned into a `LookupError::ParseError` with the provider, the path of the offending field,
+//! like `connection.asn` or `data[0].ip`, and the part of the reply around it.
+
+use super::{error::LookupError, LookupProvider};
+
+/// Number of characters of the reply kept on each side of the error position
+const SNIPPET_RADIUS: usize = 60;
+
+/// Turns a serde error of the provider reply into a `LookupError::ParseError`
+///
+/// Other errors are returned unchanged.
+pub(crate) fn diagnose(provider: LookupProvider, reply: &str, error: LookupError) -> LookupError {
+    let LookupError::SerdeError(source) = error else {
+        return error;
+    };
+    let offset = offset(reply, source.line(), source.column());
+    LookupError::ParseError {
+        provider,
+        path: path_at(reply, offset),
+        snippet: snippet(reply, offset),
+        source,
+    }
+}
+
+/// Converts the one based line and column reported by serde into a byte offset
+fn offset(reply: &str, line: usize, column: usize) -> usize {
+    let start: usize = reply
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    floor_char_boundary(reply, start + column)
+}
+
+/// Returns the closest char boundary at or before the offset
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Returns the part of the reply around the offset, on a single line
+fn snippet(reply: &str, offset: usize) -> String {
+    let start = floor_char_boundary(reply, offset.saturating_sub(SNIPPET_RADIUS * 4));
+    let end = floor_char_boundary(reply, offset + SNIPPET_RADIUS * 4);
+    let before: Vec<char> = reply[start..offset].chars().collect();
+    let before: String = before[before.len().saturating_sub(SNIPPET_RADIUS)..]
+        .iter()
+        .collect();
+    let after: String = reply[offset..end].chars().take(SNIPPET_RADIUS).collect();
+
+    let mut snippet = String::new();
+    if before.len() < offset {
+        snippet.push_str("...");
+    }
+    snippet.push_str(&before);
+    snippet.push_str(&after);
+    if offset + after.len() < reply.len() {
+        snippet.push_str("...");
+    }
+    snippet.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Position inside an object or array of the reply
+enum Frame {
+    Object { key: Option<String> },
+    Array { index: usize },
+}
+
+/// Returns the path of the value at the offset, `.` for the root
+fn path_at(reply: &str, offset: usize) -> String {
+    let mut frames: Vec<Frame> = Vec::new();
+    // an object key is pending until its `:` is seen
+    let mut last_string: Option<String> = None;
+    let mut chars = reply[..offset].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => frames.push(Frame::Object { key: None }),
+            '[' => frames.push(Frame::Array { index: 0 }),
+            '}' | ']' => {
+                frames.pop();
+            }
+            ':' => {
+                if let Some(Frame::Object { key }) = frames.last_mut() {
+                    *key = last_string.take();
+                }
+            }
+            ',' => match frames.last_mut() {
+                Some(Frame::Object { key }) => *key = None,
+                Some(Frame::Array { index }) => *index += 1,
+                None => {}
+            },
+            '"' => {
+                let mut string = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => string.extend(chars.next()),
+                        c => string.push(c),
+                    }
+                }
+                last_string = Some(string);
+            }
+            _ => {}
+        }
+    }
+
+    let mut path = String::new();
+    for frame in &frames {
+        match frame {
+            Frame::Object { key: Some(key) } => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+            Frame::Array { index } => path.push_str(&format!("[{}]", index)),
+            Frame::Object { key: None } => {}
+        }
+    }
+    if path.is_empty() {
+        path.push('.');
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Reply {
+        ip: String,
+        data: Vec<Data>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Data {
+        asn: u32,
+    }
+
+    fn parse(reply: &str) -> LookupError {
+        let error = serde_json::from_str::<Reply>(reply).unwrap_err();
+        diagnose(LookupProvider::IpWhoIs, reply, error.into())
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let reply =
+            "{\n  \"ip\": \"1.1.1.1\",\n  \"data\": [{\"asn\": 1}, {\"asn\": \"AS13335\"}]\n}";
+        let LookupError::ParseError {
+            provider,
+            path,
+            snippet,
+            ..
+        } = parse(reply)
+        else {
+            panic!("Serde error should be diagnosed");
+        };
+        assert_eq!(provider, LookupProvider::IpWhoIs);
+        assert_eq!(path, "data[1].asn");
+        assert!(snippet.contains("\"AS13335\""), "{}", snippet);
+
+        let LookupError::ParseError { path, .. } = parse(r#"{"ip": 1, "data": []}"#) else {
+            panic!("Serde error should be diagnosed");
+        };
+        assert_eq!(path, "ip");
+
+        let LookupError::ParseError { path, .. } = parse("<html>") else {
+            panic!("Serde error should be diagnosed");
+        };
+        assert_eq!(path, ".");
+
+        let error = diagnose(LookupProvider::IpWhoIs, "", LookupError::Timeout);
+        assert!(matches!(error, LookupError::Timeout));
+    }
+
+    #[test]
+    fn test_snippet() {
+        let reply = format!("{{\"ip\": \"ü{}\"}}", "x".repeat(200));
+        let snippet = snippet(&reply, 100);
+        assert!(snippet.starts_with("..."));
+        assert!(snippet.ends_with("..."));
+        assert!(snippet.chars().count() <= SNIPPET_RADIUS * 2 + 6);
+        assert_eq!(super::snippet("{}", 1), "{}");
+    }
+}
@@ -0,0 +1,25 @@
+//! Stand-in for lookup providers compiled out through their feature flag
+
+use super::{LookupError, Result};
+use crate::{
+    lookup::{LookupProvider, Provider},
+    LookupResponse,
+};
+use std::net::IpAddr;
+
+/// Provider that was disabled at compile time, every lookup fails with `LookupError::ProviderDisabled`
+pub struct Disabled(pub LookupProvider);
+
+impl Provider for Disabled {
+    fn get_endpoint(&self, _key: &Option<String>, _target: &Option<IpAddr>) -> String {
+        String::new()
+    }
+
+    fn parse_reply(&self, _json: String) -> Result<LookupResponse> {
+        Err(LookupError::ProviderDisabled(self.0.to_string()))
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        self.0.clone()
+    }
+}
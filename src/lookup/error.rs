@@ -1,4 +1,6 @@
 //! Lookup error types
+use super::{bogon::AddressKind, client, LookupProvider};
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Result type for the lookup crate
@@ -8,9 +10,14 @@ pub type Result<T> = std::result::Result<T, LookupError>;
 #[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum LookupError {
-    /// API request error
+    /// API request error, shared so that concurrent identical lookups can report it as well
+    #[cfg(not(feature = "ureq"))]
     #[error("Reqwuest error")]
-    ReqwestError(#[from] reqwest::Error),
+    ReqwestError(#[source] Arc<reqwest::Error>),
+    /// API request error of the ureq backend, shared like [`LookupError::ReqwestError`]
+    #[cfg(feature = "ureq")]
+    #[error("Ureq error")]
+    UreqError(#[source] Arc<ureq::Transport>),
     /// Too many requests
     #[error("Too many API requests")]
     TooManyRequests(String),
@@ -26,4 +33,97 @@ pub enum LookupError {
     /// Target address not supported by this provider
     #[error("Target lookup not supported")]
     TargetNotSupported,
+    /// Provider can only look up a given target, not the caller's own address
+    #[error("Target address required")]
+    TargetRequired,
+    /// Provider has no endpoint returning only the network data of an address
+    #[error("ASN lookup not supported")]
+    AsnNotSupported,
+    /// Requests are intercepted by a captive portal, contains the address of the portal
+    #[error("Captive portal")]
+    CaptivePortal(String),
+    /// Provider host name could not be resolved, the machine is likely offline
+    #[error("Offline")]
+    Offline(#[source] Arc<client::Error>),
+    /// Request over plain HTTP refused because HTTPS is enforced, contains the refused host
+    #[error("Insecure endpoint refused")]
+    InsecureEndpoint(String),
+    /// Redirect from HTTPS to plain HTTP refused, contains the refused address
+    #[error("Insecure redirect refused")]
+    InsecureRedirect(String),
+    /// Certificate of the provider doesn't match its pins, contains the provider host
+    #[error("Certificate pin mismatch")]
+    PinMismatch(String),
+    /// Custom provider endpoint refused because it resolves to a non public address
+    #[error("Forbidden endpoint")]
+    ForbiddenEndpoint(String),
+    /// Provider was compiled out through its feature flag
+    #[error("Provider disabled at compile time")]
+    ProviderDisabled(String),
+    /// Target address is not publicly routable and can't be looked up
+    #[error("Non public address")]
+    NonPublicAddress(AddressKind),
+    /// The lookup did not finish within the given time budget
+    #[error("Lookup timed out")]
+    Timeout,
+    /// The lookup was cancelled by the caller
+    #[error("Lookup cancelled")]
+    Cancelled,
+    /// Target hostname could not be resolved
+    #[error("Hostname resolution error")]
+    ResolveError(#[source] std::io::Error),
+    /// Provider answered with an error object instead of a response, like for an invalid API key
+    #[error("Provider error")]
+    ProviderError {
+        /// Provider that answered with the error
+        provider: LookupProvider,
+        /// Error code reported by the provider, if any
+        code: Option<String>,
+        /// Error message reported by the provider
+        message: String,
+    },
+    /// Provider reply could not be deserialized, likely after a change of the provider API
+    #[error("Failed to parse the reply of {provider} at {path}")]
+    ParseError {
+        /// Provider that sent the reply
+        provider: LookupProvider,
+        /// Path of the offending field, like `connection.asn`, `.` for the whole reply
+        path: String,
+        /// Truncated part of the reply around the offending field
+        snippet: String,
+        /// Underlying serde error
+        #[source]
+        source: serde_json::Error,
+    },
+    /// Provider reported an address that could not be parsed, contains the reported value, empty
+    /// if the reply has no address
+    #[error("Invalid IP address in response")]
+    InvalidResponseIp(String),
+    /// Provider reply exceeds the maximum response size, contains the limit in bytes
+    #[error("Response too large")]
+    ResponseTooLarge(usize),
+    /// Provider answered with an HTML page, likely a captive portal or a proxy error page,
+    /// contains the reported content type
+    #[error("Unexpected content type")]
+    UnexpectedContentType(String),
+}
+
+impl LookupError {
+    /// Returns the error of the HTTP backend, for requests that failed without a reply
+    pub(crate) fn client_error(&self) -> Option<&client::Error> {
+        match self {
+            #[cfg(not(feature = "ureq"))]
+            LookupError::ReqwestError(e) => Some(e.as_ref()),
+            #[cfg(feature = "ureq")]
+            LookupError::UreqError(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(not(feature = "ureq"))]
+impl From<reqwest::Error> for LookupError {
+    fn from(error: reqwest::Error) -> Self {
+        LookupError::ReqwestError(Arc::new(error))
+    }
 }
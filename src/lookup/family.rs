@@ -0,0 +1,129 @@
+//! Address family of the connection to the provider
+//!
+//! The public address reported by a provider is the address of the connection it was reached
+//! over, so on dual-stack hosts the answer depends on whether the socket used IPv4 or IPv6. The
+//! socket is bound to the unspecified address of the family, which restricts the connection to
+//! the addresses of the endpoint in that family.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
+/// Address family preference of the connection, see [`Parameters::with_family`](super::Parameters::with_family)
+///
+/// Preferred families are tried first and fall back to the system default when no connection can
+/// be made, required families never fall back.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum AddressFamily {
+    /// Connect over IPv4 first
+    PreferV4,
+    /// Connect over IPv6 first
+    PreferV6,
+    /// Connect over IPv4 only
+    RequireV4,
+    /// Connect over IPv6 only
+    RequireV6,
+}
+
+impl AddressFamily {
+    /// Returns the unspecified local address the socket is bound to
+    pub fn local_address(&self) -> IpAddr {
+        match self {
+            AddressFamily::PreferV4 | AddressFamily::RequireV4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            AddressFamily::PreferV6 | AddressFamily::RequireV6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+
+    /// Checks if the connection falls back to the other family
+    pub fn is_preference(&self) -> bool {
+        matches!(self, AddressFamily::PreferV4 | AddressFamily::PreferV6)
+    }
+
+    /// Orders the resolved addresses by preference, dropping the ones of a family that isn't allowed
+    ///
+    /// The relative order of the resolver is kept within each family.
+    pub fn order(&self, addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        let (v4, v6): (Vec<SocketAddr>, Vec<SocketAddr>) =
+            addresses.into_iter().partition(SocketAddr::is_ipv4);
+        match self {
+            AddressFamily::PreferV4 => v4.into_iter().chain(v6).collect(),
+            AddressFamily::PreferV6 => v6.into_iter().chain(v4).collect(),
+            AddressFamily::RequireV4 => v4,
+            AddressFamily::RequireV6 => v6,
+        }
+    }
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressFamily::PreferV4 => write!(f, "prefer-v4"),
+            AddressFamily::PreferV6 => write!(f, "prefer-v6"),
+            AddressFamily::RequireV4 => write!(f, "require-v4"),
+            AddressFamily::RequireV6 => write!(f, "require-v6"),
+        }
+    }
+}
+
+impl FromStr for AddressFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "prefer-v4" | "prefer-ipv4" => Ok(AddressFamily::PreferV4),
+            "prefer-v6" | "prefer-ipv6" => Ok(AddressFamily::PreferV6),
+            "require-v4" | "require-ipv4" | "v4" | "ipv4" => Ok(AddressFamily::RequireV4),
+            "require-v6" | "require-ipv6" | "v6" | "ipv6" => Ok(AddressFamily::RequireV6),
+            _ => Err(format!("Unknown address family: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order() {
+        let addresses: Vec<SocketAddr> = vec![
+            "[2001:db8::1]:0".parse().unwrap(),
+            "192.0.2.1:0".parse().unwrap(),
+            "[2001:db8::2]:0".parse().unwrap(),
+        ];
+        assert_eq!(
+            AddressFamily::PreferV4.order(addresses.clone()),
+            vec![addresses[1], addresses[0], addresses[2]]
+        );
+        assert_eq!(
+            AddressFamily::PreferV6.order(addresses.clone()),
+            vec![addresses[0], addresses[2], addresses[1]]
+        );
+        assert_eq!(
+            AddressFamily::RequireV4.order(addresses.clone()),
+            vec![addresses[1]]
+        );
+        assert!(AddressFamily::RequireV4
+            .order(vec![addresses[0]])
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        for family in [
+            AddressFamily::PreferV4,
+            AddressFamily::PreferV6,
+            AddressFamily::RequireV4,
+            AddressFamily::RequireV6,
+        ] {
+            assert_eq!(family.to_string().parse::<AddressFamily>(), Ok(family));
+            let json = serde_json::to_string(&family).unwrap();
+            assert_eq!(json, format!("\"{}\"", family));
+        }
+        assert!("v5".parse::<AddressFamily>().is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Record and replay of provider replies for offline tests
+//!
+//! In record mode the replies of the providers are stored in a directory, one file per provider
+//! and target, like `ipwhois.body` or `ipwhois-8.8.8.8.body`. In replay mode the lookups are
+//! answered from these files without any request, so tests run against the real schemas of the
+//! providers without network access or rate limits.
+//!
+//! Available with the `fixtures` feature.
+//!
+//! ```rust,no_run
+//! use public_ip_address::lookup::{fixtures::Fixtures, LookupProvider, LookupService};
+//!
+//! let mut service = LookupService::new(LookupProvider::IpWhoIs, None);
+//! service.set_fixtures(Fixtures::Replay("tests/fixtures".into()));
+//! ```
+
+use super::{error::LookupError, LookupProvider, Result};
+use log::debug;
+use std::{
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+/// Mode of the fixtures and their directory
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Fixtures {
+    /// Makes the requests and stores the replies in the directory
+    Record(PathBuf),
+    /// Answers from the replies stored in the directory, without making requests
+    Replay(PathBuf),
+}
+
+impl Fixtures {
+    /// Returns the directory of the fixtures
+    pub fn dir(&self) -> &Path {
+        match self {
+            Fixtures::Record(dir) | Fixtures::Replay(dir) => dir,
+        }
+    }
+
+    /// Returns the path of the fixture of a provider and target
+    pub fn path(&self, provider: &LookupProvider, target: Option<IpAddr>) -> PathBuf {
+        let mut name = provider.to_string();
+        if let Some(target) = target {
+            name.push_str(&format!("-{}", target));
+        }
+        let name: String = name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect();
+        self.dir().join(format!("{}.body", name))
+    }
+
+    /// Reads the stored reply, failing if it was never recorded
+    pub(crate) fn replay(
+        &self,
+        provider: &LookupProvider,
+        target: Option<IpAddr>,
+    ) -> Result<String> {
+        let path = self.path(provider, target);
+        debug!("Replaying fixture {}", path.display());
+        fs::read_to_string(&path).map_err(|e| {
+            LookupError::GenericError(format!("Missing fixture {}: {}", path.display(), e))
+        })
+    }
+
+    /// Stores the reply, creating the directory if needed
+    pub(crate) fn record(
+        &self,
+        provider: &LookupProvider,
+        target: Option<IpAddr>,
+        reply: &str,
+    ) -> Result<()> {
+        let path = self.path(provider, target);
+        debug!("Recording fixture {}", path.display());
+        fs::create_dir_all(self.dir())
+            .and_then(|_| fs::write(&path, reply))
+            .map_err(|e| {
+                LookupError::GenericError(format!("Failed to record {}: {}", path.display(), e))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::LookupService;
+
+    #[test]
+    fn test_path() {
+        let fixtures = Fixtures::Replay("fixtures".into());
+        assert_eq!(
+            fixtures.path(&LookupProvider::IpWhoIs, None),
+            Path::new("fixtures").join("ipwhois.body")
+        );
+        let target = "2606:4700::1111".parse().ok();
+        assert_eq!(
+            fixtures.path(&LookupProvider::IpWhoIs, target),
+            Path::new("fixtures").join("ipwhois-2606_4700__1111.body")
+        );
+        let provider = LookupProvider::Custom("https://example.com/ip".to_string());
+        assert_eq!(
+            fixtures.path(&provider, None),
+            Path::new("fixtures").join("custom_https___example.com_ip.body")
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_record_replay() {
+        let dir = std::env::temp_dir().join(format!("public-ip-fixtures-{}", std::process::id()));
+        let target = "8.8.8.8".parse().ok();
+        let reply = r#"{"ip": "8.8.8.8", "success": true, "country": "United States"}"#;
+        Fixtures::Record(dir.clone())
+            .record(&LookupProvider::IpWhoIs, target, reply)
+            .unwrap();
+
+        let mut service = LookupService::new(LookupProvider::IpWhoIs, None);
+        service.set_fixtures(Fixtures::Replay(dir.clone()));
+        let response = service.lookup(target).await.unwrap();
+        assert_eq!(response.ip, target.unwrap());
+        assert_eq!(response.country, Some("United States".to_string()));
+
+        let response = service.lookup(None).await;
+        assert!(
+            matches!(response, Err(LookupError::GenericError(_))),
+            "Missing fixture should fail, got {:?}",
+            response
+        );
+        _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,12 +1,12 @@
 //! <https://freeipapi.com> lookup provider
 
-use super::{client::RequestBuilder, ProviderResponse, Result};
+use super::{client::RequestBuilder, parse_response_ip, ProviderResponse, Result};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://docs.freeipapi.com/response.html>
 #[derive(Serialize, Deserialize, Debug)]
@@ -28,11 +28,9 @@ pub struct FreeIpApiResponse {
 }
 
 impl ProviderResponse<FreeIpApiResponse> for FreeIpApiResponse {
-    fn into_response(self) -> LookupResponse {
+    fn into_response(self) -> Result<LookupResponse> {
         let mut response = LookupResponse::new(
-            self.ip_address
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
+            parse_response_ip(&self.ip_address)?,
             LookupProvider::FreeIpApi,
         );
         response.country = self.country_name;
@@ -45,7 +43,7 @@ impl ProviderResponse<FreeIpApiResponse> for FreeIpApiResponse {
         response.longitude = self.longitude;
         response.time_zone = self.time_zone;
         response.is_proxy = self.is_proxy;
-        response
+        Ok(response)
     }
 }
 
@@ -70,16 +68,24 @@ impl Provider for FreeIpApi {
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
         let response = FreeIpApiResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::FreeIpApi
     }
 
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(86_400))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -118,7 +124,7 @@ mod tests {
     fn test_parse() {
         let response = FreeIpApiResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip_address, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
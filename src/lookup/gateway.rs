@@ -0,0 +1,380 @@
+//! Local gateway lookup provider
+//!
+//! Asks the router of the local network for its external address, through NAT-PMP first and
+//! UPnP IGD `GetExternalIPAddress` second, without any request leaving the LAN. Gateways without
+//! either protocol, or sitting behind another NAT and reporting a non public address, fail
+//! quickly so that the next provider of the chain is tried.
+//!
+//! Available with the `local-discovery` feature, which also puts the provider first in the default
+//! chain.
+
+use super::{bogon, error::LookupError, Result};
+use crate::{
+    lookup::{DailyQuota, LookupProvider, Provider},
+    time::Instant,
+    LookupResponse,
+};
+use log::debug;
+use std::{
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, TcpStream, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+/// Port the gateway listens on for NAT-PMP requests
+pub const NAT_PMP_PORT: u16 = 5351;
+
+/// Multicast address of the SSDP discovery used by UPnP
+pub const SSDP_ADDRESS: &str = "239.255.255.250:1900";
+
+/// Time waited for the NAT-PMP reply
+const NAT_PMP_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Time waited for the UPnP discovery and each request to the gateway
+const UPNP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Service types answering `GetExternalIPAddress`
+const WAN_SERVICES: [&str; 2] = ["WANIPConnection", "WANPPPConnection"];
+
+/// Local gateway lookup provider
+pub struct Gateway;
+
+impl Gateway {
+    /// Returns the external address reported by the gateway
+    fn external_address(&self) -> Result<IpAddr> {
+        let nat_pmp = default_gateway()
+            .ok_or(LookupError::GenericError(
+                "No default gateway found".to_string(),
+            ))
+            .and_then(|gateway| nat_pmp(gateway).map_err(|e| io_error("NAT-PMP", e)));
+        let address = match nat_pmp {
+            Ok(address) => IpAddr::V4(address),
+            Err(e) => {
+                debug!("NAT-PMP lookup failed: {}, trying UPnP", e);
+                upnp().map_err(|e| io_error("UPnP", e))?
+            }
+        };
+        if let Some(kind) = bogon::non_public_kind(&address) {
+            return Err(LookupError::GenericError(format!(
+                "Gateway reported a {} address {}, it is behind another NAT",
+                kind, address
+            )));
+        }
+        Ok(address)
+    }
+}
+
+impl Provider for Gateway {
+    fn get_endpoint(&self, _key: &Option<String>, _target: &Option<IpAddr>) -> String {
+        format!("natpmp://gateway:{}", NAT_PMP_PORT)
+    }
+
+    /// NAT-PMP and UPnP wait on their sockets, async builds run the lookup on a blocking thread
+    fn local_reply_blocks(&self) -> bool {
+        true
+    }
+
+    fn local_reply(&self, _target: Option<IpAddr>) -> Option<Result<String>> {
+        let reply = self.external_address().and_then(|address| {
+            let response = LookupResponse::new(address, self.get_type());
+            Ok(serde_json::to_string(&response)?)
+        });
+        Some(reply)
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::Gateway
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Unlimited)
+    }
+}
+
+fn io_error(protocol: &str, error: io::Error) -> LookupError {
+    LookupError::GenericError(format!("{} gateway lookup failed: {}", protocol, error))
+}
+
+/// Returns the default IPv4 gateway
+///
+/// Read from the routing table on Linux, elsewhere the first address of the local subnet is assumed.
+fn default_gateway() -> Option<Ipv4Addr> {
+    #[cfg(target_os = "linux")]
+    if let Some(gateway) = std::fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|table| parse_route_table(&table))
+    {
+        return Some(gateway);
+    }
+    // connecting a UDP socket sends nothing, it only picks the outgoing interface
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+    match socket.local_addr().ok()?.ip() {
+        IpAddr::V4(local) => {
+            let [a, b, c, _] = local.octets();
+            Some(Ipv4Addr::new(a, b, c, 1))
+        }
+        IpAddr::V6(_) => None,
+    }
+}
+
+/// Parses the gateway of the default route out of `/proc/net/route`
+pub fn parse_route_table(table: &str) -> Option<Ipv4Addr> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        // the address is written as a little-endian hexadecimal number
+        let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_le_bytes())).filter(|g| !g.is_unspecified())
+    })
+}
+
+/// Requests the external address from the gateway over NAT-PMP
+fn nat_pmp(gateway: Ipv4Addr) -> io::Result<Ipv4Addr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_read_timeout(Some(NAT_PMP_TIMEOUT))?;
+    socket.connect((gateway, NAT_PMP_PORT))?;
+    // version 0, opcode 0: external address request
+    socket.send(&[0, 0])?;
+    let mut reply = [0u8; 16];
+    let len = socket.recv(&mut reply)?;
+    parse_nat_pmp(&reply[..len])
+}
+
+/// Parses a NAT-PMP external address reply
+pub fn parse_nat_pmp(reply: &[u8]) -> io::Result<Ipv4Addr> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    if reply.len() < 12 || reply[0] != 0 || reply[1] != 128 {
+        return Err(invalid("Invalid NAT-PMP reply"));
+    }
+    let result = u16::from_be_bytes([reply[2], reply[3]]);
+    if result != 0 {
+        return Err(invalid(&format!("NAT-PMP result code {}", result)));
+    }
+    Ok(Ipv4Addr::new(reply[8], reply[9], reply[10], reply[11]))
+}
+
+/// Discovers the gateway over SSDP and requests the external address over UPnP IGD
+fn upnp() -> io::Result<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_read_timeout(Some(UPNP_TIMEOUT))?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\n\
+         ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n",
+        SSDP_ADDRESS
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDRESS)?;
+    let started = Instant::now();
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "No UPnP gateway found");
+    let mut buffer = [0u8; 2048];
+    while started.elapsed() < UPNP_TIMEOUT {
+        let Ok(len) = socket.recv(&mut buffer) else {
+            break;
+        };
+        let reply = String::from_utf8_lossy(&buffer[..len]);
+        let Some(location) = header(&reply, "location") else {
+            continue;
+        };
+        match external_address_at(location) {
+            Ok(address) => return Ok(address),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Requests the external address from the gateway described at `location`
+fn external_address_at(location: &str) -> io::Result<IpAddr> {
+    let description = http_request(location, None)?;
+    let (service_type, control_url) = wan_service(&description).ok_or(io::Error::new(
+        io::ErrorKind::NotFound,
+        "No WAN connection service",
+    ))?;
+    let control_url = match control_url.starts_with("http") {
+        true => control_url.to_string(),
+        false => format!(
+            "{}/{}",
+            base_url(location),
+            control_url.trim_start_matches('/')
+        ),
+    };
+    let body = format!(
+        "<?xml version=\"1.0\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:GetExternalIPAddress xmlns:u=\"{}\"></u:GetExternalIPAddress></s:Body>\
+         </s:Envelope>",
+        service_type
+    );
+    let reply = http_request(&control_url, Some((service_type, &body)))?;
+    tag(&reply, "NewExternalIPAddress")
+        .and_then(|address| address.trim().parse().ok())
+        .ok_or(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No external address in the UPnP reply",
+        ))
+}
+
+/// Returns the value of a header of an SSDP reply
+fn header<'a>(reply: &'a str, name: &str) -> Option<&'a str> {
+    reply.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+/// Returns the text of the first element with the tag name
+fn tag<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", name))?;
+    Some(&xml[start..end])
+}
+
+/// Returns the service type and the control URL of the WAN connection service of a device description
+pub fn wan_service(description: &str) -> Option<(&str, &str)> {
+    description.split("<service>").skip(1).find_map(|service| {
+        let service_type = tag(service, "serviceType")?.trim();
+        if !WAN_SERVICES.iter().any(|s| service_type.contains(s)) {
+            return None;
+        }
+        Some((service_type, tag(service, "controlURL")?.trim()))
+    })
+}
+
+/// Returns the scheme, host and port of a URL
+fn base_url(url: &str) -> &str {
+    let rest = url.strip_prefix("http://").unwrap_or(url);
+    let end = rest.find('/').unwrap_or(rest.len());
+    &url[..url.len() - rest.len() + end]
+}
+
+/// Makes a plain HTTP/1.0 request to the gateway, a SOAP action if `soap` holds the service type and body
+///
+/// The reply is capped at the maximum response size of the process-wide configuration.
+fn http_request(url: &str, soap: Option<(&str, &str)>) -> io::Result<String> {
+    let rest = url.strip_prefix("http://").ok_or(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Only plain HTTP gateways are supported",
+    ))?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let address = match host.contains(':') {
+        true => host.to_string(),
+        false => format!("{}:80", host),
+    };
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or(io::Error::from(io::ErrorKind::NotFound))?;
+    let mut stream = TcpStream::connect_timeout(&address, UPNP_TIMEOUT)?;
+    stream.set_read_timeout(Some(UPNP_TIMEOUT))?;
+    stream.set_write_timeout(Some(UPNP_TIMEOUT))?;
+    let request = match soap {
+        Some((service_type, body)) => format!(
+            "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: text/xml; charset=\"utf-8\"\r\n\
+             SOAPAction: \"{}#GetExternalIPAddress\"\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            host,
+            service_type,
+            body.len(),
+            body
+        ),
+        None => format!("GET {} HTTP/1.0\r\nHost: {}\r\n\r\n", path, host),
+    };
+    stream.write_all(request.as_bytes())?;
+    let limit = crate::config::config().max_response_size;
+    let mut reply = String::new();
+    stream.take(limit as u64 + 1).read_to_string(&mut reply)?;
+    if reply.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Gateway reply larger than {} bytes", limit),
+        ));
+    }
+    let (status, body) = reply.split_once("\r\n\r\n").unwrap_or((&reply, ""));
+    if !status
+        .lines()
+        .next()
+        .is_some_and(|line| line.contains(" 200"))
+    {
+        return Err(io::Error::other(format!(
+            "Gateway answered {}",
+            status.lines().next().unwrap_or("")
+        )));
+    }
+    Ok(body.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ignore]
+    #[test]
+    fn test_external_address() {
+        let address = Gateway.external_address();
+        assert!(address.is_ok(), "Gateway lookup failed {:?}", address);
+    }
+
+    #[test]
+    fn test_parse_nat_pmp() {
+        let reply = [0, 128, 0, 0, 0, 0, 1, 44, 203, 0, 113, 7];
+        assert_eq!(
+            parse_nat_pmp(&reply).unwrap(),
+            Ipv4Addr::new(203, 0, 113, 7)
+        );
+        let refused = [0, 128, 0, 3, 0, 0, 1, 44, 0, 0, 0, 0];
+        assert!(parse_nat_pmp(&refused).is_err(), "Result code should fail");
+        assert!(
+            parse_nat_pmp(&reply[..8]).is_err(),
+            "Short reply should fail"
+        );
+    }
+
+    #[test]
+    fn test_parse_route_table() {
+        let table = "Iface\tDestination\tGateway\tFlags\tRefCnt\tUse\tMetric\tMask\n\
+                     eth0\t0000A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\n\
+                     eth0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000\n";
+        assert_eq!(
+            parse_route_table(table),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_wan_service() {
+        let description = r#"<root><device><serviceList>
+<service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType><controlURL>/l3f</controlURL></service>
+<service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType><controlURL>/ctl/IPConn</controlURL></service>
+</serviceList></device></root>"#;
+        assert_eq!(
+            wan_service(description),
+            Some((
+                "urn:schemas-upnp-org:service:WANIPConnection:1",
+                "/ctl/IPConn"
+            ))
+        );
+        assert_eq!(
+            base_url("http://192.168.1.1:5000/rootDesc.xml"),
+            "http://192.168.1.1:5000"
+        );
+        assert_eq!(
+            header(
+                "HTTP/1.1 200 OK\r\nLOCATION: http://192.168.1.1/desc.xml\r\n",
+                "location"
+            ),
+            Some("http://192.168.1.1/desc.xml")
+        );
+    }
+}
@@ -1,12 +1,12 @@
 //! <https://getjsonip.com> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{parse_response_ip, ProviderResponse, Result};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <http://getjsonip.com>
 #[derive(Serialize, Deserialize, Debug)]
@@ -15,13 +15,11 @@ pub struct GetJsonIpResponse {
 }
 
 impl ProviderResponse<GetJsonIpResponse> for GetJsonIpResponse {
-    fn into_response(self) -> LookupResponse {
-        LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
+    fn into_response(self) -> Result<LookupResponse> {
+        Ok(LookupResponse::new(
+            parse_response_ip(&self.ip)?,
             LookupProvider::GetJsonIp,
-        )
+        ))
     }
 }
 
@@ -35,12 +33,16 @@ impl Provider for GetJsonIp {
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
         let response = GetJsonIpResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::GetJsonIp
     }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Unlimited)
+    }
 }
 
 #[cfg(test)]
@@ -67,7 +69,7 @@ mod tests {
     fn test_parse() {
         let response = GetJsonIpResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<std::net::IpAddr>().unwrap(),
@@ -1,12 +1,12 @@
 //! <https://ifconfig.co> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{parse_response_ip, ProviderResponse, Result};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <http://github.com/leafcloudhq/echoip/blob/master/http/http.go>
 #[derive(Serialize, Deserialize, Debug)]
@@ -31,13 +31,9 @@ pub struct IfConfigResponse {
 }
 
 impl ProviderResponse<IfConfigResponse> for IfConfigResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IfConfig,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IfConfig);
         response.country = self.country;
         response.country_code = self.country_iso;
         if self.country_eu.unwrap_or(false) {
@@ -52,7 +48,7 @@ impl ProviderResponse<IfConfigResponse> for IfConfigResponse {
         response.asn = self.asn;
         response.asn_org = self.asn_org;
         response.hostname = self.hostname;
-        response
+        Ok(response)
     }
 }
 
@@ -65,21 +61,29 @@ impl Provider for IfConfig {
             Some(t) => format!("?ip={}", t),
             None => "".to_string(),
         };
-        format!("http://ifconfig.co/json{}", target)
+        format!("https://ifconfig.co/json{}", target)
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
         let response = IfConfigResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::IfConfig
     }
 
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(1_440))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -103,7 +107,7 @@ mod tests {
     fn test_parse() {
         let response = IfConfigResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
@@ -1,12 +1,12 @@
 //! <https://ip2location.io> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{check_error_body, error_field, parse_response_ip, ProviderResponse, Result};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <http://www.ip2location.io/ip2location-documentation>
 #[derive(Serialize, Deserialize, Debug)]
@@ -27,13 +27,9 @@ pub struct Ip2LocationResponse {
 }
 
 impl ProviderResponse<Ip2LocationResponse> for Ip2LocationResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::Ip2Location,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::Ip2Location);
         response.country = self.country_name;
         response.country_code = self.country_code;
         response.region = self.region_name;
@@ -46,7 +42,7 @@ impl ProviderResponse<Ip2LocationResponse> for Ip2LocationResponse {
         response.asn = self.asn;
         response.is_proxy = self.is_proxy;
 
-        response
+        Ok(response)
     }
 }
 
@@ -67,14 +63,23 @@ impl Provider for Ip2Location {
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::Ip2Location, &json, |reply| {
+            let error = reply.get("error")?;
+            let message = error_field(error, "error_message").unwrap_or(error.to_string());
+            Some((error_field(error, "error_code"), message))
+        })?;
         let response = Ip2LocationResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::Ip2Location
     }
 
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(500))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
@@ -146,7 +151,7 @@ mod tests {
 
         let response = Ip2LocationResponse::parse(result).unwrap();
         assert_eq!(response.ip, "8.8.8.8", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(lookup.ip, target.unwrap(), "IP address not matching");
     }
 
@@ -154,7 +159,7 @@ mod tests {
     fn test_parse() {
         let response = Ip2LocationResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "8.8.8.8", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "8.8.8.8".parse::<IpAddr>().unwrap(),
@@ -0,0 +1,184 @@
+//! <https://ip2proxy.com> lookup provider
+
+use super::{check_error_body, error_field, parse_response_ip, ProviderResponse, Result};
+use crate::{
+    lookup::{DailyQuota, LookupProvider, Provider},
+    LookupResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
+
+/// <https://www.ip2location.com/web-service/ip2proxy>
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Ip2ProxyResponse {
+    ip: Option<String>,
+    response: Option<String>,
+    country_code: Option<String>,
+    country_name: Option<String>,
+    region_name: Option<String>,
+    city_name: Option<String>,
+    isp: Option<String>,
+    domain: Option<String>,
+    usage_type: Option<String>,
+    asn: Option<String>,
+    #[serde(rename = "as")]
+    as_name: Option<String>,
+    last_seen: Option<String>,
+    proxy_type: Option<String>,
+    threat: Option<String>,
+    is_proxy: Option<String>,
+    provider: Option<String>,
+}
+
+/// IP2Proxy uses "-" for fields that don't apply
+fn value(field: Option<String>) -> Option<String> {
+    field.filter(|v| !v.is_empty() && v != "-")
+}
+
+impl ProviderResponse<Ip2ProxyResponse> for Ip2ProxyResponse {
+    fn into_response(self) -> Result<LookupResponse> {
+        // the address is usually missing, it is filled in from the target of the lookup, lookups
+        // without a target fail on it
+        let ip = match self.ip {
+            Some(ip) => parse_response_ip(&ip)?,
+            None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        };
+        let mut response = LookupResponse::new(ip, LookupProvider::Ip2Proxy);
+        response.country = value(self.country_name);
+        response.country_code = value(self.country_code);
+        response.region = value(self.region_name);
+        response.city = value(self.city_name);
+        response.isp = value(self.isp);
+        response.asn_org = value(self.as_name);
+        response.asn = value(self.asn).map(|asn| format!("AS{asn}"));
+        response.is_proxy = match value(self.is_proxy).as_deref() {
+            Some("YES") => Some(true),
+            Some("NO") => Some(false),
+            _ => None,
+        };
+        response.proxy_type = value(self.proxy_type);
+        response.proxy_provider = value(self.provider);
+
+        Ok(response)
+    }
+}
+
+/// Ip2Proxy lookup provider
+///
+/// The IP2Proxy web service does not echo the queried address, it is meant to be used for
+/// target lookups where the address of the response is filled in from the target.
+pub struct Ip2Proxy;
+
+impl Provider for Ip2Proxy {
+    fn get_endpoint(&self, key: &Option<String>, target: &Option<IpAddr>) -> String {
+        let key = match key {
+            Some(k) => format!("&key={}", k),
+            None => "".to_string(),
+        };
+        let target = match target.map(|t| t.to_string()) {
+            Some(t) => format!("&ip={}", t),
+            None => "".to_string(),
+        };
+        format!("https://api.ip2proxy.com/?package=PX11{}{}", key, target)
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::Ip2Proxy, &json, |reply| {
+            let response = error_field(reply, "response")?;
+            (response != "OK").then_some((None, response))
+        })?;
+        let response = Ip2ProxyResponse::parse(json)?;
+        response.into_response()
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::Ip2Proxy
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(166))
+    }
+
+    fn supports_target_lookup(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const TEST_INPUT: &str = r#"
+{
+  "response": "OK",
+  "countryCode": "US",
+  "countryName": "United States of America",
+  "regionName": "California",
+  "cityName": "Los Angeles",
+  "isp": "Example Hosting LLC",
+  "domain": "example.com",
+  "usageType": "DCH",
+  "asn": "64496",
+  "as": "Example Hosting LLC",
+  "lastSeen": "3",
+  "proxyType": "VPN",
+  "threat": "-",
+  "isProxy": "YES",
+  "provider": "ExampleVPN"
+}
+"#;
+
+    #[ignore]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_request_target() {
+        use std::env;
+        let key = env::var("IP2PROXY_APIKEY").ok();
+        assert!(key.is_some(), "Missing APIKEY");
+
+        let service = Box::new(Ip2Proxy);
+        let target = "8.8.8.8".parse().ok();
+        let result = service.get_client(key, target).send().await;
+        let result = super::super::handle_response(result).await.unwrap();
+        assert!(!result.is_empty(), "Result is empty");
+        println!("Ip2Proxy: {:#?}", result);
+
+        let response = Ip2ProxyResponse::parse(result);
+        assert!(response.is_ok(), "Failed parsing response {:#?}", response);
+    }
+
+    #[test]
+    fn test_parse() {
+        let response = Ip2ProxyResponse::parse(TEST_INPUT.to_string()).unwrap();
+        let lookup = response.into_response().unwrap();
+        assert_eq!(lookup.is_proxy, Some(true), "Proxy flag not matching");
+        assert_eq!(
+            lookup.proxy_type,
+            Some("VPN".to_string()),
+            "Proxy type not matching"
+        );
+        assert_eq!(
+            lookup.proxy_provider,
+            Some("ExampleVPN".to_string()),
+            "Proxy provider not matching"
+        );
+        assert_eq!(lookup.asn, Some("AS64496".to_string()), "ASN not matching");
+    }
+
+    #[test]
+    fn test_parse_not_proxy() {
+        let input = TEST_INPUT
+            .replace(r#""isProxy": "YES""#, r#""isProxy": "NO""#)
+            .replace(r#""proxyType": "VPN""#, r#""proxyType": "-""#)
+            .replace(r#""provider": "ExampleVPN""#, r#""provider": "-""#);
+        let lookup = Ip2ProxyResponse::parse(input)
+            .unwrap()
+            .into_response()
+            .unwrap();
+        assert_eq!(lookup.is_proxy, Some(false), "Proxy flag not matching");
+        assert_eq!(lookup.proxy_type, None, "Proxy type should be empty");
+        assert_eq!(
+            lookup.proxy_provider, None,
+            "Proxy provider should be empty"
+        );
+    }
+}
@@ -1,12 +1,15 @@
 //! <https://ipapi.co> lookup provider
 
-use super::{client::RequestBuilder, ProviderResponse, Result};
+use super::{
+    check_error_body, client::RequestBuilder, error_field, parse_response_ip, ProviderResponse,
+    Result,
+};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <http://ipapi.co/api/>
 #[derive(Serialize, Deserialize, Debug)]
@@ -32,13 +35,9 @@ pub struct IpApiCoResponse {
 }
 
 impl ProviderResponse<IpApiCoResponse> for IpApiCoResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpApiCo,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpApiCo);
         response.country = self.country_name;
         response.country_code = self.country_code;
         response.region = self.region;
@@ -49,8 +48,9 @@ impl ProviderResponse<IpApiCoResponse> for IpApiCoResponse {
         response.time_zone = self.timezone;
         response.asn_org = self.org;
         response.asn = self.asn;
+        response.isp = self.isp;
         response.hostname = self.hostname;
-        response
+        Ok(response)
     }
 }
 
@@ -71,17 +71,32 @@ impl Provider for IpApiCo {
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::IpApiCo, &json, |reply| {
+            (reply["error"] == true).then(|| {
+                let reason = error_field(reply, "reason");
+                let message = error_field(reply, "message").or(reason.clone());
+                (reason, message.unwrap_or("Request failed".to_string()))
+            })
+        })?;
         let response = IpApiCoResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::IpApiCo
     }
 
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(1_000))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -126,7 +141,7 @@ mod tests {
     fn test_parse() {
         let response = IpApiCoResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
@@ -1,12 +1,16 @@
 //! <https://ip-api.com> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{
+    append_language, check_error_body,
+    client::{Client, RequestBuilder},
+    error_field, parse_response_ip, with_json_body, ProviderResponse, Result,
+};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Parameters, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -40,16 +44,13 @@ pub struct IpApiComResponse {
 }
 
 impl ProviderResponse<IpApiComResponse> for IpApiComResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.query
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpApiCom,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.query)?, LookupProvider::IpApiCom);
         response.country = self.country;
         response.country_code = self.country_code;
         response.region = self.region_name;
+        response.district = self.district.filter(|d| !d.is_empty());
         response.postal_code = self.zip;
         response.city = self.city;
         response.latitude = self.lat;
@@ -57,9 +58,49 @@ impl ProviderResponse<IpApiComResponse> for IpApiComResponse {
         response.time_zone = self.timezone;
         response.asn_org = self.org;
         response.asn = self.asn;
+        response.isp = self.isp;
         response.hostname = self.reverse;
         response.is_proxy = self.proxy;
-        response
+        response.is_mobile = self.mobile;
+        Ok(response)
+    }
+}
+
+/// Fields requested by default, every field the response knows about
+const DEFAULT_FIELDS: &str = "66846719";
+
+/// Fields requested by ASN lookups
+const ASN_FIELDS: &str = "status,message,query,as,org";
+
+/// Returns the endpoint requesting the `fields` mask
+///
+/// The mask is overridden with the `fields` option of the parameters and has to include `query`.
+fn endpoint(key: &Option<String>, target: &Option<IpAddr>, fields: &str) -> String {
+    let target = match target.map(|t| t.to_string()) {
+        Some(t) => t,
+        None => "".to_string(),
+    };
+    // HTTPS is only offered on the paid tier
+    match key {
+        Some(k) => format!(
+            "https://pro.ip-api.com/json/{}?fields={}&key={}",
+            target, fields, k
+        ),
+        None => format!("http://ip-api.com/json/{}?fields={}", target, fields),
+    }
+}
+
+/// Maximum number of targets accepted by the batch endpoint
+const BULK_LIMIT: usize = 100;
+
+/// Returns the batch endpoint requesting the default fields
+fn bulk_endpoint(key: &Option<String>) -> String {
+    match key {
+        Some(k) => format!(
+            "https://pro.ip-api.com/batch?fields={}&key={}",
+            DEFAULT_FIELDS, k
+        ),
+        None => format!("http://ip-api.com/batch?fields={}", DEFAULT_FIELDS),
     }
 }
 
@@ -67,31 +108,83 @@ impl ProviderResponse<IpApiComResponse> for IpApiComResponse {
 pub struct IpApiCom;
 
 impl Provider for IpApiCom {
-    fn get_endpoint(&self, _key: &Option<String>, target: &Option<IpAddr>) -> String {
-        let target = match target.map(|t| t.to_string()) {
-            Some(t) => t,
-            None => "".to_string(),
-        };
-        format!("http://ip-api.com/json/{}?fields=66846719", target)
+    fn get_endpoint(&self, key: &Option<String>, target: &Option<IpAddr>) -> String {
+        endpoint(key, target, DEFAULT_FIELDS)
+    }
+
+    fn get_endpoint_with_parameters(
+        &self,
+        key: &Option<String>,
+        target: &Option<IpAddr>,
+        parameters: &Parameters,
+    ) -> String {
+        let fields = parameters.option("fields").unwrap_or(DEFAULT_FIELDS);
+        append_language(endpoint(key, target, fields), "lang", parameters)
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::IpApiCom, &json, |reply| {
+            (reply["status"] == "fail").then(|| {
+                let message = error_field(reply, "message");
+                (None, message.unwrap_or("Request failed".to_string()))
+            })
+        })?;
         let response = IpApiComResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::IpApiCom
     }
 
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(64_800))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    fn supports_asn_lookup(&self) -> bool {
+        true
+    }
+
+    fn get_asn_endpoint(&self, key: &Option<String>, target: &Option<IpAddr>) -> String {
+        endpoint(key, target, ASN_FIELDS)
+    }
+
+    fn bulk_limit(&self) -> Option<usize> {
+        Some(BULK_LIMIT)
+    }
+
+    /// Returns a POST request client for the batch endpoint, the targets are sent as a JSON array
+    fn get_bulk_client(
+        &self,
+        client: &Client,
+        key: Option<String>,
+        targets: &[IpAddr],
+    ) -> RequestBuilder {
+        let targets: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+        let request = client.post(bulk_endpoint(&key));
+        with_json_body(request, Some(targets.into()))
+    }
+
+    /// The batch endpoint answers with an array in request order, targets that failed, like
+    /// reserved ranges, are reported with a `fail` status and skipped.
+    fn parse_bulk_reply(&self, json: String) -> Result<Vec<LookupResponse>> {
+        let responses: Vec<IpApiComResponse> = serde_json::from_str(&json)?;
+        responses
+            .into_iter()
+            .filter(|response| response.status.as_deref() != Some("fail"))
+            .map(|response| response.into_response())
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lookup::error::LookupError;
     const TEST_INPUT: &str = r#"
 {
   "query": "1.1.1.1",
@@ -137,11 +230,87 @@ mod tests {
     fn test_parse() {
         let response = IpApiComResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.query, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
             "IP address not matching"
         );
+        assert_eq!(lookup.is_mobile, Some(false), "Mobile flag not matching");
+        assert_eq!(lookup.district, None, "Empty district should be dropped");
+        assert_eq!(
+            lookup.isp,
+            Some("Le Groupe Videotron Ltee".to_string()),
+            "ISP not matching"
+        );
+    }
+
+    #[test]
+    fn test_endpoint() {
+        let target = "1.1.1.1".parse().ok();
+        assert!(IpApiCom
+            .get_endpoint(&None, &target)
+            .starts_with("http://ip-api.com/"));
+        assert_eq!(
+            IpApiCom.get_endpoint(&Some("abc".to_string()), &target),
+            "https://pro.ip-api.com/json/1.1.1.1?fields=66846719&key=abc",
+            "Keyed requests should use the HTTPS pro endpoint"
+        );
+
+        let parameters = Parameters::default().with_option("fields", "query,country");
+        assert_eq!(
+            IpApiCom.get_endpoint_with_parameters(&None, &target, &parameters),
+            "http://ip-api.com/json/1.1.1.1?fields=query,country"
+        );
+        let parameters = Parameters::default().with_language("pt-BR");
+        assert_eq!(
+            IpApiCom.get_endpoint_with_parameters(&None, &target, &parameters),
+            "http://ip-api.com/json/1.1.1.1?fields=66846719&lang=pt-BR"
+        );
+        assert_eq!(
+            IpApiCom.get_endpoint_with_parameters(&None, &target, &Parameters::default()),
+            IpApiCom.get_endpoint(&None, &target)
+        );
+        assert_eq!(
+            IpApiCom.get_asn_endpoint(&None, &target),
+            "http://ip-api.com/json/1.1.1.1?fields=status,message,query,as,org"
+        );
+    }
+
+    #[test]
+    fn test_bulk() {
+        let targets = ["1.1.1.1".parse().unwrap(), "8.8.8.8".parse().unwrap()];
+        let request = IpApiCom
+            .get_bulk_client(&super::super::client::shared(), None, &targets)
+            .build()
+            .unwrap();
+        assert_eq!(request.method(), crate::lookup::client::Method::POST);
+        assert_eq!(
+            request.url().as_str(),
+            "http://ip-api.com/batch?fields=66846719"
+        );
+        assert_eq!(request.body(), Some(r#"["1.1.1.1","8.8.8.8"]"#.as_bytes()));
+
+        let reply = format!(
+            r#"[{}, {{"status": "fail", "message": "reserved range", "query": "10.0.0.1"}}]"#,
+            TEST_INPUT
+        );
+        let responses = IpApiCom.parse_bulk_reply(reply).unwrap();
+        assert_eq!(responses.len(), 1, "Failed targets should be skipped");
+        assert_eq!(responses[0].ip, "1.1.1.1".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let input = r#"{"status": "fail", "message": "reserved range", "query": "127.0.0.1"}"#;
+        let response = IpApiCom.parse_reply(input.to_string());
+        assert!(
+            matches!(
+                response,
+                Err(LookupError::ProviderError { message, .. }) if message == "reserved range"
+            ),
+            "Error body should be detected"
+        );
+        assert!(IpApiCom.parse_reply(TEST_INPUT.to_string()).is_ok());
     }
 }
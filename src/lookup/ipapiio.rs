@@ -1,12 +1,12 @@
 //! <https://ip-api.io> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{parse_response_ip, parse_versioned, ProviderResponse, Result};
 use crate::{
     lookup::{LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://ip-api.io/>
 #[derive(Serialize, Deserialize, Debug)]
@@ -38,13 +38,9 @@ struct SuspiciousFactors {
 }
 
 impl ProviderResponse<IpApiIoResponse> for IpApiIoResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpApiIo,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpApiIo);
         response.country = self.country_name;
         response.country_code = self.country_code;
         response.region = self.region_name;
@@ -60,7 +56,55 @@ impl ProviderResponse<IpApiIoResponse> for IpApiIoResponse {
         if self.is_in_european_union.unwrap_or(false) {
             response.continent = Some("Europe".to_string());
         }
-        response
+        Ok(response)
+    }
+}
+
+/// Response of the newer API, grouping the location fields in a nested object
+///
+/// <https://ip-api.io/>
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IpApiIoResponseV2 {
+    ip: String,
+    location: LocationV2,
+    suspicious_factors: Option<SuspiciousFactorsV2>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct LocationV2 {
+    country: Option<String>,
+    country_code: Option<String>,
+    city: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    zip: Option<String>,
+    timezone: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SuspiciousFactorsV2 {
+    is_proxy: Option<bool>,
+    is_tor_node: Option<bool>,
+    is_spam: Option<bool>,
+    is_suspicious: Option<bool>,
+}
+
+impl ProviderResponse<IpApiIoResponseV2> for IpApiIoResponseV2 {
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpApiIo);
+        let location = self.location;
+        response.country = location.country.filter(|c| !c.is_empty());
+        response.country_code = location.country_code.filter(|c| !c.is_empty());
+        response.city = location.city.filter(|c| !c.is_empty());
+        response.postal_code = location.zip.filter(|z| !z.is_empty());
+        response.latitude = location.latitude;
+        response.longitude = location.longitude;
+        response.time_zone = location.timezone.filter(|t| !t.is_empty());
+        if let Some(suspicious) = self.suspicious_factors {
+            response.is_proxy = suspicious.is_proxy;
+        }
+        Ok(response)
     }
 }
 
@@ -81,8 +125,13 @@ impl Provider for IpApiIo {
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
-        let response = IpApiIoResponse::parse(json)?;
-        Ok(response.into_response())
+        parse_versioned(
+            json,
+            &[
+                |json| IpApiIoResponseV2::parse(json)?.into_response(),
+                |json| IpApiIoResponse::parse(json)?.into_response(),
+            ],
+        )
     }
 
     fn get_type(&self) -> LookupProvider {
@@ -143,11 +192,51 @@ mod tests {
     fn test_parse() {
         let response = IpApiIoResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
             "IP address not matching"
         );
     }
+
+    #[test]
+    fn test_parse_v2() {
+        let input = r#"
+{
+  "ip": "8.8.8.8",
+  "suspicious_factors": {
+    "is_proxy": false,
+    "is_tor_node": false,
+    "is_spam": false,
+    "is_suspicious": false,
+    "is_datacenter": true
+  },
+  "location": {
+    "country": "United States",
+    "country_code": "US",
+    "city": "Mountain View",
+    "latitude": 37.4056,
+    "longitude": -122.0775,
+    "zip": "94043",
+    "timezone": "America/Los_Angeles",
+    "local_time": "2024-05-07T04:15:10-07:00",
+    "local_time_unix": 1715080510,
+    "is_daylight_savings": true
+  }
+}
+"#;
+        let lookup = IpApiIo.parse_reply(input.to_string()).unwrap();
+        assert_eq!(
+            lookup.ip,
+            "8.8.8.8".parse::<IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+        assert_eq!(lookup.city, Some("Mountain View".to_string()));
+        assert_eq!(lookup.is_proxy, Some(false));
+
+        let lookup = IpApiIo.parse_reply(TEST_INPUT.to_string()).unwrap();
+        assert_eq!(lookup.country_code, Some("US".to_string()));
+        assert_eq!(lookup.asn_org, Some("GOOGLE".to_string()));
+    }
 }
@@ -1,12 +1,16 @@
 //! <https://ipbase.com> lookup provider
 
-use super::{client::RequestBuilder, ProviderResponse, Result};
+use super::{
+    append_language, client::RequestBuilder, parse_response_ip, parse_versioned, Parameters,
+    ProviderResponse, Result,
+};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
+    response::SecurityInfo,
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://ipbase.com/docs/info>
 #[derive(Serialize, Deserialize, Debug)]
@@ -47,6 +51,7 @@ struct Location {
 #[derive(Serialize, Deserialize, Debug)]
 struct Continent {
     name: Option<String>,
+    name_translated: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -54,11 +59,15 @@ struct Country {
     #[serde(rename = "alpha2")]
     code: Option<String>,
     name: Option<String>,
+    name_translated: Option<String>,
+    geonames_id: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct City {
     name: Option<String>,
+    name_translated: Option<String>,
+    geonames_id: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -66,6 +75,7 @@ struct Region {
     #[serde(rename = "alpha2")]
     code: Option<String>,
     name: Option<String>,
+    geonames_id: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -81,17 +91,14 @@ struct Security {
 }
 
 impl ProviderResponse<IpBaseResponse> for IpBaseResponse {
-    fn into_response(self) -> LookupResponse {
+    fn into_response(self) -> Result<LookupResponse> {
         let data = self.data;
-        let mut response = LookupResponse::new(
-            data.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpBase,
-        );
+        let mut response =
+            LookupResponse::new(parse_response_ip(&data.ip)?, LookupProvider::IpBase);
         response.hostname = data.hostname;
         if let Some(connection) = data.connection {
             response.asn_org = connection.organization;
+            response.isp = connection.isp;
             if let Some(number) = connection.asn {
                 response.asn = Some(format!("{number}"));
             }
@@ -100,15 +107,24 @@ impl ProviderResponse<IpBaseResponse> for IpBaseResponse {
         if let Some(location) = data.location {
             response.latitude = location.latitude;
             response.longitude = location.longitude;
+            if let Some(continent) = location.continent {
+                response.continent = continent.name;
+                response.continent_localized = continent.name_translated;
+            }
             if let Some(country) = location.country {
                 response.country = country.name;
+                response.country_localized = country.name_translated;
                 response.country_code = country.code;
+                response.country_geoname_id = country.geonames_id;
             }
             if let Some(city) = location.city {
                 response.city = city.name;
+                response.city_localized = city.name_translated;
+                response.city_geoname_id = city.geonames_id;
             }
             if let Some(region) = location.region {
                 response.region = region.name;
+                response.region_geoname_id = region.geonames_id;
             }
         }
 
@@ -116,7 +132,47 @@ impl ProviderResponse<IpBaseResponse> for IpBaseResponse {
             response.time_zone = timezone.id;
         }
 
-        response
+        if let Some(security) = data.security {
+            response.security = Some(SecurityInfo {
+                is_tor: security.is_tor,
+                ..Default::default()
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+/// Flat response of the retired v1 API, still returned by some deployments
+///
+/// <https://ipbase.com/docs/info>
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IpBaseResponseV1 {
+    ip: String,
+    country_code: Option<String>,
+    country_name: Option<String>,
+    region_code: Option<String>,
+    region_name: Option<String>,
+    city: Option<String>,
+    zip_code: Option<String>,
+    time_zone: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+impl ProviderResponse<IpBaseResponseV1> for IpBaseResponseV1 {
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpBase);
+        response.country = self.country_name.filter(|c| !c.is_empty());
+        response.country_code = self.country_code.filter(|c| !c.is_empty());
+        response.region = self.region_name.filter(|r| !r.is_empty());
+        response.city = self.city.filter(|c| !c.is_empty());
+        response.postal_code = self.zip_code.filter(|z| !z.is_empty());
+        response.time_zone = self.time_zone.filter(|t| !t.is_empty());
+        response.latitude = self.latitude;
+        response.longitude = self.longitude;
+        Ok(response)
     }
 }
 
@@ -132,6 +188,15 @@ impl Provider for IpBase {
         format!("https://api.ipbase.com/v2/info{}", target)
     }
 
+    fn get_endpoint_with_parameters(
+        &self,
+        key: &Option<String>,
+        target: &Option<IpAddr>,
+        parameters: &Parameters,
+    ) -> String {
+        append_language(self.get_endpoint(key, target), "language", parameters)
+    }
+
     fn add_auth(&self, request: RequestBuilder, key: &Option<String>) -> RequestBuilder {
         if let Some(key) = key {
             return request.header("apikey", key);
@@ -140,14 +205,23 @@ impl Provider for IpBase {
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
-        let response = IpBaseResponse::parse(json)?;
-        Ok(response.into_response())
+        parse_versioned(
+            json,
+            &[
+                |json| IpBaseResponse::parse(json)?.into_response(),
+                |json| IpBaseResponseV1::parse(json)?.into_response(),
+            ],
+        )
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::IpBase
     }
 
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(240))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
@@ -320,11 +394,84 @@ mod tests {
     fn test_parse() {
         let response = IpBaseResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.data.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
+        assert_eq!(
+            lookup.ip,
+            "1.1.1.1".parse::<IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+        assert_eq!(
+            lookup.city_geoname_id,
+            Some(5368753),
+            "City id not matching"
+        );
+        assert_eq!(
+            lookup.country_localized,
+            Some("United States".to_string()),
+            "Localized country not matching"
+        );
+        assert_eq!(
+            lookup.region_geoname_id,
+            Some(5332921),
+            "Region id not matching"
+        );
+        assert_eq!(
+            lookup.country_geoname_id,
+            Some(6252001),
+            "Country id not matching"
+        );
+    }
+
+    #[test]
+    fn test_endpoint_language() {
+        let target = "1.1.1.1".parse().ok();
+        let parameters = Parameters::default().with_language("de");
+        assert_eq!(
+            IpBase.get_endpoint_with_parameters(&None, &target, &parameters),
+            "https://api.ipbase.com/v2/info?ip=1.1.1.1&language=de"
+        );
+        assert_eq!(
+            IpBase.get_endpoint_with_parameters(&None, &None, &parameters),
+            "https://api.ipbase.com/v2/info?language=de"
+        );
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let input = r#"
+{
+    "ip": "1.1.1.1",
+    "country_code": "AU",
+    "country_name": "Australia",
+    "region_code": "",
+    "region_name": "",
+    "city": "",
+    "zip_code": "",
+    "time_zone": "Australia/Sydney",
+    "latitude": -33.494,
+    "longitude": 143.2104,
+    "metro_code": 0
+}
+"#;
+        let lookup = IpBase.parse_reply(input.to_string()).unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
             "IP address not matching"
         );
+        assert_eq!(lookup.country_code, Some("AU".to_string()));
+        assert_eq!(lookup.region, None, "Empty region should be dropped");
+
+        let lookup = IpBase.parse_reply(TEST_INPUT.to_string()).unwrap();
+        assert_eq!(lookup.country_code, Some("US".to_string()));
+
+        let error = IpBase
+            .parse_reply(r#"{"data": {}}"#.to_string())
+            .unwrap_err();
+        assert!(
+            matches!(error, crate::lookup::error::LookupError::SerdeError(_)),
+            "Error of the current schema expected, got {:?}",
+            error
+        );
     }
 }
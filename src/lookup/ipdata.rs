@@ -1,12 +1,16 @@
 //! <https://ipdata.co> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{
+    append_language, check_error_body, error_field, parse_response_ip, Parameters,
+    ProviderResponse, Result,
+};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
+    response::ConnectionType,
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://docs.ipdata.co/docs>
 #[derive(Serialize, Deserialize, Debug)]
@@ -76,13 +80,9 @@ struct Carrier {
 }
 
 impl ProviderResponse<IpDataResponse> for IpDataResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpData,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpData);
         response.continent = self.continent_name;
         response.country = self.country_name;
         response.country_code = self.country_code;
@@ -97,12 +97,17 @@ impl ProviderResponse<IpDataResponse> for IpDataResponse {
         if let Some(asn) = self.asn {
             response.asn_org = asn.name;
             response.asn = asn.asn;
+            response.network = asn.route;
+            response.connection_type = asn
+                .service_type
+                .filter(|t| !t.is_empty())
+                .map(|t| ConnectionType::from(t.as_str()));
         }
         if let Some(threat) = self.threat {
             response.is_proxy = threat.is_proxy;
         }
 
-        response
+        Ok(response)
     }
 }
 
@@ -122,18 +127,43 @@ impl Provider for IpData {
         format!("https://api.ipdata.co/{}{}", target, key)
     }
 
+    fn get_endpoint_with_parameters(
+        &self,
+        key: &Option<String>,
+        target: &Option<IpAddr>,
+        parameters: &Parameters,
+    ) -> String {
+        append_language(self.get_endpoint(key, target), "lang", parameters)
+    }
+
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::IpData, &json, |reply| {
+            let message = error_field(reply, "message").filter(|_| reply.get("ip").is_none())?;
+            Some((None, message))
+        })?;
         let response = IpDataResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::IpData
     }
 
+    fn requires_key(&self) -> bool {
+        true
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(1_500))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    fn supports_etag(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -242,15 +272,29 @@ mod tests {
         assert!(response.is_ok(), "Failed parsing response {:#?}", response);
     }
 
+    #[test]
+    fn test_endpoint_language() {
+        let parameters = Parameters::default().with_language("de");
+        assert_eq!(
+            IpData.get_endpoint_with_parameters(&Some("abc".to_string()), &None, &parameters),
+            "https://api.ipdata.co/?api-key=abc&lang=de"
+        );
+    }
+
     #[test]
     fn test_parse() {
         let response = IpDataResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
             "IP address not matching"
         );
+        assert_eq!(
+            lookup.connection_type,
+            Some(ConnectionType::Hosting),
+            "Connection type not matching"
+        );
     }
 }
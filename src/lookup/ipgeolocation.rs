@@ -1,12 +1,16 @@
 //! <https://ipgeolocation.io> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{
+    check_error_body, error_field, parse_response_ip, ParseWarning, ProviderResponse, Result,
+};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
+    response::ConnectionType,
     LookupResponse,
 };
+use log::warn;
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://ipgeolocation.io/documentation>
 #[derive(Serialize, Deserialize, Debug)]
@@ -20,6 +24,7 @@ pub struct IpGeolocationResponse {
     country_name: Option<String>,
     state_prov: Option<String>,
     state_code: Option<String>,
+    district: Option<String>,
     city: Option<String>,
     zipcode: Option<String>,
     longitude: Option<String>,
@@ -28,6 +33,7 @@ pub struct IpGeolocationResponse {
     connection_type: Option<String>,
     organization: Option<String>,
     isp: Option<String>,
+    asn: Option<String>,
     time_zone: Option<Timezone>,
 }
 
@@ -37,33 +43,46 @@ struct Timezone {
 }
 
 impl ProviderResponse<IpGeolocationResponse> for IpGeolocationResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpGeolocation,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        Ok(self.into_response_with_warnings()?.0)
+    }
+
+    fn into_response_with_warnings(self) -> Result<(LookupResponse, Vec<ParseWarning>)> {
+        let mut warnings = Vec::new();
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpGeolocation);
         response.continent = self.continent_name;
         response.country = self.country_name;
         response.country_code = self.country_code2;
         response.region = self.state_prov;
+        response.district = self.district.filter(|d| !d.is_empty());
         response.postal_code = self.zipcode;
         response.city = self.city;
-        if let Some(lat) = self.latitude {
+        if let Some(lat) = self.latitude.filter(|lat| !lat.is_empty()) {
             response.latitude = lat.parse().ok();
+            if response.latitude.is_none() {
+                warnings.push(ParseWarning::new("latitude", &lat));
+            }
         }
-        if let Some(lon) = self.longitude {
+        if let Some(lon) = self.longitude.filter(|lon| !lon.is_empty()) {
             response.longitude = lon.parse().ok();
+            if response.longitude.is_none() {
+                warnings.push(ParseWarning::new("longitude", &lon));
+            }
         }
         if let Some(timezone) = self.time_zone {
             response.time_zone = timezone.name;
         }
         response.hostname = self.hostname;
         response.asn_org = self.organization;
-        response.asn = self.isp;
-
-        response
+        response.asn = self.asn;
+        response.isp = self.isp;
+        response.connection_type = self
+            .connection_type
+            .filter(|t| !t.is_empty())
+            .map(|t| ConnectionType::from(t.as_str()));
+
+        Ok((response, warnings))
     }
 }
 
@@ -84,14 +103,28 @@ impl Provider for IpGeolocation {
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
-        let response = IpGeolocationResponse::parse(json)?;
-        Ok(response.into_response())
+        check_error_body(LookupProvider::IpGeolocation, &json, |reply| {
+            let message = error_field(reply, "message").filter(|_| reply.get("ip").is_none())?;
+            Some((None, message))
+        })?;
+        let (response, warnings) =
+            IpGeolocationResponse::parse(json)?.into_response_with_warnings()?;
+        warnings.iter().for_each(|warning| warn!("{}", warning));
+        Ok(response)
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::IpGeolocation
     }
 
+    fn requires_key(&self) -> bool {
+        true
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(1_000))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
@@ -181,11 +214,37 @@ mod tests {
     fn test_parse() {
         let response = IpGeolocationResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "8.8.8.8", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "8.8.8.8".parse::<IpAddr>().unwrap(),
             "IP address not matching"
         );
+        assert_eq!(lookup.connection_type, None, "Empty connection type");
+        assert_eq!(
+            lookup.district,
+            Some("Santa Clara".to_string()),
+            "District not matching"
+        );
+        assert_eq!(lookup.asn, Some("AS15169".to_string()), "ASN not matching");
+        assert_eq!(
+            lookup.isp,
+            Some("Google LLC".to_string()),
+            "ISP not matching"
+        );
+    }
+
+    #[test]
+    fn test_parse_warnings() {
+        let input = TEST_INPUT.replace(r#""latitude": "37.42240""#, r#""latitude": "north""#);
+        let response = IpGeolocationResponse::parse(input).unwrap();
+        let (lookup, warnings) = response.into_response_with_warnings().unwrap();
+        assert_eq!(lookup.latitude, None, "Latitude should be dropped");
+        assert_eq!(lookup.longitude, Some(-122.08421), "Longitude not matching");
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::new("latitude", "north")],
+            "Warnings not matching"
+        );
     }
 }
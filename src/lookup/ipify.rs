@@ -1,12 +1,12 @@
 //! <https://ipify.org> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{parse_response_ip, ProviderResponse, Result};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://www.ipify.org>
 #[derive(Serialize, Deserialize, Debug)]
@@ -15,17 +15,17 @@ pub struct IpifyResponse {
 }
 
 impl ProviderResponse<IpifyResponse> for IpifyResponse {
-    fn into_response(self) -> LookupResponse {
-        LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
+    fn into_response(self) -> Result<LookupResponse> {
+        Ok(LookupResponse::new(
+            parse_response_ip(&self.ip)?,
             LookupProvider::Ipify,
-        )
+        ))
     }
 }
 
 /// Ipify lookup provider
+///
+/// Uses the dual stack endpoint `api64.ipify.org`, which answers over IPv6 when available and IPv4 otherwise.
 pub struct Ipify;
 
 impl Provider for Ipify {
@@ -35,12 +35,70 @@ impl Provider for Ipify {
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
         let response = IpifyResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::Ipify
     }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Unlimited)
+    }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
+}
+
+/// Ipify lookup provider using the IPv4 only endpoint `api.ipify.org`
+pub struct IpifyV4;
+
+impl Provider for IpifyV4 {
+    fn get_endpoint(&self, _key: &Option<String>, _target: &Option<IpAddr>) -> String {
+        "https://api.ipify.org/?format=json".to_string()
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        let mut response = IpifyResponse::parse(json)?.into_response()?;
+        response.provider = LookupProvider::IpifyV4;
+        Ok(response)
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::IpifyV4
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Unlimited)
+    }
+}
+
+/// Ipify lookup provider using the IPv6 only endpoint `api6.ipify.org`
+pub struct IpifyV6;
+
+impl Provider for IpifyV6 {
+    fn get_endpoint(&self, _key: &Option<String>, _target: &Option<IpAddr>) -> String {
+        "https://api6.ipify.org/?format=json".to_string()
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        let mut response = IpifyResponse::parse(json)?.into_response()?;
+        response.provider = LookupProvider::IpifyV6;
+        Ok(response)
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::IpifyV6
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Unlimited)
+    }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -63,11 +121,29 @@ mod tests {
         assert!(response.is_ok(), "Failed parsing response {:#?}", response);
     }
 
+    #[test]
+    fn test_endpoints() {
+        assert!(Ipify.get_endpoint(&None, &None).contains("api64.ipify.org"));
+        assert!(IpifyV4.get_endpoint(&None, &None).contains("api.ipify.org"));
+        assert!(IpifyV6
+            .get_endpoint(&None, &None)
+            .contains("api6.ipify.org"));
+    }
+
+    #[test]
+    fn test_parse_family() {
+        let response = IpifyV6
+            .parse_reply(r#"{"ip": "2606:4700:4700::1111"}"#.to_string())
+            .unwrap();
+        assert!(response.ip.is_ipv6(), "IP address should be IPv6");
+        assert_eq!(response.provider, LookupProvider::IpifyV6);
+    }
+
     #[test]
     fn test_parse() {
         let response = IpifyResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<std::net::IpAddr>().unwrap(),
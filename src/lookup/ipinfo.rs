@@ -1,12 +1,20 @@
 //! <https://ipinfo.io> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{
+    client::{Client, RequestBuilder},
+    parse_response_ip, with_json_body, ParseWarning, ProviderResponse, Result,
+};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
+    response::ConnectionType,
     LookupResponse,
 };
+use log::warn;
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::{collections::BTreeMap, net::IpAddr};
+
+/// Maximum number of addresses accepted by the batch endpoint
+pub const BULK_LIMIT: usize = 1000;
 
 /// <https://ipinfo.io/json>
 #[derive(Serialize, Deserialize, Debug)]
@@ -21,10 +29,57 @@ pub struct IpInfoResponse {
     postal: Option<String>,
     timezone: Option<String>,
     readme: Option<String>,
+    asn: Option<Asn>,
+    company: Option<Company>,
+    privacy: Option<Privacy>,
+    carrier: Option<Carrier>,
+}
+
+/// Available on paid plans
+#[derive(Serialize, Deserialize, Debug)]
+struct Asn {
+    asn: Option<String>,
+    name: Option<String>,
+    domain: Option<String>,
+    route: Option<String>,
+    #[serde(rename = "type")]
+    asn_type: Option<String>,
+}
+
+/// Available on paid plans
+#[derive(Serialize, Deserialize, Debug)]
+struct Company {
+    name: Option<String>,
+    domain: Option<String>,
+    #[serde(rename = "type")]
+    company_type: Option<String>,
+}
+
+/// Available on paid plans
+#[derive(Serialize, Deserialize, Debug)]
+struct Privacy {
+    vpn: Option<bool>,
+    proxy: Option<bool>,
+    tor: Option<bool>,
+    relay: Option<bool>,
+    hosting: Option<bool>,
+}
+
+/// Available on paid plans, only present for mobile networks
+#[derive(Serialize, Deserialize, Debug)]
+struct Carrier {
+    name: Option<String>,
+    mcc: Option<String>,
+    mnc: Option<String>,
 }
 
 impl ProviderResponse<IpInfoResponse> for IpInfoResponse {
-    fn into_response(self) -> LookupResponse {
+    fn into_response(self) -> Result<LookupResponse> {
+        Ok(self.into_response_with_warnings()?.0)
+    }
+
+    fn into_response_with_warnings(self) -> Result<(LookupResponse, Vec<ParseWarning>)> {
+        let mut warnings = Vec::new();
         let mut latitude = None;
         let mut longitude = None;
 
@@ -32,17 +87,16 @@ impl ProviderResponse<IpInfoResponse> for IpInfoResponse {
         if let Some(loc) = self.loc {
             let coords: Vec<&str> = loc.split(',').collect();
             if coords.len() == 2 {
-                latitude = coords[0].parse().ok();
-                longitude = coords[1].parse().ok();
+                latitude = coords[0].trim().parse().ok();
+                longitude = coords[1].trim().parse().ok();
+            }
+            if latitude.is_none() || longitude.is_none() {
+                warnings.push(ParseWarning::new("loc", &loc));
             }
         }
 
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpInfo,
-        );
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpInfo);
         response.country = self.country.clone();
         response.country_code = self.country;
         response.region = self.region;
@@ -51,15 +105,64 @@ impl ProviderResponse<IpInfoResponse> for IpInfoResponse {
         response.latitude = latitude;
         response.longitude = longitude;
         response.time_zone = self.timezone;
-        response.asn_org = self.org.clone();
-        response.asn = self.org;
-        response
+        response.hostname = self.hostname;
+        if let Some(org) = self.org {
+            set_org(&mut response, org);
+        }
+        if let Some(asn) = self.asn {
+            response.asn = asn.asn.or(response.asn);
+            response.asn_org = asn.name.or(response.asn_org);
+            response.network = asn.route;
+            response.connection_type = asn
+                .asn_type
+                .filter(|t| !t.is_empty())
+                .map(|t| ConnectionType::from(t.as_str()));
+        }
+        if let Some(company) = self.company {
+            response.isp = company.name;
+        }
+        if let Some(privacy) = self.privacy {
+            response.is_proxy = match (privacy.vpn, privacy.proxy, privacy.tor) {
+                (None, None, None) => None,
+                (vpn, proxy, tor) => {
+                    Some(vpn.unwrap_or(false) || proxy.unwrap_or(false) || tor.unwrap_or(false))
+                }
+            };
+        }
+        if self.carrier.is_some() {
+            response.is_mobile = Some(true);
+        }
+        Ok((response, warnings))
+    }
+}
+
+/// Sets the ASN and its organization from an org reported as "AS10507 Sprint Personal Communications Systems"
+fn set_org(response: &mut LookupResponse, org: String) {
+    match org.split_once(' ') {
+        Some((asn, name)) if asn.starts_with("AS") => {
+            response.asn = Some(asn.to_string());
+            response.asn_org = Some(name.to_string());
+        }
+        _ => response.asn_org = Some(org),
     }
 }
 
 /// IpInfo lookup provider
 pub struct IpInfo;
 
+impl IpInfo {
+    /// Returns the batch API endpoint
+    ///
+    /// The batch endpoint accepts up to [`BULK_LIMIT`] addresses per request and requires a token.
+    pub fn get_bulk_endpoint(&self, key: &Option<String>) -> String {
+        let key = match key {
+            Some(k) => format!("?token={}", k),
+            None => "".to_string(),
+        };
+        format!("https://ipinfo.io/batch{}", key)
+    }
+}
+
 impl Provider for IpInfo {
     fn get_endpoint(&self, key: &Option<String>, target: &Option<IpAddr>) -> String {
         let key = match key {
@@ -74,17 +177,160 @@ impl Provider for IpInfo {
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
-        let response = IpInfoResponse::parse(json)?;
-        Ok(response.into_response())
+        let (response, warnings) = IpInfoResponse::parse(json)?.into_response_with_warnings()?;
+        warnings.iter().for_each(|warning| warn!("{}", warning));
+        Ok(response)
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::IpInfo
     }
 
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(1_666))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
+
+    fn supports_etag(&self) -> bool {
+        true
+    }
+
+    fn supports_asn_lookup(&self) -> bool {
+        true
+    }
+
+    /// Returns the `/org` endpoint, answering with the org as plain text
+    ///
+    /// The `/org` endpoint doesn't echo the address, lookups of the current public address use the
+    /// regular endpoint, which reports it.
+    fn get_asn_endpoint(&self, key: &Option<String>, target: &Option<IpAddr>) -> String {
+        match target {
+            Some(_) => self.get_endpoint(key, target).replacen("/json", "/org", 1),
+            None => self.get_endpoint(key, target),
+        }
+    }
+
+    /// The `/org` endpoint doesn't echo the address, it is left unspecified for the lookup to fill
+    /// in the target
+    fn parse_asn_reply(&self, reply: String) -> Result<LookupResponse> {
+        let org = reply.trim();
+        if org.is_empty() || org.starts_with('{') {
+            return self.parse_reply(reply);
+        }
+        let mut response = LookupResponse::new(
+            IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+            LookupProvider::IpInfo,
+        );
+        set_org(&mut response, org.to_string());
+        Ok(response)
+    }
+
+    fn bulk_limit(&self) -> Option<usize> {
+        Some(BULK_LIMIT)
+    }
+
+    /// Returns a POST request client for the batch API endpoint
+    fn get_bulk_client(
+        &self,
+        client: &Client,
+        key: Option<String>,
+        targets: &[IpAddr],
+    ) -> RequestBuilder {
+        let targets: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+        let request = client.post(self.get_bulk_endpoint(&key));
+        with_json_body(request, Some(targets.into()))
+    }
+
+    /// The batch endpoint answers with an object keyed by the requested address,
+    /// so the responses are ordered by address rather than by request order.
+    fn parse_bulk_reply(&self, json: String) -> Result<Vec<LookupResponse>> {
+        let responses: BTreeMap<String, IpInfoResponse> = serde_json::from_str(&json)?;
+        responses
+            .into_values()
+            .map(|response| response.into_response())
+            .collect()
+    }
+}
+
+/// <https://ipinfo.io/developers/lite-api>
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IpInfoLiteResponse {
+    ip: String,
+    asn: Option<String>,
+    as_name: Option<String>,
+    as_domain: Option<String>,
+    country_code: Option<String>,
+    country: Option<String>,
+    continent_code: Option<String>,
+    continent: Option<String>,
+}
+
+impl ProviderResponse<IpInfoLiteResponse> for IpInfoLiteResponse {
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpInfoLite);
+        response.continent = self.continent;
+        response.country = self.country;
+        response.country_code = self.country_code;
+        response.asn = self.asn;
+        response.asn_org = self.as_name;
+        Ok(response)
+    }
+}
+
+/// IpInfo Lite lookup provider
+///
+/// Free, unlimited country and ASN level lookups, requires a token.
+pub struct IpInfoLite;
+
+impl Provider for IpInfoLite {
+    fn get_endpoint(&self, key: &Option<String>, target: &Option<IpAddr>) -> String {
+        let key = match key {
+            Some(k) => format!("?token={}", k),
+            None => "".to_string(),
+        };
+        let target = match target.map(|t| t.to_string()) {
+            Some(t) => t,
+            None => "me".to_string(),
+        };
+        format!("https://api.ipinfo.io/lite/{}{}", target, key)
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        let response = IpInfoLiteResponse::parse(json)?;
+        response.into_response()
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::IpInfoLite
+    }
+
+    fn supports_asn_lookup(&self) -> bool {
+        true
+    }
+
+    fn requires_key(&self) -> bool {
+        true
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Unlimited)
+    }
+
+    fn supports_target_lookup(&self) -> bool {
+        true
+    }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -102,6 +348,38 @@ mod tests {
   "postal": "01101",
   "timezone": "America/New_York"
 }
+"#;
+    const TEST_INPUT_EXTENDED: &str = r#"
+{
+  "ip": "8.8.8.8",
+  "hostname": "dns.google",
+  "city": "Mountain View",
+  "region": "California",
+  "country": "US",
+  "loc": "37.4056,-122.0775",
+  "postal": "94043",
+  "timezone": "America/Los_Angeles",
+  "asn": {
+    "asn": "AS15169",
+    "name": "Google LLC",
+    "domain": "google.com",
+    "route": "8.8.8.0/24",
+    "type": "hosting"
+  },
+  "company": {
+    "name": "Google LLC",
+    "domain": "google.com",
+    "type": "hosting"
+  },
+  "privacy": {
+    "vpn": false,
+    "proxy": false,
+    "tor": false,
+    "relay": false,
+    "hosting": true,
+    "service": ""
+  }
+}
 "#;
 
     #[ignore]
@@ -120,11 +398,140 @@ mod tests {
     fn test_parse() {
         let response = IpInfoResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
             "IP address not matching"
         );
     }
+
+    #[test]
+    fn test_parse_org() {
+        let response = IpInfoResponse::parse(TEST_INPUT.to_string()).unwrap();
+        let lookup = response.into_response().unwrap();
+        assert_eq!(lookup.asn, Some("AS10507".to_string()), "ASN not matching");
+        assert_eq!(
+            lookup.asn_org,
+            Some("Sprint Personal Communications Systems".to_string()),
+            "ASN organization not matching"
+        );
+    }
+
+    #[test]
+    fn test_parse_asn() {
+        assert_eq!(
+            IpInfo.get_asn_endpoint(&Some("abc".to_string()), &"8.8.8.8".parse().ok()),
+            "https://ipinfo.io/8.8.8.8/org?token=abc"
+        );
+        assert_eq!(
+            IpInfo.get_asn_endpoint(&None, &None),
+            IpInfo.get_endpoint(&None, &None),
+            "Own address should be looked up on an endpoint reporting it"
+        );
+        let lookup = IpInfo
+            .parse_asn_reply("AS15169 Google LLC\n".to_string())
+            .unwrap();
+        assert!(lookup.ip.is_unspecified(), "Address should be unspecified");
+        assert_eq!(lookup.asn, Some("AS15169".to_string()), "ASN not matching");
+        assert_eq!(
+            lookup.asn_org,
+            Some("Google LLC".to_string()),
+            "ASN organization not matching"
+        );
+        assert_eq!(lookup.country, None, "Geolocation should not be set");
+    }
+
+    #[test]
+    fn test_parse_extended() {
+        let response = IpInfoResponse::parse(TEST_INPUT_EXTENDED.to_string()).unwrap();
+        let lookup = response.into_response().unwrap();
+        assert_eq!(lookup.asn, Some("AS15169".to_string()), "ASN not matching");
+        assert_eq!(
+            lookup.network,
+            Some("8.8.8.0/24".to_string()),
+            "Network not matching"
+        );
+        assert_eq!(
+            lookup.isp,
+            Some("Google LLC".to_string()),
+            "ISP not matching"
+        );
+        assert_eq!(lookup.is_proxy, Some(false), "Proxy flag not matching");
+        assert_eq!(lookup.is_mobile, None, "Mobile flag not matching");
+        assert_eq!(
+            lookup.connection_type,
+            Some(ConnectionType::Hosting),
+            "Connection type not matching"
+        );
+    }
+
+    #[test]
+    fn test_parse_bulk() {
+        let input = format!(
+            r#"{{"8.8.8.8": {}, "1.1.1.1": {}}}"#,
+            TEST_INPUT_EXTENDED, TEST_INPUT
+        );
+        let responses = IpInfo.parse_bulk_reply(input).unwrap();
+        assert_eq!(responses.len(), 2, "Bulk response length not matching");
+        assert_eq!(
+            responses[1].ip,
+            "8.8.8.8".parse::<IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+    }
+
+    #[test]
+    fn test_bulk_endpoint() {
+        assert_eq!(
+            IpInfo.get_bulk_endpoint(&Some("abc".to_string())),
+            "https://ipinfo.io/batch?token=abc"
+        );
+        let request = IpInfo
+            .get_bulk_client(
+                &super::super::client::shared(),
+                None,
+                &["1.1.1.1".parse().unwrap()],
+            )
+            .build()
+            .unwrap();
+        assert_eq!(request.method(), "POST");
+    }
+
+    #[test]
+    fn test_parse_lite() {
+        let input = r#"
+{
+  "ip": "8.8.8.8",
+  "asn": "AS15169",
+  "as_name": "Google LLC",
+  "as_domain": "google.com",
+  "country_code": "US",
+  "country": "United States",
+  "continent_code": "NA",
+  "continent": "North America"
+}
+"#;
+        let lookup = IpInfoLite.parse_reply(input.to_string()).unwrap();
+        assert_eq!(lookup.provider, LookupProvider::IpInfoLite);
+        assert_eq!(lookup.asn, Some("AS15169".to_string()), "ASN not matching");
+        assert_eq!(
+            IpInfoLite.get_endpoint(&Some("abc".to_string()), &None),
+            "https://api.ipinfo.io/lite/me?token=abc"
+        );
+    }
+
+    #[test]
+    fn test_parse_warnings() {
+        let input = TEST_INPUT.replace("42.1015,-72.5898", "unknown");
+        let response = IpInfoResponse::parse(input).unwrap();
+        let (lookup, warnings) = response.into_response_with_warnings().unwrap();
+        assert_eq!(lookup.latitude, None, "Latitude should be dropped");
+        assert_eq!(lookup.longitude, None, "Longitude should be dropped");
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::new("loc", "unknown")],
+            "Warnings not matching"
+        );
+    }
 }
@@ -1,12 +1,12 @@
 //! <https://ipleak.net> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{parse_response_ip, ProviderResponse, Result};
 use crate::{
     lookup::{LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://ipleak.net/>
 #[derive(Serialize, Deserialize, Debug)]
@@ -29,13 +29,9 @@ pub struct IpLeakResponse {
 }
 
 impl ProviderResponse<IpLeakResponse> for IpLeakResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpLeak,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpLeak);
         response.country = self.country_name;
         response.country_code = self.country_code;
         response.region = self.region_name;
@@ -45,12 +41,13 @@ impl ProviderResponse<IpLeakResponse> for IpLeakResponse {
         response.latitude = self.latitude;
         response.longitude = self.longitude;
         response.time_zone = self.time_zone;
-        response.asn_org = self.isp_name;
+        response.asn_org = self.isp_name.clone();
+        response.isp = self.isp_name;
         if let Some(asn) = self.as_number {
             response.asn = Some(asn.to_string());
         }
         response.hostname = self.reverse;
-        response
+        Ok(response)
     }
 }
 
@@ -68,7 +65,7 @@ impl Provider for IpLeak {
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
         let response = IpLeakResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
@@ -78,6 +75,10 @@ impl Provider for IpLeak {
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -127,7 +128,7 @@ mod tests {
     fn test_parse() {
         let response = IpLeakResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "8.8.8.8", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "8.8.8.8".parse::<IpAddr>().unwrap(),
@@ -1,12 +1,12 @@
 //! <https://iplocate.io> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{check_error_body, error_field, parse_response_ip, ProviderResponse, Result};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://iplocate.docs.apiary.io/>
 #[derive(Serialize, Deserialize, Debug)]
@@ -22,6 +22,7 @@ pub struct IpLocateIoResponse {
     time_zone: Option<String>,
     postal_code: Option<String>,
     subdivision: Option<String>,
+    subdivision2: Option<String>,
     org: Option<String>,
     asn: Option<String>,
     threat: Option<Threat>,
@@ -33,17 +34,14 @@ struct Threat {
 }
 
 impl ProviderResponse<IpLocateIoResponse> for IpLocateIoResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpLocateIo,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpLocateIo);
         response.country = self.country;
         response.continent = self.continent;
         response.country_code = self.country_code;
         response.region = self.subdivision;
+        response.district = self.subdivision2.filter(|d| !d.is_empty());
         response.postal_code = self.postal_code;
         response.city = self.city;
         response.latitude = self.latitude;
@@ -54,7 +52,7 @@ impl ProviderResponse<IpLocateIoResponse> for IpLocateIoResponse {
         if let Some(threat) = self.threat {
             response.is_proxy = threat.is_proxy;
         }
-        response
+        Ok(response)
     }
 }
 
@@ -75,17 +73,29 @@ impl Provider for IpLocateIo {
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::IpLocateIo, &json, |reply| {
+            Some((None, error_field(reply, "error")?))
+        })?;
         let response = IpLocateIoResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::IpLocateIo
     }
 
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(50))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    /// The regular reply is small and already carries the ASN
+    fn supports_asn_lookup(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -104,6 +114,7 @@ mod tests {
   "longitude": -122.0946,
   "postal_code": "95014",
   "subdivision": "California",
+  "subdivision2": "Santa Clara County",
   "time_zone": "America/Los_Angeles"
 }
 "#;
@@ -124,11 +135,16 @@ mod tests {
     fn test_parse() {
         let response = IpLocateIoResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<IpAddr>().unwrap(),
             "IP address not matching"
         );
+        assert_eq!(
+            lookup.district,
+            Some("Santa Clara County".to_string()),
+            "District not matching"
+        );
     }
 }
@@ -0,0 +1,128 @@
+//! <https://ip.sb> lookup provider
+
+use super::{parse_response_ip, ProviderResponse, Result};
+use crate::{
+    lookup::{LookupProvider, Provider},
+    LookupResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// <https://ip.sb/api>
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IpSbResponse {
+    ip: String,
+    country: Option<String>,
+    country_code: Option<String>,
+    continent_code: Option<String>,
+    region: Option<String>,
+    region_code: Option<String>,
+    city: Option<String>,
+    postal_code: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    timezone: Option<String>,
+    offset: Option<i64>,
+    asn: Option<i64>,
+    asn_organization: Option<String>,
+    organization: Option<String>,
+    isp: Option<String>,
+}
+
+impl ProviderResponse<IpSbResponse> for IpSbResponse {
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response = LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpSb);
+        response.country = self.country;
+        response.country_code = self.country_code;
+        response.region = self.region;
+        response.postal_code = self.postal_code;
+        response.city = self.city;
+        response.latitude = self.latitude;
+        response.longitude = self.longitude;
+        response.time_zone = self.timezone;
+        response.asn_org = self.asn_organization.or(self.organization);
+        response.isp = self.isp;
+        if let Some(number) = self.asn {
+            response.asn = Some(format!("AS{number}"));
+        }
+        Ok(response)
+    }
+}
+
+/// IpSb lookup provider
+pub struct IpSb;
+
+impl Provider for IpSb {
+    fn get_endpoint(&self, _key: &Option<String>, target: &Option<IpAddr>) -> String {
+        let target = target.map(|t| t.to_string()).unwrap_or_default();
+        format!("https://api.ip.sb/geoip/{}", target)
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        let response = IpSbResponse::parse(json)?;
+        response.into_response()
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::IpSb
+    }
+
+    fn supports_target_lookup(&self) -> bool {
+        true
+    }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const TEST_INPUT: &str = r#"
+{
+  "organization": "Cloudflare",
+  "longitude": 143.2104,
+  "timezone": "Australia/Sydney",
+  "isp": "Cloudflare",
+  "offset": 36000,
+  "asn": 13335,
+  "asn_organization": "CLOUDFLARENET",
+  "country": "Australia",
+  "ip": "1.1.1.1",
+  "latitude": -33.494,
+  "continent_code": "OC",
+  "country_code": "AU"
+}
+"#;
+
+    #[ignore]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_request() {
+        let service = Box::new(IpSb);
+        let result = service.get_client(None, None).send().await;
+        let result = super::super::handle_response(result).await.unwrap();
+        assert!(!result.is_empty(), "Result is empty");
+        println!("IpSb: {:#?}", result);
+        let response = IpSbResponse::parse(result);
+        assert!(response.is_ok(), "Failed parsing response {:#?}", response);
+    }
+
+    #[test]
+    fn test_parse() {
+        let response = IpSbResponse::parse(TEST_INPUT.to_string()).unwrap();
+        assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
+        let lookup = response.into_response().unwrap();
+        assert_eq!(
+            lookup.ip,
+            "1.1.1.1".parse::<IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+        assert_eq!(lookup.asn, Some("AS13335".to_string()), "ASN not matching");
+        assert_eq!(
+            lookup.asn_org,
+            Some("CLOUDFLARENET".to_string()),
+            "ASN organization not matching"
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! <https://ipstack.com> lookup provider
+
+use super::{
+    check_error_body,
+    client::{Client, RequestBuilder},
+    error_field, parse_response_ip, ProviderResponse, Result,
+};
+use crate::{
+    lookup::{DailyQuota, LookupProvider, Provider},
+    response::SecurityInfo,
+    LookupResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Maximum number of addresses accepted by the bulk endpoint
+pub const BULK_LIMIT: usize = 50;
+
+/// <https://ipstack.com/documentation>
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IpStackResponse {
+    ip: String,
+    hostname: Option<String>,
+    #[serde(rename = "type")]
+    ip_type: Option<String>,
+    continent_code: Option<String>,
+    continent_name: Option<String>,
+    country_code: Option<String>,
+    country_name: Option<String>,
+    region_code: Option<String>,
+    region_name: Option<String>,
+    city: Option<String>,
+    zip: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    time_zone: Option<TimeZone>,
+    connection: Option<Connection>,
+    security: Option<Security>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TimeZone {
+    id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Connection {
+    asn: Option<i64>,
+    isp: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Security {
+    is_proxy: Option<bool>,
+    proxy_type: Option<String>,
+    is_tor: Option<bool>,
+}
+
+impl ProviderResponse<IpStackResponse> for IpStackResponse {
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpStack);
+        response.continent = self.continent_name;
+        response.country = self.country_name;
+        response.country_code = self.country_code;
+        response.region = self.region_name;
+        response.postal_code = self.zip;
+        response.city = self.city;
+        response.latitude = self.latitude;
+        response.longitude = self.longitude;
+        response.hostname = self.hostname;
+        if let Some(time_zone) = self.time_zone {
+            response.time_zone = time_zone.id;
+        }
+        if let Some(connection) = self.connection {
+            response.asn_org = connection.isp.clone();
+            response.isp = connection.isp;
+            if let Some(number) = connection.asn {
+                response.asn = Some(format!("AS{number}"));
+            }
+        }
+        if let Some(security) = self.security {
+            response.is_proxy = security.is_proxy;
+            response.security = Some(SecurityInfo {
+                is_tor: security.is_tor,
+                ..Default::default()
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+/// IpStack lookup provider
+pub struct IpStack;
+
+/// HTTPS is only offered on the paid plans, it is used when the process-wide `force_https` is set
+fn scheme() -> &'static str {
+    if crate::config::config().force_https {
+        "https"
+    } else {
+        "http"
+    }
+}
+
+impl IpStack {
+    /// Returns the bulk API endpoint for a list of targets
+    ///
+    /// The bulk endpoint accepts up to [`BULK_LIMIT`] addresses per request and requires a paid plan.
+    pub fn get_bulk_endpoint(&self, key: &Option<String>, targets: &[IpAddr]) -> String {
+        let key = match key {
+            Some(k) => format!("?access_key={}", k),
+            None => "".to_string(),
+        };
+        let targets = targets
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        format!("{}://api.ipstack.com/{}{}", scheme(), targets, key)
+    }
+}
+
+impl Provider for IpStack {
+    fn get_endpoint(&self, key: &Option<String>, target: &Option<IpAddr>) -> String {
+        let key = match key {
+            Some(k) => format!("?access_key={}", k),
+            None => "".to_string(),
+        };
+        let target = match target.map(|t| t.to_string()) {
+            Some(t) => t,
+            None => "check".to_string(),
+        };
+        format!("{}://api.ipstack.com/{}{}", scheme(), target, key)
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::IpStack, &json, |reply| {
+            let error = reply.get("error")?;
+            let message = error_field(error, "info")
+                .or(error_field(error, "type"))
+                .unwrap_or(error.to_string());
+            Some((error_field(error, "code"), message))
+        })?;
+        let response = IpStackResponse::parse(json)?;
+        response.into_response()
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::IpStack
+    }
+
+    fn requires_key(&self) -> bool {
+        true
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(3))
+    }
+
+    fn supports_target_lookup(&self) -> bool {
+        true
+    }
+
+    fn bulk_limit(&self) -> Option<usize> {
+        Some(BULK_LIMIT)
+    }
+
+    fn get_bulk_client(
+        &self,
+        client: &Client,
+        key: Option<String>,
+        targets: &[IpAddr],
+    ) -> RequestBuilder {
+        client.get(self.get_bulk_endpoint(&key, targets))
+    }
+
+    fn parse_bulk_reply(&self, json: String) -> Result<Vec<LookupResponse>> {
+        let responses: Vec<IpStackResponse> = serde_json::from_str(&json)?;
+        responses
+            .into_iter()
+            .map(|response| response.into_response())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::error::LookupError;
+    const TEST_INPUT: &str = r#"
+{
+  "ip": "134.201.250.155",
+  "hostname": "134.201.250.155",
+  "type": "ipv4",
+  "continent_code": "NA",
+  "continent_name": "North America",
+  "country_code": "US",
+  "country_name": "United States",
+  "region_code": "CA",
+  "region_name": "California",
+  "city": "Los Angeles",
+  "zip": "90013",
+  "latitude": 34.0453,
+  "longitude": -118.2413,
+  "location": {
+    "geoname_id": 5368361,
+    "capital": "Washington D.C.",
+    "languages": [
+      {
+        "code": "en",
+        "name": "English",
+        "native": "English"
+      }
+    ],
+    "country_flag": "https://assets.ipstack.com/images/assets/flags_svg/us.svg",
+    "country_flag_emoji": "🇺🇸",
+    "country_flag_emoji_unicode": "U+1F1FA U+1F1F8",
+    "calling_code": "1",
+    "is_eu": false
+  },
+  "time_zone": {
+    "id": "America/Los_Angeles",
+    "current_time": "2018-03-29T07:35:08-07:00",
+    "gmt_offset": -25200,
+    "code": "PDT",
+    "is_daylight_saving": true
+  },
+  "currency": {
+    "code": "USD",
+    "name": "US Dollar",
+    "plural": "US dollars",
+    "symbol": "$",
+    "symbol_native": "$"
+  },
+  "connection": {
+    "asn": 25876,
+    "isp": "Los Angeles Department of Water & Power"
+  },
+  "security": {
+    "is_proxy": false,
+    "proxy_type": null,
+    "is_crawler": false,
+    "crawler_name": null,
+    "crawler_type": null,
+    "is_tor": false,
+    "threat_level": "low",
+    "threat_types": null
+  }
+}
+"#;
+
+    #[ignore]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_request() {
+        use std::env;
+        let key = env::var("IPSTACK_APIKEY").ok();
+        assert!(key.is_some(), "Missing APIKEY");
+
+        let service = Box::new(IpStack);
+        let result = service.get_client(key, None).send().await;
+        let result = super::super::handle_response(result).await.unwrap();
+        assert!(!result.is_empty(), "Result is empty");
+        println!("IpStack: {:#?}", result);
+
+        let response = IpStackResponse::parse(result);
+        assert!(response.is_ok(), "Failed parsing response {:#?}", response);
+    }
+
+    #[test]
+    fn test_parse() {
+        let response = IpStackResponse::parse(TEST_INPUT.to_string()).unwrap();
+        assert_eq!(response.ip, "134.201.250.155", "IP address not matching");
+        let lookup = response.into_response().unwrap();
+        assert_eq!(
+            lookup.ip,
+            "134.201.250.155".parse::<IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+        assert_eq!(lookup.asn, Some("AS25876".to_string()), "ASN not matching");
+    }
+
+    #[test]
+    fn test_parse_bulk() {
+        let input = format!("[{},{}]", TEST_INPUT, TEST_INPUT.replace("134.201", "8.8"));
+        let responses = IpStack.parse_bulk_reply(input).unwrap();
+        assert_eq!(responses.len(), 2, "Bulk response length not matching");
+        assert_eq!(
+            responses[1].ip,
+            "8.8.250.155".parse::<IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+    }
+
+    #[test]
+    fn test_bulk_endpoint() {
+        let targets = ["1.1.1.1".parse().unwrap(), "8.8.8.8".parse().unwrap()];
+        assert_eq!(
+            IpStack.get_bulk_endpoint(&Some("abc".to_string()), &targets),
+            "http://api.ipstack.com/1.1.1.1,8.8.8.8?access_key=abc"
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let input = r#"{"success": false, "error": {"code": 101, "type": "invalid_access_key", "info": "You have not supplied a valid API Access Key."}}"#;
+        let response = IpStack.parse_reply(input.to_string());
+        assert!(
+            matches!(
+                response,
+                Err(LookupError::ProviderError { code: Some(code), .. }) if code == "101"
+            ),
+            "Error body should be detected"
+        );
+    }
+}
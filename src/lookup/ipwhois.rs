@@ -1,12 +1,15 @@
 //! <https://ipwhois.io> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{
+    append_language, check_error_body, error_field, parse_response_ip, Parameters,
+    ProviderResponse, Result,
+};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://ipwhois.io/documentation>
 #[derive(Serialize, Deserialize, Debug)]
@@ -40,13 +43,9 @@ struct Timezone {
 }
 
 impl ProviderResponse<IpWhoIsResponse> for IpWhoIsResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::IpWhoIs,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::IpWhoIs);
         response.continent = self.continent;
         response.region = self.region;
         response.country = self.country;
@@ -60,11 +59,12 @@ impl ProviderResponse<IpWhoIsResponse> for IpWhoIsResponse {
         }
         if let Some(connection) = self.connection {
             response.asn_org = connection.org;
+            response.isp = connection.isp;
             if let Some(asn) = connection.asn {
                 response.asn = Some(format!("{asn}"));
             }
         }
-        response
+        Ok(response)
     }
 }
 
@@ -77,23 +77,47 @@ impl Provider for IpWhoIs {
         format!("https://ipwho.is/{}", target)
     }
 
+    fn get_endpoint_with_parameters(
+        &self,
+        key: &Option<String>,
+        target: &Option<IpAddr>,
+        parameters: &Parameters,
+    ) -> String {
+        append_language(self.get_endpoint(key, target), "lang", parameters)
+    }
+
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        check_error_body(LookupProvider::IpWhoIs, &json, |reply| {
+            (reply["success"] == false).then(|| {
+                let message = error_field(reply, "message");
+                (None, message.unwrap_or("Request failed".to_string()))
+            })
+        })?;
         let response = IpWhoIsResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::IpWhoIs
     }
 
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Requests(333))
+    }
+
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lookup::error::LookupError;
     const TEST_INPUT: &str = r#"
 {
   "ip": "1.1.1.1",
@@ -151,11 +175,36 @@ mod tests {
     fn test_parse() {
         let response = IpWhoIsResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<std::net::IpAddr>().unwrap(),
             "IP address not matching"
         );
+
+        let input = TEST_INPUT.replace(r#""ip": "1.1.1.1""#, r#""ip": "1.1.1""#);
+        let response = IpWhoIsResponse::parse(input).unwrap();
+        assert!(
+            matches!(
+                response.into_response(),
+                Err(LookupError::InvalidResponseIp(ip)) if ip == "1.1.1"
+            ),
+            "Invalid address should be refused"
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let input = r#"{"ip": "1.1.1", "success": false, "message": "Invalid IP address"}"#;
+        let response = IpWhoIs.parse_reply(input.to_string());
+        assert!(
+            matches!(
+                response,
+                Err(LookupError::ProviderError { provider: LookupProvider::IpWhoIs, message, .. })
+                    if message == "Invalid IP address"
+            ),
+            "Error body should be detected"
+        );
+        assert!(IpWhoIs.parse_reply(TEST_INPUT.to_string()).is_ok());
     }
 }
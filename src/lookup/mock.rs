@@ -1,16 +1,133 @@
 //! Mock lookup provider
+//!
+//! The mock answers without touching the network. `LookupProvider::Mock` takes the address to
+//! return, or a comma separated list of [`MockReply`]s consumed one per lookup, the last reply
+//! being repeated:
+//!
+//! ```rust
+//! use public_ip_address::lookup::{mock::MockReply, LookupProvider};
+//!
+//! // first lookup fails with a 429, the following ones return a complete response
+//! let provider = LookupProvider::Mock("429,full:1.1.1.1".to_string());
+//! let replies = [MockReply::TooManyRequests, MockReply::Full("1.1.1.1".to_string())];
+//! assert_eq!(provider, LookupProvider::mock(&replies));
+//! ```
 
-use super::Result;
+use super::{error::LookupError, parse_response_ip, Result};
 use crate::{
     lookup::{LookupProvider, Provider},
     LookupResponse,
 };
-use std::net::IpAddr;
+use std::{
+    fmt,
+    net::IpAddr,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Reply of the mock provider to a single lookup
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MockReply {
+    /// Response with the address only, written as the address
+    Ip(String),
+    /// Response with the address and a complete geolocation, written as `full:<address>`
+    Full(String),
+    /// Fails with `LookupError::TooManyRequests`, written as `429`
+    TooManyRequests,
+    /// Fails with `LookupError::Timeout`, written as `timeout`
+    Timeout,
+    /// Answers with a reply that isn't valid JSON, written as `malformed`
+    Malformed,
+}
+
+impl fmt::Display for MockReply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MockReply::Ip(ip) => write!(f, "{}", ip),
+            MockReply::Full(ip) => write!(f, "full:{}", ip),
+            MockReply::TooManyRequests => write!(f, "429"),
+            MockReply::Timeout => write!(f, "timeout"),
+            MockReply::Malformed => write!(f, "malformed"),
+        }
+    }
+}
+
+impl FromStr for MockReply {
+    type Err = LookupError;
+
+    /// Parses a reply, anything that isn't a known keyword is taken as the address to return
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        Ok(match s.to_lowercase().as_str() {
+            "429" => MockReply::TooManyRequests,
+            "timeout" => MockReply::Timeout,
+            "malformed" => MockReply::Malformed,
+            lower if lower.starts_with("full:") => MockReply::Full(s[5..].trim().to_string()),
+            _ => MockReply::Ip(s.to_string()),
+        })
+    }
+}
+
+impl LookupProvider {
+    /// Returns a mock provider answering with the replies in order, the last one being repeated
+    pub fn mock(replies: &[MockReply]) -> Self {
+        let replies: Vec<String> = replies.iter().map(MockReply::to_string).collect();
+        LookupProvider::Mock(replies.join(","))
+    }
+}
+
+/// Returns a complete response located in Sydney, as returned by `MockReply::Full`
+pub(crate) fn full_response(ip: IpAddr, provider: LookupProvider) -> LookupResponse {
+    let mut response = LookupResponse::new(ip, provider);
+    response.continent = Some("Oceania".to_string());
+    response.country = Some("Australia".to_string());
+    response.country_code = Some("AU".to_string());
+    response.region = Some("New South Wales".to_string());
+    response.postal_code = Some("2000".to_string());
+    response.city = Some("Sydney".to_string());
+    response.latitude = Some(-33.8591);
+    response.longitude = Some(151.2006);
+    response.time_zone = Some("Australia/Sydney".to_string());
+    response.asn = Some("AS13335".to_string());
+    response.asn_org = Some("Cloudflare, Inc.".to_string());
+    response.isp = Some("Cloudflare".to_string());
+    response.hostname = Some("one.one.one.one".to_string());
+    response.is_proxy = Some(false);
+    response
+}
 
 /// Mock lookup provider
 pub struct Mock {
-    /// IP address to return
+    /// Address to return, or the replies of the mock, see [`MockReply`]
     pub ip: String,
+    calls: AtomicUsize,
+}
+
+impl Mock {
+    /// Creates a mock answering with the address or the replies
+    pub fn new(ip: String) -> Self {
+        Mock {
+            ip,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the serialized reply to the next lookup
+    fn reply(&self) -> Result<String> {
+        let replies: Vec<&str> = self.ip.split(',').collect();
+        let call = self.calls.fetch_add(1, Ordering::Relaxed);
+        let response = match replies[call.min(replies.len() - 1)].parse()? {
+            MockReply::Ip(ip) => LookupResponse::new(parse_response_ip(&ip)?, self.get_type()),
+            MockReply::Full(ip) => full_response(parse_response_ip(&ip)?, self.get_type()),
+            MockReply::TooManyRequests => {
+                return Err(LookupError::TooManyRequests("Mock rate limit".to_string()))
+            }
+            MockReply::Timeout => return Err(LookupError::Timeout),
+            MockReply::Malformed => return Ok(r#"{"ip": "1.1.1.1", "country": "#.to_string()),
+        };
+        Ok(serde_json::to_string(&response)?)
+    }
 }
 
 impl Provider for Mock {
@@ -18,11 +135,12 @@ impl Provider for Mock {
         "https://httpbin.org/status/200".to_string()
     }
 
-    fn parse_reply(&self, _json: String) -> Result<LookupResponse> {
-        Ok(LookupResponse::new(
-            self.ip.parse::<std::net::IpAddr>().unwrap(),
-            LookupProvider::Mock(self.ip.to_string()),
-        ))
+    fn local_reply(&self, _target: Option<IpAddr>) -> Option<Result<String>> {
+        Some(self.reply())
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        Ok(serde_json::from_str(&json)?)
     }
 
     fn get_type(&self) -> LookupProvider {
@@ -32,4 +150,69 @@ impl Provider for Mock {
     fn supports_target_lookup(&self) -> bool {
         true
     }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
+
+    fn supports_asn_lookup(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::LookupService;
+
+    #[test]
+    fn test_reply_conversions() {
+        for reply in [
+            MockReply::Ip("1.1.1.1".to_string()),
+            MockReply::Full("::1".to_string()),
+            MockReply::TooManyRequests,
+            MockReply::Timeout,
+            MockReply::Malformed,
+        ] {
+            assert_eq!(reply.to_string().parse::<MockReply>().unwrap(), reply);
+        }
+        let provider: LookupProvider = "Mock:timeout,full:1.1.1.1".parse().unwrap();
+        assert_eq!(
+            provider,
+            LookupProvider::mock(&[MockReply::Timeout, MockReply::Full("1.1.1.1".to_string())])
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_replies() {
+        let provider = LookupProvider::mock(&[
+            MockReply::TooManyRequests,
+            MockReply::Timeout,
+            MockReply::Malformed,
+            MockReply::Full("1.1.1.1".to_string()),
+        ]);
+        let service = LookupService::new(provider.clone(), None);
+        let response = service.lookup(None).await;
+        assert!(matches!(response, Err(LookupError::TooManyRequests(_))));
+        let response = service.lookup(None).await;
+        assert!(matches!(response, Err(LookupError::Timeout)));
+        let response = service.lookup(None).await;
+        assert!(
+            matches!(response, Err(LookupError::ParseError { .. })),
+            "Malformed reply should fail parsing {:?}",
+            response
+        );
+
+        for _ in 0..2 {
+            let response = service.lookup(None).await.unwrap();
+            assert_eq!(response.city, Some("Sydney".to_string()));
+            assert_eq!(response.asn, Some("AS13335".to_string()));
+            assert_eq!(response.provider, provider);
+        }
+
+        let service = LookupService::new(LookupProvider::Mock("8.8.8.8".to_string()), None);
+        let response = service.lookup(None).await.unwrap();
+        assert_eq!(response.ip, "8.8.8.8".parse::<IpAddr>().unwrap());
+        assert_eq!(response.city, None);
+    }
 }
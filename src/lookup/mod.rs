@@ -20,37 +20,110 @@
 //! }
 //! ```
 
-use crate::LookupResponse;
-use client::{Client, RequestBuilder, Response};
+use crate::{response::LookupMeta, time::Instant, LookupResponse};
+use client::{header, Method, RequestBuilder, Response, StatusCode};
 use error::{LookupError, Result};
-use reqwest::StatusCode;
-use serde::de::DeserializeOwned;
+use log::warn;
+use serde::de::{self, DeserializeOwned};
 use serde::{Deserialize, Serialize};
-use std::{fmt, net::IpAddr, str::FromStr};
+use std::{
+    collections::BTreeMap, env, fmt, net::IpAddr, str::FromStr, sync::OnceLock, time::Duration,
+};
 
-mod client;
+pub(crate) mod client;
 
+#[cfg(feature = "provider-abstractapi")]
 pub mod abstractapi;
+#[cfg(feature = "provider-bgpview")]
+pub mod bgpview;
+pub mod bogon;
+pub mod chain;
+pub mod custom;
+mod diagnostics;
+pub mod disabled;
 pub mod error;
+pub mod family;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+#[cfg(feature = "provider-freeipapi")]
 pub mod freeipapi;
+#[cfg(feature = "local-discovery")]
+pub mod gateway;
+#[cfg(feature = "provider-getjsonip")]
 pub mod getjsonip;
+#[cfg(feature = "provider-ifconfig")]
 pub mod ifconfig;
+#[cfg(feature = "provider-ip2location")]
 pub mod ip2location;
+#[cfg(feature = "provider-ip2proxy")]
+pub mod ip2proxy;
+#[cfg(feature = "provider-ipapico")]
 pub mod ipapico;
+#[cfg(feature = "provider-ipapicom")]
 pub mod ipapicom;
+#[cfg(feature = "provider-ipapiio")]
 pub mod ipapiio;
+#[cfg(feature = "provider-ipbase")]
 pub mod ipbase;
+#[cfg(feature = "provider-ipdata")]
 pub mod ipdata;
+#[cfg(feature = "provider-ipgeolocation")]
 pub mod ipgeolocation;
+#[cfg(any(
+    feature = "provider-ipify",
+    feature = "provider-ipifyv4",
+    feature = "provider-ipifyv6",
+))]
 pub mod ipify;
+#[cfg(any(feature = "provider-ipinfo", feature = "provider-ipinfolite",))]
 pub mod ipinfo;
+#[cfg(feature = "provider-ipleak")]
 pub mod ipleak;
+#[cfg(feature = "provider-iplocateio")]
 pub mod iplocateio;
+#[cfg(feature = "provider-ipsb")]
+pub mod ipsb;
+#[cfg(feature = "provider-ipstack")]
+pub mod ipstack;
+#[cfg(feature = "provider-ipwhois")]
 pub mod ipwhois;
 pub mod mock;
+#[cfg(feature = "provider-mullvad")]
 pub mod mullvad;
+#[cfg(feature = "provider-myip")]
 pub mod myip;
+#[cfg(feature = "provider-myipcom")]
 pub mod myipcom;
+#[cfg(feature = "cert-pinning")]
+pub mod pinning;
+#[cfg(any(
+    feature = "provider-ifconfigme",
+    feature = "provider-icanhazip",
+    feature = "provider-identme",
+    feature = "provider-awscheckip",
+    feature = "provider-akamai",
+))]
+pub mod plaintext;
+pub mod policy;
+pub mod redirect;
+#[cfg(feature = "provider-ripestat")]
+pub mod ripestat;
+#[cfg(feature = "provider-seeip")]
+pub mod seeip;
+#[cfg(all(not(feature = "blocking"), not(target_arch = "wasm32")))]
+pub mod sync;
+pub mod target;
+
+pub use chain::ProviderConfig;
+pub use family::AddressFamily;
+pub use policy::ProviderPolicy;
+#[cfg(all(not(feature = "blocking"), not(target_arch = "wasm32")))]
+pub use sync::LookupServiceBlocking;
+pub use target::LookupTarget;
+
+/// Lookup service with a synchronous interface, the `blocking` feature makes `LookupService` synchronous
+#[cfg(feature = "blocking")]
+pub type LookupServiceBlocking = LookupService;
 
 /// Provider trait to define the methods that a provider must implement
 pub trait Provider {
@@ -63,10 +136,75 @@ pub trait Provider {
 
     /// Returns a request client for the provider
     fn get_client(&self, key: Option<String>, target: Option<IpAddr>) -> RequestBuilder {
-        let client = Client::new().get(self.get_endpoint(&key, &target));
+        let client = client::shared().request(self.get_method(), self.get_endpoint(&key, &target));
+        let client = with_json_body(client, self.get_body(&key, &target));
         self.add_auth(client, &key)
     }
 
+    /// Returns the HTTP method of the lookup request
+    ///
+    /// Providers with a POST endpoint, or one using another method, override it together with
+    /// `get_body`. Defaults to GET.
+    fn get_method(&self) -> Method {
+        Method::GET
+    }
+
+    /// Returns the JSON body sent with the lookup request, `None` sends no body
+    fn get_body(
+        &self,
+        _key: &Option<String>,
+        _target: &Option<IpAddr>,
+    ) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// Returns the API endpoint for the provider with the lookup parameters applied
+    ///
+    /// Providers override it to map the language and their own options, by default the
+    /// parameters are ignored.
+    fn get_endpoint_with_parameters(
+        &self,
+        key: &Option<String>,
+        target: &Option<IpAddr>,
+        _parameters: &Parameters,
+    ) -> String {
+        self.get_endpoint(key, target)
+    }
+
+    /// Returns a request client for the provider with the lookup parameters applied
+    ///
+    /// The extra headers and query parameters are added after the authentication.
+    fn get_client_with_parameters(
+        &self,
+        key: Option<String>,
+        target: Option<IpAddr>,
+        parameters: &Parameters,
+    ) -> Result<RequestBuilder> {
+        let endpoint = self.get_endpoint_with_parameters(&key, &target, parameters);
+        let request = parameters.client()?.request(self.get_method(), endpoint);
+        let request = with_json_body(request, self.get_body(&key, &target));
+        let request = self.add_auth(request, &key);
+        Ok(parameters.apply(request))
+    }
+
+    /// Returns the reply without making a request, for providers answering locally
+    fn local_reply(&self, _target: Option<IpAddr>) -> Option<Result<String>> {
+        None
+    }
+
+    /// Check if [`Provider::local_reply`] does blocking I/O
+    ///
+    /// Async builds run such local replies on a blocking thread instead of the executor of the
+    /// caller.
+    fn local_reply_blocks(&self) -> bool {
+        false
+    }
+
+    /// Validates the provider before a request is made
+    fn prepare(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Add authentication header to the request
     fn add_auth(&self, request: RequestBuilder, _key: &Option<String>) -> RequestBuilder {
         request
@@ -76,6 +214,79 @@ pub trait Provider {
     fn supports_target_lookup(&self) -> bool {
         false
     }
+
+    /// Check if the provider can only look up a given target, not the caller's own address
+    fn requires_target(&self) -> bool {
+        false
+    }
+
+    /// Check if the provider refuses requests without an API key
+    fn requires_key(&self) -> bool {
+        false
+    }
+
+    /// Check if the provider can return only the network data of an address, see `get_asn_endpoint`
+    fn supports_asn_lookup(&self) -> bool {
+        false
+    }
+
+    /// Returns the API endpoint returning only the ASN, prefix and organization of the address
+    ///
+    /// Providers supporting ASN lookups override it with a smaller reply when they offer one, by
+    /// default the regular endpoint is used.
+    fn get_asn_endpoint(&self, key: &Option<String>, target: &Option<IpAddr>) -> String {
+        self.get_endpoint(key, target)
+    }
+
+    /// Parses the reply of the ASN endpoint
+    fn parse_asn_reply(&self, reply: String) -> Result<LookupResponse> {
+        self.parse_reply(reply)
+    }
+
+    /// Check if the provider endpoint is reachable over IPv6
+    fn supports_ipv6(&self) -> bool {
+        false
+    }
+
+    /// Check if the provider answers conditional requests with `304 Not Modified`
+    fn supports_etag(&self) -> bool {
+        false
+    }
+
+    /// Returns the approximate number of free requests per day, if known
+    ///
+    /// Providers without a limit return [`DailyQuota::Unlimited`], providers with an unknown limit
+    /// return `None`.
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        None
+    }
+
+    /// Returns the maximum number of targets accepted by the bulk endpoint
+    ///
+    /// Providers without a bulk endpoint return `None` and are queried one target at a time.
+    fn bulk_limit(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns a request for the bulk endpoint, sent through the given client
+    ///
+    /// The client is bound like the client of the regular requests, see [`Parameters::client`].
+    fn get_bulk_client(
+        &self,
+        client: &client::Client,
+        key: Option<String>,
+        targets: &[IpAddr],
+    ) -> RequestBuilder {
+        let target = targets.first().copied();
+        let request = client.request(self.get_method(), self.get_endpoint(&key, &target));
+        let request = with_json_body(request, self.get_body(&key, &target));
+        self.add_auth(request, &key)
+    }
+
+    /// Parses the response from the bulk endpoint
+    fn parse_bulk_reply(&self, json: String) -> Result<Vec<LookupResponse>> {
+        Ok(vec![self.parse_reply(json)?])
+    }
 }
 
 /// ProviderResponse trait that define methods to parse the response from the provider
@@ -86,11 +297,181 @@ pub trait ProviderResponse<T: DeserializeOwned> {
         Ok(deserialized)
     }
     /// Convert the response into a LookupResponse
-    fn into_response(self) -> LookupResponse;
+    ///
+    /// Fails with `LookupError::InvalidResponseIp` if the reported address can't be parsed.
+    fn into_response(self) -> Result<LookupResponse>;
+    /// Convert the response into a LookupResponse, reporting the fields that had to be dropped
+    /// because their values could not be converted
+    fn into_response_with_warnings(self) -> Result<(LookupResponse, Vec<ParseWarning>)>
+    where
+        Self: Sized,
+    {
+        Ok((self.into_response()?, Vec::new()))
+    }
+}
+
+/// Fails with `LookupError::ProviderError` if the reply is an error object
+///
+/// Some providers answer errors like an invalid API key with a `200 OK` status and an error object
+/// in place of the response. `detect` returns the error code and message found in the reply, or
+/// `None` for a regular reply. Replies that aren't JSON are left to the response parser.
+#[cfg(any(
+    feature = "provider-abstractapi",
+    feature = "provider-bgpview",
+    feature = "provider-ip2location",
+    feature = "provider-ip2proxy",
+    feature = "provider-ipapico",
+    feature = "provider-ipapicom",
+    feature = "provider-ipdata",
+    feature = "provider-ipgeolocation",
+    feature = "provider-iplocateio",
+    feature = "provider-ipstack",
+    feature = "provider-ipwhois",
+    feature = "provider-ripestat",
+))]
+pub(crate) fn check_error_body(
+    provider: LookupProvider,
+    json: &str,
+    detect: impl FnOnce(&serde_json::Value) -> Option<(Option<String>, String)>,
+) -> Result<()> {
+    let Ok(reply) = serde_json::from_str::<serde_json::Value>(json) else {
+        return Ok(());
+    };
+    match detect(&reply) {
+        Some((code, message)) => {
+            warn!("Provider {} answered with an error: {}", provider, message);
+            Err(LookupError::ProviderError {
+                provider,
+                code,
+                message,
+            })
+        }
+        None => Ok(()),
+    }
+}
+
+/// Sends the value as the JSON body of the request
+pub(crate) fn with_json_body(
+    request: RequestBuilder,
+    body: Option<serde_json::Value>,
+) -> RequestBuilder {
+    match body {
+        Some(body) => request
+            .header(client::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string()),
+        None => request,
+    }
+}
+
+/// Returns the field of an error object as text, numbers are formatted
+#[cfg(any(
+    feature = "provider-abstractapi",
+    feature = "provider-bgpview",
+    feature = "provider-ip2location",
+    feature = "provider-ip2proxy",
+    feature = "provider-ipapico",
+    feature = "provider-ipapicom",
+    feature = "provider-ipdata",
+    feature = "provider-ipgeolocation",
+    feature = "provider-iplocateio",
+    feature = "provider-ipstack",
+    feature = "provider-ipwhois",
+))]
+pub(crate) fn error_field(reply: &serde_json::Value, field: &str) -> Option<String> {
+    match reply.get(field)? {
+        serde_json::Value::String(value) => Some(value.clone()),
+        serde_json::Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Appends the preferred language of the parameters to the endpoint as the `name` query parameter
+#[cfg(any(
+    feature = "provider-ipapicom",
+    feature = "provider-ipbase",
+    feature = "provider-ipdata",
+    feature = "provider-ipwhois",
+))]
+pub(crate) fn append_language(endpoint: String, name: &str, parameters: &Parameters) -> String {
+    let Some(language) = parameters.language.as_deref().filter(|l| !l.is_empty()) else {
+        return endpoint;
+    };
+    match client::Url::parse(&endpoint) {
+        Ok(mut url) => {
+            url.query_pairs_mut().append_pair(name, language);
+            url.to_string()
+        }
+        Err(_) => endpoint,
+    }
+}
+
+/// Parses the address reported by a provider
+pub(crate) fn parse_response_ip(ip: &str) -> Result<IpAddr> {
+    ip.trim()
+        .parse()
+        .map_err(|_| LookupError::InvalidResponseIp(ip.to_string()))
+}
+
+/// Parses the reply with the first response schema that accepts it
+///
+/// Providers change the shape of their replies over time and roll the change out gradually, so
+/// `schemas` lists the parsers of every known version, newest first. Only deserialization errors
+/// move on to the next schema, if none accepts the reply the error of the newest one is returned.
+#[cfg(any(
+    feature = "provider-ipapiio",
+    feature = "provider-ipbase",
+    feature = "provider-myip",
+))]
+pub(crate) fn parse_versioned(
+    json: String,
+    schemas: &[fn(String) -> Result<LookupResponse>],
+) -> Result<LookupResponse> {
+    let mut first_error = None;
+    for schema in schemas {
+        match schema(json.clone()) {
+            Err(LookupError::SerdeError(e)) => {
+                first_error.get_or_insert(LookupError::SerdeError(e));
+            }
+            result => return result,
+        }
+    }
+    Err(first_error.unwrap_or_else(|| LookupError::GenericError("No response schema".to_string())))
+}
+
+/// Warning about a provider response field that was dropped because its value could not be converted
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ParseWarning {
+    /// Name of the field in the provider response
+    pub field: String,
+    /// Raw value of the field
+    pub value: String,
+}
+
+impl ParseWarning {
+    /// Creates a new ParseWarning for a field and its raw value
+    pub fn new(field: &str, value: &str) -> Self {
+        ParseWarning {
+            field: field.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Dropped field `{}` with unparseable value `{}`",
+            self.field, self.value
+        )
+    }
 }
 
 /// Available lookup service providers
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+///
+/// Providers are displayed, parsed and serialized by their canonical name, see [`LookupProvider::name`].
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 #[non_exhaustive]
 pub enum LookupProvider {
     /// FreeIpApi provider (<https://freeipapi.com>)
@@ -129,85 +510,416 @@ pub enum LookupProvider {
     MyIpCom,
     /// Ipify provider (<https://www.ipify.org>)
     Ipify,
+    /// Ipify provider using the IPv4 only endpoint (<https://www.ipify.org>)
+    IpifyV4,
+    /// Ipify provider using the IPv6 only endpoint (<https://www.ipify.org>)
+    IpifyV6,
     /// GetJsonIp provider (<https://getjsonip.com>)
     GetJsonIp,
-    /// Mock provider for testing
+    /// IpStack provider (<https://ipstack.com>)
+    IpStack,
+    /// IfConfigMe plain text provider (<https://ifconfig.me>)
+    IfConfigMe,
+    /// ICanHazIp plain text provider (<https://icanhazip.com>)
+    ICanHazIp,
+    /// IdentMe plain text provider (<https://ident.me>)
+    IdentMe,
+    /// AWS checkip plain text provider (<https://checkip.amazonaws.com>)
+    AwsCheckIp,
+    /// Akamai plain text provider (<http://whatismyip.akamai.com>)
+    Akamai,
+    /// IpSb provider (<https://ip.sb>)
+    IpSb,
+    /// SeeIp provider (<https://seeip.org>)
+    SeeIp,
+    /// IpInfo Lite provider (<https://ipinfo.io/lite>)
+    IpInfoLite,
+    /// Ip2Proxy provider (<https://ip2proxy.com>)
+    Ip2Proxy,
+    /// BGPView provider returning the routing data of a target (<https://bgpview.io>)
+    BgpView,
+    /// RIPEstat provider returning the routing data of a target (<https://stat.ripe.net>)
+    RipeStat,
+    /// Router of the local network, asked over NAT-PMP or UPnP IGD, see [`gateway`]
+    Gateway,
+    /// Custom provider answering with the address as plain text or JSON at the given URL
+    Custom(String),
+    /// Mock provider for testing, answering without a request, see [`mock::MockReply`]
     Mock(String),
 }
 
+impl LookupProvider {
+    /// Returns the canonical lowercase name of the provider, `None` for custom and mock providers
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match self {
+            LookupProvider::FreeIpApi => "freeipapi",
+            LookupProvider::IfConfig => "ifconfig",
+            LookupProvider::IpInfo => "ipinfo",
+            LookupProvider::MyIp => "myip",
+            LookupProvider::IpApiCom => "ipapicom",
+            LookupProvider::IpWhoIs => "ipwhois",
+            LookupProvider::IpApiCo => "ipapico",
+            LookupProvider::IpApiIo => "ipapiio",
+            LookupProvider::IpBase => "ipbase",
+            LookupProvider::IpLocateIo => "iplocateio",
+            LookupProvider::IpLeak => "ipleak",
+            LookupProvider::Mullvad => "mullvad",
+            LookupProvider::AbstractApi => "abstractapi",
+            LookupProvider::IpGeolocation => "ipgeolocation",
+            LookupProvider::IpData => "ipdata",
+            LookupProvider::Ip2Location => "ip2location",
+            LookupProvider::MyIpCom => "myipcom",
+            LookupProvider::Ipify => "ipify",
+            LookupProvider::IpifyV4 => "ipifyv4",
+            LookupProvider::IpifyV6 => "ipifyv6",
+            LookupProvider::GetJsonIp => "getjsonip",
+            LookupProvider::IpStack => "ipstack",
+            LookupProvider::IfConfigMe => "ifconfigme",
+            LookupProvider::ICanHazIp => "icanhazip",
+            LookupProvider::IdentMe => "identme",
+            LookupProvider::AwsCheckIp => "awscheckip",
+            LookupProvider::Akamai => "akamai",
+            LookupProvider::IpSb => "ipsb",
+            LookupProvider::SeeIp => "seeip",
+            LookupProvider::IpInfoLite => "ipinfolite",
+            LookupProvider::Ip2Proxy => "ip2proxy",
+            LookupProvider::BgpView => "bgpview",
+            LookupProvider::RipeStat => "ripestat",
+            LookupProvider::Gateway => "gateway",
+            LookupProvider::Custom(_) | LookupProvider::Mock(_) => return None,
+        };
+        Some(name)
+    }
+
+    /// Returns the other names the provider is parsed from, like its domain name
+    pub fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            LookupProvider::FreeIpApi => &["freeipapi.com"],
+            LookupProvider::IfConfig => &["ifconfig.co"],
+            LookupProvider::IpInfo => &["ipinfo.io"],
+            LookupProvider::MyIp => &["my-ip", "my-ip.io"],
+            LookupProvider::IpApiCom => &["ip-api", "ip-api.com"],
+            LookupProvider::IpWhoIs => &["ipwhois.io", "ipwho.is"],
+            LookupProvider::IpApiCo => &["ipapi.co"],
+            LookupProvider::IpApiIo => &["ip-api.io"],
+            LookupProvider::IpBase => &["ipbase.com"],
+            LookupProvider::IpLocateIo => &["iplocate", "iplocate.io"],
+            LookupProvider::IpLeak => &["ipleak.net"],
+            LookupProvider::Mullvad => &["mullvad.net"],
+            LookupProvider::AbstractApi => &["abstract", "abstractapi.com"],
+            LookupProvider::IpGeolocation => &["ipgeolocation.io"],
+            LookupProvider::IpData => &["ipdata.co"],
+            LookupProvider::Ip2Location => &["ip2location.io"],
+            LookupProvider::MyIpCom => &["myip.com"],
+            LookupProvider::Ipify => &["ipify.org"],
+            LookupProvider::GetJsonIp => &["getjsonip.com"],
+            LookupProvider::IpStack => &["ipstack.com"],
+            LookupProvider::IfConfigMe => &["ifconfig.me"],
+            LookupProvider::ICanHazIp => &["icanhazip.com"],
+            LookupProvider::IdentMe => &["ident.me"],
+            LookupProvider::AwsCheckIp => &["checkip.amazonaws.com"],
+            LookupProvider::Akamai => &["whatismyip.akamai.com"],
+            LookupProvider::IpSb => &["ip.sb"],
+            LookupProvider::SeeIp => &["seeip.org"],
+            LookupProvider::Ip2Proxy => &["ip2proxy.com"],
+            LookupProvider::BgpView => &["bgpview.io"],
+            LookupProvider::RipeStat => &["stat.ripe.net"],
+            LookupProvider::Gateway => &["upnp", "natpmp", "nat-pmp"],
+            _ => &[],
+        }
+    }
+}
+
 impl fmt::Display for LookupProvider {
+    /// Formats the provider with its canonical name, or as `custom:<url>` and `mock:<ip>`
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            LookupProvider::Custom(url) => write!(f, "custom:{}", url),
+            LookupProvider::Mock(ip) => write!(f, "mock:{}", ip),
+            provider => f.write_str(provider.name().unwrap_or_default()),
+        }
     }
 }
 
 impl FromStr for LookupProvider {
     type Err = LookupError;
     /// Parse a `&str` into a LookupProvider
+    ///
+    /// Accepts the canonical name, case insensitive and with optional dashes or underscores, the
+    /// aliases of the provider, and `custom:<url>` or `mock:<ip>`. Only the first word is parsed.
     fn from_str(s: &str) -> Result<Self> {
-        let s = s.trim().to_lowercase();
-        // split the string into parts
-        let s = s
-            .split_whitespace()
-            .map(str::to_string)
-            .collect::<Vec<String>>();
-        // get the provider
         let p = s
-            .first()
+            .split_whitespace()
+            .next()
             .ok_or(LookupError::GenericError("No provider given".to_string()))?;
+        if let Some((kind, value)) = p.split_once(':') {
+            match kind.to_lowercase().as_str() {
+                "custom" => return Ok(LookupProvider::Custom(value.to_string())),
+                "mock" => return Ok(LookupProvider::Mock(value.to_string())),
+                _ => {}
+            }
+        }
+
+        let name = p.to_lowercase();
+        let normalized = name.replace(['-', '_'], "");
+        let found = ALL_PROVIDERS
+            .iter()
+            .find(|provider| provider.aliases().contains(&name.as_str()))
+            .or_else(|| {
+                ALL_PROVIDERS
+                    .iter()
+                    .find(|provider| provider.name() == Some(normalized.as_str()))
+            });
+        found.cloned().ok_or(LookupError::GenericError(format!(
+            "Provider not found: {}",
+            p
+        )))
+    }
+}
+
+impl Serialize for LookupProvider {
+    /// Serializes the provider as its `Display` string
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for LookupProvider {
+    /// Deserializes the provider from its string form, or from the `{"Mock": "1.1.1.1"}` form
+    /// written by earlier versions
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct ProviderVisitor;
+
+        impl<'de> de::Visitor<'de> for ProviderVisitor {
+            type Value = LookupProvider;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a lookup provider name")
+            }
 
-        match p.as_str() {
-            "freeipapi" => Ok(LookupProvider::FreeIpApi),
-            "ifconfig" => Ok(LookupProvider::IfConfig),
-            "ipinfo" => Ok(LookupProvider::IpInfo),
-            "myip" => Ok(LookupProvider::MyIp),
-            "ipapicom" => Ok(LookupProvider::IpApiCom),
-            "ipwhois" => Ok(LookupProvider::IpWhoIs),
-            "ipapico" => Ok(LookupProvider::IpApiCo),
-            "ipapiio" => Ok(LookupProvider::IpApiIo),
-            "ipbase" => Ok(LookupProvider::IpBase),
-            "iplocateio" => Ok(LookupProvider::IpLocateIo),
-            "ipleak" => Ok(LookupProvider::IpLeak),
-            "mullvad" => Ok(LookupProvider::Mullvad),
-            "abstract" => Ok(LookupProvider::AbstractApi),
-            "ipgeolocation" => Ok(LookupProvider::IpGeolocation),
-            "ipdata" => Ok(LookupProvider::IpData),
-            "ip2location" => Ok(LookupProvider::Ip2Location),
-            "myipcom" => Ok(LookupProvider::MyIpCom),
-            "ipify" => Ok(LookupProvider::Ipify),
-            "getjsonip" => Ok(LookupProvider::GetJsonIp),
-            _ => Err(LookupError::GenericError(format!(
-                "Provider not found: {}",
-                p
-            ))),
+            fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Self::Value, E> {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                let (kind, value): (String, String) = map
+                    .next_entry()?
+                    .ok_or_else(|| de::Error::custom("empty lookup provider"))?;
+                format!("{}:{}", kind, value)
+                    .parse()
+                    .map_err(de::Error::custom)
+            }
         }
+
+        deserializer.deserialize_any(ProviderVisitor)
     }
 }
 
+/// All built-in providers in declaration order, the public services and the local gateway
+const ALL_PROVIDERS: [LookupProvider; 34] = [
+    LookupProvider::FreeIpApi,
+    LookupProvider::IfConfig,
+    LookupProvider::IpInfo,
+    LookupProvider::MyIp,
+    LookupProvider::IpApiCom,
+    LookupProvider::IpWhoIs,
+    LookupProvider::IpApiCo,
+    LookupProvider::IpApiIo,
+    LookupProvider::IpBase,
+    LookupProvider::IpLocateIo,
+    LookupProvider::IpLeak,
+    LookupProvider::Mullvad,
+    LookupProvider::AbstractApi,
+    LookupProvider::IpGeolocation,
+    LookupProvider::IpData,
+    LookupProvider::Ip2Location,
+    LookupProvider::MyIpCom,
+    LookupProvider::Ipify,
+    LookupProvider::IpifyV4,
+    LookupProvider::IpifyV6,
+    LookupProvider::GetJsonIp,
+    LookupProvider::IpStack,
+    LookupProvider::IfConfigMe,
+    LookupProvider::ICanHazIp,
+    LookupProvider::IdentMe,
+    LookupProvider::AwsCheckIp,
+    LookupProvider::Akamai,
+    LookupProvider::IpSb,
+    LookupProvider::SeeIp,
+    LookupProvider::IpInfoLite,
+    LookupProvider::Ip2Proxy,
+    LookupProvider::BgpView,
+    LookupProvider::RipeStat,
+    LookupProvider::Gateway,
+];
+
 impl LookupProvider {
+    /// Returns an iterator over references to all built-in providers, like `strum`'s `EnumIter`
+    ///
+    /// Custom and mock providers are not included. [`LookupProvider::Gateway`] is included even
+    /// though it asks the router of the local network instead of a public service. Providers
+    /// compiled out through their feature flag are included, see [`LookupProvider::is_enabled`].
+    ///
+    /// Handy to fill a map of per-provider settings:
+    /// ```rust
+    /// use public_ip_address::lookup::{DailyQuota, LookupProvider};
+    /// use std::collections::HashMap;
+    ///
+    /// let quotas: HashMap<&LookupProvider, Option<DailyQuota>> = LookupProvider::iter()
+    ///     .map(|provider| (provider, provider.capabilities().rate_limit_hint))
+    ///     .collect();
+    /// ```
+    pub fn iter() -> std::slice::Iter<'static, LookupProvider> {
+        ALL_PROVIDERS.iter()
+    }
+
+    /// Returns an iterator over all built-in providers, the owning counterpart of
+    /// [`LookupProvider::iter`]
+    pub fn all() -> impl Iterator<Item = LookupProvider> {
+        Self::iter().cloned()
+    }
+
+    /// Returns what the provider supports
+    ///
+    /// Providers compiled out through their feature flag report no capabilities.
+    pub fn capabilities(&self) -> ProviderCapabilities {
+        let provider = self.clone().build();
+        ProviderCapabilities {
+            supports_target: provider.supports_target_lookup(),
+            supports_bulk: provider.bulk_limit().is_some(),
+            requires_key: provider.requires_key(),
+            supports_ipv6: provider.supports_ipv6(),
+            https_only: provider.get_endpoint(&None, &None).starts_with("https://"),
+            supports_etag: provider.supports_etag(),
+            supports_asn: provider.supports_asn_lookup(),
+            rate_limit_hint: provider.daily_quota(),
+        }
+    }
+
     /// Builds the concrete lookup service out of a LookupProvider enum
     pub fn build(self) -> Box<dyn Provider + Send + Sync> {
         match self {
+            #[cfg(feature = "provider-freeipapi")]
             LookupProvider::FreeIpApi => Box::new(freeipapi::FreeIpApi),
+            #[cfg(feature = "provider-ifconfig")]
             LookupProvider::IfConfig => Box::new(ifconfig::IfConfig),
+            #[cfg(feature = "provider-ipinfo")]
             LookupProvider::IpInfo => Box::new(ipinfo::IpInfo),
+            #[cfg(feature = "provider-myip")]
             LookupProvider::MyIp => Box::new(myip::MyIp),
+            #[cfg(feature = "provider-ipapicom")]
             LookupProvider::IpApiCom => Box::new(ipapicom::IpApiCom),
+            #[cfg(feature = "provider-ipapico")]
             LookupProvider::IpApiCo => Box::new(ipapico::IpApiCo),
+            #[cfg(feature = "provider-ipapiio")]
             LookupProvider::IpApiIo => Box::new(ipapiio::IpApiIo),
+            #[cfg(feature = "provider-ipwhois")]
             LookupProvider::IpWhoIs => Box::new(ipwhois::IpWhoIs),
+            #[cfg(feature = "provider-ipbase")]
             LookupProvider::IpBase => Box::new(ipbase::IpBase),
+            #[cfg(feature = "provider-iplocateio")]
             LookupProvider::IpLocateIo => Box::new(iplocateio::IpLocateIo),
+            #[cfg(feature = "provider-ipleak")]
             LookupProvider::IpLeak => Box::new(ipleak::IpLeak),
+            #[cfg(feature = "provider-mullvad")]
             LookupProvider::Mullvad => Box::new(mullvad::Mullvad),
+            #[cfg(feature = "provider-abstractapi")]
             LookupProvider::AbstractApi => Box::new(abstractapi::AbstractApi),
+            #[cfg(feature = "provider-ipgeolocation")]
             LookupProvider::IpGeolocation => Box::new(ipgeolocation::IpGeolocation),
+            #[cfg(feature = "provider-ipdata")]
             LookupProvider::IpData => Box::new(ipdata::IpData),
+            #[cfg(feature = "provider-ip2location")]
             LookupProvider::Ip2Location => Box::new(ip2location::Ip2Location),
+            #[cfg(feature = "provider-myipcom")]
             LookupProvider::MyIpCom => Box::new(myipcom::MyIpCom),
+            #[cfg(feature = "provider-ipify")]
             LookupProvider::Ipify => Box::new(ipify::Ipify),
+            #[cfg(feature = "provider-ipifyv4")]
+            LookupProvider::IpifyV4 => Box::new(ipify::IpifyV4),
+            #[cfg(feature = "provider-ipifyv6")]
+            LookupProvider::IpifyV6 => Box::new(ipify::IpifyV6),
+            #[cfg(feature = "provider-getjsonip")]
             LookupProvider::GetJsonIp => Box::new(getjsonip::GetJsonIp),
-            LookupProvider::Mock(ip) => Box::new(mock::Mock { ip }),
+            #[cfg(feature = "provider-ipstack")]
+            LookupProvider::IpStack => Box::new(ipstack::IpStack),
+            #[cfg(feature = "provider-ifconfigme")]
+            LookupProvider::IfConfigMe => Box::new(plaintext::ifconfig_me()),
+            #[cfg(feature = "provider-icanhazip")]
+            LookupProvider::ICanHazIp => Box::new(plaintext::icanhazip()),
+            #[cfg(feature = "provider-identme")]
+            LookupProvider::IdentMe => Box::new(plaintext::ident_me()),
+            #[cfg(feature = "provider-awscheckip")]
+            LookupProvider::AwsCheckIp => Box::new(plaintext::aws_checkip()),
+            #[cfg(feature = "provider-akamai")]
+            LookupProvider::Akamai => Box::new(plaintext::akamai()),
+            #[cfg(feature = "provider-ipsb")]
+            LookupProvider::IpSb => Box::new(ipsb::IpSb),
+            #[cfg(feature = "provider-seeip")]
+            LookupProvider::SeeIp => Box::new(seeip::SeeIp),
+            #[cfg(feature = "provider-ipinfolite")]
+            LookupProvider::IpInfoLite => Box::new(ipinfo::IpInfoLite),
+            #[cfg(feature = "provider-ip2proxy")]
+            LookupProvider::Ip2Proxy => Box::new(ip2proxy::Ip2Proxy),
+            #[cfg(feature = "provider-bgpview")]
+            LookupProvider::BgpView => Box::new(bgpview::BgpView),
+            #[cfg(feature = "provider-ripestat")]
+            LookupProvider::RipeStat => Box::new(ripestat::RipeStat),
+            #[cfg(feature = "local-discovery")]
+            LookupProvider::Gateway => Box::new(gateway::Gateway),
+            LookupProvider::Custom(url) => Box::new(custom::Custom::new(&url)),
+            LookupProvider::Mock(ip) => Box::new(mock::Mock::new(ip)),
+            #[allow(unreachable_patterns)]
+            provider => Box::new(disabled::Disabled(provider)),
+        }
+    }
+
+    /// Check if the provider was compiled in through its `provider-*` feature flag
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            LookupProvider::FreeIpApi => cfg!(feature = "provider-freeipapi"),
+            LookupProvider::IfConfig => cfg!(feature = "provider-ifconfig"),
+            LookupProvider::IpInfo => cfg!(feature = "provider-ipinfo"),
+            LookupProvider::MyIp => cfg!(feature = "provider-myip"),
+            LookupProvider::IpApiCom => cfg!(feature = "provider-ipapicom"),
+            LookupProvider::IpApiCo => cfg!(feature = "provider-ipapico"),
+            LookupProvider::IpApiIo => cfg!(feature = "provider-ipapiio"),
+            LookupProvider::IpWhoIs => cfg!(feature = "provider-ipwhois"),
+            LookupProvider::IpBase => cfg!(feature = "provider-ipbase"),
+            LookupProvider::IpLocateIo => cfg!(feature = "provider-iplocateio"),
+            LookupProvider::IpLeak => cfg!(feature = "provider-ipleak"),
+            LookupProvider::Mullvad => cfg!(feature = "provider-mullvad"),
+            LookupProvider::AbstractApi => cfg!(feature = "provider-abstractapi"),
+            LookupProvider::IpGeolocation => cfg!(feature = "provider-ipgeolocation"),
+            LookupProvider::IpData => cfg!(feature = "provider-ipdata"),
+            LookupProvider::Ip2Location => cfg!(feature = "provider-ip2location"),
+            LookupProvider::MyIpCom => cfg!(feature = "provider-myipcom"),
+            LookupProvider::Ipify => cfg!(feature = "provider-ipify"),
+            LookupProvider::IpifyV4 => cfg!(feature = "provider-ipifyv4"),
+            LookupProvider::IpifyV6 => cfg!(feature = "provider-ipifyv6"),
+            LookupProvider::GetJsonIp => cfg!(feature = "provider-getjsonip"),
+            LookupProvider::IpStack => cfg!(feature = "provider-ipstack"),
+            LookupProvider::IfConfigMe => cfg!(feature = "provider-ifconfigme"),
+            LookupProvider::ICanHazIp => cfg!(feature = "provider-icanhazip"),
+            LookupProvider::IdentMe => cfg!(feature = "provider-identme"),
+            LookupProvider::AwsCheckIp => cfg!(feature = "provider-awscheckip"),
+            LookupProvider::Akamai => cfg!(feature = "provider-akamai"),
+            LookupProvider::IpSb => cfg!(feature = "provider-ipsb"),
+            LookupProvider::SeeIp => cfg!(feature = "provider-seeip"),
+            LookupProvider::IpInfoLite => cfg!(feature = "provider-ipinfolite"),
+            LookupProvider::Ip2Proxy => cfg!(feature = "provider-ip2proxy"),
+            LookupProvider::BgpView => cfg!(feature = "provider-bgpview"),
+            LookupProvider::RipeStat => cfg!(feature = "provider-ripestat"),
+            LookupProvider::Gateway => cfg!(feature = "local-discovery"),
+            LookupProvider::Custom(_) | LookupProvider::Mock(_) => true,
         }
     }
 
@@ -216,8 +928,7 @@ impl LookupProvider {
     /// This function parses a `&str` into a LookupProvider enum variant and extracts the API key as parameter if it exists.
     /// The `&str` should be formatted as `<provider> <api_key>` or `<provider>`.
     pub fn from_str_with_params(s: &str) -> Result<(LookupProvider, Option<Parameters>)> {
-        let s = s.trim().to_lowercase();
-        // split the string into parts
+        // split the string into parts, keeping the case of the key
         let s = s
             .split_whitespace()
             .map(str::to_string)
@@ -231,131 +942,1211 @@ impl LookupProvider {
         let key = s.get(1).map(|key| Parameters::new(key.to_owned()));
         Ok((provider, key))
     }
+
+    /// Returns the environment variable holding the API key of the provider, like `PUBLIC_IP_IPDATA_APIKEY`
+    ///
+    /// Custom and mock providers have no API key variable.
+    pub fn api_key_env(&self) -> Option<String> {
+        match self {
+            LookupProvider::Custom(_) | LookupProvider::Mock(_) => None,
+            provider => Some(format!(
+                "PUBLIC_IP_{}_APIKEY",
+                provider.name()?.to_uppercase()
+            )),
+        }
+    }
+
+    /// Check if the provider endpoint is reachable over IPv6
+    pub fn supports_ipv6(&self) -> bool {
+        self.clone().build().supports_ipv6()
+    }
+
+    /// Returns the approximate number of free requests per day for the provider, if known
+    pub fn daily_quota(&self) -> Option<DailyQuota> {
+        self.clone().build().daily_quota()
+    }
+
+    /// Check if the provider supports target lookup
+    pub fn supports_target_lookup(&self) -> bool {
+        self.clone().build().supports_target_lookup()
+    }
+
+    /// Check if the provider supports ASN lookups, see [`LookupService::lookup_asn`]
+    pub fn supports_asn_lookup(&self) -> bool {
+        self.clone().build().supports_asn_lookup()
+    }
+
+    /// Returns the maximum number of targets accepted by the bulk endpoint of the provider, if any
+    pub fn bulk_limit(&self) -> Option<usize> {
+        self.clone().build().bulk_limit()
+    }
 }
 
-/// Parameters hold the API key for lookup providers
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+/// Parameters hold the API key and the request options for lookup providers
+///
+/// ```rust
+/// use public_ip_address::lookup::Parameters;
+///
+/// let parameters = Parameters::new("key".to_string())
+///     .with_header("X-Client", "monitor")
+///     .with_language("de")
+///     .with_option("fields", "status,message,query,country");
+/// ```
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 #[non_exhaustive]
 pub struct Parameters {
-    /// API key for the lookup provider
+    /// API key for the lookup provider, empty to use the environment variable of the provider
     pub api_key: String,
+    /// Extra headers sent with the request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<(String, String)>,
+    /// Extra query parameters appended to the endpoint
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query: Vec<(String, String)>,
+    /// Preferred language of the localized names, like `de` or `pt-BR`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Provider specific options, like the `fields` mask of ip-api.com
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, String>,
+    /// Local address the request is sent from, on hosts with several addresses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_address: Option<IpAddr>,
+    /// Network interface the request is sent through, like `eth0` or `wg0`, Linux only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Address family the request is sent over, the system default if `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<AddressFamily>,
+    /// Expected pins of the provider certificate, like `sha256/<base64>`, see [`pinning`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pins: Vec<String>,
+    /// PEM encoded root certificates trusted instead of the system roots
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub root_certificates: Vec<String>,
 }
 
 impl Parameters {
     /// Creates new Parameters with an API key
     pub fn new(api_key: String) -> Self {
-        Self { api_key }
+        Self {
+            api_key,
+            ..Default::default()
+        }
     }
-}
 
-/// LookupService instance to handle the lookup process
-///
-/// # Example
-/// ```
-/// use public_ip_address::lookup::{LookupProvider, LookupService};
-///
-/// let service = LookupService::new(LookupProvider::IpApiCom, None);
-/// ```
-#[non_exhaustive]
-pub struct LookupService {
-    provider: Box<dyn Provider + Send + Sync>,
-    parameters: Option<Parameters>,
-}
+    /// Adds a header sent with the request
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
 
-impl LookupService {
-    /// Creates a new `LookupService` instance with parameters.
-    pub fn new(provider: LookupProvider, parameters: Option<Parameters>) -> Self {
-        LookupService {
-            provider: provider.build(),
-            parameters,
-        }
+    /// Adds a query parameter appended to the endpoint
+    pub fn with_query(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
     }
 
-    /// Changes the provider for the LookupService
-    pub fn set_provider(&mut self, provider: LookupProvider) -> &Self {
-        self.provider = provider.build();
+    /// Sets the preferred language of the localized names
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
         self
     }
 
-    /// Sets the parameters for the LookupService
-    pub fn set_parameters(&mut self, parameters: Parameters) -> &Self {
-        self.parameters = Some(parameters);
+    /// Sets a provider specific option
+    pub fn with_option(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.insert(name.into(), value.into());
         self
     }
 
-    /// Returns the type of the current lookup provider.
+    /// Returns the value of a provider specific option
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    /// Sends the request from a local address, like the address of a VPN tunnel
+    pub fn with_local_address(mut self, address: IpAddr) -> Self {
+        self.local_address = Some(address);
+        self
+    }
+
+    /// Sends the request through a network interface, like `eth0` or `wg0`
     ///
-    /// This function returns the `LookupProvider` enum variant that represents the type of the current lookup provider.
-    pub fn get_provider_type(&self) -> LookupProvider {
-        self.provider.get_type()
+    /// Only supported on Linux, Android and Fuchsia, elsewhere the lookup fails.
+    pub fn with_interface(mut self, interface: impl Into<String>) -> Self {
+        self.interface = Some(interface.into());
+        self
     }
 
-    /// Makes a request to the lookup provider
+    /// Sends the request over an address family, like `AddressFamily::RequireV6` to look up the
+    /// IPv6 address of a dual-stack host through any provider reachable over IPv6
+    pub fn with_family(mut self, family: AddressFamily) -> Self {
+        self.family = Some(family);
+        self
+    }
+
+    /// Adds an expected pin of the provider certificate, the reply is refused with
+    /// `LookupError::PinMismatch` unless the certificate matches one of the pins
     ///
-    /// This function makes an API request to the current lookup provider and parses the response into a `LookupResponse` instance.
-    #[maybe_async::maybe_async]
-    pub async fn lookup(&self, target: Option<IpAddr>) -> Result<LookupResponse> {
-        if target.is_some() && !self.provider.supports_target_lookup() {
-            return Err(LookupError::TargetNotSupported);
+    /// Requires the `cert-pinning` feature, without it pinned requests fail.
+    pub fn with_pin(mut self, pin: impl Into<String>) -> Self {
+        self.pins.push(pin.into());
+        self
+    }
+
+    /// Adds a PEM encoded root certificate, the system roots are no longer trusted once set
+    ///
+    /// An invalid certificate fails the lookup rather than falling back to the system roots. Not
+    /// supported on WebAssembly.
+    pub fn with_root_certificate(mut self, pem: impl Into<String>) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Checks if the request is bound to a local address, an interface or an address family, or
+    /// needs its own root certificates
+    pub fn is_bound(&self) -> bool {
+        self.local_address.is_some()
+            || self.interface.is_some()
+            || self.family.is_some()
+            || !self.root_certificates.is_empty()
+    }
+
+    /// Returns the client the request is sent with, bound to the local address, interface and
+    /// address family
+    ///
+    /// Fails if the binding can't be applied, like an interface on a platform without interface
+    /// binding, instead of falling back to an unbound client. In the browser the connections
+    /// can't be bound at all.
+    pub fn client(&self) -> Result<client::Client> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return client::bound(self);
+        #[cfg(target_arch = "wasm32")]
+        if self.is_bound() {
+            Err(LookupError::GenericError(
+                "Binding the connections is not supported on wasm32".to_string(),
+            ))
+        } else {
+            Ok(client::shared())
         }
-        let response = self.make_api_request(target).await?;
-        self.provider.parse_reply(response)
     }
 
-    /// Internal function to make the API request
-    #[maybe_async::maybe_async]
-    async fn make_api_request(&self, target: Option<IpAddr>) -> Result<String> {
-        let key = self.parameters.as_ref().map(|p| p.api_key.clone());
-        let response = self.provider.get_client(key, target).send().await;
-        handle_response(response).await
+    /// Adds the extra headers and query parameters to the request
+    ///
+    /// The preferred language is also sent as the `Accept-Language` header.
+    pub fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(language) = self.language.as_deref().filter(|l| !l.is_empty()) {
+            request = request.header(client::header::ACCEPT_LANGUAGE, language);
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if !self.query.is_empty() {
+            request = request.query(&self.query);
+        }
+        request
+    }
+
+    /// Reads the API key of the provider from its `PUBLIC_IP_<PROVIDER>_APIKEY` environment variable
+    pub fn from_env(provider: &LookupProvider) -> Option<Self> {
+        let key = env::var(provider.api_key_env()?).ok()?;
+        let key = key.trim();
+        (!key.is_empty()).then(|| Parameters::new(key.to_string()))
     }
 }
 
-/// Handles the response from reqwest
-#[maybe_async::maybe_async]
-pub async fn handle_response(response: reqwest::Result<Response>) -> Result<String> {
-    match response {
-        Ok(response) => match response.status() {
-            StatusCode::OK => Ok(response.text().await?),
-            StatusCode::TOO_MANY_REQUESTS => Err(LookupError::TooManyRequests(format!(
-                "Too many requests: {}",
-                response.status()
-            ))),
-            s => Err(LookupError::RequestStatus(format!("Status: {}", s))),
-        },
-        Err(e) => Err(LookupError::ReqwestError(e)),
+/// Returns the API key to use for the provider
+///
+/// Falls back to the environment variable of the provider when no parameters or an empty key are
+/// given, unless
+/// disabled through [`Config::without_env_api_keys`](crate::Config::without_env_api_keys).
+pub(crate) fn resolve_api_key(
+    provider: &LookupProvider,
+    parameters: &Option<Parameters>,
+) -> Option<String> {
+    match parameters {
+        Some(parameters) if !parameters.api_key.is_empty() => Some(parameters.api_key.clone()),
+        _ if crate::config::config().env_api_keys => {
+            Parameters::from_env(provider).map(|parameters| parameters.api_key)
+        }
+        _ => None,
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Number of free requests a provider allows per day, see [`Provider::daily_quota`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DailyQuota {
+    /// Approximate number of requests allowed per day
+    Requests(u64),
+    /// No limit on the number of requests
+    Unlimited,
+}
 
-    #[test]
-    fn test_set_provider() {
-        let mut provider = LookupService::new(LookupProvider::IpApiCom, None);
-        assert_eq!(provider.get_provider_type(), LookupProvider::IpApiCom);
-        provider.set_provider(LookupProvider::IpInfo);
-        assert_eq!(provider.get_provider_type(), LookupProvider::IpInfo);
+impl DailyQuota {
+    /// Returns the number of requests allowed per day, `None` if unlimited
+    pub fn requests(&self) -> Option<u64> {
+        match self {
+            DailyQuota::Requests(requests) => Some(*requests),
+            DailyQuota::Unlimited => None,
+        }
     }
+}
 
-    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
-    async fn test_make_request() {
-        let address = "1.1.1.1".parse::<std::net::IpAddr>().unwrap();
-        let provider = LookupService::new(LookupProvider::Mock(address.to_string()), None);
-        let response = provider.lookup(None).await.unwrap();
-        assert_eq!(response.ip, address);
-    }
+/// Capabilities of a lookup provider, see [`LookupProvider::capabilities`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct ProviderCapabilities {
+    /// Looks up addresses other than the caller's own
+    pub supports_target: bool,
+    /// Accepts several targets in a single request
+    pub supports_bulk: bool,
+    /// Refuses requests without an API key
+    pub requires_key: bool,
+    /// Endpoint is reachable over IPv6
+    pub supports_ipv6: bool,
+    /// Endpoint is reached over HTTPS without an API key
+    pub https_only: bool,
+    /// Answers conditional requests with `304 Not Modified`, so cached responses are revalidated
+    pub supports_etag: bool,
+    /// Returns only the network data of an address, see [`LookupService::lookup_asn`]
+    pub supports_asn: bool,
+    /// Approximate number of free requests per day, `None` if unknown
+    pub rate_limit_hint: Option<DailyQuota>,
+}
 
-    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
-    async fn test_handle_response() {
-        let response = client::get("https://httpbin.org/status/200").await;
-        let body = handle_response(response).await;
-        assert!(body.is_ok(), "Response is an error {:#?}", body);
+/// Result of a provider health check, see [`LookupService::health_check`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ProviderHealth {
+    /// Checked provider
+    pub provider: LookupProvider,
+    /// Provider answered the request, even if with an error status
+    pub reachable: bool,
+    /// Reply was parsed into a response
+    pub parsed: bool,
+    /// Time taken by the check
+    pub latency: Duration,
+    /// Error of the failed check
+    pub error: Option<String>,
+}
+
+impl ProviderHealth {
+    /// Returns true if the provider answered with a reply that could be parsed
+    pub fn is_healthy(&self) -> bool {
+        self.reachable && self.parsed
     }
+}
 
-    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
-    async fn test_handle_response_error() {
+/// Options controlling how long a lookup may take
+///
+/// # Example
+/// ```
+/// use public_ip_address::lookup::LookupOptions;
+/// use std::time::Duration;
+///
+/// let options = LookupOptions::default()
+///     .with_total_timeout(Duration::from_secs(2))
+///     .with_per_provider_timeout(Duration::from_millis(500));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub struct LookupOptions {
+    /// Deadline for the whole provider chain, `None` for no deadline
+    pub total_timeout: Option<Duration>,
+    /// Timeout for each provider request, `None` uses the process-wide timeout
+    pub per_provider_timeout: Option<Duration>,
+    /// Fetch only the network data of the address, see [`LookupService::lookup_asn`]
+    pub asn_only: bool,
+    /// Try the providers in a weighted random order, see [`crate::perform_rotating_lookup_with`]
+    pub rotate: bool,
+    /// Serve the expired cached response when all providers fail, see [`LookupOptions::with_serve_stale`]
+    pub serve_stale: bool,
+    /// Age after which a cached response is refreshed in the background, see
+    /// [`LookupOptions::with_stale_while_revalidate`]
+    pub soft_ttl: Option<Duration>,
+}
+
+impl LookupOptions {
+    /// Sets the deadline for the whole provider chain
+    pub fn with_total_timeout(mut self, timeout: Duration) -> Self {
+        self.total_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for each provider request
+    pub fn with_per_provider_timeout(mut self, timeout: Duration) -> Self {
+        self.per_provider_timeout = Some(timeout);
+        self
+    }
+
+    /// Fetches only the ASN, prefix and organization of the address
+    pub fn with_asn_only(mut self) -> Self {
+        self.asn_only = true;
+        self
+    }
+
+    /// Tries the providers in a weighted random order instead of the order of the chain
+    pub fn with_rotation(mut self) -> Self {
+        self.rotate = true;
+        self
+    }
+
+    /// Serves the expired cached response instead of an error when all providers fail
+    ///
+    /// The response is marked with [`SelectionReason::StaleCache`](crate::response::SelectionReason::StaleCache).
+    /// Only applies to cached lookups.
+    pub fn with_serve_stale(mut self) -> Self {
+        self.serve_stale = true;
+        self
+    }
+
+    /// Refreshes cached responses older than `soft_ttl` in the background
+    ///
+    /// A cache hit past the soft TTL, but not yet expired, is returned immediately while the
+    /// entry is refreshed for the next lookup. Async builds refresh on a background task, blocking
+    /// builds and the browser, which have no executor to spawn on, refresh on the next lookup
+    /// instead. Only applies to cached lookups.
+    pub fn with_stale_while_revalidate(mut self, soft_ttl: Duration) -> Self {
+        self.soft_ttl = Some(soft_ttl);
+        self
+    }
+}
+
+/// LookupService instance to handle the lookup process
+///
+/// All services send their requests through a single shared HTTP client, so repeated lookups reuse
+/// the open connections and TLS sessions. The service is cheap to clone and can be shared between
+/// threads, see [`default_service`] for a process-wide instance.
+///
+/// # Example
+/// ```
+/// use public_ip_address::lookup::{LookupProvider, LookupService};
+///
+/// let service = LookupService::new(LookupProvider::IpApiCom, None);
+/// ```
+#[non_exhaustive]
+pub struct LookupService {
+    provider: Box<dyn Provider + Send + Sync>,
+    parameters: Option<Parameters>,
+    timeout: Option<Duration>,
+    cached: Option<(String, LookupResponse)>,
+    #[cfg(feature = "fixtures")]
+    fixtures: Option<fixtures::Fixtures>,
+}
+
+impl Clone for LookupService {
+    fn clone(&self) -> Self {
+        LookupService {
+            provider: self.provider.get_type().build(),
+            parameters: self.parameters.clone(),
+            timeout: self.timeout,
+            cached: self.cached.clone(),
+            #[cfg(feature = "fixtures")]
+            fixtures: self.fixtures.clone(),
+        }
+    }
+}
+
+/// Returns the process-wide lookup service, created on first use
+///
+/// The service uses the first provider of the configured chain, see [`crate::Config`]. Monitors
+/// doing frequent checks can keep using it instead of building a new service for every lookup.
+///
+/// # Example
+/// ```rust,no_run
+/// use public_ip_address::lookup::default_service;
+///
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let response = default_service().lookup(None).await?;
+/// println!("{}", response.ip);
+/// # Ok(())
+/// # }
+/// ```
+pub fn default_service() -> &'static LookupService {
+    static SERVICE: OnceLock<LookupService> = OnceLock::new();
+    SERVICE.get_or_init(|| {
+        let (provider, parameters) = crate::config::config()
+            .providers
+            .first()
+            .cloned()
+            .unwrap_or((LookupProvider::IpWhoIs, None));
+        LookupService::new(provider, parameters)
+    })
+}
+
+impl LookupService {
+    /// Creates a new `LookupService` instance with parameters.
+    pub fn new(provider: LookupProvider, parameters: Option<Parameters>) -> Self {
+        LookupService {
+            provider: provider.build(),
+            parameters,
+            timeout: None,
+            cached: None,
+            #[cfg(feature = "fixtures")]
+            fixtures: None,
+        }
+    }
+
+    /// Changes the provider for the LookupService
+    pub fn set_provider(&mut self, provider: LookupProvider) -> &Self {
+        self.provider = provider.build();
+        self
+    }
+
+    /// Sets the parameters for the LookupService
+    ///
+    /// Without parameters the API key is read from the `PUBLIC_IP_<PROVIDER>_APIKEY` environment
+    /// variable, see [`LookupProvider::api_key_env`].
+    pub fn set_parameters(&mut self, parameters: Parameters) -> &Self {
+        self.parameters = Some(parameters);
+        self
+    }
+
+    /// Sets the request timeout for the LookupService, overriding the process-wide timeout
+    pub fn set_timeout(&mut self, timeout: Duration) -> &Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets a cached response of the provider and its entity tag
+    ///
+    /// Providers supporting ETags are sent a conditional request, when they answer with
+    /// `304 Not Modified` the cached response is returned instead of downloading it again.
+    pub fn set_cached(&mut self, etag: String, response: LookupResponse) -> &Self {
+        self.cached = Some((etag, response));
+        self
+    }
+
+    /// Records the replies of the provider to disk, or replays them without making requests
+    ///
+    /// Overrides the process-wide fixtures, available with the `fixtures` feature.
+    #[cfg(feature = "fixtures")]
+    pub fn set_fixtures(&mut self, fixtures: fixtures::Fixtures) -> &Self {
+        self.fixtures = Some(fixtures);
+        self
+    }
+
+    /// Returns the fixtures used by the requests
+    #[cfg(feature = "fixtures")]
+    fn fixtures(&self) -> Option<fixtures::Fixtures> {
+        self.fixtures
+            .clone()
+            .or_else(|| crate::config::config().fixtures.clone())
+    }
+
+    /// Fails if the provider was compiled out, so that no request is made
+    fn ensure_enabled(&self) -> Result<()> {
+        let provider = self.provider.get_type();
+        if provider.is_enabled() {
+            Ok(())
+        } else {
+            Err(LookupError::ProviderDisabled(provider.to_string()))
+        }
+    }
+
+    /// Returns the timeout applied to the requests
+    fn request_timeout(&self) -> Option<Duration> {
+        self.timeout.or(crate::config::config().timeout)
+    }
+
+    /// Returns the expected pins of the provider certificate
+    fn pins(&self) -> &[String] {
+        self.parameters
+            .as_ref()
+            .map_or(&[], |parameters| parameters.pins.as_slice())
+    }
+
+    /// Returns the type of the current lookup provider.
+    ///
+    /// This function returns the `LookupProvider` enum variant that represents the type of the current lookup provider.
+    pub fn get_provider_type(&self) -> LookupProvider {
+        self.provider.get_type()
+    }
+
+    /// Builds the request to the provider, conditional if a cached response can be revalidated
+    fn request(&self, target: Option<IpAddr>) -> Result<RequestBuilder> {
+        self.request_with(target, &self.parameters)
+    }
+
+    /// Builds the request to the provider with the given parameters
+    fn request_with(
+        &self,
+        target: Option<IpAddr>,
+        parameters: &Option<Parameters>,
+    ) -> Result<RequestBuilder> {
+        let key = resolve_api_key(&self.provider.get_type(), parameters);
+        let request = match parameters {
+            Some(parameters) => self
+                .provider
+                .get_client_with_parameters(key, target, parameters)?,
+            None => self.provider.get_client(key, target),
+        };
+        Ok(match &self.cached {
+            Some((etag, _)) if self.provider.supports_etag() => {
+                request.header(client::header::IF_NONE_MATCH, etag)
+            }
+            _ => request,
+        })
+    }
+
+    /// Makes a request to the lookup provider
+    ///
+    /// This function makes an API request to the current lookup provider and parses the response into a `LookupResponse` instance.
+    #[maybe_async::maybe_async]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "lookup",
+            skip(self),
+            fields(
+                provider = %self.provider.get_type(),
+                duration_ms = tracing::field::Empty,
+                peer.service = %self.provider.get_type(),
+                otel.kind = tracing::field::Empty,
+                otel.status_code = tracing::field::Empty,
+                http.request.method = tracing::field::Empty,
+                http.response.status_code = tracing::field::Empty,
+                url.full = tracing::field::Empty,
+                error.type = tracing::field::Empty,
+            ),
+            err(Display)
+        )
+    )]
+    pub async fn lookup(&self, target: Option<IpAddr>) -> Result<LookupResponse> {
+        #[cfg(feature = "tracing")]
+        let _timer = crate::telemetry::SpanTimer::start();
+        let response = self.lookup_inner(target).await;
+        #[cfg(feature = "otel")]
+        if let Err(e) = &response {
+            crate::telemetry::record_error(e);
+        }
+        response
+    }
+
+    /// Performs the lookup of `lookup`, outside of its span bookkeeping
+    #[maybe_async::maybe_async]
+    async fn lookup_inner(&self, target: Option<IpAddr>) -> Result<LookupResponse> {
+        self.ensure_enabled()?;
+        self.provider.prepare()?;
+        self.check_target(target)?;
+        let (response, meta) = self.make_api_request(target).await?;
+        if meta.is_not_modified() {
+            return self.revalidated(meta);
+        }
+        let mut response = self
+            .provider
+            .parse_reply(response.clone())
+            .map_err(|e| diagnostics::diagnose(self.provider.get_type(), &response, e))?;
+        response.meta = meta;
+        #[cfg(feature = "iso3166")]
+        response.normalize_country();
+        fill_address(&mut response, target)?;
+        Ok(response)
+    }
+
+    /// Fails if the provider can't look up the target
+    fn check_target(&self, target: Option<IpAddr>) -> Result<()> {
+        if let Some(kind) = target.as_ref().and_then(bogon::non_public_kind) {
+            return Err(LookupError::NonPublicAddress(kind));
+        }
+        match target {
+            Some(_) if !self.provider.supports_target_lookup() => {
+                Err(LookupError::TargetNotSupported)
+            }
+            None if self.provider.requires_target() => Err(LookupError::TargetRequired),
+            _ => Ok(()),
+        }
+    }
+
+    /// Makes a request for only the network data of the address
+    ///
+    /// The response carries the ASN, the organization and, when the provider reports it, the
+    /// routed prefix, without the geolocation. Providers answering with the organization only,
+    /// like the ipinfo `/org` endpoint, don't echo the address, the target is filled in instead.
+    #[maybe_async::maybe_async]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "lookup_asn",
+            skip(self),
+            fields(provider = %self.provider.get_type()),
+            err(Display)
+        )
+    )]
+    pub async fn lookup_asn(&self, target: Option<IpAddr>) -> Result<LookupResponse> {
+        self.ensure_enabled()?;
+        self.provider.prepare()?;
+        if !self.provider.supports_asn_lookup() {
+            return Err(LookupError::AsnNotSupported);
+        }
+        self.check_target(target)?;
+        let (reply, meta) = match self.local_reply(target).await {
+            Some(reply) => {
+                let meta = LookupMeta {
+                    attempts: 1,
+                    ..Default::default()
+                };
+                (reply?, meta)
+            }
+            None => {
+                let key = resolve_api_key(&self.provider.get_type(), &self.parameters);
+                let endpoint = self.provider.get_asn_endpoint(&key, &target);
+                let request = match &self.parameters {
+                    Some(parameters) => {
+                        let request = parameters.client()?.get(endpoint);
+                        parameters.apply(self.provider.add_auth(request, &key))
+                    }
+                    None => self.provider.add_auth(client::shared().get(endpoint), &key),
+                };
+                send_request(with_timeout(request, self.request_timeout()), self.pins()).await?
+            }
+        };
+        let mut response = self
+            .provider
+            .parse_asn_reply(reply.clone())
+            .map_err(|e| diagnostics::diagnose(self.provider.get_type(), &reply, e))?;
+        response.meta = meta;
+        fill_address(&mut response, target)?;
+        Ok(response)
+    }
+
+    /// Returns the cached response confirmed by a `304 Not Modified` reply
+    fn revalidated(&self, mut meta: LookupMeta) -> Result<LookupResponse> {
+        let Some((etag, cached)) = &self.cached else {
+            return Err(LookupError::RequestStatus(
+                "Status: 304 Not Modified without a cached response".to_string(),
+            ));
+        };
+        // keep the entity tag for the next revalidation if the reply didn't repeat it
+        meta.headers
+            .entry("etag".to_string())
+            .or_insert_with(|| etag.clone());
+        let mut response = cached.clone();
+        response.meta = meta;
+        Ok(response)
+    }
+
+    /// Checks that the provider is reachable and that its reply can be parsed
+    ///
+    /// Looks up the current public address once and reports the outcome instead of failing, use it
+    /// at startup to pick a working set of providers.
+    #[maybe_async::maybe_async]
+    pub async fn health_check(&self) -> ProviderHealth {
+        let started = Instant::now();
+        let response = self.lookup(None).await;
+        let latency = started.elapsed();
+        let (reachable, parsed) = match &response {
+            Ok(_) => (true, true),
+            Err(
+                LookupError::SerdeError(_)
+                | LookupError::ParseError { .. }
+                | LookupError::InvalidResponseIp(_)
+                | LookupError::ResponseTooLarge(_)
+                | LookupError::ProviderError { .. }
+                | LookupError::GenericError(_)
+                | LookupError::TooManyRequests(_)
+                | LookupError::RequestStatus(_),
+            ) => (true, false),
+            Err(_) => (false, false),
+        };
+        ProviderHealth {
+            provider: self.provider.get_type(),
+            reachable,
+            parsed,
+            latency,
+            error: response.err().map(|e| match &e {
+                LookupError::GenericError(message)
+                | LookupError::TooManyRequests(message)
+                | LookupError::RequestStatus(message) => message.clone(),
+                e => e.to_string(),
+            }),
+        }
+    }
+
+    /// Makes a request to the lookup provider for every address of the target
+    ///
+    /// Hostnames are resolved through the system resolver first, the responses are returned in the
+    /// order of the resolved addresses.
+    #[maybe_async::maybe_async]
+    pub async fn lookup_target(&self, target: &LookupTarget) -> Result<Vec<LookupResponse>> {
+        let mut responses = Vec::new();
+        for address in target.resolve()? {
+            responses.push(self.lookup(Some(address)).await?);
+        }
+        Ok(responses)
+    }
+
+    /// Makes requests to the lookup provider for a list of targets
+    ///
+    /// Providers with a bulk endpoint are queried with up to `bulk_limit` targets per request,
+    /// the other providers one target at a time. The responses are not guaranteed to be in the
+    /// order of the targets.
+    ///
+    /// Targets whose request fails are logged and left out of the responses, the lookup only fails
+    /// when none of the targets could be looked up.
+    #[maybe_async::maybe_async]
+    pub async fn bulk_lookup(&self, targets: &[IpAddr]) -> Result<Vec<LookupResponse>> {
+        self.ensure_enabled()?;
+        self.provider.prepare()?;
+        let limit = self.provider.bulk_limit();
+        // fixtures are stored per target
+        #[cfg(feature = "fixtures")]
+        let limit = limit.filter(|_| self.fixtures().is_none());
+        let mut responses = Vec::with_capacity(targets.len());
+        let mut error = None;
+        let Some(limit) = limit else {
+            for target in targets {
+                match self.lookup(Some(*target)).await {
+                    Ok(response) => responses.push(response),
+                    Err(e) => {
+                        warn!("Lookup of {} failed: {}", target, e);
+                        error.get_or_insert(e);
+                    }
+                }
+            }
+            return partial_responses(responses, error);
+        };
+        if let Some(kind) = targets.iter().find_map(bogon::non_public_kind) {
+            return Err(LookupError::NonPublicAddress(kind));
+        }
+        let key = resolve_api_key(&self.provider.get_type(), &self.parameters);
+        let client = match &self.parameters {
+            Some(parameters) => parameters.client()?,
+            None => client::shared(),
+        };
+        for chunk in targets.chunks(limit.max(1)) {
+            match self.bulk_request(&client, key.clone(), chunk).await {
+                Ok(chunk) => responses.extend(chunk),
+                Err(e) => {
+                    warn!("Bulk lookup of {} targets failed: {}", chunk.len(), e);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        partial_responses(responses, error)
+    }
+
+    /// Sends a single request to the bulk endpoint of the provider
+    #[maybe_async::maybe_async]
+    async fn bulk_request(
+        &self,
+        client: &client::Client,
+        key: Option<String>,
+        targets: &[IpAddr],
+    ) -> Result<Vec<LookupResponse>> {
+        let mut request = self.provider.get_bulk_client(client, key, targets);
+        if let Some(parameters) = &self.parameters {
+            request = parameters.apply(request);
+        }
+        let request = with_timeout(request, self.request_timeout());
+        let (response, meta) = send_request(request, self.pins()).await?;
+        let mut responses = self
+            .provider
+            .parse_bulk_reply(response.clone())
+            .map_err(|e| diagnostics::diagnose(self.provider.get_type(), &response, e))?;
+        for response in responses.iter_mut() {
+            response.meta = meta.clone();
+            #[cfg(feature = "iso3166")]
+            response.normalize_country();
+        }
+        Ok(responses)
+    }
+
+    /// Returns the reply of providers answering without a request, see [`Provider::local_reply`]
+    #[maybe_async::maybe_async]
+    async fn local_reply(&self, target: Option<IpAddr>) -> Option<Result<String>> {
+        #[cfg(all(not(feature = "blocking"), not(target_arch = "wasm32")))]
+        if self.provider.local_reply_blocks() {
+            let provider = self.provider.get_type().build();
+            return match sync::unblock(move || provider.local_reply(target)).await {
+                Ok(reply) => reply,
+                Err(e) => Some(Err(e)),
+            };
+        }
+        self.provider.local_reply(target)
+    }
+
+    /// Internal function to make the API request
+    #[maybe_async::maybe_async]
+    async fn make_api_request(&self, target: Option<IpAddr>) -> Result<(String, LookupMeta)> {
+        if let Some(reply) = self.local_reply(target).await {
+            let meta = LookupMeta {
+                attempts: 1,
+                ..Default::default()
+            };
+            return reply.map(|reply| (reply, meta));
+        }
+        #[cfg(feature = "fixtures")]
+        let mode = self.fixtures();
+        #[cfg(feature = "fixtures")]
+        if let Some(replay @ fixtures::Fixtures::Replay(_)) = &mode {
+            let meta = LookupMeta {
+                attempts: 1,
+                ..Default::default()
+            };
+            return Ok((replay.replay(&self.provider.get_type(), target)?, meta));
+        }
+        let reply = match send_request(
+            with_timeout(self.request(target)?, self.request_timeout()),
+            self.pins(),
+        )
+        .await
+        {
+            // a preferred address family falls back to the system default
+            Err(e) if e.client_error().is_some_and(client::is_connect) => match &self.parameters {
+                Some(parameters) if parameters.family.is_some_and(|f| f.is_preference()) => {
+                    let fallback = Parameters {
+                        family: None,
+                        ..parameters.clone()
+                    };
+                    let request = self.request_with(target, &Some(fallback))?;
+                    send_request(with_timeout(request, self.request_timeout()), self.pins()).await?
+                }
+                _ => return Err(e),
+            },
+            reply => reply?,
+        };
+        #[cfg(feature = "fixtures")]
+        if let Some(record @ fixtures::Fixtures::Record(_)) = &mode {
+            record.record(&self.provider.get_type(), target, &reply.0)?;
+        }
+        Ok(reply)
+    }
+}
+
+/// Returns the responses of a bulk lookup, or its first error if none of the targets succeeded
+fn partial_responses(
+    responses: Vec<LookupResponse>,
+    error: Option<LookupError>,
+) -> Result<Vec<LookupResponse>> {
+    match error {
+        Some(e) if responses.is_empty() => Err(e),
+        _ => Ok(responses),
+    }
+}
+
+/// Fills in the target of the lookup for providers that don't echo the queried address
+///
+/// Without a target the reply carries no address at all, it fails with
+/// `LookupError::InvalidResponseIp` instead of reporting the unspecified address as the public
+/// one, so a fallback chain moves on to the next provider.
+fn fill_address(response: &mut LookupResponse, target: Option<IpAddr>) -> Result<()> {
+    if !response.ip.is_unspecified() {
+        return Ok(());
+    }
+    match target {
+        Some(target) => {
+            response.ip = target;
+            Ok(())
+        }
+        None => {
+            warn!("Provider {} answered without an address", response.provider);
+            Err(LookupError::InvalidResponseIp(String::new()))
+        }
+    }
+}
+
+/// Removes duplicate entries from a provider chain
+///
+/// Entries with the same provider and the same parameters would hit the same endpoint twice and
+/// burn through the quota, only the first occurrence is kept and a warning is logged for the rest.
+pub fn dedup_providers(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+) -> Vec<(LookupProvider, Option<Parameters>)> {
+    let mut unique: Vec<(LookupProvider, Option<Parameters>)> = Vec::with_capacity(providers.len());
+    for entry in providers {
+        if unique.contains(&entry) {
+            warn!("Skipping duplicate provider {} in the chain", entry.0);
+        } else {
+            unique.push(entry);
+        }
+    }
+    unique
+}
+
+/// Filters a provider chain down to the providers reachable over IPv6
+///
+/// Use this on IPv6-only networks, where IPv4-only endpoints can only time out.
+///
+/// # Example
+/// ```
+/// use public_ip_address::lookup::{ipv6_only, LookupProvider};
+///
+/// let providers = ipv6_only(vec![
+///     (LookupProvider::IpApiCom, None),
+///     (LookupProvider::IpifyV6, None),
+/// ]);
+/// assert_eq!(providers, vec![(LookupProvider::IpifyV6, None)]);
+/// ```
+pub fn ipv6_only(
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+) -> Vec<(LookupProvider, Option<Parameters>)> {
+    providers
+        .into_iter()
+        .filter(|(provider, _)| {
+            let supported = provider.supports_ipv6();
+            if !supported {
+                warn!("Skipping provider {} not reachable over IPv6", provider);
+            }
+            supported
+        })
+        .collect()
+}
+
+/// Endpoint answering with an empty `204 No Content` when the internet is reachable
+///
+/// It is served over plain HTTP on purpose, so that captive portals can intercept the request.
+const CONNECTIVITY_CHECK_URL: &str = "http://connectivitycheck.gstatic.com/generate_204";
+
+/// Checks if the internet is reachable before performing a lookup
+///
+/// Captive portals (e.g. hotel Wi-Fi login pages) intercept plain HTTP requests and answer with
+/// a redirect or their own page instead of `204 No Content`. This allows telling a captive portal
+/// apart from a provider outage.
+///
+/// # Example
+/// ```rust
+/// use public_ip_address::lookup::{check_connectivity, error::LookupError};
+///
+/// # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+/// # #[maybe_async::maybe_async]
+/// # async fn main() {
+/// match check_connectivity().await {
+///     Ok(()) => {
+///         // Perform the lookup
+///     }
+///     Err(LookupError::CaptivePortal(url)) => {
+///         // Ask the user to log in at `url`
+///     }
+///     Err(e) => {
+///         // Handle error
+///     }
+/// }
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// * `Ok(())` if the internet is reachable, `LookupError::CaptivePortal` with the portal address if
+///   the request was intercepted, or `LookupError::Offline` if the check endpoint could not be resolved.
+#[maybe_async::maybe_async]
+pub async fn check_connectivity() -> Result<()> {
+    let response = client::get(CONNECTIVITY_CHECK_URL).await?;
+    if response.status() == StatusCode::NO_CONTENT {
+        return Ok(());
+    }
+    warn!("Connectivity check intercepted, likely behind a captive portal");
+    Err(LookupError::CaptivePortal(response.url().to_string()))
+}
+
+/// Applies the timeout to the request
+///
+/// The browser fetch backend has no request timeouts, there the timeout is ignored.
+fn with_timeout(request: RequestBuilder, timeout: Option<Duration>) -> RequestBuilder {
+    match timeout {
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(timeout) => request.timeout(timeout),
+        _ => request,
+    }
+}
+
+/// Sends a request and records the reply metadata alongside the body
+///
+/// The reply is refused unless the certificate of the connection matches one of the pins, if any.
+#[maybe_async::maybe_async]
+async fn send_request(request: RequestBuilder, pins: &[String]) -> Result<(String, LookupMeta)> {
+    if crate::config::config().force_https {
+        ensure_https(&request)?;
+    }
+    #[cfg(not(feature = "cert-pinning"))]
+    if !pins.is_empty() {
+        return Err(LookupError::GenericError(
+            "Certificate pinning requires the cert-pinning feature".to_string(),
+        ));
+    }
+    // ureq doesn't expose the certificate, the request isn't sent unpinned
+    #[cfg(all(feature = "cert-pinning", feature = "ureq"))]
+    if !pins.is_empty() {
+        return Err(LookupError::GenericError(
+            "Certificate pinning is not supported by the ureq backend".to_string(),
+        ));
+    }
+    #[cfg(feature = "otel")]
+    if let Ok(request) = request.clone().build() {
+        crate::telemetry::record_request(&request);
+    }
+    let started = Instant::now();
+    let response = request.send().await;
+    #[cfg(feature = "cert-pinning")]
+    if let (Ok(reply), false) = (&response, pins.is_empty()) {
+        pinning::verify(reply, pins)?;
+    }
+    let mut meta = LookupMeta {
+        attempts: 1,
+        ..Default::default()
+    };
+    if let Ok(reply) = &response {
+        meta.record_reply(reply.status().as_u16(), reply.headers());
+        #[cfg(feature = "otel")]
+        crate::telemetry::record_status(reply.status().as_u16());
+    }
+    if meta.is_not_modified() {
+        meta.duration = Some(started.elapsed());
+        return Ok((String::new(), meta));
+    }
+    let body = handle_response(response).await?;
+    meta.duration = Some(started.elapsed());
+    Ok((body, meta))
+}
+
+/// Refuses requests that would be sent over plain HTTP
+fn ensure_https(request: &RequestBuilder) -> Result<()> {
+    let request = request.clone().build()?;
+    let url = request.url();
+    if url.scheme() == "https" {
+        Ok(())
+    } else {
+        warn!(
+            "Refusing plain HTTP request to {}",
+            url.host_str().unwrap_or_default()
+        );
+        Err(LookupError::InsecureEndpoint(
+            url.host_str().unwrap_or_default().to_string(),
+        ))
+    }
+}
+
+/// Handles the response of a provider request
+///
+/// Replies longer than the configured maximum response size fail with
+/// `LookupError::ResponseTooLarge`, HTML pages with `LookupError::UnexpectedContentType`.
+#[maybe_async::maybe_async]
+pub async fn handle_response(response: Result<Response>) -> Result<String> {
+    let response = response?;
+    match response.status() {
+        StatusCode::OK => read_body(response).await,
+        StatusCode::TOO_MANY_REQUESTS => Err(LookupError::TooManyRequests(format!(
+            "Too many requests: {}",
+            response.status()
+        ))),
+        s => Err(LookupError::RequestStatus(format!("Status: {}", s))),
+    }
+}
+
+/// Reads the body of a successful response, refusing HTML pages and bodies over the size limit
+///
+/// Captive portals and proxies answer with a login or error page instead of the provider reply,
+/// which would otherwise fail deserializing with a cryptic serde error. Plain text replies are
+/// accepted, some providers only answer with the address.
+#[maybe_async::maybe_async]
+async fn read_body(response: Response) -> Result<String> {
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if is_html(&content_type) {
+        return Err(LookupError::UnexpectedContentType(content_type));
+    }
+    let limit = crate::config::config().max_response_size;
+    if response
+        .content_length()
+        .is_some_and(|length| length > limit as u64)
+    {
+        return Err(LookupError::ResponseTooLarge(limit));
+    }
+    let body = response.read_limited(limit).await?;
+    let body = String::from_utf8_lossy(&body).into_owned();
+    // portals often send their page without a content type
+    if looks_like_html(&body) {
+        return Err(LookupError::UnexpectedContentType("text/html".to_string()));
+    }
+    Ok(body)
+}
+
+/// Checks if the content type is an HTML page
+fn is_html(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime == "text/html" || mime == "application/xhtml+xml"
+}
+
+/// Checks if the body starts like an HTML page
+fn looks_like_html(body: &str) -> bool {
+    let start: String = body.trim_start().chars().take(14).collect();
+    let start = start.to_ascii_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_provider() {
+        let mut provider = LookupService::new(LookupProvider::IpApiCom, None);
+        assert_eq!(provider.get_provider_type(), LookupProvider::IpApiCom);
+        provider.set_provider(LookupProvider::IpInfo);
+        assert_eq!(provider.get_provider_type(), LookupProvider::IpInfo);
+    }
+
+    #[test]
+    fn test_ensure_https() {
+        let request = client::shared().get("http://ip-api.com/json/");
+        assert!(matches!(
+            ensure_https(&request),
+            Err(LookupError::InsecureEndpoint(host)) if host == "ip-api.com"
+        ));
+        let request = client::shared().get("https://ipwho.is/");
+        assert!(ensure_https(&request).is_ok());
+    }
+
+    #[test]
+    fn test_disabled_provider() {
+        assert!(LookupProvider::Mock("1.1.1.1".to_string()).is_enabled());
+        let provider = disabled::Disabled(LookupProvider::IpInfo);
+        assert_eq!(provider.get_type(), LookupProvider::IpInfo);
+        assert!(matches!(
+            provider.parse_reply(String::new()),
+            Err(LookupError::ProviderDisabled(_))
+        ));
+    }
+
+    #[test]
+    fn test_lookup_options() {
+        let options = LookupOptions::default();
+        assert_eq!(options.total_timeout, None);
+        assert_eq!(options.per_provider_timeout, None);
+        let options = options
+            .with_total_timeout(Duration::from_secs(2))
+            .with_per_provider_timeout(Duration::from_millis(500));
+        assert_eq!(options.total_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(
+            options.per_provider_timeout,
+            Some(Duration::from_millis(500))
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_make_request() {
+        let address = "1.1.1.1".parse::<std::net::IpAddr>().unwrap();
+        let provider = LookupService::new(LookupProvider::Mock(address.to_string()), None);
+        let response = provider.lookup(None).await.unwrap();
+        assert_eq!(response.ip, address);
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_handle_response() {
+        let response = client::get("https://httpbin.org/status/200").await;
+        let body = handle_response(response).await;
+        assert!(body.is_ok(), "Response is an error {:#?}", body);
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_handle_response_error() {
         let response = client::get("https://httpbin.org/status/500").await;
         let body = handle_response(response).await;
         assert!(body.is_err(), "Response should be an error {:#?}", body);
@@ -382,6 +2173,23 @@ mod tests {
         );
     }
 
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_handle_response_offline() {
+        let response = client::get("https://public-ip-address.invalid").await;
+        let body = handle_response(response).await;
+        assert!(
+            matches!(body, Err(LookupError::Offline(_))),
+            "Response should be an offline error {:#?}",
+            body
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_check_connectivity() {
+        let result = check_connectivity().await;
+        assert!(result.is_ok(), "Connectivity check failed {:#?}", result);
+    }
+
     #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
     async fn test_target_supported() {
         let address = "8.8.8.8".parse::<std::net::IpAddr>().unwrap();
@@ -402,6 +2210,455 @@ mod tests {
         );
     }
 
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_lookup_asn() {
+        let address = "1.1.1.1".parse::<std::net::IpAddr>().unwrap();
+        let service = LookupService::new(LookupProvider::Mock("full:1.1.1.1".to_string()), None);
+        let response = service.lookup_asn(Some(address)).await.unwrap();
+        assert_eq!(response.asn, Some("AS13335".to_string()));
+
+        let service = LookupService::new(LookupProvider::IpWhoIs, None);
+        let response = service.lookup_asn(Some(address)).await;
+        assert!(
+            matches!(response, Err(LookupError::AsnNotSupported)),
+            "Provider without ASN lookups should fail, got {:?}",
+            response
+        );
+
+        #[cfg(feature = "provider-bgpview")]
+        {
+            let service = LookupService::new(LookupProvider::BgpView, None);
+            let response = service.lookup_asn(None).await;
+            assert!(
+                matches!(response, Err(LookupError::TargetRequired)),
+                "Lookup without a target should fail, got {:?}",
+                response
+            );
+        }
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_health_check() {
+        let service = LookupService::new(LookupProvider::Mock("1.1.1.1".to_string()), None);
+        let health = service.health_check().await;
+        assert_eq!(health.provider, LookupProvider::Mock("1.1.1.1".to_string()));
+        assert!(
+            health.is_healthy(),
+            "Health check failed: {:?}",
+            health.error
+        );
+
+        let provider = LookupProvider::Custom("http://127.0.0.1:1/ip".to_string());
+        let service = LookupService::new(provider, None);
+        let health = service.health_check().await;
+        assert!(!health.is_healthy());
+        assert!(
+            !health.reachable,
+            "Forbidden endpoint should not be reached"
+        );
+        assert!(health.error.is_some());
+    }
+
+    #[test]
+    fn test_dedup_providers() {
+        let key = Some(Parameters::new("abc".to_string()));
+        let providers = dedup_providers(vec![
+            (LookupProvider::IpInfo, None),
+            (LookupProvider::IpData, key.clone()),
+            (LookupProvider::IpInfo, None),
+            (LookupProvider::IpData, None),
+            (LookupProvider::IpData, key.clone()),
+        ]);
+        assert_eq!(
+            providers,
+            vec![
+                (LookupProvider::IpInfo, None),
+                (LookupProvider::IpData, key),
+                (LookupProvider::IpData, None),
+            ],
+            "Duplicates should be removed"
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_bulk_lookup_partial() {
+        let targets: [IpAddr; 2] = ["1.1.1.1".parse().unwrap(), "8.8.8.8".parse().unwrap()];
+        let service = LookupService::new(LookupProvider::Mock("429,1.1.1.1".to_string()), None);
+        let responses = service.bulk_lookup(&targets).await.unwrap();
+        assert_eq!(responses.len(), 1, "Successful lookups should be kept");
+
+        let service = LookupService::new(LookupProvider::Mock("429".to_string()), None);
+        let responses = service.bulk_lookup(&targets).await;
+        assert!(
+            matches!(responses, Err(LookupError::TooManyRequests(_))),
+            "Lookup should fail when every target failed, got {:?}",
+            responses
+        );
+    }
+
+    #[test]
+    fn test_fill_address() {
+        let unspecified = IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED);
+        let target: IpAddr = "1.1.1.1".parse().unwrap();
+        let mut response = LookupResponse::new(unspecified, LookupProvider::RipeStat);
+        fill_address(&mut response, Some(target)).unwrap();
+        assert_eq!(response.ip, target, "Target should be filled in");
+
+        let mut response = LookupResponse::new(unspecified, LookupProvider::IpInfo);
+        let result = fill_address(&mut response, None);
+        assert!(
+            matches!(result, Err(LookupError::InvalidResponseIp(_))),
+            "Reply without an address should fail, got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_ipv6_only() {
+        let providers = ipv6_only(vec![
+            (LookupProvider::IpApiCom, None),
+            (LookupProvider::Ipify, None),
+            (LookupProvider::IpifyV4, None),
+            (LookupProvider::GetJsonIp, None),
+            (LookupProvider::IpifyV6, None),
+        ]);
+        assert_eq!(
+            providers,
+            vec![
+                (LookupProvider::Ipify, None),
+                (LookupProvider::IpifyV6, None),
+            ],
+            "IPv4 only providers should be removed"
+        );
+    }
+
+    #[test]
+    fn test_api_key_env() {
+        assert_eq!(
+            LookupProvider::IpData.api_key_env(),
+            Some("PUBLIC_IP_IPDATA_APIKEY".to_string())
+        );
+        assert_eq!(
+            LookupProvider::Mock("1.1.1.1".to_string()).api_key_env(),
+            None
+        );
+
+        env::set_var("PUBLIC_IP_IPGEOLOCATION_APIKEY", " abc ");
+        assert_eq!(
+            resolve_api_key(&LookupProvider::IpGeolocation, &None),
+            Some("abc".to_string())
+        );
+        assert_eq!(
+            resolve_api_key(
+                &LookupProvider::IpGeolocation,
+                &Some(Parameters::new("xyz".to_string()))
+            ),
+            Some("xyz".to_string()),
+            "Given parameters should take precedence"
+        );
+        assert_eq!(
+            resolve_api_key(
+                &LookupProvider::IpGeolocation,
+                &Some(Parameters::default().with_language("de"))
+            ),
+            Some("abc".to_string()),
+            "Parameters without a key should fall back to the environment"
+        );
+        env::remove_var("PUBLIC_IP_IPGEOLOCATION_APIKEY");
+        assert_eq!(resolve_api_key(&LookupProvider::IpGeolocation, &None), None);
+    }
+
+    #[test]
+    fn test_parameters() {
+        let parameters = Parameters::new("abc".to_string())
+            .with_header("X-Client", "test")
+            .with_query("format", "json")
+            .with_language("de")
+            .with_option("fields", "query");
+        assert_eq!(parameters.option("fields"), Some("query"));
+        assert_eq!(parameters.option("lang"), None);
+
+        let request = LookupProvider::IpWhoIs
+            .build()
+            .get_client_with_parameters(None, None, &parameters)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://ipwho.is/?lang=de&format=json"
+        );
+        assert_eq!(request.headers()["X-Client"], "test");
+        assert_eq!(request.headers()["Accept-Language"], "de");
+
+        let json = serde_json::to_string(&parameters).unwrap();
+        let parsed: Parameters = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, parameters, "Parameters should round trip");
+        let parsed: Parameters = serde_json::from_str(r#"{"api_key": "abc"}"#).unwrap();
+        assert_eq!(parsed, Parameters::new("abc".to_string()));
+    }
+
+    #[test]
+    fn test_request_method() {
+        struct Token;
+        impl Provider for Token {
+            fn get_endpoint(&self, _key: &Option<String>, _target: &Option<IpAddr>) -> String {
+                "https://example.com/token".to_string()
+            }
+            fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+                LookupProvider::IpWhoIs.build().parse_reply(json)
+            }
+            fn get_type(&self) -> LookupProvider {
+                LookupProvider::IpWhoIs
+            }
+            fn get_method(&self) -> Method {
+                Method::POST
+            }
+            fn get_body(
+                &self,
+                key: &Option<String>,
+                target: &Option<IpAddr>,
+            ) -> Option<serde_json::Value> {
+                Some(serde_json::json!({"key": key, "ip": target}))
+            }
+        }
+
+        let target = "1.1.1.1".parse().ok();
+        let parameters = Parameters::default().with_header("X-Client", "test");
+        let request = Token
+            .get_client_with_parameters(Some("abc".to_string()), target, &parameters)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.headers()["Content-Type"], "application/json");
+        assert_eq!(
+            request.body(),
+            Some(r#"{"ip":"1.1.1.1","key":"abc"}"#.as_bytes())
+        );
+        let request = LookupProvider::IpWhoIs
+            .build()
+            .get_client(None, None)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.method(),
+            Method::GET,
+            "Providers should default to GET"
+        );
+        assert!(request.body().is_none());
+    }
+
+    // ureq can't bind its connections, see the tests of its backend
+    #[cfg(not(feature = "ureq"))]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_bound_parameters() {
+        use std::{io::Write, net::TcpListener};
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, peer) = listener.accept().unwrap();
+            let mut buffer = [0; 1024];
+            let _ = std::io::Read::read(&mut stream, &mut buffer);
+            let _ = stream.write_all(b"HTTP/1.0 200 OK\r\nContent-Length: 7\r\n\r\n1.2.3.4");
+            peer.ip()
+        });
+
+        let parameters = Parameters::default().with_local_address("127.0.0.1".parse().unwrap());
+        let result = parameters.client().unwrap().get(&endpoint).send().await;
+        let body = handle_response(result).await.unwrap();
+        assert_eq!(body, "1.2.3.4");
+        assert_eq!(
+            server.join().unwrap(),
+            "127.0.0.1".parse::<IpAddr>().unwrap(),
+            "Request should be sent from the bound address"
+        );
+
+        let parameters = parameters.with_interface("wg0");
+        let json = serde_json::to_string(&parameters).unwrap();
+        let parsed: Parameters = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, parameters, "Binding should round trip");
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_handle_response_body() {
+        use std::{io::Write, net::TcpListener};
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let oversized = "1".repeat(crate::config::config().max_response_size + 1);
+        let replies = [
+            "HTTP/1.0 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<p>Login</p>"
+                .to_string(),
+            "HTTP/1.0 200 OK\r\n\r\n  <!DOCTYPE html><html></html>".to_string(),
+            format!("HTTP/1.0 200 OK\r\n\r\n{}", oversized),
+            "HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\n1.2.3.4".to_string(),
+        ];
+        let server = std::thread::spawn(move || {
+            for reply in replies {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0; 1024];
+                let _ = std::io::Read::read(&mut stream, &mut buffer);
+                let _ = stream.write_all(reply.as_bytes());
+            }
+        });
+
+        let client = client::Client::new();
+        let result = handle_response(client.get(&endpoint).send().await).await;
+        assert!(
+            matches!(result, Err(LookupError::UnexpectedContentType(ref t)) if t.starts_with("text/html")),
+            "HTML page should be refused, got {:?}",
+            result
+        );
+        let result = handle_response(client.get(&endpoint).send().await).await;
+        assert!(
+            matches!(result, Err(LookupError::UnexpectedContentType(_))),
+            "HTML page without content type should be refused, got {:?}",
+            result
+        );
+        let result = handle_response(client.get(&endpoint).send().await).await;
+        assert!(
+            matches!(result, Err(LookupError::ResponseTooLarge(_))),
+            "Oversized body should be refused, got {:?}",
+            result
+        );
+        let result = handle_response(client.get(&endpoint).send().await).await;
+        assert_eq!(result.unwrap(), "1.2.3.4");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_is_html() {
+        assert!(is_html("text/html"));
+        assert!(is_html("text/html; charset=utf-8"));
+        assert!(!is_html("application/json"));
+        assert!(looks_like_html("\n<HTML><body>Login</body></HTML>"));
+        assert!(!looks_like_html("{\"ip\": \"1.2.3.4\"}"));
+    }
+
+    #[test]
+    fn test_certificate_parameters() {
+        let parameters = Parameters::default()
+            .with_pin("sha256/8cnf/bwW+tjcSSYLKCYaSjk4CIY0DR0PqXyrSx3BRgY=")
+            .with_root_certificate("not a certificate");
+        assert!(
+            parameters.is_bound(),
+            "Root certificates should need their own client"
+        );
+        let json = serde_json::to_string(&parameters).unwrap();
+        let parsed: Parameters = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, parameters, "Certificates should round trip");
+        assert!(
+            parameters.client().is_err(),
+            "Invalid certificate should fail instead of trusting the system roots"
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_address_family() {
+        use std::{io::Write, net::TcpListener};
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 1024];
+            let _ = std::io::Read::read(&mut stream, &mut buffer);
+            let _ = stream.write_all(b"HTTP/1.0 200 OK\r\nContent-Length: 7\r\n\r\n1.2.3.4");
+        });
+
+        let parameters = Parameters::default().with_family(AddressFamily::RequireV6);
+        let result = parameters.client().unwrap().get(&endpoint).send().await;
+        assert!(
+            result
+                .as_ref()
+                .is_err_and(|e| e.client_error().is_some_and(client::is_connect)),
+            "IPv4 endpoint should not be reached over IPv6, got {:?}",
+            result
+        );
+
+        let parameters = Parameters::default().with_family(AddressFamily::RequireV4);
+        let result = parameters.client().unwrap().get(&endpoint).send().await;
+        let body = handle_response(result).await.unwrap();
+        assert_eq!(body, "1.2.3.4");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_shared_service() {
+        fn assert_shareable<T: Clone + Send + Sync>() {}
+        assert_shareable::<LookupService>();
+        assert!(std::ptr::eq(default_service(), default_service()));
+
+        let mut service = LookupService::new(LookupProvider::IpInfo, None);
+        service.set_timeout(Duration::from_secs(1));
+        let clone = service.clone();
+        assert_eq!(clone.get_provider_type(), LookupProvider::IpInfo);
+        assert_eq!(clone.timeout, Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_conditional_request() {
+        let cached = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpInfo);
+        let mut service = LookupService::new(LookupProvider::IpInfo, None);
+        service.set_cached("\"abc\"".to_string(), cached.clone());
+        let request = service.request(None).unwrap().build().unwrap();
+        assert_eq!(request.headers()["If-None-Match"], "\"abc\"");
+
+        let mut meta = LookupMeta::default();
+        meta.record_reply(304, &client::header::HeaderMap::new());
+        let response = service.revalidated(meta).unwrap();
+        assert_eq!(response.ip, cached.ip);
+        assert!(response.meta.is_not_modified());
+        assert_eq!(
+            response.meta.etag(),
+            Some("\"abc\""),
+            "Entity tag should be kept for the next revalidation"
+        );
+
+        let mut service = LookupService::new(LookupProvider::IpWhoIs, None);
+        service.set_cached("\"abc\"".to_string(), cached);
+        let request = service.request(None).unwrap().build().unwrap();
+        assert!(
+            !request.headers().contains_key("If-None-Match"),
+            "Providers without ETags should get plain requests"
+        );
+        assert!(LookupProvider::IpData.capabilities().supports_etag);
+    }
+
+    #[test]
+    fn test_iter() {
+        use std::collections::HashSet;
+        let providers: HashSet<&LookupProvider> = LookupProvider::iter().collect();
+        assert_eq!(
+            providers.len(),
+            ALL_PROVIDERS.len(),
+            "Providers should be unique"
+        );
+        assert!(LookupProvider::iter().eq(LookupProvider::all().collect::<Vec<_>>().iter()));
+        assert!(providers.contains(&LookupProvider::IpWhoIs));
+        assert!(providers.contains(&LookupProvider::Gateway));
+        assert!(!providers.contains(&LookupProvider::Mock("1.1.1.1".to_string())));
+    }
+
+    #[test]
+    fn test_capabilities() {
+        assert_eq!(LookupProvider::all().count(), ALL_PROVIDERS.len());
+        assert!(LookupProvider::all().all(|p| !matches!(p, LookupProvider::Mock(_))));
+
+        let capabilities = LookupProvider::IpData.capabilities();
+        assert!(capabilities.requires_key);
+        assert!(capabilities.supports_target);
+        assert!(capabilities.https_only);
+        assert!(!LookupProvider::Akamai.capabilities().https_only);
+        assert!(LookupProvider::IpApiCom.capabilities().supports_asn);
+        assert!(!LookupProvider::IpWhoIs.capabilities().supports_asn);
+
+        let keyless: Vec<LookupProvider> = LookupProvider::all()
+            .filter(|p| !p.capabilities().requires_key)
+            .collect();
+        assert!(keyless.contains(&LookupProvider::IpWhoIs));
+        assert!(!keyless.contains(&LookupProvider::IpStack));
+    }
+
     #[test]
     fn test_conversions() {
         let provider = LookupProvider::from_str("freeipapi").unwrap();
@@ -409,6 +2666,70 @@ mod tests {
 
         let provider = LookupProvider::from_str("unknown");
         assert!(provider.is_err(), "Conversion should fail");
+
+        for provider in LookupProvider::all() {
+            let name = provider.to_string();
+            assert_eq!(
+                name.parse::<LookupProvider>().unwrap(),
+                provider,
+                "{}",
+                name
+            );
+            for alias in provider.aliases() {
+                assert_eq!(
+                    alias.parse::<LookupProvider>().unwrap(),
+                    provider,
+                    "{}",
+                    alias
+                );
+            }
+        }
+        assert_eq!(LookupProvider::IpApiCom.to_string(), "ipapicom");
+        assert_eq!(
+            LookupProvider::from_str("ip-api.com").unwrap(),
+            LookupProvider::IpApiCom
+        );
+        assert_eq!(
+            LookupProvider::from_str("IpWhoIs").unwrap(),
+            LookupProvider::IpWhoIs
+        );
+        assert_eq!(
+            LookupProvider::from_str("ip_locate_io").unwrap(),
+            LookupProvider::IpLocateIo
+        );
+
+        let provider = LookupProvider::Custom("https://example.com/IP".to_string());
+        assert_eq!(provider.to_string(), "custom:https://example.com/IP");
+        assert_eq!(
+            provider.to_string().parse::<LookupProvider>().unwrap(),
+            provider
+        );
+        let provider = LookupProvider::Mock("1.1.1.1".to_string());
+        assert_eq!(
+            provider.to_string().parse::<LookupProvider>().unwrap(),
+            provider
+        );
+    }
+
+    #[test]
+    fn test_serde() {
+        let json = serde_json::to_string(&LookupProvider::IpApiCo).unwrap();
+        assert_eq!(json, r#""ipapico""#);
+        let provider: LookupProvider = serde_json::from_str(&json).unwrap();
+        assert_eq!(provider, LookupProvider::IpApiCo);
+
+        let provider: LookupProvider = serde_json::from_str(r#""IpWhoIs""#).unwrap();
+        assert_eq!(
+            provider,
+            LookupProvider::IpWhoIs,
+            "Old names should still load"
+        );
+        let provider: LookupProvider = serde_json::from_str(r#"{"Mock":"1.1.1.1"}"#).unwrap();
+        assert_eq!(provider, LookupProvider::Mock("1.1.1.1".to_string()));
+        let json = serde_json::to_string(&provider).unwrap();
+        assert_eq!(json, r#""mock:1.1.1.1""#);
+
+        assert!(serde_json::from_str::<LookupProvider>(r#""unknown""#).is_err());
     }
 
     #[test]
@@ -417,12 +2738,17 @@ mod tests {
         assert_eq!(provider, LookupProvider::IpData, "Conversion failed");
         assert_eq!(
             parameters,
-            Some(Parameters {
-                api_key: "abc".to_string()
-            }),
+            Some(Parameters::new("abc".to_string())),
             "Parameter conversion failed"
         );
 
+        let (_, parameters) = LookupProvider::from_str_with_params("IpData AbC").unwrap();
+        assert_eq!(
+            parameters.unwrap().api_key,
+            "AbC",
+            "The key should keep its case"
+        );
+
         let (provider, parameters) = LookupProvider::from_str_with_params("ipdata").unwrap();
         assert_eq!(provider, LookupProvider::IpData, "Conversion failed");
         assert_eq!(parameters, None, "Parameter conversion failed");
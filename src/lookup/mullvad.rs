@@ -1,12 +1,12 @@
 //! <https://mullvad.net> lookup provider
 
-use super::Result;
+use super::{parse_response_ip, Result};
 use crate::{
     lookup::{LookupProvider, Provider, ProviderResponse},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://mullvad.net/>
 #[derive(Serialize, Deserialize, Debug)]
@@ -21,20 +21,16 @@ pub struct MullvadResponse {
 }
 
 impl ProviderResponse<MullvadResponse> for MullvadResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::Mullvad,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::Mullvad);
         response.country = self.country;
         response.city = self.city;
         response.latitude = self.latitude;
         response.longitude = self.longitude;
         response.asn_org = self.organization;
         response.is_proxy = self.mullvad_exit_ip;
-        response
+        Ok(response)
     }
 }
 
@@ -48,12 +44,16 @@ impl Provider for Mullvad {
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
         let response = MullvadResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::Mullvad
     }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -91,7 +91,7 @@ mod tests {
     fn test_parse() {
         let response = MullvadResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "8.8.8.8", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "8.8.8.8".parse::<std::net::IpAddr>().unwrap(),
@@ -1,12 +1,12 @@
 //! <https://my-ip.io> lookup provider
 
-use super::Result;
+use super::{parse_response_ip, parse_versioned, Result};
 use crate::{
     lookup::{LookupProvider, Provider, ProviderResponse},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://www.my-ip.io/api-usage>
 #[derive(Serialize, Deserialize, Debug)]
@@ -44,13 +44,8 @@ struct Asn {
 }
 
 impl ProviderResponse<MyIpResponse> for MyIpResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::MyIp,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response = LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::MyIp);
         if let Some(country) = self.country {
             response.country = country.name;
             response.country_code = country.code;
@@ -64,11 +59,39 @@ impl ProviderResponse<MyIpResponse> for MyIpResponse {
         response.time_zone = self.time_zone;
         if let Some(asn) = self.asn {
             response.asn_org = asn.name;
+            response.network = asn.network;
             if let Some(number) = asn.number {
                 response.asn = Some(format!("{number}"));
             }
         }
-        response
+        Ok(response)
+    }
+}
+
+/// Response of the v1 API, which reports the country as a plain code
+///
+/// <https://www.my-ip.io/api-usage>
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MyIpResponseV1 {
+    success: bool,
+    ip: String,
+    #[serde(rename = "type")]
+    ip_type: Option<String>,
+    country: Option<String>,
+    region: Option<String>,
+    city: Option<String>,
+    time_zone: Option<String>,
+}
+
+impl ProviderResponse<MyIpResponseV1> for MyIpResponseV1 {
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response = LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::MyIp);
+        response.country_code = self.country;
+        response.region = self.region;
+        response.city = self.city;
+        response.time_zone = self.time_zone;
+        Ok(response)
     }
 }
 
@@ -81,8 +104,13 @@ impl Provider for MyIp {
     }
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
-        let response = MyIpResponse::parse(json)?;
-        Ok(response.into_response())
+        parse_versioned(
+            json,
+            &[
+                |json| MyIpResponse::parse(json)?.into_response(),
+                |json| MyIpResponseV1::parse(json)?.into_response(),
+            ],
+        )
     }
 
     fn get_type(&self) -> LookupProvider {
@@ -133,11 +161,37 @@ mod tests {
     fn test_parse() {
         let response = MyIpResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
+        assert_eq!(
+            lookup.ip,
+            "1.1.1.1".parse::<std::net::IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let input = r#"
+{
+  "success": true,
+  "ip": "1.1.1.1",
+  "type": "IPv4",
+  "country": "DE",
+  "region": "Bavaria",
+  "city": "Gunzenhausen",
+  "timeZone": "Europe/Berlin"
+}
+"#;
+        let lookup = MyIp.parse_reply(input.to_string()).unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<std::net::IpAddr>().unwrap(),
             "IP address not matching"
         );
+        assert_eq!(lookup.country_code, Some("DE".to_string()));
+        assert_eq!(lookup.country, None, "Country name not reported by v1");
+
+        let lookup = MyIp.parse_reply(TEST_INPUT.to_string()).unwrap();
+        assert_eq!(lookup.country, Some("Germany".to_string()));
     }
 }
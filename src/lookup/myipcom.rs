@@ -1,12 +1,12 @@
 //! <https://myip.com> lookup provider
 
-use super::{ProviderResponse, Result};
+use super::{parse_response_ip, ProviderResponse, Result};
 use crate::{
-    lookup::{LookupProvider, Provider},
+    lookup::{DailyQuota, LookupProvider, Provider},
     LookupResponse,
 };
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 
 /// <https://www.myip.com/api-docs>
 #[derive(Serialize, Deserialize, Debug)]
@@ -17,17 +17,13 @@ pub struct MyIpComResponse {
 }
 
 impl ProviderResponse<MyIpComResponse> for MyIpComResponse {
-    fn into_response(self) -> LookupResponse {
-        let mut response = LookupResponse::new(
-            self.ip
-                .parse()
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            LookupProvider::MyIpCom,
-        );
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response =
+            LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::MyIpCom);
         response.country = self.country;
         response.country_code = self.cc;
 
-        response
+        Ok(response)
     }
 }
 
@@ -41,12 +37,16 @@ impl Provider for MyIpCom {
 
     fn parse_reply(&self, json: String) -> Result<LookupResponse> {
         let response = MyIpComResponse::parse(json)?;
-        Ok(response.into_response())
+        response.into_response()
     }
 
     fn get_type(&self) -> LookupProvider {
         LookupProvider::MyIpCom
     }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Unlimited)
+    }
 }
 
 #[cfg(test)]
@@ -75,7 +75,7 @@ mod tests {
     fn test_parse() {
         let response = MyIpComResponse::parse(TEST_INPUT.to_string()).unwrap();
         assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
-        let lookup = response.into_response();
+        let lookup = response.into_response().unwrap();
         assert_eq!(
             lookup.ip,
             "1.1.1.1".parse::<std::net::IpAddr>().unwrap(),
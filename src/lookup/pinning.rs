@@ -0,0 +1,169 @@
+//! Certificate pinning of the provider connections
+//!
+//! A pin is the SHA-256 hash of the SubjectPublicKeyInfo of the provider certificate, encoded in
+//! base64 and optionally prefixed with `sha256/`, the format of curl's `--pinnedpubkey`. The pin
+//! of a provider is printed by:
+//! ```sh
+//! openssl s_client -connect ipinfo.io:443 </dev/null | openssl x509 -pubkey -noout \
+//!     | openssl pkey -pubin -outform der | openssl dgst -sha256 -binary | base64
+//! ```
+//! Only the leaf certificate is exposed by the TLS backend, so the pin changes whenever the
+//! provider renews its key. The pin is checked once the reply arrives, before its body is read,
+//! the request itself, API key included, is already sent by then. Root certificates set with
+//! [`Parameters::with_root_certificate`](super::Parameters::with_root_certificate) are checked
+//! during the handshake instead.
+//!
+//! Enabled through the `cert-pinning` feature flag.
+
+use super::{
+    client::Response,
+    error::{LookupError, Result},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::warn;
+use sha2::{Digest, Sha256};
+
+/// Returns the pin of a DER encoded certificate, `None` if the certificate can't be parsed
+pub fn pin(certificate: &[u8]) -> Option<String> {
+    let spki = subject_public_key_info(certificate)?;
+    Some(format!("sha256/{}", STANDARD.encode(Sha256::digest(spki))))
+}
+
+/// Checks that the certificate of the connection the response was received over matches one of
+/// the pins
+pub(crate) fn verify(response: &Response, pins: &[String]) -> Result<()> {
+    let pin = response.peer_certificate().and_then(pin);
+    let host = response.url().host_str().unwrap_or_default().to_string();
+    match pin {
+        Some(pin) if pins.iter().any(|expected| matches(expected, &pin)) => Ok(()),
+        pin => {
+            warn!(
+                "Certificate of {} does not match its pins, got {}",
+                host,
+                pin.as_deref().unwrap_or("no certificate")
+            );
+            Err(LookupError::PinMismatch(host))
+        }
+    }
+}
+
+/// Compares an expected pin, with or without its `sha256/` prefix, to a computed one
+fn matches(expected: &str, pin: &str) -> bool {
+    let expected = expected.trim();
+    let expected = expected.strip_prefix("sha256/").unwrap_or(expected);
+    pin.strip_prefix("sha256/") == Some(expected)
+}
+
+/// DER element at the start of an input
+struct Element<'a> {
+    tag: u8,
+    /// Whole encoding of the element, header included
+    encoded: &'a [u8],
+    contents: &'a [u8],
+    /// Input following the element
+    rest: &'a [u8],
+}
+
+/// Parses the DER element at the start of the input
+fn element(input: &[u8]) -> Option<Element<'_>> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (length, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        // long form, the low bits count the length bytes
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |length, &byte| length << 8 | byte as usize);
+        (length, &rest[count..])
+    };
+    if rest.len() < length {
+        return None;
+    }
+    let header = input.len() - rest.len();
+    Some(Element {
+        tag,
+        encoded: &input[..header + length],
+        contents: &rest[..length],
+        rest: &rest[length..],
+    })
+}
+
+/// Returns the DER encoded SubjectPublicKeyInfo of a certificate
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+    const VERSION: u8 = 0xa0;
+    let sequence = |input| element(input).filter(|element| element.tag == SEQUENCE);
+    let certificate = sequence(certificate)?.contents;
+    let mut fields = sequence(certificate)?.contents;
+    // the version is omitted for v1 certificates
+    if fields.first() == Some(&VERSION) {
+        fields = element(fields)?.rest;
+    }
+    // serial number, signature algorithm, issuer, validity and subject
+    for _ in 0..5 {
+        fields = element(fields)?.rest;
+    }
+    Some(sequence(fields)?.encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // self-signed P-256 certificate for localhost
+    const TEST_CERTIFICATE: &str = "
+MIIBfzCCASWgAwIBAgIUbrKEOzm1QrDEJHSdXySri89XVtcwCgYIKoZIzj0EAwIw
+FDESMBAGA1UEAwwJbG9jYWxob3N0MCAXDTI2MTAxNjE0MDE0NFoYDzIxMjYwOTIy
+MTQwMTQ0WjAUMRIwEAYDVQQDDAlsb2NhbGhvc3QwWTATBgcqhkjOPQIBBggqhkjO
+PQMBBwNCAASTNlNUCrGf4bFKVPT/7QvEjWEpHxgEkb/ESBLlrrndLTE/+9UpK62x
+i9/lWvIjy6hCZT6tPsEiBxU8dxopo/wQo1MwUTAdBgNVHQ4EFgQU0ZP61qprv6Ri
+B9Ukw4g+8DDqM90wHwYDVR0jBBgwFoAU0ZP61qprv6RiB9Ukw4g+8DDqM90wDwYD
+VR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNIADBFAiBhBbPVuZ67XARxphWSLEWA
+0DPSjnj8CF5VgiwXCSOnGgIhAPHU8m+3ljWKlGSJeW352Fu4hDhs3oCZvmo5ygra
+iHS3";
+    // computed with openssl
+    const TEST_PIN: &str = "sha256/8cnf/bwW+tjcSSYLKCYaSjk4CIY0DR0PqXyrSx3BRgY=";
+
+    #[test]
+    fn test_pin() {
+        let der: String = TEST_CERTIFICATE.split_whitespace().collect();
+        let der = STANDARD.decode(der).unwrap();
+        assert_eq!(pin(&der).as_deref(), Some(TEST_PIN));
+        assert_eq!(pin(&der[..100]), None, "Truncated certificate should fail");
+        assert_eq!(pin(b"not a certificate"), None);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(TEST_PIN, TEST_PIN));
+        assert!(matches(&TEST_PIN[7..], TEST_PIN));
+        assert!(!matches("sha256/AAAA", TEST_PIN));
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_verify_plain_http() {
+        use std::{io::Write, net::TcpListener};
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 1024];
+            let _ = std::io::Read::read(&mut stream, &mut buffer);
+            let _ = stream.write_all(b"HTTP/1.0 200 OK\r\nContent-Length: 7\r\n\r\n1.2.3.4");
+        });
+        let response = super::super::client::shared()
+            .get(&endpoint)
+            .send()
+            .await
+            .unwrap();
+        assert!(
+            matches!(verify(&response, &[TEST_PIN.to_string()]), Err(LookupError::PinMismatch(host)) if host == "127.0.0.1"),
+            "Connection without certificate should fail the pins"
+        );
+        server.join().unwrap();
+    }
+}
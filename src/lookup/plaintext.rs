@@ -0,0 +1,160 @@
+//! Plain text lookup providers
+//!
+//! These providers answer with a `text/plain` body containing nothing but the IP address.
+//! They are fast and reliable for IP-only lookups, but provide no geolocation information.
+
+use super::{LookupError, Result};
+use crate::{
+    lookup::{DailyQuota, LookupProvider, Provider},
+    LookupResponse,
+};
+use std::net::IpAddr;
+
+/// Generic plain text lookup provider
+pub struct PlainText {
+    endpoint: String,
+    provider: LookupProvider,
+    ipv6: bool,
+    daily_quota: Option<DailyQuota>,
+}
+
+impl PlainText {
+    /// Creates a new plain text provider for the given endpoint
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - URL answering with the IP address as plain text.
+    /// * `provider` - `LookupProvider` reported in the responses.
+    /// * `ipv6` - Whether the endpoint is reachable over IPv6.
+    pub fn new(endpoint: &str, provider: LookupProvider, ipv6: bool) -> Self {
+        PlainText {
+            endpoint: endpoint.to_string(),
+            provider,
+            ipv6,
+            daily_quota: None,
+        }
+    }
+
+    /// Sets the number of requests allowed per day
+    pub fn with_daily_quota(mut self, quota: DailyQuota) -> Self {
+        self.daily_quota = Some(quota);
+        self
+    }
+}
+
+/// Parses a plain text body containing only an IP address
+pub fn parse_plain_text(body: &str, provider: LookupProvider) -> Result<LookupResponse> {
+    let ip = body
+        .trim()
+        .parse::<IpAddr>()
+        .map_err(|_| LookupError::InvalidResponseIp(body.trim().to_string()))?;
+    Ok(LookupResponse::new(ip, provider))
+}
+
+impl Provider for PlainText {
+    fn get_endpoint(&self, _key: &Option<String>, _target: &Option<IpAddr>) -> String {
+        self.endpoint.to_owned()
+    }
+
+    fn parse_reply(&self, body: String) -> Result<LookupResponse> {
+        parse_plain_text(&body, self.get_type())
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        self.provider.to_owned()
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        self.daily_quota
+    }
+
+    fn supports_ipv6(&self) -> bool {
+        self.ipv6
+    }
+}
+
+/// IfConfigMe lookup provider (<https://ifconfig.me>)
+pub fn ifconfig_me() -> PlainText {
+    PlainText::new("https://ifconfig.me/ip", LookupProvider::IfConfigMe, true)
+}
+
+/// ICanHazIp lookup provider (<https://icanhazip.com>)
+pub fn icanhazip() -> PlainText {
+    PlainText::new("https://icanhazip.com", LookupProvider::ICanHazIp, true)
+        .with_daily_quota(DailyQuota::Unlimited)
+}
+
+/// IdentMe lookup provider (<https://ident.me>)
+pub fn ident_me() -> PlainText {
+    PlainText::new("https://ident.me", LookupProvider::IdentMe, true)
+        .with_daily_quota(DailyQuota::Unlimited)
+}
+
+/// AWS checkip lookup provider (<https://checkip.amazonaws.com>)
+pub fn aws_checkip() -> PlainText {
+    PlainText::new(
+        "https://checkip.amazonaws.com",
+        LookupProvider::AwsCheckIp,
+        false,
+    )
+    .with_daily_quota(DailyQuota::Unlimited)
+}
+
+/// Akamai lookup provider (<http://whatismyip.akamai.com>)
+pub fn akamai() -> PlainText {
+    PlainText::new(
+        "http://whatismyip.akamai.com/",
+        LookupProvider::Akamai,
+        false,
+    )
+    .with_daily_quota(DailyQuota::Unlimited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_request() {
+        let service = Box::new(icanhazip());
+        let result = service.get_client(None, None).send().await;
+        let result = super::super::handle_response(result).await.unwrap();
+        assert!(!result.is_empty(), "Result is empty");
+        println!("ICanHazIp: {:#?}", result);
+        let response = service.parse_reply(result);
+        assert!(response.is_ok(), "Failed parsing response {:#?}", response);
+    }
+
+    #[test]
+    fn test_parse() {
+        let lookup = ident_me().parse_reply("1.1.1.1\n".to_string()).unwrap();
+        assert_eq!(
+            lookup.ip,
+            "1.1.1.1".parse::<IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+        assert_eq!(lookup.provider, LookupProvider::IdentMe);
+
+        let lookup = ifconfig_me()
+            .parse_reply("2606:4700:4700::1111".to_string())
+            .unwrap();
+        assert!(lookup.ip.is_ipv6(), "IP address should be IPv6");
+    }
+
+    #[test]
+    fn test_quota() {
+        assert_eq!(ifconfig_me().daily_quota(), None);
+        assert_eq!(aws_checkip().daily_quota(), Some(DailyQuota::Unlimited));
+        assert!(!akamai().supports_ipv6(), "Akamai endpoint is IPv4 only");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let lookup = icanhazip().parse_reply("<html>Login</html>".to_string());
+        assert!(
+            matches!(lookup, Err(LookupError::InvalidResponseIp(ref ip)) if ip == "<html>Login</html>"),
+            "Invalid body should fail to parse, got {:?}",
+            lookup
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Provider policy filtering the provider chain
+
+use super::{LookupProvider, Parameters};
+use log::debug;
+
+/// Rules deciding which providers may be contacted
+///
+/// The process-wide policy set through [`Config`](crate::Config) is applied by the `perform_lookup*`
+/// functions before the provider chain is built.
+///
+/// # Example
+/// ```
+/// use public_ip_address::lookup::{LookupProvider, ProviderPolicy};
+///
+/// let policy = ProviderPolicy::default()
+///     .with_https_only()
+///     .with_excluded(LookupProvider::IpLeak);
+/// let chain = policy.apply(vec![
+///     (LookupProvider::IpApiCom, None),
+///     (LookupProvider::IpLeak, None),
+///     (LookupProvider::IpWhoIs, None),
+/// ]);
+/// assert_eq!(chain, vec![(LookupProvider::IpWhoIs, None)]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct ProviderPolicy {
+    /// Only allow providers whose endpoint is reached over HTTPS
+    pub https_only: bool,
+    /// Only allow providers that were given an API key
+    pub keyed_only: bool,
+    /// Providers that are never contacted
+    pub exclude: Vec<LookupProvider>,
+}
+
+impl ProviderPolicy {
+    /// Rejects providers reached over plain HTTP
+    pub fn with_https_only(mut self) -> Self {
+        self.https_only = true;
+        self
+    }
+
+    /// Rejects providers used without an API key
+    pub fn with_keyed_only(mut self) -> Self {
+        self.keyed_only = true;
+        self
+    }
+
+    /// Rejects the given provider
+    pub fn with_excluded(mut self, provider: LookupProvider) -> Self {
+        self.exclude.push(provider);
+        self
+    }
+
+    /// Checks if the provider with the given parameters may be contacted
+    pub fn allows(&self, provider: &LookupProvider, parameters: &Option<Parameters>) -> bool {
+        if self.exclude.contains(provider) {
+            return false;
+        }
+        let key = super::resolve_api_key(provider, parameters);
+        if self.keyed_only && key.is_none() {
+            return false;
+        }
+        // the endpoint may depend on the API key
+        !self.https_only
+            || provider
+                .clone()
+                .build()
+                .get_endpoint(&key, &None)
+                .starts_with("https://")
+    }
+
+    /// Removes the providers rejected by the policy from the chain, keeping the order
+    pub fn apply(
+        &self,
+        providers: Vec<(LookupProvider, Option<Parameters>)>,
+    ) -> Vec<(LookupProvider, Option<Parameters>)> {
+        providers
+            .into_iter()
+            .filter(|(provider, parameters)| {
+                let allowed = self.allows(provider, parameters);
+                if !allowed {
+                    debug!("Provider {} rejected by the provider policy", provider);
+                }
+                allowed
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_https_only() {
+        let policy = ProviderPolicy::default().with_https_only();
+        assert!(!policy.allows(&LookupProvider::IpApiCom, &None));
+        assert!(!policy.allows(&LookupProvider::IpStack, &None));
+        assert!(!policy.allows(&LookupProvider::Akamai, &None));
+        assert!(policy.allows(&LookupProvider::IpWhoIs, &None));
+    }
+
+    #[test]
+    fn test_keyed_only() {
+        let policy = ProviderPolicy::default().with_keyed_only();
+        let chain = policy.apply(vec![
+            (LookupProvider::IpInfo, None),
+            (
+                LookupProvider::IpData,
+                Some(Parameters::new("key".to_string())),
+            ),
+        ]);
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].0, LookupProvider::IpData);
+    }
+
+    #[test]
+    fn test_default_allows_all() {
+        let policy = ProviderPolicy::default();
+        assert!(policy.allows(&LookupProvider::IpStack, &None));
+        assert!(!policy
+            .with_excluded(LookupProvider::IpStack)
+            .allows(&LookupProvider::IpStack, &None));
+    }
+}
@@ -0,0 +1,133 @@
+//! Redirect handling of the provider requests
+//!
+//! Providers don't redirect in normal operation, redirects are rather the doing of proxies,
+//! captive portals or moved endpoints. Redirects from HTTPS to plain HTTP are refused unless
+//! allowed in the configuration, since the reply of the provider could then be read and altered on
+//! the way.
+
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "ureq"))]
+use std::fmt;
+
+/// Redirects followed by the provider requests, see [`Config::with_redirect_policy`](crate::Config::with_redirect_policy)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum RedirectPolicy {
+    /// Never follow redirects, the redirect response fails the request with its status
+    None,
+    /// Follow up to the given number of redirects
+    Limited(usize),
+    /// Follow every redirect
+    Follow,
+}
+
+impl Default for RedirectPolicy {
+    /// Follows up to 10 redirects, like reqwest
+    fn default() -> Self {
+        RedirectPolicy::Limited(10)
+    }
+}
+
+/// Refused redirect from HTTPS to plain HTTP, contains the refused address
+#[cfg(not(feature = "ureq"))]
+#[derive(Debug)]
+pub(crate) struct DowngradeRefused(pub String);
+
+#[cfg(not(feature = "ureq"))]
+impl fmt::Display for DowngradeRefused {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Refused redirect from HTTPS to {}", self.0)
+    }
+}
+
+#[cfg(not(feature = "ureq"))]
+impl std::error::Error for DowngradeRefused {}
+
+/// Checks if the redirect leaves HTTPS for plain HTTP
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn is_downgrade(previous: &[url::Url], next: &url::Url) -> bool {
+    next.scheme() == "http" && previous.last().is_some_and(|url| url.scheme() == "https")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_is_downgrade() {
+        let https: url::Url = "https://ipinfo.io/json".parse().unwrap();
+        let http: url::Url = "http://ipinfo.io/json".parse().unwrap();
+        let chain = [https.clone(), http.clone()];
+        assert!(is_downgrade(&chain[..1], &http));
+        assert!(!is_downgrade(&chain[1..], &https));
+        assert!(!is_downgrade(&chain[1..], &http));
+        assert!(!is_downgrade(&chain, &https));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_policy() {
+        use crate::lookup::{client, error::LookupError, handle_response};
+        use std::{io::Write, net::TcpListener};
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let redirect = format!(
+            "HTTP/1.0 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+            endpoint
+        );
+        let server = std::thread::spawn(move || {
+            // one refused redirect, then two followed ones before the limit is hit
+            for _ in 0..3 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0; 1024];
+                let _ = std::io::Read::read(&mut stream, &mut buffer);
+                let _ = stream.write_all(redirect.as_bytes());
+            }
+        });
+
+        let client = client::Client::builder()
+            .redirect(RedirectPolicy::None)
+            .build()
+            .unwrap();
+        let result = handle_response(client.get(&endpoint).send().await).await;
+        assert!(
+            matches!(result, Err(LookupError::RequestStatus(ref status)) if status.contains("302")),
+            "Redirect should not be followed, got {:?}",
+            result
+        );
+
+        let client = client::Client::builder()
+            .redirect(RedirectPolicy::Limited(1))
+            .build()
+            .unwrap();
+        let result = client.get(&endpoint).send().await;
+        assert!(
+            result.is_err(),
+            "Redirect limit should be enforced, got {:?}",
+            result
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_policy_serde() {
+        let policies = [
+            RedirectPolicy::None,
+            RedirectPolicy::Limited(3),
+            RedirectPolicy::Follow,
+        ];
+        for policy in policies {
+            let json = serde_json::to_string(&policy).unwrap();
+            assert_eq!(
+                serde_json::from_str::<RedirectPolicy>(&json).unwrap(),
+                policy
+            );
+        }
+        assert_eq!(
+            serde_json::from_str::<RedirectPolicy>(r#"{"limited": 5}"#).unwrap(),
+            RedirectPolicy::Limited(5)
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! <https://stat.ripe.net> lookup provider
+//!
+//! The prefix overview of the RIPEstat Data API returns the announced prefix of an address, its
+//! origin AS and the block allocated by the regional internet registry, reported in the `whois`
+//! field of the response. It only looks up a given target and has no geolocation.
+
+use super::{check_error_body, ProviderResponse, Result};
+use crate::{
+    lookup::{DailyQuota, LookupProvider, Provider},
+    response::WhoisInfo,
+    LookupResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
+
+/// <https://stat.ripe.net/docs/data_api#prefix-overview>
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RipeStatResponse {
+    status: String,
+    data: RipeStatData,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RipeStatData {
+    /// Announced prefix covering the address
+    resource: Option<String>,
+    announced: Option<bool>,
+    #[serde(default)]
+    asns: Vec<Asn>,
+    block: Option<Block>,
+}
+
+/// Origin AS of the prefix
+#[derive(Serialize, Deserialize, Debug)]
+struct Asn {
+    asn: u32,
+    holder: Option<String>,
+}
+
+/// Address block allocated by the registry
+#[derive(Serialize, Deserialize, Debug)]
+struct Block {
+    resource: Option<String>,
+    desc: Option<String>,
+}
+
+impl ProviderResponse<RipeStatResponse> for RipeStatResponse {
+    fn into_response(self) -> Result<LookupResponse> {
+        // the queried address isn't echoed, the lookup fills in its required target
+        let mut response =
+            LookupResponse::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), LookupProvider::RipeStat);
+        if self.data.announced != Some(false) {
+            response.network = self.data.resource;
+        }
+        if let Some(asn) = self.data.asns.into_iter().next() {
+            response.asn = Some(format!("AS{}", asn.asn));
+            // holder is reported as "CLOUDFLARENET - Cloudflare, Inc."
+            response.asn_org = asn.holder.map(|holder| match holder.split_once(" - ") {
+                Some((_, org)) => org.to_string(),
+                None => holder,
+            });
+        }
+        if let Some(block) = self.data.block {
+            // desc is reported as "APNIC (Status: ALLOCATED)"
+            let (registry, status) = match block.desc.as_deref().map(str::trim) {
+                Some(desc) => match desc.split_once(" (Status: ") {
+                    Some((registry, status)) => (
+                        Some(registry.to_string()),
+                        Some(status.trim_end_matches(')').to_lowercase()),
+                    ),
+                    None => (Some(desc.to_string()), None),
+                },
+                None => (None, None),
+            };
+            response.whois = Some(WhoisInfo {
+                registry,
+                allocation: block.resource,
+                allocation_status: status,
+                ..Default::default()
+            });
+        }
+        Ok(response)
+    }
+}
+
+/// RipeStat lookup provider
+pub struct RipeStat;
+
+impl Provider for RipeStat {
+    fn get_endpoint(&self, _key: &Option<String>, target: &Option<IpAddr>) -> String {
+        let target = target.map(|t| t.to_string()).unwrap_or_default();
+        format!(
+            "https://stat.ripe.net/data/prefix-overview/data.json?resource={}",
+            target
+        )
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        // errors are reported as "messages": [["error", "..."]]
+        check_error_body(LookupProvider::RipeStat, &json, |reply| {
+            (reply["status"] == "error").then(|| {
+                let message = reply["messages"]
+                    .as_array()
+                    .and_then(|messages| messages.first())
+                    .and_then(|message| message.get(1))
+                    .and_then(|message| message.as_str())
+                    .map(str::to_string);
+                (None, message.unwrap_or("Request failed".to_string()))
+            })
+        })?;
+        let response = RipeStatResponse::parse(json)?;
+        response.into_response()
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::RipeStat
+    }
+
+    fn daily_quota(&self) -> Option<DailyQuota> {
+        Some(DailyQuota::Unlimited)
+    }
+
+    fn supports_target_lookup(&self) -> bool {
+        true
+    }
+
+    fn requires_target(&self) -> bool {
+        true
+    }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
+
+    fn supports_asn_lookup(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::error::LookupError;
+    const TEST_INPUT: &str = r#"
+{
+  "messages": [],
+  "see_also": [],
+  "version": "1.3",
+  "data_call_name": "prefix-overview",
+  "data_call_status": "supported",
+  "cached": false,
+  "data": {
+    "is_less_specific": false,
+    "announced": true,
+    "asns": [
+      {
+        "asn": 13335,
+        "holder": "CLOUDFLARENET - Cloudflare, Inc."
+      }
+    ],
+    "related_prefixes": [],
+    "resource": "1.1.1.0/24",
+    "type": "prefix",
+    "block": {
+      "resource": "1.0.0.0/8",
+      "desc": "APNIC (Status: ALLOCATED)",
+      "name": "IANA IPv4 Address Space Registry"
+    },
+    "actual_num_related": 0,
+    "query_time": "2024-05-01T08:00:00",
+    "num_filtered_out": 0
+  },
+  "query_id": "20240501080000-1",
+  "process_time": 12,
+  "server_id": "app111",
+  "build_version": "live.2024.4.30.194",
+  "status": "ok",
+  "status_code": 200,
+  "time": "2024-05-01T08:00:00.000000"
+}
+"#;
+
+    #[ignore]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_request() {
+        let service = Box::new(RipeStat);
+        let result = service
+            .get_client(None, "1.1.1.1".parse().ok())
+            .send()
+            .await;
+        let result = super::super::handle_response(result).await.unwrap();
+        assert!(!result.is_empty(), "Result is empty");
+        println!("RipeStat: {:#?}", result);
+        let response = RipeStatResponse::parse(result);
+        assert!(response.is_ok(), "Failed parsing response {:#?}", response);
+    }
+
+    #[test]
+    fn test_parse() {
+        let response = RipeStatResponse::parse(TEST_INPUT.to_string()).unwrap();
+        let lookup = response.into_response().unwrap();
+        assert!(lookup.ip.is_unspecified(), "Address should be unspecified");
+        assert_eq!(lookup.asn, Some("AS13335".to_string()), "ASN not matching");
+        assert_eq!(
+            lookup.asn_org,
+            Some("Cloudflare, Inc.".to_string()),
+            "ASN organization not matching"
+        );
+        assert_eq!(
+            lookup.network,
+            Some("1.1.1.0/24".to_string()),
+            "Network not matching"
+        );
+        let whois = lookup.whois.unwrap();
+        assert_eq!(whois.registry, Some("APNIC".to_string()));
+        assert_eq!(whois.allocation, Some("1.0.0.0/8".to_string()));
+        assert_eq!(whois.allocation_status, Some("allocated".to_string()));
+    }
+
+    #[test]
+    fn test_error() {
+        let reply =
+            r#"{"status": "error", "messages": [["error", "Invalid resource"]], "data": {}}"#;
+        let response = RipeStat.parse_reply(reply.to_string());
+        assert!(
+            matches!(response, Err(LookupError::ProviderError { ref message, .. }) if message == "Invalid resource"),
+            "Error status should fail, got {:?}",
+            response
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! <https://seeip.org> lookup provider
+
+use super::{parse_response_ip, ProviderResponse, Result};
+use crate::{
+    lookup::{LookupProvider, Provider},
+    LookupResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// <https://seeip.org>
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SeeIpResponse {
+    ip: String,
+    organization: Option<String>,
+    city: Option<String>,
+    region: Option<String>,
+    region_code: Option<String>,
+    postal_code: Option<String>,
+    timezone: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    country: Option<String>,
+    country_code: Option<String>,
+    country_code3: Option<String>,
+    continent_code: Option<String>,
+}
+
+impl ProviderResponse<SeeIpResponse> for SeeIpResponse {
+    fn into_response(self) -> Result<LookupResponse> {
+        let mut response = LookupResponse::new(parse_response_ip(&self.ip)?, LookupProvider::SeeIp);
+        response.country = self.country;
+        response.country_code = self.country_code;
+        response.region = self.region.filter(|r| !r.is_empty());
+        response.postal_code = self.postal_code.filter(|p| !p.is_empty());
+        response.city = self.city;
+        response.latitude = self.latitude;
+        response.longitude = self.longitude;
+        response.time_zone = self.timezone;
+        // organization is reported as "AS13335 Cloudflare, Inc."
+        if let Some(organization) = self.organization {
+            match organization.split_once(' ') {
+                Some((asn, org)) if asn.starts_with("AS") => {
+                    response.asn = Some(asn.to_string());
+                    response.asn_org = Some(org.to_string());
+                }
+                _ => response.asn_org = Some(organization),
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// SeeIp lookup provider
+pub struct SeeIp;
+
+impl Provider for SeeIp {
+    fn get_endpoint(&self, _key: &Option<String>, target: &Option<IpAddr>) -> String {
+        let target = target.map(|t| t.to_string()).unwrap_or_default();
+        format!("https://api.seeip.org/geoip/{}", target)
+    }
+
+    fn parse_reply(&self, json: String) -> Result<LookupResponse> {
+        let response = SeeIpResponse::parse(json)?;
+        response.into_response()
+    }
+
+    fn get_type(&self) -> LookupProvider {
+        LookupProvider::SeeIp
+    }
+
+    fn supports_target_lookup(&self) -> bool {
+        true
+    }
+
+    fn supports_ipv6(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const TEST_INPUT: &str = r#"
+{
+  "ip": "1.1.1.1",
+  "organization": "AS13335 Cloudflare, Inc.",
+  "city": "Sydney",
+  "region": "New South Wales",
+  "dma_code": "0",
+  "area_code": "0",
+  "timezone": "Australia/Sydney",
+  "offset": "10",
+  "longitude": 151.2006,
+  "country_code3": "AUS",
+  "postal_code": "2000",
+  "continent_code": "OC",
+  "country": "Australia",
+  "region_code": "NSW",
+  "country_code": "AU",
+  "latitude": -33.8591
+}
+"#;
+
+    #[ignore]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_request() {
+        let service = Box::new(SeeIp);
+        let result = service.get_client(None, None).send().await;
+        let result = super::super::handle_response(result).await.unwrap();
+        assert!(!result.is_empty(), "Result is empty");
+        println!("SeeIp: {:#?}", result);
+        let response = SeeIpResponse::parse(result);
+        assert!(response.is_ok(), "Failed parsing response {:#?}", response);
+    }
+
+    #[test]
+    fn test_parse() {
+        let response = SeeIpResponse::parse(TEST_INPUT.to_string()).unwrap();
+        assert_eq!(response.ip, "1.1.1.1", "IP address not matching");
+        let lookup = response.into_response().unwrap();
+        assert_eq!(
+            lookup.ip,
+            "1.1.1.1".parse::<IpAddr>().unwrap(),
+            "IP address not matching"
+        );
+        assert_eq!(lookup.asn, Some("AS13335".to_string()), "ASN not matching");
+        assert_eq!(
+            lookup.asn_org,
+            Some("Cloudflare, Inc.".to_string()),
+            "ASN organization not matching"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Synchronous lookup service for async builds
+//!
+//! `LookupServiceBlocking` wraps a [`LookupService`] and drives its requests on a small runtime
+//! with a single worker thread created on first use, so synchronous code can perform lookups
+//! without enabling the `blocking` feature. The worker keeps driving the connections of the shared
+//! HTTP client between calls, so they can be reused from any runtime. Calls made from inside an
+//! async runtime are moved to a helper thread instead of panicking.
+//!
+//! With the `blocking` feature `LookupService` is already synchronous and `LookupServiceBlocking`
+//! is an alias of it, so code written against the facade compiles in both modes.
+
+use super::{error::LookupError, LookupProvider, LookupService, LookupTarget, Parameters, Result};
+use crate::LookupResponse;
+use std::{future::Future, net::IpAddr, sync::OnceLock, thread, time::Duration};
+use tokio::runtime::{Builder, Handle, Runtime};
+
+static RUNTIME: OnceLock<std::result::Result<Runtime, String>> = OnceLock::new();
+
+/// Lookup service with a synchronous interface
+///
+/// # Example
+/// ```rust,no_run
+/// use public_ip_address::lookup::{LookupProvider, LookupServiceBlocking};
+///
+/// let service = LookupServiceBlocking::new(LookupProvider::IpWhoIs, None);
+/// let response = service.lookup(None)?;
+/// println!("{}", response.ip);
+/// # Ok::<(), public_ip_address::lookup::error::LookupError>(())
+/// ```
+#[non_exhaustive]
+pub struct LookupServiceBlocking {
+    service: LookupService,
+}
+
+impl LookupServiceBlocking {
+    /// Creates a new `LookupServiceBlocking` instance with parameters.
+    pub fn new(provider: LookupProvider, parameters: Option<Parameters>) -> Self {
+        LookupServiceBlocking {
+            service: LookupService::new(provider, parameters),
+        }
+    }
+
+    /// Changes the provider for the service
+    pub fn set_provider(&mut self, provider: LookupProvider) -> &Self {
+        self.service.set_provider(provider);
+        self
+    }
+
+    /// Sets the parameters for the service
+    pub fn set_parameters(&mut self, parameters: Parameters) -> &Self {
+        self.service.set_parameters(parameters);
+        self
+    }
+
+    /// Sets the request timeout for the service, overriding the process-wide timeout
+    pub fn set_timeout(&mut self, timeout: Duration) -> &Self {
+        self.service.set_timeout(timeout);
+        self
+    }
+
+    /// Returns the type of the current lookup provider.
+    pub fn get_provider_type(&self) -> LookupProvider {
+        self.service.get_provider_type()
+    }
+
+    /// Makes a request to the lookup provider, see [`LookupService::lookup`]
+    pub fn lookup(&self, target: Option<IpAddr>) -> Result<LookupResponse> {
+        block_on(self.service.lookup(target))?
+    }
+
+    /// Makes a request for every address of the target, see [`LookupService::lookup_target`]
+    pub fn lookup_target(&self, target: &LookupTarget) -> Result<Vec<LookupResponse>> {
+        block_on(self.service.lookup_target(target))?
+    }
+
+    /// Makes requests for a list of targets, see [`LookupService::bulk_lookup`]
+    pub fn bulk_lookup(&self, targets: &[IpAddr]) -> Result<Vec<LookupResponse>> {
+        block_on(self.service.bulk_lookup(targets))?
+    }
+}
+
+impl From<LookupService> for LookupServiceBlocking {
+    fn from(service: LookupService) -> Self {
+        LookupServiceBlocking { service }
+    }
+}
+
+/// Returns the shared runtime, building it on first use
+fn runtime() -> Result<&'static Runtime> {
+    RUNTIME
+        .get_or_init(|| {
+            // a current thread runtime would only poll the pooled connections inside `block_on`,
+            // leaving them stalled between calls and unusable from other runtimes
+            Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("public-ip-address")
+                .enable_all()
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| LookupError::GenericError(format!("Failed to start the runtime: {}", e)))
+}
+
+/// Runs the future to completion on the shared runtime
+///
+/// A runtime can't be blocked on from inside another one, so such calls run on a scoped thread.
+pub(crate) fn block_on<F>(future: F) -> Result<F::Output>
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    let runtime = runtime()?;
+    if Handle::try_current().is_err() {
+        return Ok(runtime.block_on(future));
+    }
+    thread::scope(|scope| {
+        scope
+            .spawn(|| runtime.block_on(future))
+            .join()
+            .map_err(|_| LookupError::GenericError("Lookup thread panicked".to_string()))
+    })
+}
+
+/// Runs the future in the background on the shared runtime
+///
+/// The task keeps running after the caller returns, independently of the runtime of the caller,
+/// which may be a current thread runtime that is only driven while it is blocked on.
+pub(crate) fn spawn<F>(future: F) -> Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    runtime()?.spawn(future);
+    Ok(())
+}
+
+/// Runs blocking work on the shared runtime and waits for it without blocking the caller
+///
+/// The returned future can be awaited from any runtime.
+pub(crate) async fn unblock<F, T>(work: F) -> Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    runtime()?
+        .spawn_blocking(work)
+        .await
+        .map_err(|_| LookupError::GenericError("Blocking lookup task panicked".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_lookup() {
+        let service = LookupServiceBlocking::new(LookupProvider::Mock("1.1.1.1".to_string()), None);
+        let response = service.lookup(Some("127.0.0.1".parse().unwrap()));
+        assert!(matches!(response, Err(LookupError::NonPublicAddress(_))));
+        assert_eq!(
+            service.get_provider_type(),
+            LookupProvider::Mock("1.1.1.1".to_string())
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_unblock() {
+        let caller = thread::current().id();
+        let worker = unblock(|| thread::current().id()).await.unwrap();
+        assert_ne!(caller, worker, "Blocking work should run on another thread");
+    }
+
+    #[tokio::test]
+    async fn test_blocking_lookup_inside_runtime() {
+        let service = LookupServiceBlocking::new(LookupProvider::Mock("1.1.1.1".to_string()), None);
+        let response = service.bulk_lookup(&["10.0.0.1".parse().unwrap()]);
+        assert!(
+            matches!(response, Err(LookupError::NonPublicAddress(_))),
+            "Lookup inside a runtime should not panic"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Lookup targets
+//!
+//! A lookup target is either an IP address or a hostname, which is resolved through the system resolver
+//! before the lookup.
+
+use super::error::{LookupError, Result};
+use std::{
+    fmt,
+    net::{IpAddr, ToSocketAddrs},
+    str::FromStr,
+};
+
+/// Target of a lookup
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LookupTarget {
+    /// IP address
+    Ip(IpAddr),
+    /// Hostname resolved to all of its addresses
+    Hostname(String),
+}
+
+impl LookupTarget {
+    /// Resolves the target into a list of unique IP addresses
+    ///
+    /// Hostnames are resolved through the system resolver, which blocks the current thread.
+    pub fn resolve(&self) -> Result<Vec<IpAddr>> {
+        match self {
+            LookupTarget::Ip(ip) => Ok(vec![*ip]),
+            LookupTarget::Hostname(host) => {
+                let mut addresses: Vec<IpAddr> = Vec::new();
+                for address in (host.as_str(), 0)
+                    .to_socket_addrs()
+                    .map_err(LookupError::ResolveError)?
+                {
+                    if !addresses.contains(&address.ip()) {
+                        addresses.push(address.ip());
+                    }
+                }
+                Ok(addresses)
+            }
+        }
+    }
+}
+
+impl From<IpAddr> for LookupTarget {
+    fn from(ip: IpAddr) -> Self {
+        LookupTarget::Ip(ip)
+    }
+}
+
+impl FromStr for LookupTarget {
+    type Err = LookupError;
+    /// Parse an IP address or a hostname into a LookupTarget
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(LookupError::GenericError("Empty lookup target".to_string()));
+        }
+        Ok(match s.parse::<IpAddr>() {
+            Ok(ip) => LookupTarget::Ip(ip),
+            Err(_) => LookupTarget::Hostname(s.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for LookupTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LookupTarget::Ip(ip) => write!(f, "{}", ip),
+            LookupTarget::Hostname(host) => write!(f, "{}", host),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            "1.1.1.1".parse::<LookupTarget>().unwrap(),
+            LookupTarget::Ip("1.1.1.1".parse().unwrap())
+        );
+        assert_eq!(
+            " example.com ".parse::<LookupTarget>().unwrap(),
+            LookupTarget::Hostname("example.com".to_string())
+        );
+        assert!("".parse::<LookupTarget>().is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let ip: IpAddr = "8.8.8.8".parse().unwrap();
+        assert_eq!(LookupTarget::from(ip).resolve().unwrap(), vec![ip]);
+        let localhost = LookupTarget::Hostname("localhost".to_string())
+            .resolve()
+            .unwrap();
+        assert!(
+            localhost.iter().all(|ip| ip.is_loopback()),
+            "localhost should resolve to loopback addresses"
+        );
+    }
+}
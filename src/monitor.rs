@@ -0,0 +1,206 @@
+//! # 📡 Network change monitor
+//!
+//! This module listens for network change events from the operating system (netlink on Linux,
+//! SystemConfiguration on macOS, WinAPI on Windows), so that a new public IP address is picked up
+//! right after switching networks instead of waiting for the cached response to expire.
+//!
+//! The monitor can drop the cached current address, or make a [`Watcher`](crate::watch::Watcher)
+//! poll right away through its [`WatchTrigger`]. The addresses present when the monitor starts
+//! are reported by the operating system as well, they are recognized and don't count as changes.
+//!
+//! The monitor is enabled through the `network-monitor` feature flag.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use public_ip_address::monitor::NetworkMonitor;
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     // drop the cached current address whenever the network changes
+//!     let _monitor = NetworkMonitor::invalidate_cache(None)?;
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{cache::ResponseCache, error::Result, watch::WatchTrigger};
+use futures_executor::block_on;
+use futures_util::{
+    future::{poll_fn, select, Either},
+    task::AtomicWaker,
+    StreamExt,
+};
+use if_watch::{smol::IfWatcher, IfEvent};
+use log::{debug, trace, warn};
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::Poll,
+    thread,
+};
+
+/// Watches the operating system for network changes on a background thread
+///
+/// The background thread exits as soon as the monitor is dropped.
+#[derive(Debug)]
+pub struct NetworkMonitor {
+    stop: Arc<Stop>,
+}
+
+/// Stop signal waking the background thread
+#[derive(Debug, Default)]
+struct Stop {
+    stopped: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl NetworkMonitor {
+    /// Starts watching for network changes, calling `on_change` whenever a local address is added or removed.
+    pub fn spawn<F>(mut on_change: F) -> Result<NetworkMonitor>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let mut watcher = IfWatcher::new()?;
+        // the watcher reports the existing addresses on its first sync
+        let mut existing: HashSet<IpAddr> = if_addrs::get_if_addrs()?
+            .iter()
+            .map(|interface| interface.ip())
+            .collect();
+        let stop = Arc::new(Stop::default());
+        let stopped = stop.clone();
+
+        thread::Builder::new()
+            .name("network-monitor".to_string())
+            .spawn(move || {
+                block_on(async {
+                    loop {
+                        let stop = poll_fn(|cx| {
+                            stopped.waker.register(cx.waker());
+                            if stopped.stopped.load(Ordering::Acquire) {
+                                Poll::Ready(())
+                            } else {
+                                Poll::Pending
+                            }
+                        });
+                        let event = match select(stop, watcher.next()).await {
+                            Either::Right((Some(event), _)) => event,
+                            _ => break,
+                        };
+                        match event {
+                            Ok(IfEvent::Up(net) | IfEvent::Down(net)) if is_local(&net.addr()) => {
+                                trace!("Ignoring local address {}", net);
+                            }
+                            Ok(IfEvent::Up(net)) if existing.remove(&net.addr()) => {
+                                trace!("Ignoring existing address {}", net);
+                            }
+                            Ok(event) => {
+                                debug!("Network changed: {:?}", event);
+                                on_change();
+                            }
+                            Err(e) => warn!("Network monitor error: {}", e),
+                        }
+                    }
+                });
+                trace!("Network monitor stopped");
+            })?;
+
+        Ok(NetworkMonitor { stop })
+    }
+
+    /// Starts watching for network changes, dropping the cached current address whenever the network changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - An `Option<String>` representing the name of the cache file. If `None`, the default file name is used.
+    pub fn invalidate_cache(file_name: Option<String>) -> Result<NetworkMonitor> {
+        NetworkMonitor::spawn(move || {
+            if let Ok(mut cache) = ResponseCache::load(file_name.clone()) {
+                trace!("Invalidating cached current address");
+                cache.current_address = None;
+                if let Err(e) = cache.save() {
+                    warn!("Failed to save cache: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Starts watching for network changes, making the watcher of the trigger poll right away whenever the network changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `trigger` - The `WatchTrigger` returned by [`Watcher::trigger`](crate::watch::Watcher::trigger).
+    pub fn trigger_watcher(trigger: WatchTrigger) -> Result<NetworkMonitor> {
+        NetworkMonitor::spawn(move || {
+            trace!("Triggering watcher poll");
+            trigger.trigger();
+        })
+    }
+}
+
+/// Checks if the address can't carry traffic to the internet, changes to it don't affect the public address
+fn is_local(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => address.is_loopback() || address.is_link_local(),
+        IpAddr::V6(address) => address.is_loopback() || address.is_unicast_link_local(),
+    }
+}
+
+impl Drop for NetworkMonitor {
+    fn drop(&mut self) {
+        self.stop.stopped.store(true, Ordering::Release);
+        self.stop.waker.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn() {
+        let monitor = NetworkMonitor::spawn(|| {});
+        assert!(monitor.is_ok(), "Failed to start monitor {:#?}", monitor);
+    }
+
+    #[test]
+    fn test_drop_stops_thread() {
+        use std::time::{Duration, Instant};
+
+        // the callback is dropped together with the background thread
+        let alive = Arc::new(());
+        let held = alive.clone();
+        let trigger = WatchTrigger::default();
+        let remote = trigger.clone();
+        let monitor = NetworkMonitor::spawn(move || {
+            let _held = &held;
+            remote.trigger();
+        })
+        .unwrap();
+        assert!(
+            !trigger.wait_timeout(Duration::from_millis(500)),
+            "Existing addresses should not count as changes"
+        );
+
+        drop(monitor);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Arc::strong_count(&alive) > 1 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            Arc::strong_count(&alive),
+            1,
+            "Background thread should exit once the monitor is dropped"
+        );
+    }
+
+    #[test]
+    fn test_is_local() {
+        assert!(is_local(&"127.0.0.1".parse().unwrap()));
+        assert!(is_local(&"169.254.1.1".parse().unwrap()));
+        assert!(is_local(&"fe80::1".parse().unwrap()));
+        assert!(!is_local(&"192.168.1.10".parse().unwrap()));
+        assert!(!is_local(&"2001:db8::1".parse().unwrap()));
+    }
+}
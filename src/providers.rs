@@ -0,0 +1,117 @@
+//! 📋 Curated provider presets.
+//!
+//! Ready made provider chains for common constraints, so the providers don't have to be picked
+//! one by one. A preset is a list of providers, turned into the chain accepted by
+//! [`perform_lookup_with`](crate::perform_lookup_with) with [`chain`], which skips the providers
+//! compiled out through their feature flag.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use public_ip_address::providers;
+//! use std::error::Error;
+//! #[cfg_attr(not(feature = "blocking"), tokio::main)]
+//! #[maybe_async::maybe_async]
+//! async fn main() -> Result<(), Box<dyn Error>> {
+//!     let chain = providers::chain(providers::FAST_FREE);
+//!     let response = public_ip_address::perform_lookup_with(chain, None).await?;
+//!     println!("{}", response.ip);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::lookup::{LookupProvider, Parameters};
+
+/// Providers answering with the bare address, without a quota or an API key
+///
+/// The replies are a few bytes long, so they are the quickest way to learn the public address when
+/// no geolocation is needed.
+pub const FAST_FREE: &[LookupProvider] = &[
+    LookupProvider::Ipify,
+    LookupProvider::ICanHazIp,
+    LookupProvider::IdentMe,
+    LookupProvider::AwsCheckIp,
+    LookupProvider::MyIpCom,
+];
+
+/// Providers returning geolocation data without an API key
+pub const NO_API_KEY: &[LookupProvider] = &[
+    LookupProvider::IpWhoIs,
+    LookupProvider::IpInfo,
+    LookupProvider::FreeIpApi,
+    LookupProvider::IpApiCo,
+    LookupProvider::IpSb,
+    LookupProvider::SeeIp,
+    LookupProvider::IfConfig,
+    LookupProvider::IpApiCom,
+];
+
+/// Providers run by VPN operators or returning only the address, all reached over HTTPS
+pub const PRIVACY_FOCUSED: &[LookupProvider] = &[
+    LookupProvider::Mullvad,
+    LookupProvider::IpLeak,
+    LookupProvider::IdentMe,
+    LookupProvider::ICanHazIp,
+];
+
+/// Providers reachable over IPv6, reporting the IPv6 address on dual-stack hosts
+pub const IPV6_CAPABLE: &[LookupProvider] = &[
+    LookupProvider::Ipify,
+    LookupProvider::ICanHazIp,
+    LookupProvider::IdentMe,
+    LookupProvider::IfConfigMe,
+    LookupProvider::IpWhoIs,
+    LookupProvider::IpSb,
+    LookupProvider::SeeIp,
+    LookupProvider::FreeIpApi,
+];
+
+/// Returns the provider chain of a preset, without the providers that are compiled out
+pub fn chain(preset: &[LookupProvider]) -> Vec<(LookupProvider, Option<Parameters>)> {
+    preset
+        .iter()
+        .filter(|provider| provider.is_enabled())
+        .map(|provider| (provider.clone(), None))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::DailyQuota;
+
+    #[test]
+    fn test_presets() {
+        for provider in FAST_FREE {
+            let capabilities = provider.capabilities();
+            assert!(!capabilities.requires_key, "{} requires a key", provider);
+            assert_eq!(
+                capabilities.rate_limit_hint,
+                Some(DailyQuota::Unlimited),
+                "{}",
+                provider
+            );
+        }
+        for provider in NO_API_KEY {
+            let capabilities = provider.capabilities();
+            assert!(!capabilities.requires_key, "{} requires a key", provider);
+            assert!(
+                capabilities.supports_target,
+                "{} has no geolocation",
+                provider
+            );
+        }
+        for provider in PRIVACY_FOCUSED {
+            assert!(provider.capabilities().https_only, "{} uses HTTP", provider);
+        }
+        for provider in IPV6_CAPABLE {
+            assert!(provider.supports_ipv6(), "{} is IPv4 only", provider);
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        let chain = chain(FAST_FREE);
+        assert_eq!(chain.len(), FAST_FREE.len());
+        assert_eq!(chain[0], (LookupProvider::Ipify, None));
+    }
+}
@@ -1,27 +1,60 @@
 //! ✉️ Lookup response.
 
-use crate::lookup::LookupProvider;
-use serde::{Deserialize, Serialize};
-use std::{fmt, net::IpAddr};
+use crate::lookup::{bogon, LookupProvider};
+use log::warn;
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+/// Version of the serialized form of [`LookupResponse`] written by this crate
+///
+/// Fields are never renamed or removed within a version and new fields are optional, so forms
+/// written by older releases deserialize with the missing fields set to `None`. Breaking changes
+/// bump the version, [`LookupResponse::from_json_compat`] upgrades the older forms.
+pub const SCHEMA_VERSION: u32 = 1;
 
 /// Lookup response containing information like IP, country, city, hostname etc.
+///
+/// The serialized form is stable across releases, see [`SCHEMA_VERSION`].
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[non_exhaustive]
 pub struct LookupResponse {
+    /// Version of the serialized form, see [`SCHEMA_VERSION`], `0` for forms written before the
+    /// schema was versioned.
+    #[serde(default)]
+    pub schema_version: u32,
     /// Public IP address.
     pub ip: IpAddr,
     /// Continent name.
     pub continent: Option<String>,
+    /// Continent name in the requested language.
+    pub continent_localized: Option<String>,
     /// Country name.
     pub country: Option<String>,
+    /// Country name in the requested language.
+    pub country_localized: Option<String>,
     /// Country ISO code.
     pub country_code: Option<String>,
     /// Region name.
     pub region: Option<String>,
+    /// Second-level subdivision name, like county or district.
+    pub district: Option<String>,
     /// Postal code.
     pub postal_code: Option<String>,
     /// City name.
     pub city: Option<String>,
+    /// City name in the requested language.
+    pub city_localized: Option<String>,
+    /// GeoNames identifier of the country.
+    pub country_geoname_id: Option<u64>,
+    /// GeoNames identifier of the region.
+    pub region_geoname_id: Option<u64>,
+    /// GeoNames identifier of the city.
+    pub city_geoname_id: Option<u64>,
     /// Latitude of the IP address.
     pub latitude: Option<f64>,
     /// Longitude of the IP address.
@@ -32,37 +65,614 @@ pub struct LookupResponse {
     pub asn: Option<String>,
     /// Autonomous System Organization.
     pub asn_org: Option<String>,
+    /// Internet Service Provider.
+    pub isp: Option<String>,
+    /// Network prefix the IP address is routed in, in CIDR notation.
+    pub network: Option<String>,
     /// Hostname of the IP address.
     pub hostname: Option<String>,
     /// Is the IP a proxy or vpn?
     pub is_proxy: Option<bool>,
+    /// Type of the proxy, like `VPN`, `TOR` or `PUB`.
+    pub proxy_type: Option<String>,
+    /// Name of the proxy or VPN service.
+    pub proxy_provider: Option<String>,
+    /// Is the IP on a mobile (cellular) network?
+    pub is_mobile: Option<bool>,
+    /// Type of the network connection.
+    pub connection_type: Option<ConnectionType>,
+    /// Ownership data from the registry, see the `whois` module.
+    #[serde(default)]
+    pub whois: Option<WhoisInfo>,
+    /// Threat data of the IP address, see the `tor` module.
+    #[serde(default)]
+    pub security: Option<SecurityInfo>,
     /// Provider that was used for the lookup.
     pub provider: LookupProvider,
+    /// Metadata describing how the response was obtained.
+    #[serde(skip)]
+    pub meta: LookupMeta,
 }
 
 impl LookupResponse {
     /// Create a new lookup response.
     pub fn new(ip: IpAddr, provider: LookupProvider) -> Self {
         LookupResponse {
+            schema_version: SCHEMA_VERSION,
             ip,
             continent: None,
+            continent_localized: None,
             country: None,
+            country_localized: None,
             country_code: None,
             region: None,
+            district: None,
             postal_code: None,
             city: None,
+            city_localized: None,
+            country_geoname_id: None,
+            region_geoname_id: None,
+            city_geoname_id: None,
             latitude: None,
             longitude: None,
             time_zone: None,
             asn: None,
             asn_org: None,
+            isp: None,
+            network: None,
             hostname: None,
             is_proxy: None,
+            proxy_type: None,
+            proxy_provider: None,
+            is_mobile: None,
+            connection_type: None,
+            whois: None,
+            security: None,
             provider,
+            meta: LookupMeta::default(),
+        }
+    }
+
+    /// Deserializes a response written by any release of the crate, upgrading older forms.
+    ///
+    /// Forms of a newer version are read as well as possible: unknown fields are ignored and
+    /// unknown connection types are kept as [`ConnectionType::Other`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use public_ip_address::response::{LookupResponse, SCHEMA_VERSION};
+    ///
+    /// let response =
+    ///     LookupResponse::from_json_compat(r#"{"ip": "1.1.1.1", "provider": "IpWhoIs"}"#)?;
+    /// assert_eq!(response.schema_version, SCHEMA_VERSION);
+    /// # Ok::<(), serde_json::Error>(())
+    /// ```
+    pub fn from_json_compat(json: &str) -> serde_json::Result<LookupResponse> {
+        serde_json::from_value(migrate(serde_json::from_str(json)?))
+    }
+
+    /// Returns the version of the IP address.
+    pub fn ip_version(&self) -> IpVersion {
+        match self.ip {
+            IpAddr::V4(_) => IpVersion::V4,
+            IpAddr::V6(_) => IpVersion::V6,
+        }
+    }
+
+    /// Checks if the IP address is publicly routable, see [`bogon::non_public_kind`].
+    pub fn is_global(&self) -> bool {
+        bogon::non_public_kind(&self.ip).is_none()
+    }
+
+    /// Returns the IP address as IPv6, mapping IPv4 addresses to `::ffff:a.b.c.d`.
+    pub fn to_ipv6_mapped(&self) -> Ipv6Addr {
+        match self.ip {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            IpAddr::V6(ip) => ip,
+        }
+    }
+
+    /// Returns the network prefix of the IP address, if the provider reported a valid one.
+    ///
+    /// Available with the `ipnet` feature.
+    #[cfg(feature = "ipnet")]
+    pub fn ip_network(&self) -> Option<ipnet::IpNet> {
+        self.network.as_deref()?.trim().parse().ok()
+    }
+
+    /// Returns a serializable view of the response with coordinates rounded to `decimals` places.
+    ///
+    /// The response itself is not modified, which makes it possible to export coarser
+    /// locations while keeping full precision in memory.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use public_ip_address::{response::LookupResponse, lookup::LookupProvider};
+    /// let mut response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::Mock("".to_string()));
+    /// response.latitude = Some(37.42240);
+    /// let json = serde_json::to_string(&response.with_precision(1)).unwrap();
+    /// assert!(json.contains(r#""latitude":37.4"#));
+    /// ```
+    pub fn with_precision(&self, decimals: u32) -> RoundedResponse<'_> {
+        RoundedResponse {
+            response: self,
+            decimals,
+        }
+    }
+
+    /// Normalizes the country fields, `country_code` to the alpha-2 code and `country` to a name.
+    ///
+    /// Fills the name from the code when the provider reported only the code, or the code in
+    /// both fields, and the code from the name when only the name was reported. Names reported
+    /// by the provider, including localized ones, are kept.
+    ///
+    /// Available with the `iso3166` feature, lookups made by the crate are already normalized.
+    #[cfg(feature = "iso3166")]
+    pub fn normalize_country(&mut self) {
+        use crate::country::{country_code, country_name};
+
+        let known_code =
+            |value: &str| country_name(value).map(|_| value.trim().to_ascii_uppercase());
+        let code = self
+            .country_code
+            .as_deref()
+            .and_then(known_code)
+            .or_else(|| self.country.as_deref().and_then(known_code))
+            .or_else(|| {
+                let name = self.country.as_deref()?;
+                country_code(name).map(str::to_string)
+            });
+        let Some(code) = code else {
+            return;
+        };
+        if self
+            .country
+            .as_deref()
+            .is_none_or(|c| country_name(c).is_some())
+        {
+            self.country = country_name(&code).map(str::to_string);
+        }
+        self.country_code = Some(code);
+    }
+
+    /// Returns the current UTC offset of the time zone of the response.
+    ///
+    /// Providers report the time zone either as an IANA name like `Europe/Berlin` or as a fixed
+    /// offset like `+02:00`, both are accepted, as well as `UTC`, `Z` and `GMT-5` style offsets.
+    /// Offsets of IANA names follow daylight saving time.
+    ///
+    /// Available with the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn timezone_offset(&self) -> Option<chrono::FixedOffset> {
+        self.timezone_offset_at(chrono::Utc::now())
+    }
+
+    /// Returns the current local time at the location of the response, see [`Self::timezone_offset`].
+    ///
+    /// Available with the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn local_time(&self) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        let now = chrono::Utc::now();
+        Some(now.with_timezone(&self.timezone_offset_at(now)?))
+    }
+
+    /// Returns the UTC offset of the time zone of the response at the given instant.
+    #[cfg(feature = "chrono")]
+    fn timezone_offset_at(&self, at: chrono::DateTime<chrono::Utc>) -> Option<chrono::FixedOffset> {
+        use chrono::Offset;
+
+        let time_zone = self.time_zone.as_deref()?.trim();
+        if let Some(offset) = parse_utc_offset(time_zone) {
+            return Some(offset);
         }
+        let time_zone: chrono_tz::Tz = time_zone.parse().ok()?;
+        Some(at.with_timezone(&time_zone).offset().fix())
+    }
+
+    /// Returns the response as a GeoJSON `Feature` with a `Point` geometry.
+    ///
+    /// The properties hold the address, the country, the city, the ASN and the provider. The
+    /// geometry is `null` when the provider reported no coordinates.
+    ///
+    /// Available with the `geojson` feature.
+    #[cfg(feature = "geojson")]
+    pub fn to_geojson_feature(&self) -> serde_json::Value {
+        let geometry = match (self.longitude, self.latitude) {
+            (Some(lon), Some(lat)) => serde_json::json!({
+                "type": "Point",
+                "coordinates": [lon, lat],
+            }),
+            _ => serde_json::Value::Null,
+        };
+        serde_json::json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": {
+                "ip": self.ip,
+                "country": self.country,
+                "country_code": self.country_code,
+                "city": self.city,
+                "asn": self.asn,
+                "asn_org": self.asn_org,
+                "provider": self.provider,
+            },
+        })
+    }
+}
+
+/// Returns the responses as a GeoJSON `FeatureCollection`, see [`LookupResponse::to_geojson_feature`].
+///
+/// Available with the `geojson` feature.
+#[cfg(feature = "geojson")]
+pub fn to_geojson_feature_collection<'a>(
+    responses: impl IntoIterator<Item = &'a LookupResponse>,
+) -> serde_json::Value {
+    let features: Vec<serde_json::Value> = responses
+        .into_iter()
+        .map(LookupResponse::to_geojson_feature)
+        .collect();
+    serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+impl From<&LookupResponse> for IpAddr {
+    fn from(response: &LookupResponse) -> Self {
+        response.ip
+    }
+}
+
+impl From<LookupResponse> for IpAddr {
+    fn from(response: LookupResponse) -> Self {
+        response.ip
     }
 }
 
+#[cfg(feature = "ipnet")]
+impl TryFrom<&LookupResponse> for ipnet::IpNet {
+    type Error = crate::lookup::error::LookupError;
+
+    /// Converts the network of the response, failing if it is missing or not in CIDR notation.
+    fn try_from(response: &LookupResponse) -> Result<Self, Self::Error> {
+        response.ip_network().ok_or_else(|| {
+            crate::lookup::error::LookupError::GenericError(format!(
+                "Invalid network in response: {:?}",
+                response.network
+            ))
+        })
+    }
+}
+
+/// Version of an IP address.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpVersion {
+    /// IPv4 address.
+    V4,
+    /// IPv6 address.
+    V6,
+}
+
+impl fmt::Display for IpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpVersion::V4 => write!(f, "IPv4"),
+            IpVersion::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// Serializable view of a [`LookupResponse`] with rounded coordinates.
+///
+/// Created by [`LookupResponse::with_precision`].
+#[derive(Debug)]
+pub struct RoundedResponse<'a> {
+    response: &'a LookupResponse,
+    decimals: u32,
+}
+
+impl Serialize for RoundedResponse<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut response = self.response.clone();
+        response.latitude = response
+            .latitude
+            .map(|lat| round_coordinate(lat, self.decimals));
+        response.longitude = response
+            .longitude
+            .map(|lon| round_coordinate(lon, self.decimals));
+        response.serialize(serializer)
+    }
+}
+
+/// Parses a fixed UTC offset like `+02:00`, `-0530`, `UTC+2` or `Z`.
+#[cfg(feature = "chrono")]
+fn parse_utc_offset(value: &str) -> Option<chrono::FixedOffset> {
+    let upper = value.to_ascii_uppercase();
+    let offset = upper
+        .strip_prefix("UTC")
+        .or_else(|| upper.strip_prefix("GMT"))
+        .unwrap_or(&upper)
+        .trim();
+    if offset.is_empty() || offset == "Z" {
+        return chrono::FixedOffset::east_opt(0);
+    }
+    let (sign, offset) = match offset.split_at(1) {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, minutes) = match offset.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if offset.len() > 2 => offset.split_at(offset.len() - 2),
+        None => (offset, "0"),
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    chrono::FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Rounds a coordinate to the given number of decimal places.
+pub fn round_coordinate(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (value * factor).round() / factor
+}
+
+/// Serde helper rounding an optional coordinate to `DECIMALS` places when serializing.
+///
+/// Intended for use with `serialize_with` in downstream types:
+///
+/// ```rust
+/// # use serde::Serialize;
+/// #[derive(Serialize)]
+/// struct Export {
+///     #[serde(serialize_with = "public_ip_address::response::serialize_rounded::<2, _>")]
+///     latitude: Option<f64>,
+/// }
+/// let json = serde_json::to_string(&Export { latitude: Some(37.42240) }).unwrap();
+/// assert_eq!(json, r#"{"latitude":37.42}"#);
+/// ```
+pub fn serialize_rounded<const DECIMALS: u32, S: Serializer>(
+    value: &Option<f64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value
+        .map(|v| round_coordinate(v, DECIMALS))
+        .serialize(serializer)
+}
+
+/// Ownership data of the network an IP address belongs to, as registered with the regional internet registry.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct WhoisInfo {
+    /// Registry handle of the network.
+    pub handle: Option<String>,
+    /// Name of the network.
+    pub network_name: Option<String>,
+    /// Email address to report abuse to.
+    pub abuse_contact: Option<String>,
+    /// Country the network was allocated to.
+    pub country: Option<String>,
+    /// Regional internet registry the address block was allocated by, like `RIPE NCC` or `APNIC`.
+    pub registry: Option<String>,
+    /// Address block allocated by the registry, in CIDR notation.
+    pub allocation: Option<String>,
+    /// Status of the allocation, like `allocated` or `assigned`.
+    pub allocation_status: Option<String>,
+    /// Date of the allocation.
+    pub allocation_date: Option<String>,
+}
+
+/// Threat data of an IP address, as reported by the provider or checked against public lists.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct SecurityInfo {
+    /// Is the IP a Tor exit node?
+    pub is_tor: Option<bool>,
+    /// Results of the DNS blocklist checks, see the `dnsbl` module.
+    #[serde(default)]
+    pub blocklists: Vec<BlocklistResult>,
+}
+
+/// Result of checking an IP address against a DNS blocklist.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BlocklistResult {
+    /// DNS zone of the blocklist, like `zen.spamhaus.org`.
+    pub zone: String,
+    /// Is the IP listed? `None` if the blocklist refused the query.
+    pub listed: Option<bool>,
+    /// Return codes of the listing, like `127.0.0.2`, telling the reason of the listing.
+    pub codes: Vec<Ipv4Addr>,
+}
+
+/// Normalized type of the network connection behind an IP address.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConnectionType {
+    /// Mobile (cellular) network.
+    Cellular,
+    /// Cable or DSL broadband.
+    Dsl,
+    /// Dial-up connection.
+    Dialup,
+    /// Satellite connection.
+    Satellite,
+    /// Residential internet service provider.
+    Residential,
+    /// Corporate or business network.
+    Business,
+    /// Hosting provider or data center.
+    Hosting,
+    /// Educational institution.
+    Education,
+    /// Connection type not covered by the other variants, as reported by the provider.
+    Other(String),
+}
+
+impl From<&str> for ConnectionType {
+    fn from(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "cellular" | "mobile" => ConnectionType::Cellular,
+            "dsl" | "cable" | "cable/dsl" => ConnectionType::Dsl,
+            "dialup" | "dial-up" => ConnectionType::Dialup,
+            "satellite" => ConnectionType::Satellite,
+            "isp" | "residential" => ConnectionType::Residential,
+            "business" | "corporate" => ConnectionType::Business,
+            "hosting" | "datacenter" | "data center" => ConnectionType::Hosting,
+            "education" => ConnectionType::Education,
+            _ => ConnectionType::Other(value.trim().to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ConnectionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionType::Cellular => write!(f, "cellular"),
+            ConnectionType::Dsl => write!(f, "dsl"),
+            ConnectionType::Dialup => write!(f, "dialup"),
+            ConnectionType::Satellite => write!(f, "satellite"),
+            ConnectionType::Residential => write!(f, "residential"),
+            ConnectionType::Business => write!(f, "business"),
+            ConnectionType::Hosting => write!(f, "hosting"),
+            ConnectionType::Education => write!(f, "education"),
+            ConnectionType::Other(other) => write!(f, "{}", other),
+        }
+    }
+}
+
+/// Lookup metadata describing how a response was obtained.
+///
+/// The metadata is not persisted in the cache.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct LookupMeta {
+    /// Reason why the provider that answered was selected.
+    pub selection_reason: SelectionReason,
+    /// Time the provider took to answer, `None` for cached responses.
+    pub duration: Option<Duration>,
+    /// Number of providers tried until one answered, `0` for cached responses.
+    pub attempts: usize,
+    /// HTTP status code of the provider reply.
+    pub http_status: Option<u16>,
+    /// Requests left in the rate limit window of the provider, if reported.
+    pub rate_limit_remaining: Option<u64>,
+    /// HTTP headers of the provider reply.
+    pub headers: BTreeMap<String, String>,
+}
+
+/// Headers used by providers to report the remaining requests of the rate limit window.
+const RATE_LIMIT_HEADERS: [&str; 4] = [
+    "x-ratelimit-remaining",
+    "x-rate-limit-remaining",
+    "ratelimit-remaining",
+    "x-rl",
+];
+
+impl LookupMeta {
+    /// Returns `true` if the response was served from the cache.
+    pub fn is_cache_hit(&self) -> bool {
+        self.selection_reason == SelectionReason::CacheHit
+    }
+
+    /// Returns `true` if the response is an expired cached response, served because all
+    /// providers failed.
+    pub fn is_stale(&self) -> bool {
+        self.selection_reason == SelectionReason::StaleCache
+    }
+
+    /// Returns `true` if the provider confirmed that the cached response is still current with
+    /// `304 Not Modified`.
+    pub fn is_not_modified(&self) -> bool {
+        self.http_status == Some(304)
+    }
+
+    /// Returns the entity tag of the provider reply, used to revalidate the cached response.
+    pub fn etag(&self) -> Option<&str> {
+        self.headers.get("etag").map(String::as_str)
+    }
+
+    /// Records the status code and headers of a provider reply.
+    pub(crate) fn record_reply(
+        &mut self,
+        status: u16,
+        headers: &crate::lookup::client::header::HeaderMap,
+    ) {
+        self.http_status = Some(status);
+        self.headers = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|value| (name.as_str().to_string(), value.to_string()))
+            })
+            .collect();
+        self.rate_limit_remaining = RATE_LIMIT_HEADERS
+            .iter()
+            .find_map(|name| self.headers.get(*name)?.trim().parse().ok());
+    }
+}
+
+/// Reason why a particular provider answered the lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum SelectionReason {
+    /// The response was served from the cache.
+    CacheHit,
+    /// The first provider in the list answered.
+    #[default]
+    FirstHealthy,
+    /// The provider answered first while racing all providers concurrently.
+    RaceWinner,
+    /// The provider at the given position in the list answered after all previous providers failed.
+    Fallback(usize),
+    /// The provider was moved to the front of the list because it had the most remaining quota
+    /// when the cached response was about to expire.
+    UsageHint,
+    /// The provider was drawn first by the weighted rotation over the remaining quotas.
+    Rotation,
+    /// All providers failed and the expired cached response was served instead.
+    StaleCache,
+}
+
+/// Migrates a serialized response to the current schema version.
+///
+/// Unversioned forms only lack fields added since, they are read with defaults. The provider of
+/// the oldest forms, written as `IpWhoIs` or `{"Mock": "1.1.1.1"}`, is still parsed by its
+/// deserializer.
+pub(crate) fn migrate(mut value: serde_json::Value) -> serde_json::Value {
+    let Some(response) = value.as_object_mut() else {
+        return value;
+    };
+    let version = response
+        .get("schema_version")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or_default();
+    if version > SCHEMA_VERSION as u64 {
+        warn!(
+            "Response schema version {} is newer than {}, reading it anyway",
+            version, SCHEMA_VERSION
+        );
+        // connection types added by newer versions
+        if let Some(serde_json::Value::String(kind)) = response.get("connection_type") {
+            let candidate = serde_json::Value::String(kind.clone());
+            if serde_json::from_value::<ConnectionType>(candidate).is_err() {
+                let other = serde_json::json!({ "Other": kind });
+                response.insert("connection_type".to_string(), other);
+            }
+        }
+        return value;
+    }
+    response.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    value
+}
+
 impl fmt::Display for LookupResponse {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "IP: {}", self.ip)?;
@@ -80,6 +690,9 @@ impl fmt::Display for LookupResponse {
         if let Some(region) = &self.region {
             writeln!(f, "Region: {}", region)?;
         }
+        if let Some(district) = &self.district {
+            writeln!(f, "District: {}", district)?;
+        }
         if let Some(postal_code) = &self.postal_code {
             writeln!(f, "Postal code: {}", postal_code)?;
         }
@@ -105,14 +718,297 @@ impl fmt::Display for LookupResponse {
         } else {
             writeln!(f)?;
         }
+        if let Some(isp) = &self.isp {
+            writeln!(f, "ISP: {}", isp)?;
+        }
+        if let Some(network) = &self.network {
+            writeln!(f, "Network: {}", network)?;
+        }
         if let Some(hostname) = &self.hostname {
             writeln!(f, "Hostname: {}", hostname)?;
         }
         if let Some(proxy) = &self.is_proxy {
             writeln!(f, "Proxy: {}", proxy)?;
         }
+        if let Some(proxy_type) = &self.proxy_type {
+            write!(f, "Proxy type: {}", proxy_type)?;
+            if let Some(proxy_provider) = &self.proxy_provider {
+                write!(f, " ({})", proxy_provider)?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(mobile) = &self.is_mobile {
+            writeln!(f, "Mobile: {}", mobile)?;
+        }
+        if let Some(connection_type) = &self.connection_type {
+            writeln!(f, "Connection: {}", connection_type)?;
+        }
         write!(f, "Provider: {}", self.provider)?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "iso3166")]
+    #[test]
+    fn test_normalize_country() {
+        let mut response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpInfo);
+        response.country = Some("US".to_string());
+        response.country_code = Some("US".to_string());
+        response.normalize_country();
+        assert_eq!(response.country, Some("United States".to_string()));
+        assert_eq!(response.country_code, Some("US".to_string()));
+
+        response.country = Some("Deutschland".to_string());
+        response.country_code = Some("de".to_string());
+        response.normalize_country();
+        assert_eq!(
+            response.country,
+            Some("Deutschland".to_string()),
+            "Localized names should be kept"
+        );
+        assert_eq!(response.country_code, Some("DE".to_string()));
+
+        response.country = Some("Australia".to_string());
+        response.country_code = None;
+        response.normalize_country();
+        assert_eq!(response.country_code, Some("AU".to_string()));
+
+        response.country = Some("Atlantis".to_string());
+        response.country_code = Some("ZZ".to_string());
+        response.normalize_country();
+        assert_eq!(response.country, Some("Atlantis".to_string()));
+        assert_eq!(response.country_code, Some("ZZ".to_string()));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_timezone_offset() {
+        use chrono::{FixedOffset, TimeZone, Utc};
+
+        let winter = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2024, 7, 15, 12, 0, 0).unwrap();
+        let hours = |h: i32| FixedOffset::east_opt(h * 3600);
+        let mut response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpWhoIs);
+        assert_eq!(response.timezone_offset(), None);
+
+        response.time_zone = Some("Europe/Berlin".to_string());
+        assert_eq!(response.timezone_offset_at(winter), hours(1));
+        assert_eq!(response.timezone_offset_at(summer), hours(2));
+
+        response.time_zone = Some("-07:00".to_string());
+        assert_eq!(response.timezone_offset_at(summer), hours(-7));
+        let local = response.local_time().unwrap();
+        assert_eq!(local.offset(), &hours(-7).unwrap());
+
+        for (value, expected) in [
+            ("+0530", FixedOffset::east_opt(19800)),
+            ("UTC+2", hours(2)),
+            ("GMT-5", hours(-5)),
+            ("UTC", hours(0)),
+            ("Z", hours(0)),
+            ("Mars/Olympus", None),
+            ("+02:75", None),
+        ] {
+            response.time_zone = Some(value.to_string());
+            assert_eq!(response.timezone_offset_at(summer), expected, "{}", value);
+        }
+    }
+
+    #[cfg(feature = "geojson")]
+    #[test]
+    fn test_geojson() {
+        let mut response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpWhoIs);
+        response.country = Some("Australia".to_string());
+        response.asn = Some("AS13335".to_string());
+        let feature = response.to_geojson_feature();
+        assert_eq!(feature["type"], "Feature");
+        assert!(feature["geometry"].is_null(), "No coordinates reported");
+        assert_eq!(feature["properties"]["ip"], "1.1.1.1");
+        assert_eq!(feature["properties"]["country"], "Australia");
+        assert_eq!(feature["properties"]["provider"], "ipwhois");
+
+        response.latitude = Some(-33.494);
+        response.longitude = Some(143.2104);
+        let collection = to_geojson_feature_collection([&response, &response]);
+        assert_eq!(collection["type"], "FeatureCollection");
+        let features = collection["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0]["geometry"]["type"], "Point");
+        assert_eq!(
+            features[0]["geometry"]["coordinates"],
+            serde_json::json!([143.2104, -33.494]),
+            "GeoJSON expects longitude first"
+        );
+    }
+
+    #[test]
+    fn test_record_reply() {
+        use crate::lookup::client::header::{HeaderMap, HeaderValue};
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from_static(" 42"));
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        let mut meta = LookupMeta::default();
+        meta.record_reply(200, &headers);
+        assert_eq!(meta.http_status, Some(200));
+        assert_eq!(meta.rate_limit_remaining, Some(42));
+        assert_eq!(
+            meta.headers.get("content-type").map(String::as_str),
+            Some("application/json")
+        );
+        assert!(!meta.is_cache_hit());
+        assert!(!meta.is_not_modified());
+        assert_eq!(meta.etag(), None);
+        headers.insert("ETag", HeaderValue::from_static("W/\"1a2b\""));
+        meta.record_reply(304, &headers);
+        assert!(meta.is_not_modified());
+        assert_eq!(meta.etag(), Some("W/\"1a2b\""));
+    }
+
+    #[test]
+    fn test_with_precision() {
+        let mut response =
+            LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::Mock("".into()));
+        response.latitude = Some(37.42240);
+        response.longitude = Some(-122.08421);
+
+        let json = serde_json::to_string(&response.with_precision(2)).unwrap();
+        let rounded: LookupResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(rounded.latitude, Some(37.42), "Latitude not rounded");
+        assert_eq!(rounded.longitude, Some(-122.08), "Longitude not rounded");
+        assert_eq!(response.latitude, Some(37.42240), "Response was modified");
+
+        let json = serde_json::to_string(&response.with_precision(0)).unwrap();
+        let rounded: LookupResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(rounded.latitude, Some(37.0), "Latitude not rounded");
+    }
+
+    #[test]
+    fn test_address_helpers() {
+        let mut response =
+            LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::Mock("".into()));
+        assert_eq!(response.ip_version(), IpVersion::V4);
+        assert!(response.is_global());
+        assert_eq!(
+            response.to_ipv6_mapped(),
+            "::ffff:1.1.1.1".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(IpAddr::from(&response), response.ip);
+
+        response.ip = "fe80::1".parse().unwrap();
+        assert_eq!(response.ip_version(), IpVersion::V6);
+        assert!(!response.is_global());
+        assert_eq!(
+            response.to_ipv6_mapped(),
+            "fe80::1".parse::<Ipv6Addr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_stable_field_names() {
+        let response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpInfo);
+        let value = serde_json::to_value(&response).unwrap();
+        let fields: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        // renaming or removing a field needs a new schema version
+        let expected = [
+            "schema_version",
+            "ip",
+            "continent",
+            "continent_localized",
+            "country",
+            "country_localized",
+            "country_code",
+            "region",
+            "district",
+            "postal_code",
+            "city",
+            "city_localized",
+            "country_geoname_id",
+            "region_geoname_id",
+            "city_geoname_id",
+            "latitude",
+            "longitude",
+            "time_zone",
+            "asn",
+            "asn_org",
+            "isp",
+            "network",
+            "hostname",
+            "is_proxy",
+            "proxy_type",
+            "proxy_provider",
+            "is_mobile",
+            "connection_type",
+            "whois",
+            "security",
+            "provider",
+        ];
+        for field in expected {
+            assert!(fields.contains(&field), "Field {} missing", field);
+        }
+        assert_eq!(
+            fields.len(),
+            expected.len(),
+            "Unexpected fields {:?}",
+            fields
+        );
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_from_json_compat() {
+        // unversioned form with the provider written as its variant
+        let response = LookupResponse::from_json_compat(
+            r#"{"ip": "1.1.1.1", "country": "Australia", "provider": {"Mock": "1.1.1.1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(response.schema_version, SCHEMA_VERSION);
+        assert_eq!(response.country, Some("Australia".to_string()));
+        assert_eq!(
+            response.provider,
+            LookupProvider::Mock("1.1.1.1".to_string())
+        );
+
+        // newer form with fields and connection types unknown to this version
+        let json = format!(
+            r#"{{"schema_version": {}, "ip": "1.1.1.1", "provider": "ipinfo", "connection_type": "Fiber", "future": 1}}"#,
+            SCHEMA_VERSION + 1
+        );
+        let response = LookupResponse::from_json_compat(&json).unwrap();
+        assert_eq!(
+            response.connection_type,
+            Some(ConnectionType::Other("Fiber".to_string()))
+        );
+        assert!(
+            serde_json::from_str::<LookupResponse>(&json).is_err(),
+            "Plain deserialization should refuse the unknown connection type"
+        );
+
+        let response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpInfo);
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(LookupResponse::from_json_compat(&json).unwrap(), response);
+    }
+
+    #[cfg(feature = "ipnet")]
+    #[test]
+    fn test_ip_network() {
+        let mut response =
+            LookupResponse::new("8.8.8.8".parse().unwrap(), LookupProvider::Mock("".into()));
+        assert!(ipnet::IpNet::try_from(&response).is_err());
+        response.network = Some("8.8.8.0/24".to_string());
+        let network = ipnet::IpNet::try_from(&response).unwrap();
+        assert!(network.contains(&response.ip));
+        response.network = Some("8.8.8.0".to_string());
+        assert_eq!(response.ip_network(), None);
+    }
+}
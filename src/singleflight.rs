@@ -0,0 +1,355 @@
+//! In-process de-duplication of concurrent cached lookups
+//!
+//! Concurrent cached lookups of the same target with the same providers, settings and cache file,
+//! common at service startup, share a single flight: the first one performs the lookup while the
+//! others wait for its result instead of sending their own requests. If the leading lookup is
+//! cancelled before it completes, a waiting lookup takes over and performs the lookup itself.
+
+use crate::{
+    error::{CacheError, Error, Result},
+    lookup::{error::LookupError, LookupOptions, ProviderConfig},
+    LookupResponse, LookupStrategy,
+};
+#[cfg(feature = "blocking")]
+use std::sync::Condvar;
+#[cfg(not(feature = "blocking"))]
+use std::task::{Poll, Waker};
+use std::{
+    net::IpAddr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// Identifies identical lookups, lookups differing in any setting run their own flight
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Key {
+    pub file_name: Option<String>,
+    pub target: Option<IpAddr>,
+    pub ttl: Option<u64>,
+    pub flush: bool,
+    pub strategy: LookupStrategy,
+    pub options: LookupOptions,
+    pub providers: Vec<ProviderConfig>,
+}
+
+static FLIGHTS: Mutex<Vec<(Key, Arc<Flight>)>> = Mutex::new(Vec::new());
+
+/// Lookup in flight, completed with the result of its leader
+#[derive(Default)]
+pub(crate) struct Flight {
+    state: Mutex<State>,
+    #[cfg(feature = "blocking")]
+    done: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    outcome: Option<Outcome>,
+    #[cfg(not(feature = "blocking"))]
+    wakers: Vec<Waker>,
+}
+
+/// How the flight ended
+enum Outcome {
+    /// Leader completed the lookup
+    Completed(Box<Result<LookupResponse>>),
+    /// Leader was dropped before completing, the waiting lookups take over
+    Abandoned,
+}
+
+/// Role of a lookup in its flight
+pub(crate) enum Role {
+    /// Performs the lookup and completes the flight
+    Leader(Leader),
+    /// Waits for the result of the leader
+    Follower(Arc<Flight>),
+}
+
+/// Joins the flight of an identical lookup, starting a new flight if none is in flight
+pub(crate) fn join(key: Key) -> Role {
+    let mut flights = lock(&FLIGHTS);
+    if let Some((_, flight)) = flights.iter().find(|(k, _)| *k == key) {
+        return Role::Follower(flight.clone());
+    }
+    let flight = Arc::new(Flight::default());
+    flights.push((key, flight.clone()));
+    Role::Leader(Leader {
+        flight,
+        completed: false,
+    })
+}
+
+/// Lookup performing the request of its flight
+pub(crate) struct Leader {
+    flight: Arc<Flight>,
+    completed: bool,
+}
+
+impl Leader {
+    /// Hands a copy of the result to the waiting lookups
+    pub(crate) fn complete(mut self, result: &Result<LookupResponse>) {
+        self.finish(Outcome::Completed(Box::new(duplicate_result(result))));
+    }
+
+    fn finish(&mut self, outcome: Outcome) {
+        self.completed = true;
+        // later lookups start a new flight
+        lock(&FLIGHTS).retain(|(_, flight)| !Arc::ptr_eq(flight, &self.flight));
+        let mut state = lock(&self.flight.state);
+        state.outcome = Some(outcome);
+        #[cfg(not(feature = "blocking"))]
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+        #[cfg(feature = "blocking")]
+        self.flight.done.notify_all();
+    }
+}
+
+impl Drop for Leader {
+    /// Hands the lookup over to the waiting lookups if the leader is cancelled, instead of
+    /// leaving them waiting
+    fn drop(&mut self) {
+        if !self.completed {
+            self.finish(Outcome::Abandoned);
+        }
+    }
+}
+
+impl Flight {
+    /// Waits for the result of the leader, `None` if the leader was cancelled and the lookup has
+    /// to join the flight again
+    #[cfg(not(feature = "blocking"))]
+    pub(crate) async fn wait(&self) -> Option<Result<LookupResponse>> {
+        futures_util::future::poll_fn(|cx| {
+            let mut state = lock(&self.state);
+            match &state.outcome {
+                Some(Outcome::Completed(result)) => Poll::Ready(Some(duplicate_result(result))),
+                Some(Outcome::Abandoned) => Poll::Ready(None),
+                None => {
+                    if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                        state.wakers.push(cx.waker().clone());
+                    }
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Waits for the result of the leader, `None` if the leader was cancelled and the lookup has
+    /// to join the flight again
+    #[cfg(feature = "blocking")]
+    pub(crate) fn wait(&self) -> Option<Result<LookupResponse>> {
+        let mut state = lock(&self.state);
+        loop {
+            match &state.outcome {
+                Some(Outcome::Completed(result)) => return Some(duplicate_result(result)),
+                Some(Outcome::Abandoned) => return None,
+                None => {}
+            }
+            state = self
+                .done
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn duplicate_result(result: &Result<LookupResponse>) -> Result<LookupResponse> {
+    match result {
+        Ok(response) => Ok(response.clone()),
+        Err(e) => Err(duplicate(e)),
+    }
+}
+
+/// Copies an error for the waiting lookups
+///
+/// Errors of the HTTP client are shared, I/O and serde errors are rebuilt from their kind and
+/// message.
+fn duplicate(error: &Error) -> Error {
+    match error {
+        Error::CacheError(e) => Error::CacheError(match e {
+            CacheError::SerdeError(e) => CacheError::SerdeError(duplicate_serde(e)),
+            CacheError::IOError(e) => CacheError::IOError(duplicate_io(e)),
+            CacheError::Utf8Error(e) => CacheError::Utf8Error(e.clone()),
+            CacheError::EncryptionError(e) => CacheError::EncryptionError(e.clone()),
+            CacheError::IntegrityError(e) => CacheError::IntegrityError(e.clone()),
+            CacheError::StorageError(e) => CacheError::StorageError(e.clone()),
+        }),
+        Error::LookupError(e) => Error::LookupError(duplicate_lookup(e)),
+        Error::TimeError(e) => Error::TimeError(e.clone()),
+        Error::NetworkMonitorError(e) => Error::NetworkMonitorError(duplicate_io(e)),
+        Error::AlreadyInitialized => Error::AlreadyInitialized,
+        Error::ConfigError(e) => Error::ConfigError(e.clone()),
+    }
+}
+
+fn duplicate_lookup(error: &LookupError) -> LookupError {
+    match error {
+        #[cfg(not(feature = "ureq"))]
+        LookupError::ReqwestError(e) => LookupError::ReqwestError(e.clone()),
+        #[cfg(feature = "ureq")]
+        LookupError::UreqError(e) => LookupError::UreqError(e.clone()),
+        LookupError::TooManyRequests(e) => LookupError::TooManyRequests(e.clone()),
+        LookupError::RequestStatus(e) => LookupError::RequestStatus(e.clone()),
+        LookupError::SerdeError(e) => LookupError::SerdeError(duplicate_serde(e)),
+        LookupError::GenericError(e) => LookupError::GenericError(e.clone()),
+        LookupError::TargetNotSupported => LookupError::TargetNotSupported,
+        LookupError::TargetRequired => LookupError::TargetRequired,
+        LookupError::AsnNotSupported => LookupError::AsnNotSupported,
+        LookupError::CaptivePortal(e) => LookupError::CaptivePortal(e.clone()),
+        LookupError::Offline(e) => LookupError::Offline(e.clone()),
+        LookupError::InsecureEndpoint(e) => LookupError::InsecureEndpoint(e.clone()),
+        LookupError::InsecureRedirect(e) => LookupError::InsecureRedirect(e.clone()),
+        LookupError::PinMismatch(e) => LookupError::PinMismatch(e.clone()),
+        LookupError::ForbiddenEndpoint(e) => LookupError::ForbiddenEndpoint(e.clone()),
+        LookupError::ProviderDisabled(e) => LookupError::ProviderDisabled(e.clone()),
+        LookupError::NonPublicAddress(kind) => LookupError::NonPublicAddress(*kind),
+        LookupError::Timeout => LookupError::Timeout,
+        LookupError::Cancelled => LookupError::Cancelled,
+        LookupError::ResolveError(e) => LookupError::ResolveError(duplicate_io(e)),
+        LookupError::ProviderError {
+            provider,
+            code,
+            message,
+        } => LookupError::ProviderError {
+            provider: provider.clone(),
+            code: code.clone(),
+            message: message.clone(),
+        },
+        LookupError::ParseError {
+            provider,
+            path,
+            snippet,
+            source,
+        } => LookupError::ParseError {
+            provider: provider.clone(),
+            path: path.clone(),
+            snippet: snippet.clone(),
+            source: duplicate_serde(source),
+        },
+        LookupError::InvalidResponseIp(e) => LookupError::InvalidResponseIp(e.clone()),
+        LookupError::ResponseTooLarge(limit) => LookupError::ResponseTooLarge(*limit),
+        LookupError::UnexpectedContentType(e) => LookupError::UnexpectedContentType(e.clone()),
+    }
+}
+
+fn duplicate_io(error: &std::io::Error) -> std::io::Error {
+    std::io::Error::new(error.kind(), error.to_string())
+}
+
+fn duplicate_serde(error: &serde_json::Error) -> serde_json::Error {
+    serde::de::Error::custom(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::LookupProvider;
+
+    fn key(target: &str) -> Key {
+        Key {
+            file_name: Some("singleflight-test".to_string()),
+            target: target.parse().ok(),
+            ttl: None,
+            flush: false,
+            strategy: LookupStrategy::Sequential,
+            options: LookupOptions::default(),
+            providers: vec![LookupProvider::Mock(target.to_string()).into()],
+        }
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_shared_result() {
+        let Role::Leader(leader) = join(key("1.1.1.1")) else {
+            panic!("First lookup should lead");
+        };
+        let Role::Follower(flight) = join(key("1.1.1.1")) else {
+            panic!("Identical lookup should follow");
+        };
+        assert!(
+            matches!(join(key("8.8.8.8")), Role::Leader(_)),
+            "Other target should start its own flight"
+        );
+        let race = Key {
+            strategy: LookupStrategy::Race,
+            ..key("1.1.1.1")
+        };
+        assert!(
+            matches!(join(race), Role::Leader(_)),
+            "Other strategy should start its own flight"
+        );
+        let stale = Key {
+            options: LookupOptions::default().with_serve_stale(),
+            ..key("1.1.1.1")
+        };
+        assert!(
+            matches!(join(stale), Role::Leader(_)),
+            "Other options should start their own flight"
+        );
+        let response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpWhoIs);
+        #[cfg(not(feature = "blocking"))]
+        let (result, _) = futures_util::join!(flight.wait(), async {
+            leader.complete(&Ok(response.clone()))
+        });
+        #[cfg(feature = "blocking")]
+        let result = {
+            let waiter = std::thread::spawn(move || flight.wait());
+            leader.complete(&Ok(response.clone()));
+            waiter.join().unwrap()
+        };
+        assert_eq!(result.unwrap().unwrap().ip, response.ip);
+        assert!(
+            matches!(join(key("1.1.1.1")), Role::Leader(_)),
+            "Completed flight should be removed"
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_cancelled_leader() {
+        let Role::Leader(leader) = join(key("9.9.9.9")) else {
+            panic!("First lookup should lead");
+        };
+        let Role::Follower(flight) = join(key("9.9.9.9")) else {
+            panic!("Identical lookup should follow");
+        };
+        drop(leader);
+        let result = flight.wait().await;
+        assert!(
+            result.is_none(),
+            "Dropped leader should hand the lookup over"
+        );
+        let Role::Leader(leader) = join(key("9.9.9.9")) else {
+            panic!("Follower should take over the abandoned flight");
+        };
+        let Role::Follower(flight) = join(key("9.9.9.9")) else {
+            panic!("Identical lookup should follow the new leader");
+        };
+        let response = LookupResponse::new("9.9.9.9".parse().unwrap(), LookupProvider::IpWhoIs);
+        leader.complete(&Ok(response.clone()));
+        let result = flight.wait().await;
+        assert_eq!(result.unwrap().unwrap().ip, response.ip);
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let error = Error::LookupError(LookupError::ProviderError {
+            provider: LookupProvider::IpInfo,
+            code: Some("403".to_string()),
+            message: "Invalid token".to_string(),
+        });
+        assert!(matches!(
+            duplicate(&error),
+            Error::LookupError(LookupError::ProviderError { message, .. }) if message == "Invalid token"
+        ));
+        let error = Error::CacheError(CacheError::IOError(std::io::ErrorKind::NotFound.into()));
+        assert!(matches!(
+            duplicate(&error),
+            Error::CacheError(CacheError::IOError(e)) if e.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+//! # 📊 Provider statistics
+//!
+//! Counts the requests and failures of every provider and the time of its last successful lookup,
+//! so that operators can see which providers their application actually depends on. The
+//! statistics are stored in their own `lookup.stats` file next to the cache file and survive
+//! clearing the cache.
+//!
+//! Recording is enabled through [`Config::with_stats`](crate::Config::with_stats), every provider
+//! tried by a sequential lookup is then counted.
+//!
+//! ## Example
+//! ```rust
+//! use public_ip_address::stats::Stats;
+//! use std::error::Error;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!     for report in Stats::load()?.report() {
+//!         println!("{}", report);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{
+    cache::{resolve_cache_path, storage, Result},
+    lookup::LookupProvider,
+    time,
+};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, io::ErrorKind, time::SystemTime};
+
+/// Name of the statistics file, stored in the cache directory
+pub const STATS_FILE: &str = "lookup.stats";
+
+/// Request counters of a single provider
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct ProviderStats {
+    /// Number of requests made to the provider
+    pub requests: u64,
+    /// Number of requests that failed
+    pub failures: u64,
+    /// Time of the last successful request
+    pub last_success: Option<SystemTime>,
+    /// Time of the last failed request
+    pub last_failure: Option<SystemTime>,
+}
+
+impl ProviderStats {
+    /// Returns the number of successful requests
+    pub fn successes(&self) -> u64 {
+        self.requests.saturating_sub(self.failures)
+    }
+
+    /// Returns the fraction of requests that succeeded, `None` if no request was made
+    pub fn success_rate(&self) -> Option<f64> {
+        (self.requests > 0).then(|| self.successes() as f64 / self.requests as f64)
+    }
+}
+
+/// Statistics of a provider as listed by [`Stats::report`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ProviderReport {
+    /// Name of the provider
+    pub provider: String,
+    /// Request counters of the provider
+    pub stats: ProviderStats,
+}
+
+impl fmt::Display for ProviderReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} requests, {} failures",
+            self.provider, self.stats.requests, self.stats.failures
+        )?;
+        if let Some(rate) = self.stats.success_rate() {
+            write!(f, ", {:.0}% success", rate * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Persisted request counters of all providers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct Stats {
+    /// Counters per provider, keyed by the provider name
+    pub providers: BTreeMap<String, ProviderStats>,
+}
+
+impl Stats {
+    /// Loads the statistics file, empty statistics if it doesn't exist yet
+    pub fn load() -> Result<Stats> {
+        let path = resolve_cache_path(&Some(STATS_FILE.to_string()))?;
+        debug!("Loading statistics from {}", path);
+        match storage::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Stats::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the statistics file
+    pub fn save(&self) -> Result<()> {
+        let path = resolve_cache_path(&Some(STATS_FILE.to_string()))?;
+        debug!("Saving statistics to {}", path);
+        storage::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Deletes the statistics file
+    pub fn delete() -> Result<()> {
+        match storage::remove(resolve_cache_path(&Some(STATS_FILE.to_string()))?) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records a request to the provider and whether it succeeded
+    pub fn record(&mut self, provider: &LookupProvider, success: bool) {
+        let stats = self.providers.entry(provider.to_string()).or_default();
+        stats.requests += 1;
+        if success {
+            stats.last_success = Some(time::now());
+        } else {
+            stats.failures += 1;
+            stats.last_failure = Some(time::now());
+        }
+    }
+
+    /// Returns the counters of the provider, if it was ever used
+    pub fn provider(&self, provider: &LookupProvider) -> Option<&ProviderStats> {
+        self.providers.get(&provider.to_string())
+    }
+
+    /// Lists the providers by the number of successful requests, the most relied upon first
+    pub fn report(&self) -> Vec<ProviderReport> {
+        let mut reports: Vec<ProviderReport> = self
+            .providers
+            .iter()
+            .map(|(provider, stats)| ProviderReport {
+                provider: provider.clone(),
+                stats: stats.clone(),
+            })
+            .collect();
+        reports.sort_by_key(|report| std::cmp::Reverse(report.stats.successes()));
+        reports
+    }
+}
+
+/// Records the attempts of a lookup in the statistics file, if enabled in the configuration
+pub(crate) fn record(attempts: &[(LookupProvider, bool)]) {
+    if !crate::config::config().stats || attempts.is_empty() {
+        return;
+    }
+    let mut stats = Stats::load().unwrap_or_default();
+    for (provider, success) in attempts {
+        stats.record(provider, *success);
+    }
+    if let Err(e) = stats.save() {
+        warn!("Failed to save statistics: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut stats = Stats::default();
+        stats.record(&LookupProvider::IpWhoIs, true);
+        stats.record(&LookupProvider::IpWhoIs, false);
+        stats.record(&LookupProvider::IpInfo, true);
+        stats.record(&LookupProvider::IpInfo, true);
+
+        let ipwhois = stats.provider(&LookupProvider::IpWhoIs).unwrap();
+        assert_eq!(ipwhois.requests, 2);
+        assert_eq!(ipwhois.failures, 1);
+        assert_eq!(ipwhois.success_rate(), Some(0.5));
+        assert!(ipwhois.last_success.is_some() && ipwhois.last_failure.is_some());
+
+        let report = stats.report();
+        assert_eq!(
+            report[0].provider, "ipinfo",
+            "Most used provider should be first"
+        );
+        assert_eq!(
+            report[0].to_string(),
+            "ipinfo: 2 requests, 0 failures, 100% success"
+        );
+
+        let json = serde_json::to_string(&stats).unwrap();
+        let parsed: Stats = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, stats, "Statistics should round trip");
+    }
+}
@@ -0,0 +1,142 @@
+//! Tracing instrumentation helpers
+
+use crate::time::Instant;
+use tracing::Span;
+
+#[cfg(feature = "otel")]
+use crate::lookup::{
+    client::{self, Request},
+    error::LookupError,
+};
+
+/// Records the elapsed time into the `duration_ms` field of the current span when dropped.
+pub(crate) struct SpanTimer {
+    start: Instant,
+}
+
+impl SpanTimer {
+    /// Starts measuring the duration of the current span.
+    pub(crate) fn start() -> Self {
+        SpanTimer {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for SpanTimer {
+    fn drop(&mut self) {
+        Span::current().record("duration_ms", self.start.elapsed().as_millis() as u64);
+    }
+}
+
+/// Query parameters carrying credentials, their values are redacted from recorded URLs.
+#[cfg(feature = "otel")]
+const SENSITIVE_PARAMS: [&str; 6] = ["key", "apikey", "api_key", "api-key", "access_key", "token"];
+
+/// Records the OpenTelemetry client attributes of an outgoing request on the current span.
+#[cfg(feature = "otel")]
+pub(crate) fn record_request(request: &Request) {
+    let span = Span::current();
+    span.record("otel.kind", "client");
+    span.record("http.request.method", request.method().as_str());
+    span.record("url.full", redact_url(request.url()).as_str());
+}
+
+/// Records the HTTP status code of a provider reply on the current span.
+#[cfg(feature = "otel")]
+pub(crate) fn record_status(status: u16) {
+    Span::current().record("http.response.status_code", status);
+}
+
+/// Records the type of a failed lookup on the current span.
+#[cfg(feature = "otel")]
+pub(crate) fn record_error(error: &LookupError) {
+    Span::current().record("otel.status_code", "ERROR");
+    Span::current().record("error.type", error_type(error));
+}
+
+/// Returns a copy of the URL with the values of credential parameters replaced.
+#[cfg(feature = "otel")]
+pub(crate) fn redact_url(url: &client::Url) -> client::Url {
+    let mut redacted = url.clone();
+    if url.query().is_none() {
+        return redacted;
+    }
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            if SENSITIVE_PARAMS.contains(&name.to_lowercase().as_str()) {
+                (name.into_owned(), "REDACTED".to_string())
+            } else {
+                (name.into_owned(), value.into_owned())
+            }
+        })
+        .collect();
+    redacted.query_pairs_mut().clear().extend_pairs(pairs);
+    redacted
+}
+
+/// Returns a low-cardinality name for the error, used as the `error.type` attribute.
+#[cfg(feature = "otel")]
+pub(crate) fn error_type(error: &LookupError) -> &'static str {
+    match error {
+        #[cfg(not(feature = "ureq"))]
+        LookupError::ReqwestError(e) if client::is_timeout(e) => "timeout",
+        #[cfg(not(feature = "ureq"))]
+        LookupError::ReqwestError(_) => "request",
+        #[cfg(feature = "ureq")]
+        LookupError::UreqError(e) if client::is_timeout(e) => "timeout",
+        #[cfg(feature = "ureq")]
+        LookupError::UreqError(_) => "request",
+        LookupError::TooManyRequests(_) => "too_many_requests",
+        LookupError::RequestStatus(_) => "status",
+        LookupError::SerdeError(_) => "parse",
+        LookupError::GenericError(_) => "generic",
+        LookupError::TargetNotSupported => "target_not_supported",
+        LookupError::TargetRequired => "target_required",
+        LookupError::AsnNotSupported => "asn_not_supported",
+        LookupError::CaptivePortal(_) => "captive_portal",
+        LookupError::Offline(_) => "offline",
+        LookupError::InsecureEndpoint(_) => "insecure_endpoint",
+        LookupError::InsecureRedirect(_) => "insecure_redirect",
+        LookupError::PinMismatch(_) => "pin_mismatch",
+        LookupError::ForbiddenEndpoint(_) => "forbidden_endpoint",
+        LookupError::ProviderDisabled(_) => "provider_disabled",
+        LookupError::NonPublicAddress(_) => "non_public_address",
+        LookupError::Timeout => "timeout",
+        LookupError::Cancelled => "cancelled",
+        LookupError::ResolveError(_) => "resolve",
+        LookupError::InvalidResponseIp(_) => "invalid_response_ip",
+        LookupError::ResponseTooLarge(_) => "response_too_large",
+        LookupError::UnexpectedContentType(_) => "unexpected_content_type",
+        LookupError::ProviderError { .. } => "provider_error",
+        LookupError::ParseError { .. } => "parse",
+    }
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_url() {
+        let url = "https://api.ipdata.co/1.1.1.1?api-key=secret&fields=ip"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            redact_url(&url).as_str(),
+            "https://api.ipdata.co/1.1.1.1?api-key=REDACTED&fields=ip"
+        );
+        let url = "https://ipwho.is/".parse().unwrap();
+        assert_eq!(redact_url(&url).as_str(), "https://ipwho.is/");
+    }
+
+    #[test]
+    fn test_error_type() {
+        assert_eq!(error_type(&LookupError::Timeout), "timeout");
+        assert_eq!(
+            error_type(&LookupError::TooManyRequests(String::new())),
+            "too_many_requests"
+        );
+    }
+}
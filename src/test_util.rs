@@ -0,0 +1,60 @@
+//! 🧪 Test utilities.
+//!
+//! Helpers for testing code that consumes this crate, enabled through the `test-util` feature flag.
+//!
+//! ## Example
+//! ```rust
+//! use public_ip_address::{response::LookupResponse, test_util::{ip, mock_chain}};
+//!
+//! // provider chain answering with 1.1.1.1 first and 8.8.8.8 as fallback
+//! let providers = mock_chain(&["1.1.1.1", "8.8.8.8"]);
+//! assert_eq!(providers.len(), 2);
+//! assert_eq!(LookupResponse::example().ip, ip("1.1.1.1"));
+//! ```
+
+use crate::{
+    cache::ResponseCache,
+    lookup::{LookupProvider, Parameters},
+    response::LookupResponse,
+};
+use std::net::IpAddr;
+
+impl LookupResponse {
+    /// Returns a fully populated example response for `1.1.1.1`.
+    pub fn example() -> Self {
+        crate::lookup::mock::full_response(ip("1.1.1.1"), mock_provider("1.1.1.1").0)
+    }
+}
+
+/// Parses an IP address, panicking on invalid input.
+pub fn ip(ip: &str) -> IpAddr {
+    ip.parse().expect("invalid IP address")
+}
+
+/// Returns a mock provider entry answering with the given IP address.
+pub fn mock_provider(ip: &str) -> (LookupProvider, Option<Parameters>) {
+    (LookupProvider::Mock(ip.to_string()), None)
+}
+
+/// Returns a provider chain of mock providers, one for each IP address.
+pub fn mock_chain(ips: &[&str]) -> Vec<(LookupProvider, Option<Parameters>)> {
+    ips.iter().map(|ip| mock_provider(ip)).collect()
+}
+
+/// Deletes the default cache file, ignoring errors.
+pub fn clear_cache() {
+    _ = ResponseCache::default().delete();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_chain() {
+        let chain = mock_chain(&["1.1.1.1", "8.8.8.8"]);
+        assert_eq!(chain.len(), 2, "Chain length not matching");
+        assert_eq!(chain[1].0, LookupProvider::Mock("8.8.8.8".to_string()));
+        assert_eq!(LookupResponse::example().ip, ip("1.1.1.1"));
+    }
+}
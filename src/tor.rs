@@ -0,0 +1,146 @@
+//! 🧅 Tor exit node detection.
+//!
+//! Checks an IP address against the bulk exit list published by the Tor Project, instead of
+//! relying on the Tor flag of the providers, which is often stale. The list is downloaded on first
+//! use and kept in memory for [`EXIT_LIST_TTL`].
+//!
+//! Enabled through the `tor` feature flag.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use std::error::Error;
+//! #[cfg_attr(not(feature = "blocking"), tokio::main)]
+//! #[maybe_async::maybe_async]
+//! async fn main() -> Result<(), Box<dyn Error>> {
+//!     let mut response = public_ip_address::perform_lookup(None).await?;
+//!     public_ip_address::tor::enrich(&mut response).await?;
+//!     println!("{:?}", response.security);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{
+    lookup::{
+        client,
+        error::{LookupError, Result},
+        handle_response,
+    },
+    response::LookupResponse,
+    time::Instant,
+};
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Bulk exit list of the Tor Project, one address per line
+pub const EXIT_LIST_URL: &str = "https://check.torproject.org/torbulkexitlist";
+
+/// How long a downloaded exit list is used before it is downloaded again
+pub const EXIT_LIST_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Exit list and the time it was downloaded
+type ExitList = (Instant, Arc<HashSet<IpAddr>>);
+
+static EXIT_LIST: Mutex<Option<ExitList>> = Mutex::new(None);
+
+/// Returns the exit list, downloading it if it is missing or expired
+#[maybe_async::maybe_async]
+pub async fn exit_list() -> Result<Arc<HashSet<IpAddr>>> {
+    let cached = EXIT_LIST
+        .lock()
+        .map_err(|_| LookupError::GenericError("Tor exit list poisoned".to_string()))?
+        .clone();
+    if let Some((downloaded, list)) = cached {
+        if downloaded.elapsed() < EXIT_LIST_TTL {
+            return Ok(list);
+        }
+    }
+    let response = client::shared().get(EXIT_LIST_URL).send().await;
+    let list = Arc::new(parse_exit_list(&handle_response(response).await?));
+    if let Ok(mut cached) = EXIT_LIST.lock() {
+        *cached = Some((Instant::now(), list.clone()));
+    }
+    Ok(list)
+}
+
+/// Checks if the IP address is a Tor exit node
+#[maybe_async::maybe_async]
+pub async fn is_exit(ip: IpAddr) -> Result<bool> {
+    Ok(exit_list().await?.contains(&ip))
+}
+
+/// Checks the address of the response against the exit list and sets `security.is_tor`
+///
+/// The flag reported by the provider, if any, is overridden.
+#[maybe_async::maybe_async]
+pub async fn enrich(response: &mut LookupResponse) -> Result<()> {
+    let list = exit_list().await?;
+    mark(response, &list);
+    Ok(())
+}
+
+/// Sets `security.is_tor` of the response from the exit list
+fn mark(response: &mut LookupResponse, list: &HashSet<IpAddr>) {
+    response
+        .security
+        .get_or_insert_with(Default::default)
+        .is_tor = Some(list.contains(&response.ip));
+}
+
+/// Parses the bulk exit list, skipping comments and invalid lines
+pub fn parse_exit_list(body: &str) -> HashSet<IpAddr> {
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lookup::LookupProvider, response::SecurityInfo};
+    const TEST_INPUT: &str =
+        "# exit addresses\n185.220.101.1\n2001:67c:e60:c0c:192:42:116:16\n\ninvalid\n";
+
+    #[ignore]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_exit_list() {
+        let list = exit_list().await;
+        assert!(
+            list.is_ok_and(|list| !list.is_empty()),
+            "Failed downloading the exit list"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let list = parse_exit_list(TEST_INPUT);
+        assert_eq!(list.len(), 2);
+        assert!(list.contains(&"185.220.101.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_mark() {
+        let list = parse_exit_list(TEST_INPUT);
+        let mut response =
+            LookupResponse::new("185.220.101.1".parse().unwrap(), LookupProvider::IpWhoIs);
+        mark(&mut response, &list);
+        assert_eq!(response.security.unwrap().is_tor, Some(true));
+
+        let mut response = LookupResponse::new("1.1.1.1".parse().unwrap(), LookupProvider::IpBase);
+        response.security = Some(SecurityInfo {
+            is_tor: Some(true),
+            ..Default::default()
+        });
+        mark(&mut response, &list);
+        assert_eq!(
+            response.security.unwrap().is_tor,
+            Some(false),
+            "Stale provider flag should be overridden"
+        );
+    }
+}
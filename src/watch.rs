@@ -0,0 +1,486 @@
+//! # 👀 Public address watcher
+//!
+//! This module polls the lookup providers for the current public IP address and reports when it
+//! changes. The watcher does not sleep on its own, the caller waits for `Watcher::next_delay`
+//! between polls with the timer of their runtime, which keeps the watcher runtime agnostic.
+//!
+//! The delay between polls is randomized by the configured jitter so that many devices polling the
+//! same providers don't synchronize their requests, and grows exponentially while polls keep failing.
+//!
+//! Before a new address is reported it can be required to show up in several consecutive polls
+//! (debounce) and to be confirmed by a second provider, which keeps a flaky provider from
+//! triggering updates. A callback registered with `Watcher::on_change` runs for every reported
+//! change, which is all a dynamic DNS updater needs.
+//!
+//! A [`WatchTrigger`] taken from the watcher makes the next poll happen right away, e.g. from a
+//! [`NetworkMonitor`](crate::monitor::NetworkMonitor) when the local network changes. Waiting with
+//! `WatchTrigger::wait_timeout` or `WatchTrigger::triggered` instead of a plain sleep ends the
+//! delay early when the trigger fires.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use public_ip_address::{lookup::LookupProvider, watch::{WatchEvent, Watcher}};
+//! use std::time::Duration;
+//!
+//! # #[cfg_attr(not(feature = "blocking"), tokio::main)]
+//! # #[maybe_async::maybe_async]
+//! # async fn main() {
+//! let mut watcher = Watcher::new(vec![(LookupProvider::IpWhoIs, None)])
+//!     .with_interval(Duration::from_secs(60))
+//!     .with_jitter(0.2)
+//!     .with_debounce(2)
+//!     .with_confirmation(LookupProvider::IpInfo, None)
+//!     .on_change(|previous, response| println!("{:?} -> {}", previous, response.ip));
+//! loop {
+//!     if let Ok(WatchEvent::Changed { response, .. }) = watcher.poll().await {
+//!         println!("New address: {}", response.ip);
+//!     }
+//!     watcher.trigger().wait_timeout(watcher.next_delay());
+//! }
+//! # }
+//! ```
+
+use crate::{
+    error::Result,
+    lookup::{LookupProvider, Parameters},
+    perform_lookup_with, LookupResponse,
+};
+use futures_util::{future::poll_fn, task::AtomicWaker};
+use log::{debug, warn};
+use std::{
+    fmt,
+    future::Future,
+    net::IpAddr,
+    sync::{Arc, Condvar, Mutex},
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+/// Result of a single watcher poll
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum WatchEvent {
+    /// The public address is the same as during the previous poll
+    Unchanged(LookupResponse),
+    /// The public address differs from the previous poll, but the change is not debounced or
+    /// confirmed yet
+    Pending(LookupResponse),
+    /// The public address changed, `previous` is `None` on the first poll
+    Changed {
+        /// Address reported by the previous successful poll
+        previous: Option<IpAddr>,
+        /// Response of the current poll
+        response: LookupResponse,
+    },
+}
+
+/// Function called with the previous address and the new response
+type ChangeFn = dyn Fn(Option<IpAddr>, &LookupResponse) + Send + Sync;
+
+/// Callback run when the public address changes
+#[derive(Clone)]
+struct ChangeCallback(Arc<ChangeFn>);
+
+impl fmt::Debug for ChangeCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ChangeCallback")
+    }
+}
+
+/// Handle that makes a watcher poll right away
+///
+/// Clones share the same state, triggering any of them ends the delay of the watcher. The trigger
+/// stays pending until the next poll of the watcher.
+#[derive(Debug, Clone, Default)]
+pub struct WatchTrigger(Arc<TriggerState>);
+
+#[derive(Debug, Default)]
+struct TriggerState {
+    pending: Mutex<bool>,
+    condvar: Condvar,
+    waker: AtomicWaker,
+}
+
+impl WatchTrigger {
+    /// Makes the watcher poll right away
+    pub fn trigger(&self) {
+        if let Ok(mut pending) = self.0.pending.lock() {
+            *pending = true;
+        }
+        self.0.condvar.notify_all();
+        self.0.waker.wake();
+    }
+
+    /// Returns whether the trigger fired since the last poll
+    pub fn is_pending(&self) -> bool {
+        self.0
+            .pending
+            .lock()
+            .map(|pending| *pending)
+            .unwrap_or(false)
+    }
+
+    /// Blocks the current thread until the trigger fires or the timeout elapses
+    ///
+    /// Returns `true` if the trigger fired.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let Ok(mut pending) = self.0.pending.lock() else {
+            return false;
+        };
+        while !*pending {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            pending = match self.0.condvar.wait_timeout(pending, remaining) {
+                Ok((pending, _)) => pending,
+                Err(_) => return false,
+            };
+        }
+        *pending
+    }
+
+    /// Resolves once the trigger fires, to be raced against the timer of the runtime
+    pub fn triggered(&self) -> impl Future<Output = ()> + '_ {
+        poll_fn(move |cx| {
+            self.0.waker.register(cx.waker());
+            if self.is_pending() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+    }
+
+    /// Clears the pending trigger
+    fn reset(&self) {
+        if let Ok(mut pending) = self.0.pending.lock() {
+            *pending = false;
+        }
+    }
+}
+
+/// Polls the providers for the current public address and tracks changes
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    providers: Vec<(LookupProvider, Option<Parameters>)>,
+    interval: Duration,
+    max_interval: Duration,
+    jitter: f64,
+    backoff: f64,
+    debounce: u32,
+    confirmation: Option<(LookupProvider, Option<Parameters>)>,
+    on_change: Option<ChangeCallback>,
+    failures: u32,
+    last: Option<IpAddr>,
+    candidate: Option<(IpAddr, u32)>,
+    trigger: WatchTrigger,
+}
+
+impl Watcher {
+    /// Creates a new watcher polling the given providers
+    ///
+    /// Defaults to a 5 minute interval with 10% jitter, doubling the delay after every failed
+    /// poll up to one hour.
+    pub fn new(providers: Vec<(LookupProvider, Option<Parameters>)>) -> Watcher {
+        Watcher {
+            providers,
+            interval: Duration::from_secs(5 * 60),
+            max_interval: Duration::from_secs(60 * 60),
+            jitter: 0.1,
+            backoff: 2.0,
+            debounce: 1,
+            confirmation: None,
+            on_change: None,
+            failures: 0,
+            last: None,
+            candidate: None,
+            trigger: WatchTrigger::default(),
+        }
+    }
+
+    /// Sets the delay between successful polls
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the upper bound of the delay while backing off
+    pub fn with_max_interval(mut self, max_interval: Duration) -> Self {
+        self.max_interval = max_interval;
+        self
+    }
+
+    /// Sets the jitter as a fraction of the delay, clamped to `0.0..=1.0`
+    ///
+    /// A jitter of `0.2` spreads the delay randomly between 80% and 120% of its nominal value.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the factor the delay is multiplied by after every consecutive failed poll
+    ///
+    /// Factors below `1.0` are raised to `1.0`, which disables the backoff.
+    pub fn with_backoff(mut self, backoff: f64) -> Self {
+        self.backoff = backoff.max(1.0);
+        self
+    }
+
+    /// Requires a new address to be seen in this many consecutive polls before it is reported
+    ///
+    /// The first address seen by the watcher is reported right away.
+    pub fn with_debounce(mut self, polls: u32) -> Self {
+        self.debounce = polls.max(1);
+        self
+    }
+
+    /// Confirms a new address with a lookup through a second provider before it is reported
+    ///
+    /// The first address seen by the watcher is reported without confirmation.
+    pub fn with_confirmation(
+        mut self,
+        provider: LookupProvider,
+        parameters: Option<Parameters>,
+    ) -> Self {
+        self.confirmation = Some((provider, parameters));
+        self
+    }
+
+    /// Sets a callback run with the previous address and the new response on every reported change
+    pub fn on_change(
+        mut self,
+        callback: impl Fn(Option<IpAddr>, &LookupResponse) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_change = Some(ChangeCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Returns the number of consecutive failed polls
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Returns the address reported by the last successful poll
+    pub fn last_address(&self) -> Option<IpAddr> {
+        self.last
+    }
+
+    /// Returns a handle that makes the next poll happen right away
+    ///
+    /// Clones of the watcher share the same trigger.
+    pub fn trigger(&self) -> WatchTrigger {
+        self.trigger.clone()
+    }
+
+    /// Looks up the current public address and compares it with the previous poll
+    #[maybe_async::maybe_async]
+    pub async fn poll(&mut self) -> Result<WatchEvent> {
+        self.trigger.reset();
+        let response = match perform_lookup_with(self.providers.clone(), None).await {
+            Ok(response) => response,
+            Err(e) => {
+                self.failures = self.failures.saturating_add(1);
+                warn!("Watcher poll failed {} times in a row", self.failures);
+                return Err(e);
+            }
+        };
+        self.failures = 0;
+        if self.last == Some(response.ip) {
+            self.candidate = None;
+            return Ok(WatchEvent::Unchanged(response));
+        }
+        if self.last.is_some() && !self.settled(&response).await {
+            return Ok(WatchEvent::Pending(response));
+        }
+
+        self.candidate = None;
+        let previous = self.last.replace(response.ip);
+        debug!(
+            "Public address changed from {:?} to {}",
+            previous, response.ip
+        );
+        if let Some(callback) = &self.on_change {
+            (callback.0)(previous, &response);
+        }
+        Ok(WatchEvent::Changed { previous, response })
+    }
+
+    /// Checks if the new address passed the debounce and the confirmation
+    #[maybe_async::maybe_async]
+    async fn settled(&mut self, response: &LookupResponse) -> bool {
+        let seen = match self.candidate {
+            Some((candidate, seen)) if candidate == response.ip => seen.saturating_add(1),
+            _ => 1,
+        };
+        self.candidate = Some((response.ip, seen));
+        if seen < self.debounce {
+            debug!(
+                "New address {} seen {} of {} times",
+                response.ip, seen, self.debounce
+            );
+            return false;
+        }
+        let Some(confirmation) = self.confirmation.clone() else {
+            return true;
+        };
+        match perform_lookup_with(vec![confirmation], None).await {
+            Ok(confirmed) if confirmed.ip == response.ip => true,
+            Ok(confirmed) => {
+                warn!(
+                    "New address {} not confirmed, {} reported {}",
+                    response.ip, confirmed.provider, confirmed.ip
+                );
+                false
+            }
+            Err(e) => {
+                warn!("New address {} not confirmed: {}", response.ip, e);
+                false
+            }
+        }
+    }
+
+    /// Returns how long to wait before the next poll
+    ///
+    /// The interval is multiplied by the backoff factor for every consecutive failure, capped at
+    /// the maximum interval, and then randomized by the jitter. Returns zero while the trigger of
+    /// the watcher is pending.
+    pub fn next_delay(&self) -> Duration {
+        if self.trigger.is_pending() {
+            return Duration::ZERO;
+        }
+        let exponent = self.failures.min(i32::MAX as u32) as i32;
+        let nominal = (self.interval.as_secs_f64() * self.backoff.powi(exponent)).min(
+            self.max_interval
+                .as_secs_f64()
+                .max(self.interval.as_secs_f64()),
+        );
+        let spread = 1.0 + self.jitter * (2.0 * crate::time::random_unit() - 1.0);
+        Duration::from_secs_f64(nominal * spread)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_delay_jitter() {
+        let watcher = Watcher::new(vec![])
+            .with_interval(Duration::from_secs(100))
+            .with_jitter(0.2);
+        for _ in 0..100 {
+            let delay = watcher.next_delay();
+            assert!(
+                delay >= Duration::from_secs(80) && delay <= Duration::from_secs(120),
+                "Delay {:?} out of the jitter range",
+                delay
+            );
+        }
+        let watcher = watcher.with_jitter(0.0);
+        assert_eq!(watcher.next_delay(), Duration::from_secs(100));
+    }
+
+    #[test]
+    fn test_next_delay_backoff() {
+        let mut watcher = Watcher::new(vec![])
+            .with_interval(Duration::from_secs(10))
+            .with_max_interval(Duration::from_secs(60))
+            .with_jitter(0.0)
+            .with_backoff(2.0);
+        watcher.failures = 2;
+        assert_eq!(watcher.next_delay(), Duration::from_secs(40));
+        watcher.failures = 3;
+        assert_eq!(
+            watcher.next_delay(),
+            Duration::from_secs(60),
+            "Delay should be capped at the maximum interval"
+        );
+        watcher.failures = u32::MAX;
+        assert_eq!(watcher.next_delay(), Duration::from_secs(60));
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_trigger() {
+        use futures_util::FutureExt;
+
+        let mut watcher = Watcher::new(vec![(LookupProvider::Mock("1.1.1.1".to_string()), None)])
+            .with_interval(Duration::from_secs(100))
+            .with_jitter(0.0);
+        let trigger = watcher.trigger();
+        assert!(!trigger.wait_timeout(Duration::from_millis(10)));
+        assert!(trigger.triggered().now_or_never().is_none());
+
+        let remote = trigger.clone();
+        let handle = std::thread::spawn(move || remote.trigger());
+        assert!(
+            trigger.wait_timeout(Duration::from_secs(10)),
+            "Trigger should end the wait"
+        );
+        handle.join().unwrap();
+        assert!(trigger.triggered().now_or_never().is_some());
+        assert_eq!(watcher.next_delay(), Duration::ZERO);
+
+        watcher.poll().await.unwrap();
+        assert!(!trigger.is_pending(), "Poll should clear the trigger");
+        assert_eq!(watcher.next_delay(), Duration::from_secs(100));
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_poll_failure() {
+        let mut watcher = Watcher::new(vec![]);
+        let event = watcher.poll().await;
+        assert!(event.is_err(), "Poll without providers should fail");
+        assert_eq!(watcher.failures(), 1);
+        assert_eq!(watcher.last_address(), None);
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_poll_debounce() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let changes = Arc::new(AtomicUsize::new(0));
+        let counter = changes.clone();
+        let mut watcher = Watcher::new(vec![(LookupProvider::Mock("1.1.1.1".to_string()), None)])
+            .with_debounce(2)
+            .on_change(move |_, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        let event = watcher.poll().await.unwrap();
+        assert!(
+            matches!(event, WatchEvent::Changed { previous: None, .. }),
+            "First address should be reported right away"
+        );
+
+        watcher.providers = vec![(LookupProvider::Mock("8.8.8.8".to_string()), None)];
+        let event = watcher.poll().await.unwrap();
+        assert!(matches!(event, WatchEvent::Pending(_)));
+        assert_eq!(watcher.last_address(), Some("1.1.1.1".parse().unwrap()));
+        let event = watcher.poll().await.unwrap();
+        assert!(matches!(
+            event,
+            WatchEvent::Changed {
+                previous: Some(_),
+                ..
+            }
+        ));
+        assert_eq!(changes.load(Ordering::SeqCst), 2);
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_poll_confirmation() {
+        let mut watcher = Watcher::new(vec![(LookupProvider::Mock("1.1.1.1".to_string()), None)])
+            .with_confirmation(LookupProvider::Mock("1.1.1.1".to_string()), None);
+        let event = watcher.poll().await.unwrap();
+        assert!(matches!(event, WatchEvent::Changed { .. }));
+
+        watcher.providers = vec![(LookupProvider::Mock("8.8.8.8".to_string()), None)];
+        let event = watcher.poll().await.unwrap();
+        assert!(
+            matches!(event, WatchEvent::Pending(_)),
+            "Change should not be confirmed by the second provider"
+        );
+        assert_eq!(watcher.last_address(), Some("1.1.1.1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,181 @@
+//! 🏢 WHOIS/RDAP enrichment.
+//!
+//! Queries the Registration Data Access Protocol (RDAP) for the ownership data of an IP address,
+//! like the network name, the abuse contact and the allocation country.
+//!
+//! Enabled through the `rdap` feature flag.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use std::error::Error;
+//! #[cfg_attr(not(feature = "blocking"), tokio::main)]
+//! #[maybe_async::maybe_async]
+//! async fn main() -> Result<(), Box<dyn Error>> {
+//!     let mut response = public_ip_address::perform_lookup(None).await?;
+//!     public_ip_address::whois::enrich(&mut response).await?;
+//!     println!("{:?}", response.whois);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{
+    lookup::{
+        client,
+        error::{LookupError, Result},
+        handle_response,
+    },
+    response::{LookupResponse, WhoisInfo},
+};
+use serde_json::Value;
+use std::net::IpAddr;
+
+/// Returns the RDAP bootstrap endpoint for an IP address
+///
+/// The bootstrap service redirects to the regional internet registry responsible for the address.
+pub fn get_endpoint(ip: &IpAddr) -> String {
+    format!("https://rdap.org/ip/{}", ip)
+}
+
+/// Queries RDAP for the given IP address
+#[maybe_async::maybe_async]
+pub async fn lookup(ip: IpAddr) -> Result<WhoisInfo> {
+    let response = client::shared()
+        .get(get_endpoint(&ip))
+        .header("Accept", "application/rdap+json")
+        .send()
+        .await;
+    let body = handle_response(response).await?;
+    parse_reply(&body)
+}
+
+/// Queries RDAP for the address of the response and attaches the result to it
+#[maybe_async::maybe_async]
+pub async fn enrich(response: &mut LookupResponse) -> Result<()> {
+    response.whois = Some(lookup(response.ip).await?);
+    Ok(())
+}
+
+/// Parses an RDAP IP network object
+pub fn parse_reply(json: &str) -> Result<WhoisInfo> {
+    let value: Value = serde_json::from_str(json)?;
+    if value.get("objectClassName").and_then(Value::as_str) != Some("ip network") {
+        return Err(LookupError::GenericError(
+            "RDAP response is not an IP network object".to_string(),
+        ));
+    }
+    Ok(WhoisInfo {
+        handle: string(&value, "handle"),
+        network_name: string(&value, "name"),
+        abuse_contact: value
+            .get("entities")
+            .and_then(Value::as_array)
+            .and_then(|entities| find_abuse_email(entities)),
+        country: string(&value, "country"),
+        ..Default::default()
+    })
+}
+
+fn string(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+/// Abuse entities are often nested inside the registrant entity
+fn find_abuse_email(entities: &[Value]) -> Option<String> {
+    for entity in entities {
+        let is_abuse = entity
+            .get("roles")
+            .and_then(Value::as_array)
+            .is_some_and(|roles| roles.iter().any(|role| role == "abuse"));
+        if is_abuse {
+            if let Some(email) = vcard_email(entity) {
+                return Some(email);
+            }
+        }
+        if let Some(email) = entity
+            .get("entities")
+            .and_then(Value::as_array)
+            .and_then(|nested| find_abuse_email(nested))
+        {
+            return Some(email);
+        }
+    }
+    None
+}
+
+/// Extracts the email from a jCard: `["vcard", [["email", {}, "text", "abuse@example.com"], ...]]`
+fn vcard_email(entity: &Value) -> Option<String> {
+    entity
+        .get("vcardArray")?
+        .get(1)?
+        .as_array()?
+        .iter()
+        .find(|property| property.get(0).and_then(Value::as_str) == Some("email"))
+        .and_then(|property| property.get(3))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const TEST_INPUT: &str = r#"
+{
+  "objectClassName": "ip network",
+  "handle": "NET-8-8-8-0-2",
+  "startAddress": "8.8.8.0",
+  "endAddress": "8.8.8.255",
+  "ipVersion": "v4",
+  "name": "GOGL",
+  "type": "DIRECT ALLOCATION",
+  "country": "US",
+  "entities": [
+    {
+      "objectClassName": "entity",
+      "handle": "GOGL",
+      "roles": ["registrant"],
+      "vcardArray": ["vcard", [["version", {}, "text", "4.0"], ["fn", {}, "text", "Google LLC"]]],
+      "entities": [
+        {
+          "objectClassName": "entity",
+          "handle": "ABUSE5250-ARIN",
+          "roles": ["abuse"],
+          "vcardArray": [
+            "vcard",
+            [
+              ["version", {}, "text", "4.0"],
+              ["fn", {}, "text", "Abuse"],
+              ["email", {}, "text", "network-abuse@google.com"]
+            ]
+          ]
+        }
+      ]
+    }
+  ]
+}
+"#;
+
+    #[ignore]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn test_lookup() {
+        let info = lookup("8.8.8.8".parse().unwrap()).await;
+        assert!(info.is_ok(), "Failed RDAP lookup {:#?}", info);
+    }
+
+    #[test]
+    fn test_parse() {
+        let info = parse_reply(TEST_INPUT).unwrap();
+        assert_eq!(info.network_name, Some("GOGL".to_string()));
+        assert_eq!(info.country, Some("US".to_string()));
+        assert_eq!(
+            info.abuse_contact,
+            Some("network-abuse@google.com".to_string()),
+            "Abuse contact not matching"
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let input = TEST_INPUT.replace("ip network", "autnum");
+        assert!(parse_reply(&input).is_err(), "Non network object accepted");
+    }
+}
@@ -1,23 +1,14 @@
 use public_ip_address::*;
-use public_ip_address::{cache::ResponseCache, lookup::LookupProvider};
+use public_ip_address::{
+    lookup::LookupProvider,
+    response::SelectionReason,
+    test_util::{clear_cache, ip, mock_chain},
+};
 use serial_test::serial;
-use std::net::IpAddr;
-
-fn clear_cache() {
-    _ = ResponseCache::default().delete();
-}
-
-fn ip(ip: &str) -> IpAddr {
-    ip.parse().unwrap()
-}
 
 #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
 async fn test_perform_lookup() {
-    let response = perform_lookup_with(
-        vec![(LookupProvider::Mock("1.1.1.1".to_string()), None)],
-        None,
-    )
-    .await;
+    let response = perform_lookup_with(mock_chain(&["1.1.1.1"]), None).await;
     assert!(response.is_ok());
     assert_eq!(
         response.unwrap().ip,
@@ -28,11 +19,7 @@ async fn test_perform_lookup() {
 
 #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
 async fn test_perform_lookup_target() {
-    let response = perform_lookup_with(
-        vec![(LookupProvider::Mock("8.8.8.8".to_string()), None)],
-        Some(ip("8.8.8.8")),
-    )
-    .await;
+    let response = perform_lookup_with(mock_chain(&["8.8.8.8"]), Some(ip("8.8.8.8"))).await;
     assert!(response.is_ok());
     assert_eq!(
         response.unwrap().ip,
@@ -41,17 +28,62 @@ async fn test_perform_lookup_target() {
     );
 }
 
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_perform_lookup_non_public_target() {
+    let response = perform_lookup_with(mock_chain(&["8.8.8.8"]), Some(ip("192.168.1.1"))).await;
+    assert!(
+        matches!(
+            response,
+            Err(error::Error::LookupError(
+                lookup::error::LookupError::NonPublicAddress(lookup::bogon::AddressKind::Private)
+            ))
+        ),
+        "Expected non public address error, got {:?}",
+        response
+    );
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_perform_lookup_exhausted_budget() {
+    let options = lookup::LookupOptions::default().with_total_timeout(std::time::Duration::ZERO);
+    let response = perform_lookup_with_options(mock_chain(&["1.1.1.1"]), None, options).await;
+    assert!(
+        matches!(
+            response,
+            Err(error::Error::LookupError(
+                lookup::error::LookupError::Timeout
+            ))
+        ),
+        "Expected timeout error, got {:?}",
+        response
+    );
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_perform_lookup_target_hostname() {
+    let response = perform_lookup_target_with(
+        mock_chain(&["8.8.8.8"]),
+        lookup::LookupTarget::Hostname("localhost".to_string()),
+    )
+    .await;
+    assert!(
+        matches!(
+            response,
+            Err(error::Error::LookupError(
+                lookup::error::LookupError::NonPublicAddress(lookup::bogon::AddressKind::Loopback)
+            ))
+        ),
+        "Expected non public address error, got {:?}",
+        response
+    );
+}
+
 #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
 #[serial]
 async fn test_perform_lookup_cached() {
     clear_cache();
-    let response = perform_cached_lookup_with(
-        vec![(LookupProvider::Mock("11.1.1.1".to_string()), None)],
-        None,
-        Some(1),
-        false,
-    )
-    .await;
+    let response =
+        perform_cached_lookup_with(mock_chain(&["11.1.1.1"]), None, Some(1), false).await;
     assert_eq!(
         response.unwrap().ip,
         ip("11.1.1.1"),
@@ -64,37 +96,20 @@ async fn test_perform_lookup_cached() {
 #[serial]
 async fn test_perform_lookup_cached_force_expire() {
     clear_cache();
-    let response = perform_cached_lookup_with(
-        vec![(LookupProvider::Mock("21.1.1.1".to_string()), None)],
-        None,
-        None,
-        false,
-    )
-    .await;
+    let response = perform_cached_lookup_with(mock_chain(&["21.1.1.1"]), None, None, false).await;
     assert_eq!(
         response.unwrap().ip,
         ip("21.1.1.1"),
         "IP address not matching"
     );
-    let response = perform_cached_lookup_with(
-        vec![(LookupProvider::Mock("22.2.2.2".to_string()), None)],
-        None,
-        Some(1),
-        false,
-    )
-    .await;
+    let response =
+        perform_cached_lookup_with(mock_chain(&["22.2.2.2"]), None, Some(1), false).await;
     assert_eq!(
         response.unwrap().ip,
         ip("21.1.1.1"),
         "Non expiring cache should be used"
     );
-    let response = perform_cached_lookup_with(
-        vec![(LookupProvider::Mock("23.3.3.3".to_string()), None)],
-        None,
-        Some(1),
-        true,
-    )
-    .await;
+    let response = perform_cached_lookup_with(mock_chain(&["23.3.3.3"]), None, Some(1), true).await;
     // the old cache should be flushed
     assert_eq!(
         response.unwrap().ip,
@@ -108,25 +123,13 @@ async fn test_perform_lookup_cached_force_expire() {
 #[serial]
 async fn test_perform_lookup_cached_expired() {
     clear_cache();
-    let response = perform_cached_lookup_with(
-        vec![(LookupProvider::Mock("1.1.1.1".to_string()), None)],
-        None,
-        Some(1),
-        false,
-    )
-    .await;
+    let response = perform_cached_lookup_with(mock_chain(&["1.1.1.1"]), None, Some(1), false).await;
     assert_eq!(
         response.unwrap().ip,
         ip("1.1.1.1"),
         "IP address not matching"
     );
-    let response = perform_cached_lookup_with(
-        vec![(LookupProvider::Mock("2.2.2.2".to_string()), None)],
-        None,
-        Some(2),
-        false,
-    )
-    .await;
+    let response = perform_cached_lookup_with(mock_chain(&["2.2.2.2"]), None, Some(2), false).await;
     // the old cache should be returned
     assert_eq!(
         response.unwrap().ip,
@@ -134,42 +137,524 @@ async fn test_perform_lookup_cached_expired() {
         "The old cache should be returned"
     );
     std::thread::sleep(std::time::Duration::from_secs(1));
-    let response = perform_cached_lookup_with(
-        vec![(LookupProvider::Mock("3.3.3.3".to_string()), None)],
-        None,
-        Some(0),
-        false,
-    )
-    .await;
+    let response = perform_cached_lookup_with(mock_chain(&["3.3.3.3"]), None, Some(0), false).await;
     assert_eq!(
         response.unwrap().ip,
         ip("3.3.3.3"),
         "Cached value should expire"
     );
 
-    let response = perform_cached_lookup_with(
-        vec![(LookupProvider::Mock("4.4.4.4".to_string()), None)],
-        None,
-        Some(1),
-        false,
-    )
-    .await;
+    let response = perform_cached_lookup_with(mock_chain(&["4.4.4.4"]), None, Some(1), false).await;
     assert_eq!(
         response.unwrap().ip,
         ip("4.4.4.4"),
         "Cached value should expire"
     );
-    let response = perform_cached_lookup_with(
-        vec![(LookupProvider::Mock("5.5.5.5".to_string()), None)],
+    let response = perform_cached_lookup_with(mock_chain(&["5.5.5.5"]), None, Some(1), false).await;
+    assert_eq!(
+        response.unwrap().ip,
+        ip("4.4.4.4"),
+        "Cached value should be used"
+    );
+    clear_cache();
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_perform_lookup_cached_failure() {
+    clear_cache();
+    let providers = vec![
+        (LookupProvider::Mock("429".to_string()), None),
+        (LookupProvider::Mock("31.1.1.1".to_string()), None),
+    ];
+    let response = perform_cached_lookup_with(providers.clone(), None, Some(60), true).await;
+    let response = response.unwrap();
+    assert_eq!(response.meta.attempts, 2);
+    assert_eq!(response.meta.selection_reason, SelectionReason::Fallback(1));
+    let response = perform_cached_lookup_with(providers.clone(), None, Some(60), true).await;
+    let response = response.unwrap();
+    assert_eq!(
+        response.meta.attempts, 1,
+        "Rate limited provider should be skipped"
+    );
+    assert_eq!(response.ip, ip("31.1.1.1"));
+
+    let failing = vec![(LookupProvider::Mock("429".to_string()), None)];
+    let response = perform_cached_lookup_with(failing, None, Some(60), true).await;
+    assert!(
+        response.is_err(),
+        "Failing provider should be tried when no other is left"
+    );
+    clear_cache();
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_perform_lookup_cached_serve_stale() {
+    clear_cache();
+    let response =
+        perform_cached_lookup_with(mock_chain(&["41.1.1.1"]), None, Some(0), false).await;
+    assert_eq!(response.unwrap().ip, ip("41.1.1.1"));
+
+    let lookup = Lookup::builder()
+        .with_provider(LookupProvider::Mock("timeout".to_string()), None)
+        .with_cache_ttl(Some(0));
+    let response = lookup.clone().perform().await;
+    assert!(
+        response.is_err(),
+        "Stale value should not be served by default"
+    );
+
+    let response = lookup.with_serve_stale().perform().await;
+    let response = response.unwrap();
+    assert_eq!(response.ip, ip("41.1.1.1"));
+    assert!(response.meta.is_stale(), "Response should be marked stale");
+    clear_cache();
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_lookup_builder_cached() {
+    clear_cache();
+    let lookup = Lookup::builder()
+        .with_provider(LookupProvider::Mock("8.8.4.4".to_string()), None)
+        .with_target(ip("8.8.4.4"))
+        .with_cache_ttl(Some(60))
+        .with_strategy(LookupStrategy::Race);
+    let response = lookup.clone().with_flush().perform().await;
+    assert_eq!(
+        response.unwrap().meta.selection_reason,
+        SelectionReason::RaceWinner
+    );
+    let response = lookup.perform().await;
+    assert_eq!(
+        response.unwrap().meta.selection_reason,
+        SelectionReason::CacheHit,
+        "Raced response should be cached"
+    );
+    clear_cache();
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_perform_lookup_provider_config() {
+    let providers = vec![
+        lookup::ProviderConfig::new(LookupProvider::Mock("timeout,1.1.1.1".to_string()))
+            .with_retries(1),
+        lookup::ProviderConfig::new(LookupProvider::Mock("2.2.2.2".to_string())).with_weight(0),
+    ];
+    let response = perform_lookup_with(providers, None).await;
+    let response = response.unwrap();
+    assert_eq!(
+        response.ip,
+        ip("1.1.1.1"),
+        "Timed out request should be retried"
+    );
+    assert_eq!(response.meta.attempts, 1);
+
+    let providers = vec![
+        lookup::ProviderConfig::new(LookupProvider::Mock("timeout,1.1.1.1".to_string())),
+        lookup::ProviderConfig::new(LookupProvider::Mock("2.2.2.2".to_string())).with_weight(2),
+    ];
+    let response = perform_lookup_with(providers, None).await;
+    assert_eq!(
+        response.unwrap().ip,
+        ip("2.2.2.2"),
+        "Heavier provider should be tried first"
+    );
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_perform_rotating_lookup() {
+    clear_cache();
+    let providers = vec![
+        lookup::ProviderConfig::new(LookupProvider::Mock("1.1.1.1".to_string())).with_weight(0),
+        lookup::ProviderConfig::new(LookupProvider::Mock("2.2.2.2".to_string())),
+    ];
+    let response = perform_rotating_lookup_with(providers, None).await;
+    let response = response.unwrap();
+    assert_eq!(
+        response.ip,
+        ip("2.2.2.2"),
+        "Provider without weight should be tried last"
+    );
+    assert_eq!(response.meta.selection_reason, SelectionReason::Rotation);
+
+    let mut seen = std::collections::HashSet::new();
+    for _ in 0..32 {
+        let providers = mock_chain(&["1.1.1.1", "2.2.2.2"]);
+        let response = perform_rotating_lookup_with(providers, None).await;
+        seen.insert(response.unwrap().ip);
+    }
+    assert_eq!(
+        seen.len(),
+        2,
+        "Requests should be spread over the providers"
+    );
+    let cache = cache::ResponseCache::load(None).unwrap();
+    let usage = cache.requests_today(&LookupProvider::Mock("2.2.2.2".to_string()));
+    assert!(usage > 1, "Usage should be counted in the cache");
+    clear_cache();
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_perform_lookup_asn_only() {
+    let providers = vec![
+        (LookupProvider::IpWhoIs, None),
+        (LookupProvider::Mock("full:1.1.1.1".to_string()), None),
+    ];
+    let options = lookup::LookupOptions::default().with_asn_only();
+    let response = perform_lookup_with_options(providers, Some(ip("1.1.1.1")), options).await;
+    let response = response.unwrap();
+    assert_eq!(response.asn, Some("AS13335".to_string()));
+    assert_eq!(
+        response.meta.selection_reason,
+        SelectionReason::Fallback(1),
+        "Provider without ASN lookups should be skipped"
+    );
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_perform_lookup_fallback() {
+    let response = perform_lookup_with(
+        vec![
+            (LookupProvider::MyIp, None),
+            (LookupProvider::Mock("8.8.8.8".to_string()), None),
+        ],
+        Some(ip("8.8.8.8")),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.ip, ip("8.8.8.8"), "IP address not matching");
+    assert_eq!(
+        response.meta.selection_reason,
+        SelectionReason::Fallback(1),
+        "Second provider should answer"
+    );
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_perform_lookup_invalid_ip_fallback() {
+    let response = perform_lookup_with(mock_chain(&["1.1.1", "8.8.8.8"]), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.ip,
+        ip("8.8.8.8"),
+        "Invalid address should be skipped"
+    );
+    assert_eq!(response.meta.selection_reason, SelectionReason::Fallback(1));
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_perform_race_lookup() {
+    let response = perform_race_lookup_with(
+        vec![
+            (LookupProvider::MyIp, None),
+            (LookupProvider::Mock("8.8.8.8".to_string()), None),
+        ],
+        Some(ip("8.8.8.8")),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.ip, ip("8.8.8.8"), "IP address not matching");
+    assert_eq!(
+        response.meta.selection_reason,
+        SelectionReason::RaceWinner,
+        "Selection reason not matching"
+    );
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_perform_lookup_cached_selection_reason() {
+    clear_cache();
+    let providers = mock_chain(&["31.1.1.1"]);
+    let response = perform_cached_lookup_with(providers.clone(), None, Some(60), false)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.meta.selection_reason,
+        SelectionReason::FirstHealthy,
+        "Fresh lookup should be answered by the first provider"
+    );
+    let response = perform_cached_lookup_with(providers, None, Some(60), false)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.meta.selection_reason,
+        SelectionReason::CacheHit,
+        "Cached value should be used"
+    );
+    clear_cache();
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_perform_cached_bulk_lookup() {
+    clear_cache();
+    let mut cache = cache::ResponseCache::default();
+    let cached = vec![
+        response::LookupResponse::new(ip("41.1.1.1"), LookupProvider::Mock("41.1.1.1".into())),
+        response::LookupResponse::new(ip("42.2.2.2"), LookupProvider::Mock("42.2.2.2".into())),
+    ];
+    cache.update_targets(&cached, Some(60));
+    cache.save().unwrap();
+
+    let responses = perform_cached_bulk_lookup_with(
+        mock_chain(&["8.8.8.8"]),
+        vec![ip("42.2.2.2"), ip("10.0.0.1"), ip("41.1.1.1")],
+        Some(60),
+        false,
+    )
+    .await;
+    assert_eq!(responses.len(), 3, "Response count not matching");
+    assert_eq!(responses[0].as_ref().unwrap().ip, ip("42.2.2.2"));
+    assert_eq!(
+        responses[0].as_ref().unwrap().meta.selection_reason,
+        SelectionReason::CacheHit
+    );
+    assert!(responses[1].is_err(), "Private target should fail");
+    assert_eq!(responses[2].as_ref().unwrap().ip, ip("41.1.1.1"));
+    clear_cache();
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_perform_fan_out_bulk_lookup() {
+    clear_cache();
+    let mut cache = cache::ResponseCache::default();
+    let cached = vec![
+        response::LookupResponse::new(ip("41.1.1.1"), LookupProvider::Mock("41.1.1.1".into())),
+        response::LookupResponse::new(ip("42.2.2.2"), LookupProvider::Mock("42.2.2.2".into())),
+    ];
+    cache.update_targets(&cached, Some(60));
+    cache.save().unwrap();
+
+    let responses = perform_fan_out_bulk_lookup_with(
+        mock_chain(&["8.8.8.8", "9.9.9.9"]),
+        vec![ip("41.1.1.1"), ip("127.0.0.1"), ip("42.2.2.2")],
+        Some(60),
+        false,
+    )
+    .await;
+    assert_eq!(responses.len(), 3, "Response count not matching");
+    assert_eq!(responses[0].as_ref().unwrap().ip, ip("41.1.1.1"));
+    assert!(responses[1].is_err(), "Loopback target should fail");
+    assert_eq!(
+        responses[2].as_ref().unwrap().meta.selection_reason,
+        SelectionReason::CacheHit
+    );
+    clear_cache();
+}
+
+#[cfg(not(feature = "blocking"))]
+#[tokio::test]
+async fn test_perform_lookup_cancelled() {
+    let response =
+        perform_lookup_with_cancel(mock_chain(&["1.1.1.1"]), None, std::future::ready(())).await;
+    assert!(
+        matches!(
+            response,
+            Err(error::Error::LookupError(
+                lookup::error::LookupError::Cancelled
+            ))
+        ),
+        "Expected cancelled error, got {:?}",
+        response
+    );
+
+    let response = perform_lookup_with_cancel(
+        mock_chain(&["1.1.1.1"]),
+        Some(ip("127.0.0.1")),
+        std::future::pending(),
+    )
+    .await;
+    assert!(
+        matches!(
+            response,
+            Err(error::Error::LookupError(
+                lookup::error::LookupError::NonPublicAddress(_)
+            ))
+        ),
+        "Finished lookup should not be cancelled, got {:?}",
+        response
+    );
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_perform_lookup_from_missing_config() {
+    let response = perform_lookup_from_config("does-not-exist.json").await;
+    assert!(
+        matches!(response, Err(error::Error::ConfigError(_))),
+        "Expected configuration error, got {:?}",
+        response
+    );
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+async fn test_check_providers() {
+    let providers = vec![
+        (LookupProvider::Mock("1.1.1.1".to_string()), None),
+        (
+            LookupProvider::Custom("http://10.0.0.1/ip".to_string()),
+            None,
+        ),
+    ];
+    let health = public_ip_address::check_providers(providers).await;
+    assert_eq!(health.len(), 2);
+    assert_eq!(
+        health[0].provider,
+        LookupProvider::Mock("1.1.1.1".to_string())
+    );
+    assert!(health[0].is_healthy(), "Mock provider should be healthy");
+    assert!(
+        !health[1].is_healthy(),
+        "Private endpoint should be refused"
+    );
+}
+
+#[cfg(not(feature = "blocking"))]
+#[test]
+fn test_perform_lookup_without_tokio() {
+    // the lookup logic must not depend on a tokio runtime, only the reqwest requests do
+    let options = lookup::LookupOptions::default()
+        .with_total_timeout(std::time::Duration::from_secs(5))
+        .with_per_provider_timeout(std::time::Duration::from_secs(1));
+    let providers = vec![
+        (LookupProvider::Mock("timeout".to_string()), None),
+        (LookupProvider::Mock("1.1.1.1".to_string()), None),
+    ];
+    let response = futures_executor::block_on(perform_lookup_with_options(
+        providers.clone(),
         None,
-        Some(1),
+        options,
+    ));
+    assert_eq!(response.unwrap().ip, ip("1.1.1.1"));
+
+    let cancel = std::future::pending::<()>();
+    let response =
+        futures_executor::block_on(perform_lookup_with_cancel(providers.clone(), None, cancel));
+    assert_eq!(response.unwrap().ip, ip("1.1.1.1"));
+
+    let response = futures_executor::block_on(perform_race_lookup_with(providers, None));
+    assert_eq!(response.unwrap().ip, ip("1.1.1.1"));
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_perform_cached_lookup_at() {
+    clear_cache();
+    let dir = std::env::temp_dir().join("public-ip-address-location-test");
+    let location = cache::CacheLocation::new()
+        .with_dir(&dir)
+        .with_file_name("profile.cache");
+    let response =
+        perform_cached_lookup_at(mock_chain(&["1.1.1.1"]), None, None, false, &location).await;
+    assert_eq!(response.unwrap().ip, ip("1.1.1.1"));
+    assert!(
+        dir.join("profile.cache").exists(),
+        "Cache file not stored at the location"
+    );
+    assert!(
+        cache::ResponseCache::load(None).is_err(),
+        "Default cache should be untouched"
+    );
+
+    let response =
+        perform_cached_lookup_at(mock_chain(&["2.2.2.2"]), None, None, false, &location).await;
+    let response = response.unwrap();
+    assert_eq!(response.ip, ip("1.1.1.1"), "Cached value should be used");
+    assert_eq!(response.meta.selection_reason, SelectionReason::CacheHit);
+    _ = std::fs::remove_dir_all(dir);
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_rotating_and_bulk_lookup_at() {
+    clear_cache();
+    let dir = std::env::temp_dir().join("public-ip-address-rotation-location-test");
+    let location = cache::CacheLocation::new()
+        .with_dir(&dir)
+        .with_file_name("profile.cache");
+    let response = perform_rotating_lookup_at(mock_chain(&["1.1.1.1"]), None, &location).await;
+    assert_eq!(response.unwrap().ip, ip("1.1.1.1"));
+    let responses = perform_cached_bulk_lookup_at(
+        mock_chain(&["8.8.8.8"]),
+        vec![ip("8.8.8.8")],
+        Some(60),
         false,
+        &location,
     )
     .await;
+    assert_eq!(responses[0].as_ref().unwrap().ip, ip("8.8.8.8"));
+
+    let cache = cache::ResponseCache::load(location.file_name()).unwrap();
+    let usage = cache.requests_today(&LookupProvider::Mock("1.1.1.1".to_string()));
+    assert_eq!(usage, 1, "Usage should be counted at the location");
+    assert!(
+        cache.target_response(&ip("8.8.8.8")).is_some(),
+        "Bulk responses should be cached at the location"
+    );
+    assert!(
+        cache::ResponseCache::load(None).is_err(),
+        "Default cache should be untouched"
+    );
+
+    let path = dir.join("profile.cache");
+    std::fs::write(&path, "not a cache").unwrap();
+    let response = perform_rotating_lookup_at(mock_chain(&["1.1.1.1"]), None, &location).await;
+    assert!(
+        response.is_ok(),
+        "Unreadable cache should not fail the lookup"
+    );
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "not a cache",
+        "Unreadable cache should not be overwritten"
+    );
+    _ = std::fs::remove_dir_all(dir);
+}
+
+#[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+#[serial]
+async fn test_perform_lookup_cached_stale_while_revalidate() {
+    clear_cache();
+    let response =
+        perform_cached_lookup_with(mock_chain(&["51.1.1.1"]), None, Some(60), false).await;
+    assert_eq!(response.unwrap().ip, ip("51.1.1.1"));
+
+    let lookup = Lookup::builder()
+        .with_providers(mock_chain(&["52.2.2.2"]))
+        .with_cache_ttl(Some(60))
+        .with_stale_while_revalidate(std::time::Duration::ZERO);
+    let response = lookup.perform().await.unwrap();
+    assert_eq!(response.ip, ip("51.1.1.1"), "Cached value should be served");
+    assert!(response.meta.is_cache_hit());
+
+    // async builds refresh in the background, blocking builds on the next lookup
+    #[cfg(not(feature = "blocking"))]
+    for _ in 0..50 {
+        let cache = cache::ResponseCache::load(None).unwrap();
+        if cache.current_ip() == Some(ip("52.2.2.2")) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    #[cfg(feature = "blocking")]
+    assert!(
+        cache::ResponseCache::load(None)
+            .unwrap()
+            .current_address
+            .unwrap()
+            .refresh_pending
+    );
+    let response =
+        perform_cached_lookup_with(mock_chain(&["52.2.2.2"]), None, Some(60), false).await;
     assert_eq!(
         response.unwrap().ip,
-        ip("4.4.4.4"),
-        "Cached value should be used"
+        ip("52.2.2.2"),
+        "Cached value should be refreshed"
     );
     clear_cache();
 }